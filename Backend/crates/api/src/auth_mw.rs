@@ -1,11 +1,51 @@
+use crate::rate_limit_mw::extract_client_ip;
 use crate::routes::AppState;
-use axum::http::header::AUTHORIZATION;
+use axum::http::{header::AUTHORIZATION, HeaderMap};
 use axum::{extract::State, http::StatusCode, response::Response};
+use shared::api_keys::{rows_remaining, ApiKeyStore, ApiScope, KEY_PREFIX};
 use shared::auth::{verify_jwt_jwks, verify_jwt_secret, Claims};
+use shared::{ApiError, ApiResult};
+
+/// Header carrying an API key; `Authorization: Bearer oof_...` works too
+pub(crate) const API_KEY_HEADER: &str = "x-api-key";
 
 #[derive(Clone)]
 pub struct AuthUser {
     pub user_id: String,
+    /// Set when the request was authenticated with an API key
+    pub api_key: Option<ApiKeyAuth>,
+}
+
+#[derive(Clone)]
+pub struct ApiKeyAuth {
+    pub id: String,
+    pub scopes: Vec<ApiScope>,
+    /// Rows the key may still serve this billing period
+    pub rows_remaining: i64,
+    /// From the owner's plan; see `rate_limit_mw::per_key_limit`
+    pub requests_per_minute: u32,
+}
+
+impl AuthUser {
+    /// Sessions may use anything their plan allows; keys only what they
+    /// were scoped for
+    pub fn require_scope(&self, scope: ApiScope) -> ApiResult<()> {
+        match &self.api_key {
+            Some(key) if !key.scopes.contains(&scope) => Err(ApiError::Forbidden),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count `rows` served to an API key against its quota; sessions are
+    /// not metered
+    pub async fn meter_rows(&self, state: &AppState, rows: usize) -> ApiResult<()> {
+        if let Some(key) = &self.api_key {
+            ApiKeyStore::new(state.pg.0.clone())
+                .record_rows(&self.user_id, &key.id, rows as i64)
+                .await?;
+        }
+        Ok(())
+    }
 }
 
 use axum::extract::FromRequestParts;
@@ -27,20 +67,43 @@ where
     }
 }
 
-pub async fn require_auth<B>(
-    State(st): State<AppState>,
-    mut req: axum::http::Request<B>,
-    next: axum::middleware::Next<B>,
-) -> Result<Response, StatusCode> {
-    let auth = req
-        .headers()
+enum Credentials<'a> {
+    Jwt(&'a str),
+    ApiKey(&'a str),
+}
+
+fn credentials(headers: &HeaderMap) -> Option<Credentials<'_>> {
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|h| h.to_str().ok()) {
+        return Some(Credentials::ApiKey(key));
+    }
+    let token = headers
         .get(AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("");
-    let token = match auth.strip_prefix("Bearer ") {
-        Some(t) => t,
-        None => return Err(StatusCode::UNAUTHORIZED),
-    };
+        .and_then(|h| h.to_str().ok())?
+        .strip_prefix("Bearer ")?;
+    if token.starts_with(KEY_PREFIX) {
+        Some(Credentials::ApiKey(token))
+    } else {
+        Some(Credentials::Jwt(token))
+    }
+}
+
+/// Verify the credentials on a request. API keys are only accepted when
+/// `accept_keys` is set.
+async fn authenticate(
+    st: &AppState,
+    headers: &HeaderMap,
+    accept_keys: bool,
+) -> Result<AuthUser, StatusCode> {
+    match credentials(headers) {
+        Some(Credentials::Jwt(token)) => authenticate_jwt(st, token).await,
+        Some(Credentials::ApiKey(key)) if accept_keys => {
+            authenticate_key(st, key, &extract_client_ip(headers)).await
+        }
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+async fn authenticate_jwt(st: &AppState, token: &str) -> Result<AuthUser, StatusCode> {
     let claims: Claims = if let Some(jwks_url) = &st.cfg.dynamic_jwks_url {
         verify_jwt_jwks(token, jwks_url).await?
     } else if let Some(secret) = &st.cfg.jwt_secret {
@@ -48,8 +111,110 @@ pub async fn require_auth<B>(
     } else {
         return Err(StatusCode::UNAUTHORIZED);
     };
-    req.extensions_mut().insert(AuthUser {
+    Ok(AuthUser {
         user_id: claims.sub,
-    });
+        api_key: None,
+    })
+}
+
+fn internal(e: impl std::fmt::Display) -> StatusCode {
+    tracing::error!(error = %e, "API key authentication failed");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Look the key up, then check the owner's plan still includes API access
+/// and has rows left this billing period
+async fn authenticate_key(st: &AppState, key: &str, ip: &str) -> Result<AuthUser, StatusCode> {
+    let store = ApiKeyStore::new(st.pg.0.clone());
+    let record = store
+        .authenticate(key, Some(ip))
+        .await
+        .map_err(internal)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user_context = st
+        .policy_service
+        .get_user_context(&record.user_id)
+        .await
+        .map_err(internal)?;
+    if !user_context.plan.perks.api_access {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let (user_rows, key_rows) = store
+        .rows_used(&record.user_id, &record.id)
+        .await
+        .map_err(internal)?;
+    let remaining = rows_remaining(
+        user_context.plan.api_rows,
+        record.rows_limit,
+        user_rows,
+        key_rows,
+    );
+    if remaining == 0 {
+        // Same status as ApiError::QuotaExceeded
+        return Err(StatusCode::PAYMENT_REQUIRED);
+    }
+
+    Ok(AuthUser {
+        user_id: record.user_id,
+        api_key: Some(ApiKeyAuth {
+            id: record.id,
+            scopes: record.scopes,
+            rows_remaining: remaining,
+            requests_per_minute: user_context.plan.api_requests_per_minute.max(0) as u32,
+        }),
+    })
+}
+
+/// Require a session JWT
+pub async fn require_auth<B>(
+    State(st): State<AppState>,
+    mut req: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> Result<Response, StatusCode> {
+    let user = authenticate(&st, req.headers(), false).await?;
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+/// Require a session JWT or an API key; handlers check the key's scope
+/// with `AuthUser::require_scope`
+pub async fn require_auth_or_key<B>(
+    State(st): State<AppState>,
+    mut req: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> Result<Response, StatusCode> {
+    let user = authenticate(&st, req.headers(), true).await?;
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+/// For public routes: authenticate a JWT or API key if one is presented,
+/// rejecting bad credentials, and let anonymous requests through
+pub async fn optional_auth<B>(
+    State(st): State<AppState>,
+    mut req: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> Result<Response, StatusCode> {
+    if credentials(req.headers()).is_some() {
+        let user = authenticate(&st, req.headers(), true).await?;
+        req.extensions_mut().insert(user);
+    }
+    Ok(next.run(req).await)
+}
+
+/// Like `require_auth`, but only for users listed in `ADMIN_USER_IDS`
+pub async fn admin_auth_middleware<B>(
+    State(st): State<AppState>,
+    mut req: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> Result<Response, StatusCode> {
+    let user = authenticate(&st, req.headers(), false).await?;
+    if !st.cfg.admin_user_ids.contains(&user.user_id) {
+        tracing::warn!(user_id = %user.user_id, "Non-admin user attempted admin access");
+        return Err(StatusCode::FORBIDDEN);
+    }
+    req.extensions_mut().insert(user);
     Ok(next.run(req).await)
 }
@@ -1,10 +1,11 @@
 mod auth_mw;
 mod observability_mw;
+mod openapi;
 mod rate_limit_mw;
 mod routes;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use shared::{
@@ -78,6 +79,23 @@ async fn main() -> anyhow::Result<()> {
         health_checker.clone(),
     );
 
+    // Fan progress notifications out to analysis SSE streams
+    tokio::spawn(shared::progress::relay_notifications(
+        state.pg.0.clone(),
+        state.sse_broadcast.clone(),
+    ));
+
+    let admin = Router::new()
+        .route("/v1/admin/jobs", get(routes::admin::list_jobs))
+        .route("/v1/admin/jobs/purge", post(routes::admin::purge_jobs))
+        .route("/v1/admin/jobs/:id", get(routes::admin::get_job))
+        .route("/v1/admin/jobs/:id/retry", post(routes::admin::retry_job))
+        .route("/v1/admin/jobs/:id/cancel", post(routes::admin::cancel_job))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth_mw::admin_auth_middleware,
+        ));
+
     let app = Router::new()
         .route("/health", get(routes::health))
         .route("/ready", get(routes::ready))
@@ -86,26 +104,152 @@ async fn main() -> anyhow::Result<()> {
         .route("/auth/verify", post(routes::auth_verify)) // New auth verification endpoint
         .route(
             "/v1/analyze",
-            post(routes::analyze).route_layer(axum::middleware::from_fn_with_state(
-                state.clone(),
-                auth_mw::require_auth,
-            )),
+            post(routes::analyze)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth_or_key,
+                )),
+        )
+        .route(
+            "/v1/analyze/:job",
+            delete(routes::cancel_analysis)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth_or_key,
+                )),
         )
         .route("/v1/analyze/:job/stream", get(routes::analyze_stream))
         .route(
             "/v1/moments",
             get(routes::moments_list)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::optional_auth,
+                ))
                 .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
         )
         .route("/v1/moments/:id", get(routes::moment_detail))
+        .route(
+            "/v1/moments/:id/similar",
+            get(routes::similar_moments::similar_moments)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::optional_auth,
+                ))
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
+        )
         .route("/v1/moments/:id/mint", post(routes::mint_moment_nft)) // New NFT minting endpoint
         .route("/v1/moments/:id/nft", get(routes::get_moment_nft)) // New NFT details endpoint
+        .route(
+            "/v1/wallets/compare",
+            get(routes::wallet_compare::compare_wallets)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
+        )
         .route("/v1/wallets/:wallet/summary", get(routes::wallet_summary))
+        .route(
+            "/v1/wallets/:wallet/moments",
+            get(routes::wallet_moments)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::optional_auth,
+                ))
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
+        )
+        .route(
+            "/v1/wallets/:wallet/equity",
+            get(routes::wallet_equity::wallet_equity)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
+        )
+        .route(
+            "/v1/wallets/:wallet/timeline",
+            get(routes::wallet_timeline::wallet_timeline)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::optional_auth,
+                ))
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
+        )
         .route(
             "/v1/wallets/:wallet/extremes",
             get(routes::wallet_extremes)
                 .route_layer(axum::middleware::from_fn(rate_limit_mw::per_ip_limit)),
         )
+        .route(
+            "/v1/wallets/:wallet/alerts",
+            post(routes::subscribe_wallet_alerts)
+                .delete(routes::unsubscribe_wallet_alerts)
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth,
+                )),
+        )
+        .route(
+            "/v1/alerts/config",
+            get(routes::alert_config::get_alert_config)
+                .put(routes::alert_config::put_alert_config)
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth,
+                )),
+        )
+        .route(
+            "/v1/exports",
+            post(routes::exports::create_export)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth_or_key,
+                )),
+        )
+        .route(
+            "/v1/exports/:id",
+            get(routes::exports::get_export)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth_or_key,
+                )),
+        )
+        .route(
+            "/v1/exports/:id/files/:name",
+            get(routes::exports::download_export_file),
+        )
+        .route(
+            "/v1/tax/report",
+            get(routes::exports::tax_report)
+                .route_layer(axum::middleware::from_fn(rate_limit_mw::per_key_limit))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth_or_key,
+                )),
+        )
+        .route(
+            "/v1/api-keys",
+            post(routes::api_keys::create_api_key)
+                .get(routes::api_keys::list_api_keys)
+                .route_layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_mw::require_auth,
+                )),
+        )
+        .route(
+            "/v1/api-keys/:id",
+            delete(routes::api_keys::revoke_api_key).route_layer(
+                axum::middleware::from_fn_with_state(state.clone(), auth_mw::require_auth),
+            ),
+        )
+        .route(
+            "/v1/api-keys/:id/rotate",
+            post(routes::api_keys::rotate_api_key).route_layer(
+                axum::middleware::from_fn_with_state(state.clone(), auth_mw::require_auth),
+            ),
+        )
         .route("/v1/cards/moment/:id.png", get(routes::card_png))
         .route("/v1/tokens/:mint/prices", get(routes::token_prices))
         .route("/v1/leaderboard", get(routes::leaderboard))
@@ -113,6 +257,7 @@ async fn main() -> anyhow::Result<()> {
         .route("/v1/campaigns", post(routes::campaigns::create_campaign).get(routes::campaigns::get_campaigns))
         .route("/v1/campaigns/:id/actions", post(routes::campaigns::create_campaign_action).get(routes::campaigns::get_campaign_actions))
         .route("/v1/campaigns/:id/participate", post(routes::campaigns::participate_in_campaign))
+        .merge(admin)
         .merge(metrics_router())
         .layer(cors)
         .layer(axum::middleware::from_fn_with_state(
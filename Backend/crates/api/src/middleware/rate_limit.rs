@@ -150,49 +150,6 @@ pub async fn premium_rate_limit_middleware(
     Ok(response)
 }
 
-/// API key rate limiting
-#[instrument(skip(state, request, next))]
-pub async fn api_key_rate_limit_middleware(
-    State(state): State<AppState>,
-    request: Request,
-    next: Next,
-) -> Result<Response, ApiError> {
-    // Extract API key
-    let api_key = request
-        .headers()
-        .get("x-api-key")
-        .and_then(|h| h.to_str().ok());
-
-    if let Some(key) = api_key {
-        let config = get_api_key_rate_limit_config(&state, key).await?;
-        let limit_key = format!("api_key:{}", key);
-
-        let rate_limit_result = check_rate_limit(
-            &state,
-            &limit_key,
-            config.authenticated_requests_per_window,
-            &config,
-        )
-        .await?;
-
-        if rate_limit_result.exceeded {
-            return Err(ApiError::RateLimitExceeded {
-                retry_after: rate_limit_result.retry_after,
-                limit: config.authenticated_requests_per_window,
-                remaining: 0,
-            });
-        }
-
-        let mut response = next.run(request).await;
-        add_rate_limit_headers(&mut response, &rate_limit_result, &config);
-
-        Ok(response)
-    } else {
-        // No API key, continue with regular rate limiting
-        Ok(next.run(request).await)
-    }
-}
-
 /// Rate limit check result
 #[derive(Debug, Clone)]
 struct RateLimitResult {
@@ -544,16 +501,6 @@ fn record_rate_limit_metrics(state: &AppState, result: &RateLimitResult) {
     }
 }
 
-/// Get API key specific rate limit configuration
-async fn get_api_key_rate_limit_config(
-    state: &AppState,
-    api_key: &str,
-) -> Result<RateLimitConfig, ApiError> {
-    // In a real implementation, this would look up API key settings in database
-    // For now, return default config
-    Ok(RateLimitConfig::default())
-}
-
 /// Rate limit status endpoint
 pub async fn rate_limit_status(
     State(state): State<AppState>,
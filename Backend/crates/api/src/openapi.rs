@@ -0,0 +1,241 @@
+//! OpenAPI 3.1 document for the HTTP API, generated from the handlers'
+//! `#[utoipa::path]` attributes and the DTOs they return. Served at
+//! `GET /v1/openapi.json`; the tests below fail when a route mounted in
+//! `main.rs` is missing from it.
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use utoipa::openapi::{
+    security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    ContentBuilder, Ref, ResponseBuilder,
+};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::auth_mw::API_KEY_HEADER;
+use crate::routes;
+
+/// Body of every error response, as written by `ApiError`
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Human-readable message
+    pub error: String,
+    /// The HTTP status code
+    pub code: u16,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "OOF Backend API",
+        version = "1.0.0",
+        description = "Wallet analysis, OOF moments, exports and pricing."
+    ),
+    paths(
+        routes::health,
+        routes::ready,
+        routes::openapi_spec,
+        routes::metrics,
+        routes::auth_verify,
+        routes::analyze,
+        routes::cancel_analysis,
+        routes::analyze_stream,
+        routes::moments_list,
+        routes::moment_detail,
+        routes::mint_moment_nft,
+        routes::get_moment_nft,
+        routes::similar_moments::similar_moments,
+        routes::wallet_compare::compare_wallets,
+        routes::wallet_summary,
+        routes::wallet_moments,
+        routes::wallet_equity::wallet_equity,
+        routes::wallet_timeline::wallet_timeline,
+        routes::wallet_extremes,
+        routes::subscribe_wallet_alerts,
+        routes::unsubscribe_wallet_alerts,
+        routes::alert_config::get_alert_config,
+        routes::alert_config::put_alert_config,
+        routes::exports::create_export,
+        routes::exports::get_export,
+        routes::exports::download_export_file,
+        routes::exports::tax_report,
+        routes::api_keys::create_api_key,
+        routes::api_keys::list_api_keys,
+        routes::api_keys::rotate_api_key,
+        routes::api_keys::revoke_api_key,
+        routes::card_png,
+        routes::token_prices,
+        routes::tokens::trending_tokens,
+        routes::leaderboard,
+        routes::campaigns::create_campaign,
+        routes::campaigns::get_campaigns,
+        routes::campaigns::create_campaign_action,
+        routes::campaigns::get_campaign_actions,
+        routes::campaigns::participate_in_campaign,
+        routes::admin::list_jobs,
+        routes::admin::get_job,
+        routes::admin::retry_job,
+        routes::admin::cancel_job,
+        routes::admin::purge_jobs,
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&SecuritySchemes, &ServerErrors),
+    tags(
+        (name = "system", description = "Health, readiness, metrics and this document"),
+        (name = "auth", description = "Session verification"),
+        (name = "analysis", description = "Wallet analysis jobs"),
+        (name = "moments", description = "Detected OOF moments"),
+        (name = "wallets", description = "Per-wallet summaries, metrics and alerts"),
+        (name = "exports", description = "Data exports and tax reports"),
+        (name = "api-keys", description = "API keys for programmatic access"),
+        (name = "tokens", description = "Token prices and trends"),
+        (name = "leaderboard", description = "Wallet leaderboards"),
+        (name = "campaigns", description = "Social campaigns"),
+        (name = "admin", description = "Job queue operations"),
+    )
+)]
+pub struct ApiDoc;
+
+/// `bearerAuth` is a Dynamic session JWT; `apiKey` is a key minted at
+/// `/v1/api-keys`
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearerAuth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Dynamic session JWT"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "apiKey",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                API_KEY_HEADER,
+                "API key; `Authorization: Bearer oof_...` is accepted too",
+            ))),
+        );
+    }
+}
+
+/// Any handler can fail with a 500, so document it once here rather than
+/// on every path
+struct ServerErrors;
+
+impl Modify for ServerErrors {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let response = ResponseBuilder::new()
+            .description("Internal server error")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ErrorBody")))
+                    .build(),
+            )
+            .build();
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                operation
+                    .responses
+                    .responses
+                    .entry("500".to_string())
+                    .or_insert_with(|| response.clone().into());
+            }
+        }
+    }
+}
+
+static SPEC_JSON: Lazy<String> = Lazy::new(|| {
+    ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("OpenAPI document serializes")
+});
+
+/// The generated document, rendered once
+pub fn spec_json() -> &'static str {
+    &SPEC_JSON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::collections::BTreeSet;
+
+    /// `(method, path)` for every route mounted in `main.rs`, with axum's
+    /// `:param` segments in OpenAPI's `{param}` form
+    fn mounted_routes() -> BTreeSet<(String, String)> {
+        let path_re = Regex::new(r#"^\s*"(/[^"]*)""#).unwrap();
+        let method_re = Regex::new(r"\b(get|post|put|patch|delete)\(routes::").unwrap();
+        let param_re = Regex::new(r":([A-Za-z_]+)").unwrap();
+
+        let mut routes = BTreeSet::new();
+        for chunk in include_str!("main.rs").split(".route(").skip(1) {
+            let path = &path_re.captures(chunk).expect("route path literal")[1];
+            let path = param_re.replace_all(path, "{$1}").to_string();
+            for method in method_re.captures_iter(chunk) {
+                routes.insert((method[1].to_string(), path.clone()));
+            }
+        }
+        routes
+    }
+
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let spec: serde_json::Value = serde_json::from_str(spec_json()).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for method in ["get", "put", "post", "patch", "delete"] {
+                if item.get(method).is_some() {
+                    routes.insert((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn test_every_mounted_route_is_documented() {
+        let mounted = mounted_routes();
+        assert!(mounted.contains(&(
+            "get".to_string(),
+            "/v1/wallets/{wallet}/moments".to_string()
+        )));
+
+        let documented = documented_routes();
+        let missing: Vec<_> = mounted.difference(&documented).collect();
+        assert!(
+            missing.is_empty(),
+            "mounted but not documented: {:?}",
+            missing
+        );
+        let stale: Vec<_> = documented.difference(&mounted).collect();
+        assert!(stale.is_empty(), "documented but not mounted: {:?}", stale);
+    }
+
+    #[test]
+    fn test_spec_declares_auth_and_errors() {
+        let spec: serde_json::Value = serde_json::from_str(spec_json()).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+
+        let schemes = &spec["components"]["securitySchemes"];
+        assert_eq!(schemes["bearerAuth"]["scheme"], "bearer");
+        assert_eq!(schemes["apiKey"]["name"], API_KEY_HEADER);
+        assert!(spec["components"]["schemas"]["ErrorBody"].is_object());
+        assert!(spec["components"]["schemas"]["PaginationInfo"].is_object());
+
+        let analyze = &spec["paths"]["/v1/analyze"]["post"];
+        assert!(analyze["responses"]["500"].is_object());
+        assert_eq!(analyze["security"].as_array().unwrap().len(), 2);
+    }
+}
@@ -1,33 +1,53 @@
 use std::{sync::Mutex, collections::HashMap, time::{SystemTime, UNIX_EPOCH}};
 use axum::{http::StatusCode, response::Response};
 
+use crate::auth_mw::AuthUser;
+
 static RL: once_cell::sync::Lazy<Mutex<HashMap<String, (u64, u32)>>> = once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
 
 fn now_minute() -> u64 { SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() / 60 }
 
-fn extract_client_ip<B>(req: &axum::http::Request<B>) -> String {
+pub(crate) fn extract_client_ip(headers: &axum::http::HeaderMap) -> String {
     // Prefer X-Forwarded-For, then X-Real-IP, else fallback token
-    if let Some(ip) = req
-        .headers()
+    if let Some(ip) = headers
         .get("x-forwarded-for")
         .and_then(|h| h.to_str().ok())
     {
         return ip.split(',').next().unwrap_or("unknown").trim().to_string();
     }
-    if let Some(ip) = req.headers().get("x-real-ip").and_then(|h| h.to_str().ok()) {
+    if let Some(ip) = headers.get("x-real-ip").and_then(|h| h.to_str().ok()) {
         return ip.to_string();
     }
     "unknown".to_string()
 }
 
-pub async fn per_ip_limit<B>(req: axum::http::Request<B>, next: axum::middleware::Next<B>) -> Result<Response, StatusCode> {
-    let ip = extract_client_ip(&req);
+/// Count a request against `key`'s current minute; false once `limit`
+/// requests were already made in it
+fn take(key: String, limit: u32) -> bool {
     let mut map = RL.lock().unwrap();
-    let (win, cnt) = map.get(&ip).cloned().unwrap_or((now_minute(), 0));
-    let limit = 60u32;
+    let (win, cnt) = map.get(&key).cloned().unwrap_or((now_minute(), 0));
     let (new_win, mut new_cnt) = if win == now_minute() { (win, cnt) } else { (now_minute(), 0) };
-    if new_cnt >= limit { return Err(StatusCode::TOO_MANY_REQUESTS); }
+    if new_cnt >= limit { return false; }
     new_cnt += 1;
-    map.insert(ip, (new_win, new_cnt));
+    map.insert(key, (new_win, new_cnt));
+    true
+}
+
+pub async fn per_ip_limit<B>(req: axum::http::Request<B>, next: axum::middleware::Next<B>) -> Result<Response, StatusCode> {
+    let ip = extract_client_ip(req.headers());
+    if !take(ip, 60) { return Err(StatusCode::TOO_MANY_REQUESTS); }
+    Ok(next.run(req).await)
+}
+
+/// Limit API key traffic to the owner's plan rate, shared by all of the
+/// owner's keys so minting more keys buys no extra rate. Must sit inside
+/// the auth layer; sessions and anonymous requests pass through.
+pub async fn per_key_limit<B>(req: axum::http::Request<B>, next: axum::middleware::Next<B>) -> Result<Response, StatusCode> {
+    let caller = req.extensions().get::<AuthUser>();
+    if let Some((user, key)) = caller.and_then(|u| Some((u, u.api_key.as_ref()?))) {
+        if !take(format!("api:{}", user.user_id), key.requests_per_minute) {
+            return Err(StatusCode::TOO_MANY_REQUESTS);
+        }
+    }
     Ok(next.run(req).await)
 }
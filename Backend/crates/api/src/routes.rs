@@ -1,8 +1,9 @@
 use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
 use async_stream::stream;
 use axum::{
     extract::{ws::WebSocket, Path, Query, State, WebSocketUpgrade},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive},
         IntoResponse, Response, Sse,
@@ -10,17 +11,22 @@ use axum::{
     Json,
 };
 use base64::{engine::general_purpose::URL_SAFE as BASE64_URL_SAFE, Engine};
+use detectors::prices::CompositePriceProvider;
 use futures::{stream::StreamExt as _, SinkExt, Stream, StreamExt};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use shared::{
+    api_keys::ApiScope,
     constants::api::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+    equity::EQUITY_JOB_KIND,
+    moment_query::{MomentCursor, MomentFilter, MomentQuery, MomentRow, MomentSort},
     observability::{HealthChecker, MetricsRegistry},
     store::ObjectStore,
     utils::{new_id, new_request_id, truncate_wallet},
     validation::{validate_moment_kinds, validate_pagination, validate_wallet_address},
     ApiError, ApiResult, AppConfig, AuthMethod, MaybeRedis, Metrics, Pg, PolicyService,
-    UserContext,
+    JobSpec, LeaderboardMetric, LeaderboardPeriod, ParentFailure, TrackReason,
+    TrackedWalletRegistry, UserContext,
 };
 use sqlx::Row;
 use std::{collections::HashMap, sync::Arc};
@@ -29,9 +35,22 @@ use tokio::sync::broadcast;
 use tower_http::cors::CorsLayer;
 use tracing::{error, info, instrument, warn};
 use ulid::Ulid;
+use utoipa::{IntoParams, ToSchema};
 
 pub mod tokens;
 pub mod campaigns;
+pub mod admin;
+pub mod alert_config;
+pub mod exports;
+pub mod moment_context;
+pub mod similar_moments;
+pub mod wallet_compare;
+pub mod wallet_equity;
+pub mod wallet_timeline;
+pub mod api_keys;
+
+/// Fallback poll interval for analysis progress streams
+const PROGRESS_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[derive(Clone)]
 pub struct AppState {
@@ -44,6 +63,7 @@ pub struct AppState {
     pub sse_broadcast: broadcast::Sender<String>,
     pub metrics_registry: Arc<MetricsRegistry>,
     pub health_checker: Arc<HealthChecker>,
+    pub price_provider: Arc<CompositePriceProvider>,
 }
 
 impl AppState {
@@ -57,6 +77,11 @@ impl AppState {
         health_checker: Arc<HealthChecker>,
     ) -> Self {
         let policy_service = PolicyService::new(pg.0.clone());
+        let price_provider = Arc::new(CompositePriceProvider::new(
+            pg.0.clone(),
+            redis.clone(),
+            cfg.jupiter_base_url.clone(),
+        ));
         let (sse_broadcast, _) = broadcast::channel(1000);
 
         Self {
@@ -69,17 +94,18 @@ impl AppState {
             sse_broadcast,
             metrics_registry: Arc::new(metrics_registry),
             health_checker,
+            price_provider,
         }
     }
 }
 
 // Auth verification request/response
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AuthVerifyRequest {
     pub token: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AuthVerifyResponse {
     pub user_id: String,
     pub wallet_address: Option<String>,
@@ -87,12 +113,12 @@ pub struct AuthVerifyResponse {
 }
 
 // NFT minting request/response
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct MintNftRequest {
     pub moment_id: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MintNftResponse {
     pub job_id: String,
     pub status: String,
@@ -100,6 +126,16 @@ pub struct MintNftResponse {
 }
 
 /// POST /auth/verify - Verify Dynamic.xyz JWT token
+#[utoipa::path(
+    post,
+    path = "/auth/verify",
+    tag = "auth",
+    request_body = AuthVerifyRequest,
+    responses(
+        (status = 200, description = "Token is valid", body = AuthVerifyResponse),
+        (status = 401, description = "Invalid token", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn auth_verify(
     State(state): State<AppState>,
@@ -120,6 +156,19 @@ pub async fn auth_verify(
 }
 
 /// POST /v1/moments/:id/mint - Mint an NFT for a moment
+#[utoipa::path(
+    post,
+    path = "/v1/moments/{id}/mint",
+    tag = "moments",
+    params(("id" = String, Path, description = "Moment ID (ULID)")),
+    request_body = MintNftRequest,
+    responses(
+        (status = 200, description = "Minting job queued", body = MintNftResponse),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 404, description = "Moment not found or not owned by the caller", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
 #[instrument(skip(state, user))]
 pub async fn mint_moment_nft(
     State(state): State<AppState>,
@@ -150,13 +199,14 @@ pub async fn mint_moment_nft(
     });
 
     sqlx::query!(
-        "INSERT INTO job_queue (id, kind, payload_json, max_attempts, run_after, created_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        "INSERT INTO job_queue (id, kind, payload_json, max_attempts, run_after, created_at, tenant) VALUES ($1, $2, $3, $4, $5, $6, $7)",
         job_id,
         "mint_nft",
         payload,
         3,
         OffsetDateTime::now_utc(),
-        OffsetDateTime::now_utc()
+        OffsetDateTime::now_utc(),
+        user.user_id
     )
     .execute(&state.pg.0)
     .await
@@ -170,6 +220,16 @@ pub async fn mint_moment_nft(
 }
 
 /// GET /v1/moments/:id/nft - Get NFT details for a moment
+#[utoipa::path(
+    get,
+    path = "/v1/moments/{id}/nft",
+    tag = "moments",
+    params(("id" = String, Path, description = "Moment ID (ULID)")),
+    responses(
+        (status = 200, description = "Minted NFT for the moment", body = serde_json::Value),
+        (status = 404, description = "No NFT minted for this moment", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn get_moment_nft(
     State(state): State<AppState>,
@@ -223,9 +283,17 @@ fn compute_display_meta(kind: &str, severity_dec: Option<&str>) -> DisplayMeta {
     }
 }
 
-/// GET /v1/openapi.json - Serve static OpenAPI spec for v1
+/// GET /v1/openapi.json - OpenAPI spec generated from the handlers below
+#[utoipa::path(
+    get,
+    path = "/v1/openapi.json",
+    tag = "system",
+    responses(
+        (status = 200, description = "This OpenAPI document", body = serde_json::Value),
+    )
+)]
 pub async fn openapi_spec() -> Response {
-    let body = include_str!("../../../schemas/http/openapi.v1.json");
+    let body = crate::openapi::spec_json();
     (
         axum::http::StatusCode::OK,
         [(axum::http::header::CONTENT_TYPE, "application/json")],
@@ -235,6 +303,15 @@ pub async fn openapi_spec() -> Response {
 }
 
 /// GET /ready - Readiness probe: verify core dependencies
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "system",
+    responses(
+        (status = 200, description = "Ready", body = String),
+        (status = 503, description = "Not ready", body = String),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn ready(State(state): State<AppState>) -> Response {
     // Check DB
@@ -269,14 +346,14 @@ pub async fn ready(State(state): State<AppState>) -> Response {
 }
 
 // Request/Response DTOs
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AnalyzeRequest {
     pub wallets: Vec<String>,
     #[serde(rename = "planCode")]
     pub plan_code: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AnalyzeResponse {
     #[serde(rename = "jobId")]
     pub job_id: String,
@@ -286,17 +363,93 @@ pub struct AnalyzeResponse {
     pub estimated_time_seconds: u32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MomentsQuery {
     pub wallet: Option<String>,
+    /// Comma-separated wallets
+    pub wallets: Option<String>,
+    pub mint: Option<String>,
     pub kinds: Option<String>,
+    /// RFC 3339, inclusive
     pub since: Option<String>,
+    /// RFC 3339, exclusive
+    pub until: Option<String>,
     pub min_usd: Option<String>,
+    pub max_usd: Option<String>,
+    pub min_severity: Option<String>,
+    pub version: Option<String>,
+    /// time (default), severity or missed_usd
+    pub sort: Option<String>,
     pub limit: Option<usize>,
     pub cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+impl MomentsQuery {
+    /// Validate the parameters into a moment query
+    pub fn to_moment_query(&self) -> ApiResult<MomentQuery> {
+        let (limit, cursor) = validate_pagination(self.limit, self.cursor.as_deref())?;
+        let sort = match self.sort.as_deref() {
+            Some(s) => MomentSort::parse(s)
+                .ok_or_else(|| ApiError::BadRequest(format!("Invalid sort: {}", s)))?,
+            None => MomentSort::Time,
+        };
+        let cursor = cursor
+            .map(|c| MomentCursor::decode(&c, sort))
+            .transpose()?;
+
+        let mut wallets: Vec<String> = self.wallet.iter().cloned().collect();
+        if let Some(list) = &self.wallets {
+            wallets.extend(
+                list.split(',')
+                    .map(|w| w.trim().to_string())
+                    .filter(|w| !w.is_empty()),
+            );
+        }
+        for wallet in &wallets {
+            validate_wallet_address(wallet)?;
+        }
+
+        let filter = MomentFilter {
+            wallets,
+            exclude_wallets: Vec::new(),
+            mint: self.mint.clone(),
+            kinds: validate_moment_kinds(self.kinds.as_deref())?,
+            since: parse_rfc3339_param("since", self.since.as_deref())?,
+            until: parse_rfc3339_param("until", self.until.as_deref())?,
+            min_usd: parse_decimal_param("min_usd", self.min_usd.as_deref())?,
+            max_usd: parse_decimal_param("max_usd", self.max_usd.as_deref())?,
+            min_severity: parse_decimal_param("min_severity", self.min_severity.as_deref())?,
+            version: self.version.clone(),
+        };
+
+        Ok(MomentQuery::new()
+            .with_filter(filter)
+            .with_sort(sort)
+            .with_cursor(cursor)
+            .with_limit(limit))
+    }
+}
+
+fn parse_rfc3339_param(name: &str, value: Option<&str>) -> ApiResult<Option<OffsetDateTime>> {
+    value
+        .map(|v| {
+            OffsetDateTime::parse(v, &time::format_description::well_known::Rfc3339)
+                .map_err(|_| ApiError::BadRequest(format!("{} must be an RFC 3339 timestamp", name)))
+        })
+        .transpose()
+}
+
+fn parse_decimal_param(name: &str, value: Option<&str>) -> ApiResult<Option<Decimal>> {
+    value
+        .map(|v| {
+            v.parse::<Decimal>()
+                .map_err(|_| ApiError::BadRequest(format!("{} must be a number", name)))
+        })
+        .transpose()
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct DisplayMeta {
     pub emoji: String,
     #[serde(rename = "gradientFrom")]
@@ -306,7 +459,7 @@ pub struct DisplayMeta {
     pub rarity: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MomentDto {
     pub id: String,
     pub wallet: String,
@@ -337,16 +490,54 @@ pub struct MomentDto {
     pub token_symbol: Option<String>,
     #[serde(rename = "tokenLogoUrl", skip_serializing_if = "Option::is_none")]
     pub token_logo_url: Option<String>,
+    /// Only on `GET /v1/moments/:id?context=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<moment_context::MomentContextDto>,
 }
 
-#[derive(Serialize)]
+impl MomentDto {
+    pub fn from_row(row: MomentRow, cdn_base: &str) -> Self {
+        let severity_dec = row.severity_dec.map(|d| d.to_string());
+        let display = compute_display_meta(&row.kind, severity_dec.as_deref());
+        let card_url = format!(
+            "{}/v1/cards/moment/{}.png",
+            cdn_base.trim_end_matches('/'),
+            row.id
+        );
+        MomentDto {
+            id: row.id,
+            wallet: row.wallet,
+            mint: row.mint,
+            kind: row.kind,
+            t_event: row
+                .t_event
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default(),
+            pct_dec: row.pct_dec.map(|d| d.to_string()),
+            missed_usd_dec: row.missed_usd_dec.map(|d| d.to_string()),
+            severity_dec,
+            sig_ref: row.sig_ref,
+            slot_ref: row.slot_ref,
+            version: row.version,
+            explain_json: row.explain_json.unwrap_or(serde_json::json!({})),
+            preview_png_url: row.preview_png_url,
+            card_url,
+            display: Some(display),
+            token_symbol: row.token_symbol,
+            token_logo_url: row.token_logo_url,
+            context: None,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct MomentsListResponse {
     pub data: Vec<MomentDto>,
     pub pagination: PaginationInfo,
     pub total_count: Option<i64>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PaginationInfo {
     pub limit: usize,
     pub cursor: Option<String>,
@@ -356,7 +547,7 @@ pub struct PaginationInfo {
     pub next_cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct WalletSummaryResponse {
     pub wallet: String,
     pub holdings: Vec<HoldingDto>,
@@ -369,7 +560,7 @@ pub struct WalletSummaryResponse {
     pub analysis_range: Option<AnalysisRange>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct HoldingDto {
     pub mint: String,
     pub symbol: Option<String>,
@@ -380,7 +571,7 @@ pub struct HoldingDto {
     pub unrealized_pnl_usd: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MomentCounts {
     pub s2e: i64,
     pub bhd: i64,
@@ -390,7 +581,7 @@ pub struct MomentCounts {
     pub total: i64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AnalysisRange {
     pub from: String,
     pub to: String,
@@ -398,7 +589,7 @@ pub struct AnalysisRange {
     pub days_analyzed: i64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct WalletExtremesResponse {
     pub wallet: String,
     #[serde(rename = "computedAt")]
@@ -417,7 +608,7 @@ pub struct WalletExtremesResponse {
     pub largest_idle: Option<ExtremeDto>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ExtremeDto {
     pub id: String,
     pub mint: Option<String>,
@@ -430,30 +621,55 @@ pub struct ExtremeDto {
     pub card_url: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PriceQuery {
     pub tf: Option<String>, // timeframe
     pub since: Option<String>,
     pub limit: Option<usize>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PricePoint {
     pub timestamp: String,
     pub price: String,
     pub source: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, ToSchema)]
+pub struct TokenPricesResponse {
+    pub mint: String,
+    /// `1m`, `5m`, `1h` or `raw`
+    pub timeframe: String,
+    pub count: usize,
+    pub data: Vec<PricePoint>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct LeaderboardQuery {
     pub period: Option<String>, // 7d, 30d, 90d
-    pub metric: Option<String>, // highest_gains, most_s2e, worst_bhd
+    pub metric: Option<String>, // see LeaderboardMetric::as_str
     pub limit: Option<usize>,
 }
 
 // API Route Handlers
 
 /// POST /v1/analyze - Start wallet analysis
+#[utoipa::path(
+    post,
+    path = "/v1/analyze",
+    tag = "analysis",
+    request_body = AnalyzeRequest,
+    responses(
+        (status = 200, description = "Analysis queued", body = AnalyzeResponse),
+        (status = 400, description = "Invalid wallets", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 402, description = "Plan quota exceeded", body = ErrorBody),
+        (status = 403, description = "API key lacks the analyze scope", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []), ("apiKey" = []))
+)]
 #[instrument(skip(state), fields(user_id, wallet_count))]
 pub async fn analyze(
     State(state): State<AppState>,
@@ -463,6 +679,7 @@ pub async fn analyze(
     let request_id = new_request_id();
     tracing::Span::current().record("user_id", &user.user_id);
     tracing::Span::current().record("wallet_count", req.wallets.len());
+    user.require_scope(ApiScope::Analyze)?;
 
     // Validate wallets
     for wallet in &req.wallets {
@@ -493,42 +710,60 @@ pub async fn analyze(
         .consume_analysis_quota(&user.user_id, req.wallets.len() as i32)
         .await?;
 
-    // Enqueue backfill jobs
+    // Keep analyzed wallets on the live webhook for the plan's TTL
+    let registry = TrackedWalletRegistry::new(state.pg.0.clone());
+    for wallet in &req.wallets {
+        registry
+            .track(
+                wallet,
+                &user.user_id,
+                TrackReason::Analyze,
+                &user_context.plan.code,
+            )
+            .await?;
+    }
+    registry
+        .request_sync(&user.user_id, user_context.job_priority())
+        .await?;
+
+    // Enqueue the analysis as a DAG: one backfill per wallet, then a compute
+    // job that waits for all of them and an equity job after that. The
+    // returned job ID is the DAG root.
     let job_id = new_id();
+    let priority = user_context.job_priority();
+    let mut jobs = Vec::with_capacity(req.wallets.len() + 2);
     for wallet in &req.wallets {
         let backfill_payload = serde_json::json!({
             "wallet": wallet,
             "backfill_days": user_context.plan.backfill_days,
-            "max_signatures": user_context.plan.max_signatures_per_run
+            "max_signatures": user_context.plan.max_signatures_per_run,
+            "max_enhanced_tx": user_context.plan.max_enhanced_tx_per_run,
+            "skip_compute": true
         });
-
-        sqlx::query!(
-            include_str!("../../../db/queries/enqueue_job.sql"),
-            new_id(),
-            "backfill",
-            backfill_payload,
-            OffsetDateTime::now_utc(),
-            5i32
-        )
-        .execute(&state.pg.0)
-        .await?;
+        jobs.push(
+            JobSpec::new("backfill", backfill_payload)
+                .with_priority(priority.clone())
+                .with_tenant(&user.user_id),
+        );
     }
 
-    // Enqueue compute job
+    let backfill_ids: Vec<String> = jobs.iter().map(|j| j.id.clone()).collect();
     let compute_payload = serde_json::json!({
         "wallets": req.wallets
     });
-
-    sqlx::query!(
-        include_str!("../../../db/queries/enqueue_job.sql"),
-        job_id.clone(),
-        "compute",
-        compute_payload,
-        OffsetDateTime::now_utc(),
-        5i32
-    )
-    .execute(&state.pg.0)
-    .await?;
+    let compute = JobSpec::new("compute", compute_payload.clone())
+        .with_priority(priority.clone())
+        .with_tenant(&user.user_id)
+        .after(&backfill_ids, ParentFailure::Cancel);
+    // Equity curves read the positions compute just rebuilt
+    let equity = JobSpec::new(EQUITY_JOB_KIND, compute_payload)
+        .with_priority(priority)
+        .with_tenant(&user.user_id)
+        .after(&[compute.id.clone()], ParentFailure::Cancel);
+    jobs.push(compute);
+    jobs.push(equity);
+
+    shared::queue::enqueue_dag(&state.pg.0, &job_id, &jobs).await?;
 
     // Estimate completion time based on plan
     let estimated_time = match user_context.plan.perks.priority_queue {
@@ -553,47 +788,213 @@ pub async fn analyze(
     }))
 }
 
-/// GET /v1/analyze/:job/stream - SSE stream for job progress
+/// POST /v1/wallets/:wallet/alerts - Subscribe to live alerts for a wallet,
+/// up to the plan's `alerts` wallets
+#[utoipa::path(
+    post,
+    path = "/v1/wallets/{wallet}/alerts",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address")),
+    responses(
+        (status = 200, description = "Subscribed to live alerts", body = serde_json::Value),
+        (status = 400, description = "Invalid wallet address or alert limit reached", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Plan has no alerts", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
 #[instrument(skip(state))]
-pub async fn analyze_stream(State(state): State<AppState>, Path(job_id): Path<String>) -> Response {
-    let stream = stream! {
-        // Send initial status
-        yield Ok(Event::default().event("progress").data("Checking job status..."));
+pub async fn subscribe_wallet_alerts(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(wallet): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    validate_wallet_address(&wallet)?;
 
+    let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+    let limit = user_context.plan.alerts;
+    if limit <= 0 {
+        return Err(ApiError::Forbidden);
+    }
+    let registry = TrackedWalletRegistry::new(state.pg.0.clone());
+    let subscribed = registry
+        .count_other_tracked(&user.user_id, TrackReason::Alerts, &wallet)
+        .await?;
+    if subscribed >= limit as i64 {
+        return Err(ApiError::BadRequest(format!(
+            "Your plan allows alerts on {} wallets; unsubscribe one first",
+            limit
+        )));
+    }
+    registry
+        .track(
+            &wallet,
+            &user.user_id,
+            TrackReason::Alerts,
+            &user_context.plan.code,
+        )
+        .await?;
+    registry
+        .request_sync(&user.user_id, user_context.job_priority())
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "wallet": wallet,
+        "subscribed": true
+    })))
+}
+
+/// DELETE /v1/wallets/:wallet/alerts - Stop live alerts for a wallet
+#[utoipa::path(
+    delete,
+    path = "/v1/wallets/{wallet}/alerts",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address")),
+    responses(
+        (status = 200, description = "Unsubscribed from live alerts", body = serde_json::Value),
+        (status = 400, description = "Invalid wallet address", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn unsubscribe_wallet_alerts(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(wallet): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    validate_wallet_address(&wallet)?;
+
+    let registry = TrackedWalletRegistry::new(state.pg.0.clone());
+    let removed = registry
+        .untrack(&wallet, &user.user_id, TrackReason::Alerts)
+        .await?;
+    if removed {
+        let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+        registry
+            .request_sync(&user.user_id, user_context.job_priority())
+            .await?;
+    }
+
+    Ok(Json(serde_json::json!({
+        "wallet": wallet,
+        "subscribed": false
+    })))
+}
+
+/// DELETE /v1/analyze/:job - Cancel the caller's analysis. Jobs that have
+/// not started are cancelled at once; running ones stop at their next
+/// checkpoint.
+#[utoipa::path(
+    delete,
+    path = "/v1/analyze/{job}",
+    tag = "analysis",
+    params(("job" = String, Path, description = "Root job ID")),
+    responses(
+        (status = 200, description = "Cancellation result", body = serde_json::Value),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "API key lacks the analyze scope", body = ErrorBody),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn cancel_analysis(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    user.require_scope(ApiScope::Analyze)?;
+    let owner = sqlx::query_scalar!(
+        "SELECT tenant FROM job_queue WHERE root_id = $1 OR id = $1 LIMIT 1",
+        job_id
+    )
+    .fetch_optional(&state.pg.0)
+    .await?
+    .ok_or(ApiError::JobNotFound)?;
+    if owner.as_deref() != Some(user.user_id.as_str()) {
+        // Don't reveal other users' job IDs
+        return Err(ApiError::JobNotFound);
+    }
+
+    let (cancelled, stopping) =
+        shared::queue::cancel_dag(&state.pg.0, &job_id, "cancelled by user").await?;
+    info!(job_id = %job_id, cancelled, stopping, "Analysis cancelled by user");
+
+    Ok(Json(serde_json::json!({
+        "job_id": job_id,
+        "cancelled": cancelled,
+        "stopping": stopping
+    })))
+}
+
+/// GET /v1/analyze/:job/stream - SSE stream of analysis progress. Each
+/// `progress` event carries a `BackfillProgress` and its event id; clients
+/// reconnecting with `Last-Event-ID` get only the events they missed.
+#[utoipa::path(
+    get,
+    path = "/v1/analyze/{job}/stream",
+    tag = "analysis",
+    params(("job" = String, Path, description = "Root job ID"), ("Last-Event-ID" = Option<String>, Header, description = "Resume after this event")),
+    responses(
+        (status = 200, description = "Server-sent `progress` events", content_type = "text/event-stream", body = String),
+    )
+)]
+pub async fn analyze_stream(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let resume_from = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(0);
+    // Subscribe before the first read so no notification slips between them
+    let mut notifications = state.sse_broadcast.subscribe();
+
+    let stream = stream! {
+        let mut last_event_id = resume_from;
         let mut last_status = String::new();
-        let mut progress_count = 0;
 
         loop {
-            // Check job status
-            let status = sqlx::query_scalar!(
-                "SELECT status FROM job_queue WHERE id = $1",
-                job_id
-            )
-            .fetch_optional(&state.pg.0)
-            .await
-            .ok()
-            .flatten();
-
-            match status.as_deref() {
-                Some("queued") => {
-                    if last_status != "queued" {
-                        yield Ok(Event::default().event("progress").data("Queued for processing..."));
-                        last_status = "queued".to_string();
+            // Read the status first: once it is terminal every event the
+            // workers wrote is already visible below
+            let status = shared::queue::dag_status(&state.pg.0, &job_id)
+                .await
+                .ok()
+                .flatten()
+                .map(|s| s.status);
+
+            loop {
+                let events = shared::progress::events_since(&state.pg.0, &job_id, last_event_id).await;
+                let events = match events {
+                    Ok(events) => events,
+                    Err(e) => {
+                        warn!(job_id = %job_id, error = %e, "Failed to load progress events");
+                        break;
                     }
+                };
+                let Some((last, _)) = events.last() else {
+                    break;
+                };
+                last_event_id = *last;
+                for (id, progress) in events {
+                    yield Event::default()
+                        .id(id.to_string())
+                        .event("progress")
+                        .json_data(&progress);
                 }
-                Some("running") => {
-                    if last_status != "running" {
-                        yield Ok(Event::default().event("progress").data("Processing wallet data..."));
-                        last_status = "running".to_string();
-                    }
+            }
 
-                    progress_count += 1;
-                    if progress_count % 10 == 0 {
-                        yield Ok(Event::default().event("progress").data("Still processing..."));
+            match status.as_deref() {
+                Some(s @ ("queued" | "blocked" | "running")) => {
+                    if last_status != s {
+                        yield Ok(Event::default().event("status").data(s));
+                        last_status = s.to_string();
                     }
                 }
                 Some("done") => {
-                    yield Ok(Event::default().event("progress").data("Analysis complete!"));
                     yield Ok(Event::default().event("done").data(&job_id));
                     break;
                 }
@@ -601,6 +1002,10 @@ pub async fn analyze_stream(State(state): State<AppState>, Path(job_id): Path<St
                     yield Ok(Event::default().event("error").data("Analysis failed"));
                     break;
                 }
+                Some("cancelled") => {
+                    yield Ok(Event::default().event("error").data("Analysis cancelled"));
+                    break;
+                }
                 None => {
                     yield Ok(Event::default().event("error").data("Job not found"));
                     break;
@@ -608,7 +1013,21 @@ pub async fn analyze_stream(State(state): State<AppState>, Path(job_id): Path<St
                 _ => {}
             }
 
-            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            // Wake on this DAG's next event; the timeout catches status
+            // changes that publish no event and missed notifications
+            let _ = tokio::time::timeout(PROGRESS_POLL_INTERVAL, async {
+                loop {
+                    match notifications.recv().await {
+                        Ok(root_id) if root_id == job_id => break,
+                        Ok(_) => continue,
+                        Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            std::future::pending::<()>().await
+                        }
+                    }
+                }
+            })
+            .await;
         }
     };
 
@@ -618,187 +1037,134 @@ pub async fn analyze_stream(State(state): State<AppState>, Path(job_id): Path<St
 }
 
 /// GET /v1/moments - List moments with filtering and pagination
+#[utoipa::path(
+    get,
+    path = "/v1/moments",
+    tag = "moments",
+    params(MomentsQuery),
+    responses(
+        (status = 200, description = "A page of moments", body = MomentsListResponse),
+        (status = 400, description = "Invalid filters or cursor", body = ErrorBody),
+        (status = 401, description = "Invalid credentials", body = ErrorBody),
+        (status = 402, description = "API key row quota used up", body = ErrorBody),
+        (status = 403, description = "API key lacks the read:moments scope", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    ),
+    security((), ("bearerAuth" = []), ("apiKey" = []))
+)]
 #[instrument(skip(state))]
 pub async fn moments_list(
     State(state): State<AppState>,
+    user: Option<AuthUser>,
     Query(query): Query<MomentsQuery>,
 ) -> ApiResult<Json<MomentsListResponse>> {
-    let (limit, cursor) = validate_pagination(query.limit, query.cursor.as_deref())?;
-    let moment_kinds = validate_moment_kinds(query.kinds.as_deref())?;
-
-    // Validate wallet if provided
-    if let Some(ref wallet) = query.wallet {
-        validate_wallet_address(wallet)?;
-    }
+    let moment_query = query.to_moment_query()?;
+    let response = list_moments(&state, user.as_ref(), moment_query, query.cursor).await?;
 
-    let mut params: Vec<&dyn sqlx::postgres::PgHasArrayType> = Vec::new();
-    let mut param_idx = 1;
-    let mut where_clauses = Vec::new();
+    state
+        .metrics
+        .increment_counter("moments_list_requests_total");
 
-    // Build query dynamically
-    if let Some(ref wallet) = query.wallet {
-        where_clauses.push(format!("wallet = ${}", param_idx));
-        param_idx += 1;
-    }
+    Ok(Json(response))
+}
 
-    if !moment_kinds.is_empty() {
-        let kinds_str: Vec<String> = moment_kinds
-            .iter()
-            .map(|k| k.as_str().to_string())
-            .collect();
-        where_clauses.push(format!("kind = ANY(${}))", param_idx));
-        param_idx += 1;
-    }
+/// GET /v1/wallets/:wallet/moments - List one wallet's moments; accepts the
+/// same filters as /v1/moments
+#[utoipa::path(
+    get,
+    path = "/v1/wallets/{wallet}/moments",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address"), MomentsQuery),
+    responses(
+        (status = 200, description = "A page of the wallet's moments", body = MomentsListResponse),
+        (status = 400, description = "Invalid wallet, filters or cursor", body = ErrorBody),
+        (status = 401, description = "Invalid credentials", body = ErrorBody),
+        (status = 402, description = "API key row quota used up", body = ErrorBody),
+        (status = 403, description = "API key lacks the read:moments scope", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    ),
+    security((), ("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn wallet_moments(
+    State(state): State<AppState>,
+    user: Option<AuthUser>,
+    Path(wallet): Path<String>,
+    Query(query): Query<MomentsQuery>,
+) -> ApiResult<Json<MomentsListResponse>> {
+    validate_wallet_address(&wallet)?;
+    let moment_query = query.to_moment_query()?.with_wallet(wallet);
+    let response = list_moments(&state, user.as_ref(), moment_query, query.cursor).await?;
 
-    if let Some(ref since) = query.since {
-        if let Ok(since_ts) =
-            time::OffsetDateTime::parse(since, &time::format_description::well_known::Rfc3339)
-        {
-            where_clauses.push(format!("t_event >= ${}", param_idx));
-            param_idx += 1;
-        }
-    }
+    state
+        .metrics
+        .increment_counter("wallet_moments_requests_total");
 
-    if let Some(ref min_usd) = query.min_usd {
-        if let Ok(min_amount) = min_usd.parse::<Decimal>() {
-            where_clauses.push(format!("missed_usd_dec >= ${}", param_idx));
-            param_idx += 1;
-        }
-    }
+    Ok(Json(response))
+}
 
-    // Cursor pagination
-    if let Some(ref cursor) = cursor {
-        if let Ok(decoded) = BASE64_URL_SAFE.decode(cursor) {
-            if let Ok(cursor_str) = String::from_utf8(decoded) {
-                if let Some((ts_str, id_str)) = cursor_str.split_once('|') {
-                    where_clauses.push(format!(
-                        "(t_event, id) < (${}, ${})",
-                        param_idx,
-                        param_idx + 1
-                    ));
-                    param_idx += 2;
-                }
-            }
+/// API keys need `read:moments`; their pages are cut to the rows they have
+/// left this billing period and metered
+async fn list_moments(
+    state: &AppState,
+    user: Option<&AuthUser>,
+    mut moment_query: MomentQuery,
+    cursor: Option<String>,
+) -> ApiResult<MomentsListResponse> {
+    if let Some(user) = user {
+        user.require_scope(ApiScope::ReadMoments)?;
+        if let Some(key) = &user.api_key {
+            moment_query.limit = moment_query.limit.min(key.rows_remaining as usize);
         }
     }
 
-    let where_clause = if where_clauses.is_empty() {
-        String::new()
-    } else {
-        format!("WHERE {}", where_clauses.join(" AND "))
-    };
-
-    let sql = format!(
-        "SELECT m.id, m.wallet, m.mint, m.kind, m.t_event, m.pct_dec, m.missed_usd_dec,
-               m.severity_dec, m.sig_ref, m.slot_ref, m.version, m.explain_json, m.preview_png_url,
-               tf.symbol as token_symbol, tf.logo_url as token_logo_url
-         FROM oof_moments m
-         LEFT JOIN token_facts tf ON tf.mint = m.mint
-         {}
-         ORDER BY m.t_event DESC, m.id DESC
-         LIMIT {}",
-        where_clause,
-        limit + 1 // +1 to check if there are more results
-    );
-
-    // Execute query with proper parameter binding
-    let mut query_builder = sqlx::query(&sql);
-
-    if let Some(ref wallet) = query.wallet {
-        query_builder = query_builder.bind(wallet);
-    }
-
-    let rows = query_builder.fetch_all(&state.pg.0).await?;
-
-    let has_more = rows.len() > limit;
-    let data_rows = if has_more { &rows[..limit] } else { &rows };
-
-    let data: Vec<MomentDto> = data_rows
-        .iter()
-        .map(|row| {
-            let id: String = row.get("id");
-            let t_event: OffsetDateTime = row.get("t_event");
-
-            {
-                let kind: String = row.get("kind");
-                let severity_str = row
-                    .try_get::<Option<Decimal>, _>("severity_dec")
-                    .ok()
-                    .flatten()
-                    .map(|d| d.to_string());
-                let display = compute_display_meta(&kind, severity_str.as_deref());
-
-                MomentDto {
-                id: id.clone(),
-                wallet: row.get("wallet"),
-                mint: row.try_get("mint").ok(),
-                kind,
-                t_event: t_event
-                    .format(&time::format_description::well_known::Rfc3339)
-                    .unwrap_or_default(),
-                pct_dec: row
-                    .try_get::<Option<Decimal>, _>("pct_dec")
-                    .ok()
-                    .flatten()
-                    .map(|d| d.to_string()),
-                missed_usd_dec: row
-                    .try_get::<Option<Decimal>, _>("missed_usd_dec")
-                    .ok()
-                    .flatten()
-                    .map(|d| d.to_string()),
-                severity_dec: row
-                    .try_get::<Option<Decimal>, _>("severity_dec")
-                    .ok()
-                    .flatten()
-                    .map(|d| d.to_string()),
-                sig_ref: row.try_get("sig_ref").ok(),
-                slot_ref: row.try_get("slot_ref").ok(),
-                version: row.try_get("version").ok(),
-                explain_json: row
-                    .try_get::<serde_json::Value, _>("explain_json")
-                    .unwrap_or(serde_json::json!({})),
-                preview_png_url: row.try_get("preview_png_url").ok(),
-                card_url: format!(
-                    "{}/v1/cards/moment/{}.png",
-                    state.cfg.cdn_base.trim_end_matches('/'),
-                    id
-                ),
-                display: Some(display),
-                token_symbol: row.try_get("token_symbol").ok(),
-                token_logo_url: row.try_get("token_logo_url").ok(),
-            }
-        })
+    let page = moment_query.fetch_page(&state.pg.0).await?;
+    let data: Vec<MomentDto> = page
+        .rows
+        .into_iter()
+        .map(|row| MomentDto::from_row(row, &state.cfg.cdn_base))
         .collect();
+    if let Some(user) = user {
+        user.meter_rows(state, data.len()).await?;
+    }
 
-    let next_cursor = if has_more && !data.is_empty() {
-        let last_item = data.last().unwrap();
-        let cursor_data = format!("{}|{}", last_item.t_event, last_item.id);
-        Some(BASE64_URL_SAFE.encode(&cursor_data))
-    } else {
-        None
-    };
-
-    state
-        .metrics
-        .increment_counter("moments_list_requests_total");
-
-    Ok(Json(MomentsListResponse {
+    Ok(MomentsListResponse {
         data,
         pagination: PaginationInfo {
-            limit,
+            limit: moment_query.limit,
             cursor,
-            has_more,
-            next_cursor,
+            has_more: page.next_cursor.is_some(),
+            next_cursor: page.next_cursor.map(|c| c.encode()),
         },
         total_count: None, // Expensive to compute, omit for performance
-    }))
+    })
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MomentDetailQuery {
+    /// Include position, market, portfolio and counterfactual context
+    #[serde(default)]
+    pub context: bool,
 }
 
 /// GET /v1/moments/:id - Get moment details by ID
+#[utoipa::path(
+    get,
+    path = "/v1/moments/{id}",
+    tag = "moments",
+    params(("id" = String, Path, description = "Moment ID (ULID)"), MomentDetailQuery),
+    responses(
+        (status = 200, description = "The moment", body = MomentDto),
+        (status = 400, description = "Invalid moment ID", body = ErrorBody),
+        (status = 404, description = "Moment not found", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn moment_detail(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<MomentDetailQuery>,
 ) -> ApiResult<Json<MomentDto>> {
     // Validate ID format (ULID)
     if id.len() != 26 {
@@ -828,7 +1194,7 @@ pub async fn moment_detail(
             let display = compute_display_meta(&kind, severity_str.as_deref());
 
             let t_event: OffsetDateTime = row.get("t_event");
-            let moment_dto = MomentDto {
+            let mut moment_dto = MomentDto {
                 id: row.get("id"),
                 wallet: row.get("wallet"),
                 mint: row.try_get("mint").ok(),
@@ -862,8 +1228,23 @@ pub async fn moment_detail(
                 display: Some(display),
                 token_symbol: row.try_get("token_symbol").ok(),
                 token_logo_url: row.try_get("token_logo_url").ok(),
+                context: None,
             };
 
+            if query.context {
+                let context = moment_context::build_context(
+                    &state.pg.0,
+                    state.price_provider.as_ref(),
+                    &moment_dto.wallet,
+                    moment_dto.mint.as_deref(),
+                    &moment_dto.kind,
+                    t_event,
+                    &moment_dto.explain_json,
+                )
+                .await?;
+                moment_dto.context = Some(context.into());
+            }
+
             state.metrics.increment_counter("moment_detail_requests_total");
             Ok(Json(moment_dto))
         }
@@ -872,6 +1253,16 @@ pub async fn moment_detail(
 }
 
 /// GET /v1/wallets/:wallet/summary - Get wallet holdings and summary
+#[utoipa::path(
+    get,
+    path = "/v1/wallets/{wallet}/summary",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address")),
+    responses(
+        (status = 200, description = "Holdings, realized PnL and moment counts", body = WalletSummaryResponse),
+        (status = 400, description = "Invalid wallet address", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn wallet_summary(
     State(state): State<AppState>,
@@ -996,6 +1387,17 @@ pub async fn wallet_summary(
 }
 
 /// GET /v1/wallets/:wallet/extremes - Get wallet's extreme moments and trades
+#[utoipa::path(
+    get,
+    path = "/v1/wallets/{wallet}/extremes",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address")),
+    responses(
+        (status = 200, description = "The wallet's most extreme moments", body = WalletExtremesResponse),
+        (status = 400, description = "Invalid wallet address", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn wallet_extremes(
     State(state): State<AppState>,
@@ -1074,6 +1476,14 @@ pub async fn wallet_extremes(
 }
 
 /// GET /health - Health check endpoint
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses(
+        (status = 200, description = "Health report", body = serde_json::Value),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn health(State(state): State<AppState>) -> ApiResult<Json<serde_json::Value>> {
     // Update database health check
@@ -1160,12 +1570,29 @@ pub async fn health(State(state): State<AppState>) -> ApiResult<Json<serde_json:
 }
 
 /// GET /metrics - Prometheus metrics endpoint
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    responses(
+        (status = 200, description = "Prometheus metrics", content_type = "text/plain", body = String),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn metrics(State(state): State<AppState>) -> Result<String, StatusCode> {
     let metrics_data = state.metrics_registry.gather();
     Ok(metrics_data)
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/cards/moment/{id}.png",
+    tag = "moments",
+    params(("id" = String, Path, description = "Moment ID (ULID)")),
+    responses(
+        (status = 200, description = "Moment card", content_type = "image/png", body = Vec<u8>),
+    )
+)]
 pub async fn card_png(State(st): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
     // Load moment for context
     let r = sqlx::query("SELECT wallet, kind, mint, t_event FROM oof_moments WHERE id=$1")
@@ -1218,12 +1645,22 @@ pub async fn card_png(State(st): State<AppState>, Path(id): Path<String>) -> imp
 }
 
 /// GET /v1/tokens/:mint/prices - Get historical price data for a token
+#[utoipa::path(
+    get,
+    path = "/v1/tokens/{mint}/prices",
+    tag = "tokens",
+    params(("mint" = String, Path, description = "Token mint"), PriceQuery),
+    responses(
+        (status = 200, description = "Price history", body = TokenPricesResponse),
+        (status = 400, description = "Invalid mint or timestamp", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn token_prices(
     State(state): State<AppState>,
     Path(mint): Path<String>,
     Query(query): Query<PriceQuery>,
-) -> ApiResult<Json<serde_json::Value>> {
+) -> ApiResult<Json<TokenPricesResponse>> {
     // Validate mint address format (basic length check)
     if mint.len() < 32 || mint.len() > 44 {
         return Err(ApiError::BadRequest(
@@ -1277,93 +1714,72 @@ pub async fn token_prices(
         .metrics
         .increment_counter("token_prices_requests_total");
 
-    Ok(Json(serde_json::json!({
-        "mint": mint,
-        "timeframe": query.tf.unwrap_or("raw".to_string()),
-        "count": data.len(),
-        "data": data
-    })))
+    Ok(Json(TokenPricesResponse {
+        mint,
+        timeframe: query.tf.unwrap_or("raw".to_string()),
+        count: data.len(),
+        data,
+    }))
 }
 
-/// GET /v1/leaderboard - Get performance leaderboard
+/// GET /v1/leaderboard - Latest leaderboard snapshot, with each wallet's
+/// movement since the snapshot before it
+#[utoipa::path(
+    get,
+    path = "/v1/leaderboard",
+    tag = "leaderboard",
+    params(LeaderboardQuery),
+    responses(
+        (status = 200, description = "Latest leaderboard snapshot", body = serde_json::Value),
+        (status = 400, description = "Invalid period or metric", body = ErrorBody),
+    )
+)]
 #[instrument(skip(state))]
 pub async fn leaderboard(
     State(state): State<AppState>,
     Query(query): Query<LeaderboardQuery>,
 ) -> ApiResult<Json<serde_json::Value>> {
     let period = match query.period.as_deref() {
-        Some("7d") => "7 days",
-        Some("30d") => "30 days",
-        Some("90d") => "90 days",
-        _ => "7 days",
+        Some(p) => LeaderboardPeriod::parse(p)
+            .ok_or_else(|| ApiError::BadRequest("Invalid period".to_string()))?,
+        None => LeaderboardPeriod::Week,
     };
-
-    let metric = query.metric.as_deref().unwrap_or("highest_gains");
-    let limit = query.limit.unwrap_or(50).min(100); // Cap at 100 entries
-
-    let (sql, metric_name) = match metric {
-        "highest_gains" => (
-            format!(
-                "SELECT wallet, SUM(realized_pnl_usd_dec) AS metric_value
-                 FROM realized_trades
-                 WHERE ts >= NOW() - INTERVAL '{}' AND realized_pnl_usd_dec > 0
-                 GROUP BY wallet
-                 ORDER BY metric_value DESC
-                 LIMIT ${}",
-                period
-            ),
-            "total_gains_usd",
-        ),
-        "most_s2e" => (
-            format!(
-                "SELECT wallet, COUNT(*) AS metric_value
-                 FROM oof_moments
-                 WHERE t_event >= NOW() - INTERVAL '{}' AND kind = 'sold_too_early'
-                 GROUP BY wallet
-                 ORDER BY metric_value DESC
-                 LIMIT ${}",
-                period
-            ),
-            "s2e_count",
-        ),
-        "worst_bhd" => (
-            format!(
-                "SELECT wallet, COUNT(*) AS metric_value
-                 FROM oof_moments
-                 WHERE t_event >= NOW() - INTERVAL '{}' AND kind = 'bag_holder_drawdown'
-                 GROUP BY wallet
-                 ORDER BY metric_value DESC
-                 LIMIT ${}",
-                period
-            ),
-            "bhd_count",
-        ),
-        _ => return Err(ApiError::BadRequest("Invalid metric type".to_string())),
+    let metric = match query.metric.as_deref() {
+        Some(m) => LeaderboardMetric::parse(m)
+            .ok_or_else(|| ApiError::BadRequest("Invalid metric type".to_string()))?,
+        None => LeaderboardMetric::HighestGains,
     };
+    let limit = query.limit.unwrap_or(50).min(100); // Cap at 100 entries
 
-    let rows = sqlx::query(&sql)
-        .bind(limit as i64)
-        .fetch_all(&state.pg.0)
-        .await?;
+    let rows = sqlx::query!(
+        include_str!("../../../db/queries/select_leaderboard.sql"),
+        metric.as_str(),
+        period.as_str(),
+        limit as i64
+    )
+    .fetch_all(&state.pg.0)
+    .await?;
 
+    let computed_at = rows.first().and_then(|r| {
+        r.computed_at
+            .format(&time::format_description::well_known::Rfc3339)
+            .ok()
+    });
+    let value_name = metric.value_name();
     let data: Vec<serde_json::Value> = rows
         .into_iter()
-        .enumerate()
-        .map(|(idx, row)| {
-            let wallet: String = row.get("wallet");
-            let metric_value: i64 = row
-                .try_get("metric_value")
-                .or_else(|_| {
-                    row.try_get::<Decimal, _>("metric_value")
-                        .map(|d| d.to_string().parse().unwrap_or(0))
-                })
-                .unwrap_or(0);
-
+        .map(|row| {
+            // Positive means the wallet climbed
+            let rank_change = row.previous_rank.map(|prev| prev - row.rank);
             serde_json::json!({
-                "rank": idx + 1,
-                "wallet": truncate_wallet(&wallet),
-                "wallet_full": wallet,
-                metric_name: metric_value.to_string()
+                "rank": row.rank,
+                "wallet": truncate_wallet(&row.wallet),
+                "wallet_full": row.wallet,
+                value_name: row.value.normalize().to_string(),
+                "sample_size": row.sample_size,
+                "previous_rank": row.previous_rank,
+                "rank_change": rank_change,
+                "is_new": row.previous_rank.is_none()
             })
         })
         .collect();
@@ -1373,8 +1789,9 @@ pub async fn leaderboard(
         .increment_counter("leaderboard_requests_total");
 
     Ok(Json(serde_json::json!({
-        "period": query.period.unwrap_or("7d".to_string()),
-        "metric": metric,
+        "period": period.as_str(),
+        "metric": metric.as_str(),
+        "computed_at": computed_at,
         "count": data.len(),
         "data": data
     })))
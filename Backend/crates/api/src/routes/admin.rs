@@ -0,0 +1,438 @@
+//! Operator endpoints for the job queue. Mounted under `/v1/admin` behind
+//! `admin_auth_middleware`.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE as BASE64_URL_SAFE, Engine};
+use serde::{Deserialize, Serialize};
+use shared::{
+    queue::{self, CancelOutcome},
+    validation::validate_pagination,
+    ApiError, ApiResult,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
+use crate::routes::{AppState, PaginationInfo};
+
+/// Statuses a purge may delete; live jobs are never purged
+const PURGEABLE_STATUSES: [&str; 3] = ["done", "failed", "cancelled"];
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct JobsQuery {
+    pub kind: Option<String>,
+    pub status: Option<String>,
+    /// Tenant (user id) the job runs for
+    pub user: Option<String>,
+    /// Jobs of one DAG, by its root id
+    pub root: Option<String>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobSummaryDto {
+    pub id: String,
+    pub kind: String,
+    pub status: String,
+    pub priority: i16,
+    pub tenant: Option<String>,
+    pub root_id: Option<String>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_after: Option<String>,
+    pub locked_by: Option<String>,
+    pub error_message: Option<String>,
+    pub cancel_requested_at: Option<String>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JobsListResponse {
+    pub data: Vec<JobSummaryDto>,
+    pub pagination: PaginationInfo,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobAttemptDto {
+    pub attempt: i32,
+    pub worker_id: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub outcome: String,
+    pub error_class: Option<String>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobDetailDto {
+    #[serde(flatten)]
+    pub job: JobSummaryDto,
+    pub payload: serde_json::Value,
+    pub parents: Vec<String>,
+    pub children: Vec<String>,
+    pub attempt_history: Vec<JobAttemptDto>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobActionResponse {
+    pub job_id: String,
+    /// `cancelled`, `requested` or `already_finished`
+    pub result: String,
+    /// The job's terminal status, for `already_finished`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+impl JobActionResponse {
+    /// Same shape as `CancelOutcome` flattened into the response, spelled
+    /// out so the schema can be documented
+    fn new(job_id: String, outcome: CancelOutcome) -> Self {
+        let (result, status) = match outcome {
+            CancelOutcome::Cancelled => ("cancelled", None),
+            CancelOutcome::Requested => ("requested", None),
+            CancelOutcome::AlreadyFinished(status) => ("already_finished", Some(status)),
+            CancelOutcome::NotFound => ("not_found", None),
+        };
+        Self {
+            job_id,
+            result: result.to_string(),
+            status,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RetryJobResponse {
+    pub job_id: String,
+    pub retried: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PurgeJobsRequest {
+    /// Subset of done/failed/cancelled
+    pub statuses: Vec<String>,
+    /// Only jobs finished (or created, if never finished) this long ago
+    pub older_than_days: i64,
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PurgeJobsResponse {
+    pub deleted: u64,
+}
+
+fn rfc3339(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Decode a `created_at|id` keyset cursor
+fn decode_cursor(cursor: &str) -> ApiResult<(OffsetDateTime, String)> {
+    let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+    let decoded = String::from_utf8(BASE64_URL_SAFE.decode(cursor).map_err(|_| invalid())?)
+        .map_err(|_| invalid())?;
+    let (ts, id) = decoded.split_once('|').ok_or_else(invalid)?;
+    let ts = OffsetDateTime::parse(ts, &Rfc3339).map_err(|_| invalid())?;
+    Ok((ts, id.to_string()))
+}
+
+/// GET /v1/admin/jobs - List jobs, newest first
+#[utoipa::path(
+    get,
+    path = "/v1/admin/jobs",
+    tag = "admin",
+    params(JobsQuery),
+    responses(
+        (status = 200, description = "A page of jobs, newest first", body = JobsListResponse),
+        (status = 400, description = "Invalid filters or cursor", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    Query(query): Query<JobsQuery>,
+) -> ApiResult<Json<JobsListResponse>> {
+    let (limit, cursor) = validate_pagination(query.limit, query.cursor.as_deref())?;
+    let (after_ts, after_id) = match cursor.as_deref() {
+        Some(c) => {
+            let (ts, id) = decode_cursor(c)?;
+            (Some(ts), Some(id))
+        }
+        None => (None, None),
+    };
+
+    let rows = sqlx::query!(
+        "SELECT id, kind, status AS \"status!\", priority, tenant, root_id,
+                attempts AS \"attempts!\", max_attempts AS \"max_attempts!\", run_after,
+                locked_by, error_message, cancel_requested_at,
+                created_at AS \"created_at!\", completed_at
+         FROM job_queue
+         WHERE ($1::text IS NULL OR kind = $1)
+           AND ($2::text IS NULL OR status = $2)
+           AND ($3::text IS NULL OR tenant = $3)
+           AND ($4::text IS NULL OR root_id = $4 OR id = $4)
+           AND ($5::timestamptz IS NULL OR (created_at, id) < ($5, $6))
+         ORDER BY created_at DESC, id DESC
+         LIMIT $7",
+        query.kind,
+        query.status,
+        query.user,
+        query.root,
+        after_ts,
+        after_id,
+        limit as i64 + 1
+    )
+    .fetch_all(&state.pg.0)
+    .await?;
+
+    let has_more = rows.len() > limit;
+    let data: Vec<JobSummaryDto> = rows
+        .into_iter()
+        .take(limit)
+        .map(|r| JobSummaryDto {
+            id: r.id,
+            kind: r.kind,
+            status: r.status,
+            priority: r.priority,
+            tenant: r.tenant,
+            root_id: r.root_id,
+            attempts: r.attempts,
+            max_attempts: r.max_attempts,
+            run_after: r.run_after.map(rfc3339),
+            locked_by: r.locked_by,
+            error_message: r.error_message,
+            cancel_requested_at: r.cancel_requested_at.map(rfc3339),
+            created_at: rfc3339(r.created_at),
+            completed_at: r.completed_at.map(rfc3339),
+        })
+        .collect();
+
+    let next_cursor = match data.last() {
+        Some(last) if has_more => {
+            Some(BASE64_URL_SAFE.encode(format!("{}|{}", last.created_at, last.id)))
+        }
+        _ => None,
+    };
+
+    Ok(Json(JobsListResponse {
+        data,
+        pagination: PaginationInfo {
+            limit,
+            cursor,
+            has_more,
+            next_cursor,
+        },
+    }))
+}
+
+/// GET /v1/admin/jobs/:id - Job with payload, dependencies and attempt history
+#[utoipa::path(
+    get,
+    path = "/v1/admin/jobs/{id}",
+    tag = "admin",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "The job", body = JobDetailDto),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn get_job(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<JobDetailDto>> {
+    let row = sqlx::query!(
+        "SELECT id, kind, status AS \"status!\", priority, tenant, root_id,
+                attempts AS \"attempts!\", max_attempts AS \"max_attempts!\", run_after,
+                locked_by, error_message, cancel_requested_at,
+                created_at AS \"created_at!\", completed_at, payload_json
+         FROM job_queue WHERE id = $1",
+        job_id
+    )
+    .fetch_optional(&state.pg.0)
+    .await?
+    .ok_or(ApiError::JobNotFound)?;
+
+    let parents = sqlx::query_scalar!(
+        "SELECT parent_id FROM job_dependencies WHERE job_id = $1 ORDER BY parent_id",
+        job_id
+    )
+    .fetch_all(&state.pg.0)
+    .await?;
+    let children = sqlx::query_scalar!(
+        "SELECT job_id FROM job_dependencies WHERE parent_id = $1 ORDER BY job_id",
+        job_id
+    )
+    .fetch_all(&state.pg.0)
+    .await?;
+
+    let attempt_history = sqlx::query!(
+        "SELECT attempt, worker_id, started_at, finished_at, outcome, error_class, error_message
+         FROM job_attempts WHERE job_id = $1 ORDER BY attempt ASC",
+        job_id
+    )
+    .fetch_all(&state.pg.0)
+    .await?
+    .into_iter()
+    .map(|a| JobAttemptDto {
+        attempt: a.attempt,
+        worker_id: a.worker_id,
+        started_at: rfc3339(a.started_at),
+        finished_at: a.finished_at.map(rfc3339),
+        outcome: a.outcome,
+        error_class: a.error_class,
+        error_message: a.error_message,
+    })
+    .collect();
+
+    Ok(Json(JobDetailDto {
+        job: JobSummaryDto {
+            id: row.id,
+            kind: row.kind,
+            status: row.status,
+            priority: row.priority,
+            tenant: row.tenant,
+            root_id: row.root_id,
+            attempts: row.attempts,
+            max_attempts: row.max_attempts,
+            run_after: row.run_after.map(rfc3339),
+            locked_by: row.locked_by,
+            error_message: row.error_message,
+            cancel_requested_at: row.cancel_requested_at.map(rfc3339),
+            created_at: rfc3339(row.created_at),
+            completed_at: row.completed_at.map(rfc3339),
+        },
+        payload: row.payload_json,
+        parents,
+        children,
+        attempt_history,
+    }))
+}
+
+/// POST /v1/admin/jobs/:id/retry - Requeue a failed or cancelled job
+#[utoipa::path(
+    post,
+    path = "/v1/admin/jobs/{id}/retry",
+    tag = "admin",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Job requeued", body = RetryJobResponse),
+        (status = 400, description = "Job is not failed or cancelled", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state, admin))]
+pub async fn retry_job(
+    State(state): State<AppState>,
+    admin: AuthUser,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<RetryJobResponse>> {
+    if !queue::retry_job(&state.pg.0, &job_id).await? {
+        return Err(ApiError::BadRequest(
+            "Only failed or cancelled jobs can be retried".to_string(),
+        ));
+    }
+    info!(job_id = %job_id, admin = %admin.user_id, "Job retried by admin");
+    Ok(Json(RetryJobResponse {
+        job_id,
+        retried: true,
+    }))
+}
+
+/// POST /v1/admin/jobs/:id/cancel - Cancel a queued job or stop a running one
+#[utoipa::path(
+    post,
+    path = "/v1/admin/jobs/{id}/cancel",
+    tag = "admin",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "What the cancel did", body = JobActionResponse),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "Job not found", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state, admin))]
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    admin: AuthUser,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<JobActionResponse>> {
+    let reason = format!("cancelled by admin {}", admin.user_id);
+    let outcome = queue::request_cancel(&state.pg.0, &job_id, &reason).await?;
+    if outcome == CancelOutcome::NotFound {
+        return Err(ApiError::JobNotFound);
+    }
+    info!(job_id = %job_id, admin = %admin.user_id, ?outcome, "Job cancel requested by admin");
+    Ok(Json(JobActionResponse::new(job_id, outcome)))
+}
+
+/// POST /v1/admin/jobs/purge - Delete old finished jobs
+#[utoipa::path(
+    post,
+    path = "/v1/admin/jobs/purge",
+    tag = "admin",
+    request_body = PurgeJobsRequest,
+    responses(
+        (status = 200, description = "Jobs deleted", body = PurgeJobsResponse),
+        (status = 400, description = "Invalid statuses or age", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state, admin))]
+pub async fn purge_jobs(
+    State(state): State<AppState>,
+    admin: AuthUser,
+    Json(req): Json<PurgeJobsRequest>,
+) -> ApiResult<Json<PurgeJobsResponse>> {
+    if req.statuses.is_empty()
+        || req
+            .statuses
+            .iter()
+            .any(|s| !PURGEABLE_STATUSES.contains(&s.as_str()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "statuses must be a non-empty subset of {:?}",
+            PURGEABLE_STATUSES
+        )));
+    }
+    if req.older_than_days < 1 {
+        return Err(ApiError::BadRequest(
+            "older_than_days must be at least 1".to_string(),
+        ));
+    }
+
+    let deleted = sqlx::query!(
+        "DELETE FROM job_queue
+         WHERE status = ANY($1)
+           AND COALESCE(completed_at, created_at) < NOW() - make_interval(days => $2)
+           AND ($3::text IS NULL OR kind = $3)",
+        &req.statuses,
+        req.older_than_days as i32,
+        req.kind
+    )
+    .execute(&state.pg.0)
+    .await?
+    .rows_affected();
+
+    info!(deleted, admin = %admin.user_id, statuses = ?req.statuses, "Jobs purged by admin");
+    Ok(Json(PurgeJobsResponse { deleted }))
+}
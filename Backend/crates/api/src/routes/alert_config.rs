@@ -0,0 +1,116 @@
+//! Where the caller's alerts are delivered. Subscribing to a wallet
+//! (`/v1/wallets/:wallet/alerts`) chooses what to hear about; this config
+//! chooses where it goes. Webhook secrets are write-only.
+
+use axum::{extract::State, response::Json};
+use serde::{Deserialize, Serialize};
+use shared::{
+    alerts::{AlertChannel, AlertConfig, AlertConfigStore, MAX_CHANNELS},
+    ApiError, ApiResult,
+};
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+
+use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
+use crate::routes::AppState;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AlertConfigDto {
+    pub enabled: bool,
+    /// `{"type": "webhook", "url", "secret"?}` or
+    /// `{"type": "discord", "webhook_url"}`
+    #[schema(value_type = Vec<Object>)]
+    pub channels: Vec<AlertChannel>,
+}
+
+impl From<AlertConfig> for AlertConfigDto {
+    fn from(config: AlertConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            channels: config
+                .channels
+                .into_iter()
+                .map(|channel| match channel {
+                    AlertChannel::Webhook { url, .. } => {
+                        AlertChannel::Webhook { url, secret: None }
+                    }
+                    other => other,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// GET /v1/alerts/config - Where the caller's alerts are delivered
+#[utoipa::path(
+    get,
+    path = "/v1/alerts/config",
+    tag = "wallets",
+    responses(
+        (status = 200, description = "The caller's alert channels, without secrets", body = AlertConfigDto),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn get_alert_config(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<AlertConfigDto>> {
+    let config = AlertConfigStore::new(state.pg.0.clone())
+        .get(&user.user_id)
+        .await?
+        .unwrap_or(AlertConfig {
+            enabled: false,
+            channels: Vec::new(),
+        });
+    Ok(Json(config.into()))
+}
+
+/// PUT /v1/alerts/config - Replace the caller's alert channels
+#[utoipa::path(
+    put,
+    path = "/v1/alerts/config",
+    tag = "wallets",
+    request_body = AlertConfigDto,
+    responses(
+        (status = 200, description = "The saved config, without secrets", body = AlertConfigDto),
+        (status = 400, description = "Invalid or too many channels", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Plan has no alerts", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state, req))]
+pub async fn put_alert_config(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(req): Json<AlertConfigDto>,
+) -> ApiResult<Json<AlertConfigDto>> {
+    if req.channels.len() > MAX_CHANNELS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} alert channels are allowed",
+            MAX_CHANNELS
+        )));
+    }
+    for channel in &req.channels {
+        channel.validate().map_err(ApiError::BadRequest)?;
+    }
+
+    let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+    if user_context.plan.alerts <= 0 {
+        return Err(ApiError::Forbidden);
+    }
+
+    let config = AlertConfig {
+        enabled: req.enabled,
+        channels: req.channels,
+    };
+    AlertConfigStore::new(state.pg.0.clone())
+        .put(&user.user_id, &config)
+        .await?;
+    info!(user_id = %user.user_id, channels = config.channels.len(), "Alert config saved");
+
+    Ok(Json(config.into()))
+}
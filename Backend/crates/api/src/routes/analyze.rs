@@ -736,7 +736,9 @@ async fn verify_analysis_ownership(
 }
 
 async fn cancel_analysis_in_store(state: &AppState, analysis_id: &str) -> ApiResult<()> {
-    // Cancel the analysis in the job queue
-    debug!("Cancelling analysis: {}", analysis_id);
+    // Same cooperative cancellation as DELETE /v1/analyze/:job
+    let (cancelled, stopping) =
+        shared::queue::cancel_dag(&state.database.0, analysis_id, "cancelled by user").await?;
+    debug!(analysis_id, cancelled, stopping, "Cancelled analysis jobs");
     Ok(())
 }
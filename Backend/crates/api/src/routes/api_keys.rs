@@ -0,0 +1,318 @@
+//! Self-service API keys. These routes sit behind `require_auth`, which
+//! only accepts a session JWT, so a key cannot mint, rotate or revoke keys.
+//! The full key is returned once, when it is created or rotated.
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use shared::{
+    api_keys::{ApiKeyRecord, ApiKeyStore, ApiScope, NewApiKey, MAX_ACTIVE_KEYS},
+    ApiError, ApiResult,
+};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+
+use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
+use crate::routes::AppState;
+
+const MAX_NAME_LEN: usize = 64;
+const MAX_EXPIRES_IN_DAYS: i64 = 365;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    /// Any of `read:moments`, `analyze`, `export`
+    pub scopes: Vec<String>,
+    #[serde(rename = "expiresInDays")]
+    pub expires_in_days: Option<i64>,
+    /// Cap below the plan's monthly `api_rows`
+    #[serde(rename = "rowsLimit")]
+    pub rows_limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyDto {
+    pub id: String,
+    pub name: String,
+    /// Identifies the key in listings; the rest of the key is never shown again
+    pub prefix: String,
+    #[schema(value_type = Vec<String>)]
+    pub scopes: Vec<ApiScope>,
+    #[serde(rename = "rowsLimit")]
+    pub rows_limit: Option<i64>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<String>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<String>,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: Option<String>,
+    #[serde(rename = "rotatedFrom")]
+    pub rotated_from: Option<String>,
+    pub active: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedApiKeyResponse {
+    #[serde(flatten)]
+    pub key: ApiKeyDto,
+    /// The full key; store it now, it cannot be retrieved later
+    pub secret: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiUsageDto {
+    #[serde(rename = "periodStart")]
+    pub period_start: String,
+    #[serde(rename = "periodEnd")]
+    pub period_end: String,
+    #[serde(rename = "rowsUsed")]
+    pub rows_used: i64,
+    /// The plan's `api_rows`
+    #[serde(rename = "rowsLimit")]
+    pub rows_limit: i64,
+    pub requests: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeysResponse {
+    pub keys: Vec<ApiKeyDto>,
+    pub usage: ApiUsageDto,
+}
+
+fn rfc3339(t: OffsetDateTime) -> String {
+    t.format(&Rfc3339).unwrap_or_default()
+}
+
+impl From<ApiKeyRecord> for ApiKeyDto {
+    fn from(k: ApiKeyRecord) -> Self {
+        Self {
+            active: k.is_active(OffsetDateTime::now_utc()),
+            id: k.id,
+            name: k.name,
+            prefix: k.prefix,
+            scopes: k.scopes,
+            rows_limit: k.rows_limit,
+            created_at: rfc3339(k.created_at),
+            expires_at: k.expires_at.map(rfc3339),
+            last_used_at: k.last_used_at.map(rfc3339),
+            revoked_at: k.revoked_at.map(rfc3339),
+            rotated_from: k.rotated_from,
+        }
+    }
+}
+
+fn parse_scopes(raw: &[String]) -> ApiResult<Vec<ApiScope>> {
+    let mut scopes = Vec::new();
+    for s in raw {
+        let scope = ApiScope::parse(s).ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "Unknown scope '{}'; expected one of: read:moments, analyze, export",
+                s
+            ))
+        })?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err(ApiError::BadRequest(
+            "At least one scope is required".to_string(),
+        ));
+    }
+    Ok(scopes)
+}
+
+/// POST /v1/api-keys - Mint a key
+#[utoipa::path(
+    post,
+    path = "/v1/api-keys",
+    tag = "api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "The new key, with its secret shown once", body = CreatedApiKeyResponse),
+        (status = 400, description = "Invalid name, scopes, expiry or limit, or too many keys", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Plan has no API access", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state, req))]
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> ApiResult<Json<CreatedApiKeyResponse>> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "name must be 1 to {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    let scopes = parse_scopes(&req.scopes)?;
+    let expires_at = match req.expires_in_days {
+        None => None,
+        Some(days) if (1..=MAX_EXPIRES_IN_DAYS).contains(&days) => {
+            Some(OffsetDateTime::now_utc() + Duration::days(days))
+        }
+        Some(_) => {
+            return Err(ApiError::BadRequest(format!(
+                "expiresInDays must be between 1 and {}",
+                MAX_EXPIRES_IN_DAYS
+            )))
+        }
+    };
+    if req.rows_limit.is_some_and(|limit| limit < 1) {
+        return Err(ApiError::BadRequest(
+            "rowsLimit must be positive".to_string(),
+        ));
+    }
+
+    let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+    if !user_context.plan.perks.api_access {
+        return Err(ApiError::Forbidden);
+    }
+
+    let (record, secret) = ApiKeyStore::new(state.pg.0.clone())
+        .create(
+            &user.user_id,
+            &NewApiKey {
+                name,
+                scopes,
+                rows_limit: req.rows_limit,
+                expires_at,
+            },
+        )
+        .await?
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "At most {} active API keys are allowed; revoke one first",
+                MAX_ACTIVE_KEYS
+            ))
+        })?;
+    info!(user_id = %user.user_id, key_id = %record.id, "API key created");
+    state.metrics.increment_counter("api_keys_created_total");
+
+    Ok(Json(CreatedApiKeyResponse {
+        key: record.into(),
+        secret,
+    }))
+}
+
+/// GET /v1/api-keys - The caller's keys and this period's usage
+#[utoipa::path(
+    get,
+    path = "/v1/api-keys",
+    tag = "api-keys",
+    responses(
+        (status = 200, description = "The caller's keys and this period's usage", body = ApiKeysResponse),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> ApiResult<Json<ApiKeysResponse>> {
+    let store = ApiKeyStore::new(state.pg.0.clone());
+    let keys = store.list(&user.user_id).await?;
+    let usage = store.usage(&user.user_id).await?;
+    let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+
+    Ok(Json(ApiKeysResponse {
+        keys: keys.into_iter().map(Into::into).collect(),
+        usage: ApiUsageDto {
+            period_start: usage.period_start.to_string(),
+            period_end: usage.period_end.to_string(),
+            rows_used: usage.rows_used,
+            rows_limit: user_context.plan.api_rows,
+            requests: usage.requests,
+        },
+    }))
+}
+
+/// POST /v1/api-keys/:id/rotate - Replace a key with a new secret; the old
+/// key stops working immediately
+#[utoipa::path(
+    post,
+    path = "/v1/api-keys/{id}/rotate",
+    tag = "api-keys",
+    params(("id" = String, Path, description = "API key ID")),
+    responses(
+        (status = 200, description = "The replacement key, with its secret shown once", body = CreatedApiKeyResponse),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 404, description = "No such active key", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn rotate_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<CreatedApiKeyResponse>> {
+    let (record, secret) = ApiKeyStore::new(state.pg.0.clone())
+        .rotate(&user.user_id, &id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("API key not found".to_string()))?;
+    info!(user_id = %user.user_id, old_key_id = %id, key_id = %record.id, "API key rotated");
+
+    Ok(Json(CreatedApiKeyResponse {
+        key: record.into(),
+        secret,
+    }))
+}
+
+/// DELETE /v1/api-keys/:id - Revoke a key
+#[utoipa::path(
+    delete,
+    path = "/v1/api-keys/{id}",
+    tag = "api-keys",
+    params(("id" = String, Path, description = "API key ID")),
+    responses(
+        (status = 200, description = "Key revoked", body = serde_json::Value),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 404, description = "No such active key", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
+#[instrument(skip(state))]
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !ApiKeyStore::new(state.pg.0.clone())
+        .revoke(&user.user_id, &id)
+        .await?
+    {
+        return Err(ApiError::NotFound("API key not found".to_string()));
+    }
+    info!(user_id = %user.user_id, key_id = %id, "API key revoked");
+
+    Ok(Json(serde_json::json!({ "id": id, "revoked": true })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scopes() {
+        let raw = vec!["export".to_string(), "read:moments".to_string()];
+        assert_eq!(
+            parse_scopes(&raw).unwrap(),
+            vec![ApiScope::Export, ApiScope::ReadMoments]
+        );
+        assert!(parse_scopes(&["export".to_string(), "export".to_string()]).is_ok());
+        assert!(parse_scopes(&[]).is_err());
+        assert!(parse_scopes(&["admin".to_string()]).is_err());
+    }
+}
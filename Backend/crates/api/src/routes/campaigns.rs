@@ -9,10 +9,12 @@ use shared::{
     utils::new_id,
 };
 use time::OffsetDateTime;
+use utoipa::ToSchema;
 
+use crate::openapi::ErrorBody;
 use crate::routes::AppState;
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Campaign {
     pub id: String,
     pub name: String,
@@ -24,7 +26,7 @@ pub struct Campaign {
     pub created_at: Option<OffsetDateTime>,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct CampaignAction {
     pub id: String,
     pub campaign_id: String,
@@ -43,7 +45,7 @@ pub struct CampaignParticipation {
     pub created_at: Option<OffsetDateTime>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateCampaignRequest {
     pub name: String,
     pub description: String,
@@ -52,7 +54,7 @@ pub struct CreateCampaignRequest {
     pub end_date: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateCampaignActionRequest {
     pub campaign_id: String,
     pub action_type: String,
@@ -60,18 +62,32 @@ pub struct CreateCampaignActionRequest {
     pub max_participants: Option<i32>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ParticipateRequest {
     pub proof_data: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ParticipateResponse {
     pub participation_id: String,
     pub status: String,
     pub message: String,
 }
 
+/// POST /v1/campaigns - Create a campaign (admins only)
+#[utoipa::path(
+    post,
+    path = "/v1/campaigns",
+    tag = "campaigns",
+    request_body = CreateCampaignRequest,
+    responses(
+        (status = 200, description = "The new campaign", body = Campaign),
+        (status = 400, description = "Invalid dates or budget", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
 pub async fn create_campaign(
     State(state): State<AppState>,
     user: AuthUser,
@@ -114,6 +130,23 @@ pub async fn create_campaign(
     Ok(JsonResponse(campaign))
 }
 
+/// POST /v1/campaigns/:id/actions - Add a rewarded action to a campaign
+/// (admins only)
+#[utoipa::path(
+    post,
+    path = "/v1/campaigns/{id}/actions",
+    tag = "campaigns",
+    params(("id" = String, Path, description = "Campaign ID")),
+    request_body = CreateCampaignActionRequest,
+    responses(
+        (status = 200, description = "The new action", body = CampaignAction),
+        (status = 400, description = "Invalid reward amount", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not an admin", body = ErrorBody),
+        (status = 404, description = "Campaign not found", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
 pub async fn create_campaign_action(
     State(state): State<AppState>,
     user: AuthUser,
@@ -154,6 +187,15 @@ pub async fn create_campaign_action(
     Ok(JsonResponse(campaign_action))
 }
 
+/// GET /v1/campaigns - Active campaigns
+#[utoipa::path(
+    get,
+    path = "/v1/campaigns",
+    tag = "campaigns",
+    responses(
+        (status = 200, description = "Active campaigns, newest first", body = Vec<Campaign>),
+    )
+)]
 pub async fn get_campaigns(State(state): State<AppState>) -> ApiResult<JsonResponse<Vec<Campaign>>> {
     let campaigns = sqlx::query_as::<_, Campaign>("SELECT id, name, description, budget, start_date, end_date, is_active, created_at FROM campaigns WHERE is_active = TRUE ORDER BY created_at DESC")
         .fetch_all(&state.pg.0)
@@ -162,6 +204,16 @@ pub async fn get_campaigns(State(state): State<AppState>) -> ApiResult<JsonRespo
     Ok(JsonResponse(campaigns))
 }
 
+/// GET /v1/campaigns/:id/actions - A campaign's rewarded actions
+#[utoipa::path(
+    get,
+    path = "/v1/campaigns/{id}/actions",
+    tag = "campaigns",
+    params(("id" = String, Path, description = "Campaign ID")),
+    responses(
+        (status = 200, description = "The campaign's actions", body = Vec<CampaignAction>),
+    )
+)]
 pub async fn get_campaign_actions(
     State(state): State<AppState>,
     Path(campaign_id): Path<String>,
@@ -174,6 +226,20 @@ pub async fn get_campaign_actions(
     Ok(JsonResponse(actions))
 }
 
+/// POST /v1/campaigns/:id/participate - Submit proof for a campaign action
+#[utoipa::path(
+    post,
+    path = "/v1/campaigns/{id}/participate",
+    tag = "campaigns",
+    params(("id" = String, Path, description = "Campaign ID")),
+    request_body = ParticipateRequest,
+    responses(
+        (status = 200, description = "Participation result; `status` is `error` when the caller cannot take part", body = ParticipateResponse),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 404, description = "Campaign action not found", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []))
+)]
 pub async fn participate_in_campaign(
     State(state): State<AppState>,
     user: AuthUser,
@@ -0,0 +1,461 @@
+//! Wallet data exports. `POST /v1/exports` queues an `export_moments` job;
+//! the status endpoint hands out signed, expiring download links that
+//! `GET /v1/exports/:id/files/:name` checks without a session, so links
+//! can be opened directly in a browser or passed to other tools. Exports
+//! queued through an API key stop at the key's remaining rows, and the rows
+//! written are metered when the job completes.
+//!
+//! Tax reports are small enough to build per request and are served
+//! directly from `GET /v1/tax/report`.
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use shared::{
+    api_keys::ApiScope,
+    export::{
+        download_path, verify_download, ExportDataset, ExportFile, ExportFormat, ExportQuota,
+        ExportRequest, EXPORT_JOB_KIND,
+    },
+    queue::{dag_status, enqueue_dag},
+    store::ObjectStore,
+    tax::{load_report, TaxReportFormat},
+    utils::new_id,
+    validation::validate_wallet_address,
+    ApiError, ApiResult, JobPriority, JobSpec,
+};
+use sqlx::PgPool;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
+use crate::routes::AppState;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateExportRequest {
+    pub wallet: String,
+    /// `csv`, `ndjson` (or `jsonl`) or `parquet`; defaults to `csv`
+    pub format: Option<String>,
+    #[serde(default)]
+    pub include_transactions: bool,
+}
+
+/// Most wallets one tax report may cover, as for `POST /v1/analyze`
+const MAX_TAX_REPORT_WALLETS: usize = 25;
+
+/// Earliest tax year with Solana activity
+const MIN_TAX_YEAR: i32 = 2020;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaxReportQuery {
+    /// Comma-separated wallet group
+    pub wallets: String,
+    pub year: i32,
+    /// `csv`, `form8949` or `json`; defaults to `csv`
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DownloadQuery {
+    pub expires: i64,
+    pub sig: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportFileDto {
+    pub name: String,
+    #[schema(value_type = String)]
+    pub dataset: ExportDataset,
+    pub rows: u64,
+    pub bytes: u64,
+    /// Cut short at the API key's row quota
+    pub truncated: bool,
+    pub download_url: String,
+    pub download_expires_at: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExportDto {
+    pub export_id: String,
+    pub wallet: String,
+    pub format: String,
+    pub include_transactions: bool,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub files: Vec<ExportFileDto>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+fn rfc3339(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Record an export and enqueue its job; returns the export id, which is
+/// also the job's DAG root
+pub async fn queue_export(
+    pg: &PgPool,
+    user_id: &str,
+    wallet: &str,
+    format: ExportFormat,
+    include_transactions: bool,
+    quota: Option<ExportQuota>,
+) -> anyhow::Result<String> {
+    let export_id = new_id();
+    sqlx::query!(
+        "INSERT INTO exports (id, user_id, wallet, format, include_transactions)
+         VALUES ($1, $2, $3, $4, $5)",
+        export_id,
+        user_id,
+        wallet,
+        format.as_str(),
+        include_transactions
+    )
+    .execute(pg)
+    .await?;
+
+    let payload = serde_json::to_value(ExportRequest {
+        export_id: export_id.clone(),
+        user_id: user_id.to_string(),
+        wallet: wallet.to_string(),
+        format,
+        include_transactions,
+        quota,
+    })?;
+    let job = JobSpec::new(EXPORT_JOB_KIND, payload)
+        .with_priority(JobPriority::Low)
+        .with_tenant(user_id);
+    if let Err(e) = enqueue_dag(pg, &export_id, &[job]).await {
+        // Don't leave a row that no job will ever pick up
+        sqlx::query!(
+            "UPDATE exports SET status = 'failed', error_message = 'failed to enqueue'
+             WHERE id = $1",
+            export_id
+        )
+        .execute(pg)
+        .await?;
+        return Err(e);
+    }
+
+    Ok(export_id)
+}
+
+/// POST /v1/exports - Export a wallet's moments, trades and episodes
+#[utoipa::path(
+    post,
+    path = "/v1/exports",
+    tag = "exports",
+    request_body = CreateExportRequest,
+    responses(
+        (status = 200, description = "Export queued", body = ExportDto),
+        (status = 400, description = "Invalid wallet or format", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 402, description = "API key row quota used up", body = ErrorBody),
+        (status = 403, description = "Plan has no data exports, or API key lacks the export scope", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn create_export(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(req): Json<CreateExportRequest>,
+) -> ApiResult<Json<ExportDto>> {
+    user.require_scope(ApiScope::Export)?;
+    validate_wallet_address(&req.wallet)?;
+    let format = match req.format.as_deref() {
+        None => ExportFormat::Csv,
+        Some(f) => ExportFormat::parse(f).ok_or_else(|| {
+            ApiError::BadRequest("format must be one of: csv, ndjson, parquet".to_string())
+        })?,
+    };
+
+    let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+    if !user_context.plan.perks.export_data {
+        return Err(ApiError::Forbidden);
+    }
+
+    // Authentication already turned away keys with no rows left
+    let quota = user.api_key.as_ref().map(|key| ExportQuota {
+        api_key_id: key.id.clone(),
+        max_rows: key.rows_remaining as u64,
+    });
+    let export_id = queue_export(
+        &state.pg.0,
+        &user.user_id,
+        &req.wallet,
+        format,
+        req.include_transactions,
+        quota,
+    )
+    .await?;
+    info!(
+        export_id = %export_id,
+        user_id = %user.user_id,
+        wallet = %req.wallet,
+        format = format.as_str(),
+        "Export queued"
+    );
+
+    Ok(Json(ExportDto {
+        export_id,
+        wallet: req.wallet,
+        format: format.as_str().to_string(),
+        include_transactions: req.include_transactions,
+        status: "queued".to_string(),
+        error_message: None,
+        files: Vec::new(),
+        created_at: rfc3339(OffsetDateTime::now_utc()),
+        completed_at: None,
+        expires_at: None,
+    }))
+}
+
+/// GET /v1/exports/:id - Export status, with download links once done
+#[utoipa::path(
+    get,
+    path = "/v1/exports/{id}",
+    tag = "exports",
+    params(("id" = String, Path, description = "Export ID")),
+    responses(
+        (status = 200, description = "Export status, with download links once done", body = ExportDto),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "API key lacks the export scope", body = ErrorBody),
+        (status = 404, description = "Export not found", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn get_export(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(export_id): Path<String>,
+) -> ApiResult<Json<ExportDto>> {
+    user.require_scope(ApiScope::Export)?;
+    let row = sqlx::query!(
+        "SELECT id, user_id, wallet, format, include_transactions, status, files,
+                error_message, created_at, completed_at, expires_at
+         FROM exports WHERE id = $1",
+        export_id
+    )
+    .fetch_optional(&state.pg.0)
+    .await?
+    .filter(|r| r.user_id == user.user_id)
+    .ok_or_else(|| ApiError::NotFound("Export not found".to_string()))?;
+
+    let mut status = row.status;
+    let mut error_message = row.error_message;
+    if status == "queued" || status == "running" {
+        // The job may have been cancelled or dead-lettered without the
+        // worker getting to update the row
+        if let Some(dag) = dag_status(&state.pg.0, &row.id).await? {
+            if dag.status == "failed" || dag.status == "cancelled" {
+                error_message.get_or_insert_with(|| format!("export job {}", dag.status));
+                status = "failed".to_string();
+            }
+        }
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let expired = row.expires_at.map_or(false, |at| at <= now);
+    let files = if status == "done" && !expired {
+        let stored: Vec<ExportFile> = serde_json::from_value(row.files).unwrap_or_else(|e| {
+            warn!(export_id = %row.id, error = %e, "Malformed export file list");
+            Vec::new()
+        });
+        // Links never outlive the export itself
+        let mut link_expiry = now + time::Duration::seconds(state.cfg.export_url_ttl_secs as i64);
+        if let Some(at) = row.expires_at {
+            link_expiry = link_expiry.min(at);
+        }
+        let expires = link_expiry.unix_timestamp();
+        stored
+            .into_iter()
+            .map(|f| ExportFileDto {
+                download_url: download_path(&state.cfg.app_secret, &row.id, &f.name, expires),
+                download_expires_at: rfc3339(link_expiry),
+                name: f.name,
+                dataset: f.dataset,
+                rows: f.rows,
+                bytes: f.bytes,
+                truncated: f.truncated,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if expired {
+        status = "expired".to_string();
+    }
+
+    Ok(Json(ExportDto {
+        export_id: row.id,
+        wallet: row.wallet,
+        format: row.format,
+        include_transactions: row.include_transactions,
+        status,
+        error_message,
+        files,
+        created_at: rfc3339(row.created_at),
+        completed_at: row.completed_at.map(rfc3339),
+        expires_at: row.expires_at.map(rfc3339),
+    }))
+}
+
+/// GET /v1/exports/:id/files/:name - Download an export file through a
+/// signed link
+#[utoipa::path(
+    get,
+    path = "/v1/exports/{id}/files/{name}",
+    tag = "exports",
+    params(("id" = String, Path, description = "Export ID"), ("name" = String, Path, description = "File name"), DownloadQuery),
+    responses(
+        (status = 200, description = "File contents", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 403, description = "Invalid or expired signature", body = ErrorBody),
+        (status = 404, description = "Export or file not found", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state, query))]
+pub async fn download_export_file(
+    State(state): State<AppState>,
+    Path((export_id, file_name)): Path<(String, String)>,
+    Query(query): Query<DownloadQuery>,
+) -> ApiResult<Response> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if !verify_download(
+        &state.cfg.app_secret,
+        &export_id,
+        &file_name,
+        query.expires,
+        &query.sig,
+        now,
+    ) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let row = sqlx::query!(
+        "SELECT format, files FROM exports
+         WHERE id = $1 AND status = 'done' AND (expires_at IS NULL OR expires_at > NOW())",
+        export_id
+    )
+    .fetch_optional(&state.pg.0)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Export not found or expired".to_string()))?;
+
+    let files: Vec<ExportFile> =
+        serde_json::from_value(row.files).map_err(|e| ApiError::Internal(e.into()))?;
+    let file = files
+        .into_iter()
+        .find(|f| f.name == file_name)
+        .ok_or_else(|| ApiError::NotFound("Export file not found".to_string()))?;
+    let format = ExportFormat::parse(&row.format)
+        .ok_or_else(|| ApiError::Internal(anyhow::anyhow!("unknown export format")))?;
+
+    let body = state.store.get_stream(&file.key).await?;
+    let disposition = format!("attachment; filename=\"{}-{}\"", export_id, file.name);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_LENGTH, file.bytes.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+/// GET /v1/tax/report - Annual gains and losses for a wallet group
+#[utoipa::path(
+    get,
+    path = "/v1/tax/report",
+    tag = "exports",
+    params(TaxReportQuery),
+    responses(
+        (status = 200, description = "Realized disposals for the year", content(("text/csv" = String), ("application/json" = serde_json::Value))),
+        (status = 400, description = "Invalid wallets, year or format", body = ErrorBody),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 402, description = "API key row quota used up", body = ErrorBody),
+        (status = 403, description = "Plan has no data exports, or API key lacks the export scope", body = ErrorBody),
+    ),
+    security(("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn tax_report(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<TaxReportQuery>,
+) -> ApiResult<Response> {
+    user.require_scope(ApiScope::Export)?;
+    let mut wallets: Vec<String> = query
+        .wallets
+        .split(',')
+        .map(|w| w.trim().to_string())
+        .filter(|w| !w.is_empty())
+        .collect();
+    wallets.sort();
+    wallets.dedup();
+    if wallets.is_empty() || wallets.len() > MAX_TAX_REPORT_WALLETS {
+        return Err(ApiError::BadRequest(format!(
+            "Between 1 and {} wallets required",
+            MAX_TAX_REPORT_WALLETS
+        )));
+    }
+    for wallet in &wallets {
+        validate_wallet_address(wallet)?;
+    }
+
+    let current_year = OffsetDateTime::now_utc().year();
+    if !(MIN_TAX_YEAR..=current_year).contains(&query.year) {
+        return Err(ApiError::BadRequest(format!(
+            "year must be between {} and {}",
+            MIN_TAX_YEAR, current_year
+        )));
+    }
+    let format = match query.format.as_deref() {
+        None => TaxReportFormat::Csv,
+        Some(f) => TaxReportFormat::parse(f).ok_or_else(|| {
+            ApiError::BadRequest("format must be one of: csv, form8949, json".to_string())
+        })?,
+    };
+
+    let user_context = state.policy_service.get_user_context(&user.user_id).await?;
+    if !user_context.plan.perks.export_data {
+        return Err(ApiError::Forbidden);
+    }
+
+    let report = load_report(&state.pg.0, &wallets, query.year).await?;
+    info!(
+        user_id = %user.user_id,
+        wallets = wallets.len(),
+        year = query.year,
+        disposals = report.disposals.len(),
+        "Tax report generated"
+    );
+    user.meter_rows(&state, report.disposals.len()).await?;
+
+    let (body, suffix) = match format {
+        TaxReportFormat::Json => return Ok(Json(report).into_response()),
+        TaxReportFormat::Csv => (report.to_csv()?, "disposals"),
+        TaxReportFormat::Form8949 => (report.to_form8949()?, "form8949"),
+    };
+    let disposition = format!("attachment; filename=\"tax-{}-{}.csv\"", query.year, suffix);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
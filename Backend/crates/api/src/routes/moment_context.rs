@@ -0,0 +1,492 @@
+//! Context for a single moment, computed from real data: the position
+//! before and after it, how the price moved afterwards, what the rest of the
+//! wallet was worth at the time, and what acting on the detector's own
+//! numbers would have been worth.
+//!
+//! Positions are rebuilt by replaying the wallet's actions through a
+//! non-persisting [`Engine`], so building context never writes lots, trades
+//! or snapshots.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use detectors::position::{Engine, PositionState};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use shared::PriceProvider;
+use sqlx::PgPool;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use utoipa::ToSchema;
+
+/// Furthest a price point may sit from the moment and still count as the
+/// price at the moment; providers fall back to the latest price otherwise
+const MOMENT_PRICE_TOLERANCE: Duration = Duration::minutes(30);
+
+/// Horizons reported after the moment
+const LATER_OFFSETS: [Duration; 3] = [Duration::hours(1), Duration::hours(24), Duration::days(7)];
+
+/// Position in one mint at a point in time
+#[derive(Debug, Clone)]
+pub struct PositionAt {
+    pub qty: Decimal,
+    /// Over lots with a known entry price
+    pub avg_entry_px: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub value_usd: Option<Decimal>,
+    pub unrealized_pnl_usd: Option<Decimal>,
+    pub realized_pnl_usd: Decimal,
+    pub open_lots: usize,
+}
+
+impl PositionAt {
+    /// `None` when the wallet had no activity in the mint yet
+    fn from_state(state: &PositionState, price: Option<Decimal>) -> Option<Self> {
+        state.last_event_ts?;
+        Some(Self {
+            qty: state.exposure,
+            avg_entry_px: state.average_entry_price(),
+            price,
+            value_usd: price.map(|px| px * state.exposure),
+            unrealized_pnl_usd: price.map(|px| state.unrealized_pnl(px)),
+            realized_pnl_usd: state.total_realized_pnl,
+            open_lots: state.lots.len(),
+        })
+    }
+}
+
+/// Price at the moment and at fixed horizons after it
+#[derive(Debug, Clone, Default)]
+pub struct MarketMoves {
+    pub price_at_moment: Option<Decimal>,
+    pub price_1h_later: Option<Decimal>,
+    pub price_24h_later: Option<Decimal>,
+    pub price_7d_later: Option<Decimal>,
+    /// `(max - min) / avg` over the 24 hours before the moment
+    pub volatility_24h: Option<f64>,
+}
+
+/// The wallet's open positions valued at the moment
+#[derive(Debug, Clone, Default)]
+pub struct PortfolioAt {
+    pub total_value_usd: Decimal,
+    /// Share of the total held in the moment's mint, in percent
+    pub moment_mint_pct: Option<f64>,
+    pub other_positions: u32,
+    /// Open positions without a price near the moment; not in the total
+    pub unpriced_positions: u32,
+    /// `1 - HHI` of position values; 0 is a single holding
+    pub diversification: Option<f64>,
+}
+
+/// What following the detector's numbers would have changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterfactual {
+    pub action: String,
+    pub target_price: Decimal,
+    pub target_ts: Option<OffsetDateTime>,
+    /// USD better off than what actually happened
+    pub value_usd: Decimal,
+    pub confidence: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MomentContext {
+    pub position_before: Option<PositionAt>,
+    pub position_after: Option<PositionAt>,
+    pub market: MarketMoves,
+    pub portfolio: PortfolioAt,
+    pub counterfactual: Option<Counterfactual>,
+}
+
+/// Build context for a moment of `kind` at `ts`
+pub async fn build_context(
+    pg: &PgPool,
+    prices: &dyn PriceProvider,
+    wallet: &str,
+    mint: Option<&str>,
+    kind: &str,
+    ts: OffsetDateTime,
+    explain: &serde_json::Value,
+) -> anyhow::Result<MomentContext> {
+    let engine = Engine::new(pg.clone()).with_persist(false);
+    let now = OffsetDateTime::now_utc();
+
+    // One pass over the history: `before` excludes the moment's own event,
+    // `after` includes everything up to it
+    let (before, after) = engine.replay_wallet_around(wallet, ts, ts).await?;
+
+    let (market, position_before, position_after) = match mint {
+        Some(mint) => {
+            let market = market_moves(prices, mint, ts, now).await?;
+            let position_before = before
+                .get(mint)
+                .and_then(|s| PositionAt::from_state(s, market.price_at_moment));
+            let position_after = after
+                .get(mint)
+                .and_then(|s| PositionAt::from_state(s, market.price_at_moment));
+            (market, position_before, position_after)
+        }
+        None => (MarketMoves::default(), None, None),
+    };
+
+    let portfolio = portfolio_at(prices, &before, mint, ts).await?;
+
+    Ok(MomentContext {
+        position_before,
+        position_after,
+        market,
+        portfolio,
+        counterfactual: counterfactual(kind, explain),
+    })
+}
+
+/// Price within `tolerance` of `at`, ignoring provider fallbacks to a price
+/// from another time
+async fn price_near(
+    prices: &dyn PriceProvider,
+    mint: &str,
+    at: OffsetDateTime,
+    tolerance: Duration,
+) -> anyhow::Result<Option<Decimal>> {
+    Ok(prices
+        .get_price_at(mint, at)
+        .await?
+        .filter(|p| (p.timestamp - at).abs() <= tolerance)
+        .map(|p| p.price))
+}
+
+pub async fn market_moves(
+    prices: &dyn PriceProvider,
+    mint: &str,
+    ts: OffsetDateTime,
+    now: OffsetDateTime,
+) -> anyhow::Result<MarketMoves> {
+    let price_at_moment = price_near(prices, mint, ts, MOMENT_PRICE_TOLERANCE).await?;
+
+    let mut later = [None; LATER_OFFSETS.len()];
+    for (slot, offset) in later.iter_mut().zip(LATER_OFFSETS) {
+        let at = ts + offset;
+        if at <= now {
+            *slot = price_near(prices, mint, at, offset / 2).await?;
+        }
+    }
+
+    let volatility_24h = prices
+        .get_price_range(mint, ts - Duration::hours(24), ts)
+        .await?
+        .filter(|r| r.avg_price > Decimal::ZERO)
+        .and_then(|r| ((r.max_price - r.min_price) / r.avg_price).to_f64());
+
+    Ok(MarketMoves {
+        price_at_moment,
+        price_1h_later: later[0],
+        price_24h_later: later[1],
+        price_7d_later: later[2],
+        volatility_24h,
+    })
+}
+
+/// Value the open positions in `states` at `ts`
+pub async fn portfolio_at(
+    prices: &dyn PriceProvider,
+    states: &HashMap<String, PositionState>,
+    moment_mint: Option<&str>,
+    ts: OffsetDateTime,
+) -> anyhow::Result<PortfolioAt> {
+    let mut values = Vec::new();
+    let mut moment_value = None;
+    let mut portfolio = PortfolioAt::default();
+
+    for (mint, state) in states {
+        if state.exposure <= Decimal::ZERO {
+            continue;
+        }
+        let is_moment_mint = moment_mint == Some(mint.as_str());
+        if !is_moment_mint {
+            portfolio.other_positions += 1;
+        }
+        match price_near(prices, mint, ts, MOMENT_PRICE_TOLERANCE).await? {
+            Some(px) => {
+                let value = px * state.exposure;
+                if is_moment_mint {
+                    moment_value = Some(value);
+                }
+                values.push(value);
+            }
+            None => portfolio.unpriced_positions += 1,
+        }
+    }
+
+    portfolio.total_value_usd = values.iter().sum();
+    if portfolio.total_value_usd > Decimal::ZERO {
+        let total = portfolio.total_value_usd;
+        portfolio.moment_mint_pct =
+            moment_value.and_then(|v| (v / total * Decimal::ONE_HUNDRED).to_f64());
+        let hhi: Decimal = values.iter().map(|v| (v / total) * (v / total)).sum();
+        portfolio.diversification = (Decimal::ONE - hhi).to_f64();
+    }
+
+    Ok(portfolio)
+}
+
+fn explain_dec(explain: &serde_json::Value, key: &str) -> Option<Decimal> {
+    match explain.get(key)? {
+        serde_json::Value::String(s) => Decimal::from_str(s).ok(),
+        serde_json::Value::Number(n) => {
+            let s = n.to_string();
+            Decimal::from_str(&s)
+                .or_else(|_| Decimal::from_scientific(&s))
+                .ok()
+        }
+        _ => None,
+    }
+}
+
+/// Detectors write RFC 3339 strings; moments stored before that carry the
+/// `time` crate's own serde form
+fn explain_ts(explain: &serde_json::Value, key: &str) -> Option<OffsetDateTime> {
+    let value = explain.get(key)?;
+    match value.as_str() {
+        Some(s) => OffsetDateTime::parse(s, &Rfc3339).ok(),
+        None => serde_json::from_value(value.clone()).ok(),
+    }
+}
+
+/// "Held to the peak" or "exited at X", from the numbers the detector
+/// recorded in the moment's explain JSON. Kinds without a price target have
+/// no counterfactual.
+pub fn counterfactual(kind: &str, explain: &serde_json::Value) -> Option<Counterfactual> {
+    let confidence = explain
+        .get("confidence")
+        .and_then(|c| c.as_str())
+        .map(str::to_string);
+
+    let (action, target_price, target_ts, value_usd) = match kind {
+        "S2E" => {
+            let exit = explain_dec(explain, "exit_price")?;
+            let peak = explain_dec(explain, "peak_price")?;
+            let qty = explain_dec(explain, "qty_sold")?;
+            let peak_ts = explain_ts(explain, "peak_timestamp");
+            let action = match peak_ts {
+                Some(at) => format!("Hold to the peak at ${} on {}", peak.normalize(), at.date()),
+                None => format!("Hold to the peak at ${}", peak.normalize()),
+            };
+            (action, peak, peak_ts, (peak - exit) * qty)
+        }
+        "BHD" => {
+            let entry = explain_dec(explain, "entry_price")?;
+            let trough = explain_dec(explain, "trough_price")?;
+            let loss = explain_dec(explain, "unrealized_loss_usd")
+                .or_else(|| explain_dec(explain, "qty_bought").map(|qty| (entry - trough) * qty))?;
+            (
+                format!("Exit at ${} before the drawdown", entry.normalize()),
+                entry,
+                explain_ts(explain, "trough_timestamp"),
+                loss,
+            )
+        }
+        "BAD_ROUTE" => {
+            let best = explain_dec(explain, "best_available_price")?;
+            let lost = explain_dec(explain, "amount_lost_usd")?;
+            (
+                format!("Route the swap at ${}", best.normalize()),
+                best,
+                None,
+                lost,
+            )
+        }
+        _ => return None,
+    };
+
+    (value_usd > Decimal::ZERO).then(|| Counterfactual {
+        action,
+        target_price,
+        target_ts,
+        value_usd,
+        confidence,
+    })
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PositionAtDto {
+    #[serde(rename = "qtyDec")]
+    pub qty_dec: String,
+    #[serde(rename = "avgEntryPxDec")]
+    pub avg_entry_px_dec: Option<String>,
+    #[serde(rename = "priceDec")]
+    pub price_dec: Option<String>,
+    #[serde(rename = "valueUsdDec")]
+    pub value_usd_dec: Option<String>,
+    #[serde(rename = "unrealizedPnlUsdDec")]
+    pub unrealized_pnl_usd_dec: Option<String>,
+    #[serde(rename = "realizedPnlUsdDec")]
+    pub realized_pnl_usd_dec: String,
+    #[serde(rename = "openLots")]
+    pub open_lots: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MarketMovesDto {
+    #[serde(rename = "priceAtMomentDec")]
+    pub price_at_moment_dec: Option<String>,
+    #[serde(rename = "price1hLaterDec")]
+    pub price_1h_later_dec: Option<String>,
+    #[serde(rename = "price24hLaterDec")]
+    pub price_24h_later_dec: Option<String>,
+    #[serde(rename = "price7dLaterDec")]
+    pub price_7d_later_dec: Option<String>,
+    #[serde(rename = "volatility24h")]
+    pub volatility_24h: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PortfolioAtDto {
+    #[serde(rename = "totalValueUsdDec")]
+    pub total_value_usd_dec: String,
+    #[serde(rename = "momentMintPct")]
+    pub moment_mint_pct: Option<f64>,
+    #[serde(rename = "otherPositions")]
+    pub other_positions: u32,
+    #[serde(rename = "unpricedPositions")]
+    pub unpriced_positions: u32,
+    pub diversification: Option<f64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CounterfactualDto {
+    pub action: String,
+    #[serde(rename = "targetPriceDec")]
+    pub target_price_dec: String,
+    #[serde(rename = "targetTs")]
+    pub target_ts: Option<String>,
+    #[serde(rename = "valueUsdDec")]
+    pub value_usd_dec: String,
+    pub confidence: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MomentContextDto {
+    #[serde(rename = "positionBefore")]
+    pub position_before: Option<PositionAtDto>,
+    #[serde(rename = "positionAfter")]
+    pub position_after: Option<PositionAtDto>,
+    pub market: MarketMovesDto,
+    pub portfolio: PortfolioAtDto,
+    pub counterfactual: Option<CounterfactualDto>,
+}
+
+fn dec_str(d: Option<Decimal>) -> Option<String> {
+    d.map(|d| d.to_string())
+}
+
+impl From<PositionAt> for PositionAtDto {
+    fn from(p: PositionAt) -> Self {
+        Self {
+            qty_dec: p.qty.to_string(),
+            avg_entry_px_dec: dec_str(p.avg_entry_px),
+            price_dec: dec_str(p.price),
+            value_usd_dec: dec_str(p.value_usd),
+            unrealized_pnl_usd_dec: dec_str(p.unrealized_pnl_usd),
+            realized_pnl_usd_dec: p.realized_pnl_usd.to_string(),
+            open_lots: p.open_lots,
+        }
+    }
+}
+
+impl From<MomentContext> for MomentContextDto {
+    fn from(ctx: MomentContext) -> Self {
+        Self {
+            position_before: ctx.position_before.map(Into::into),
+            position_after: ctx.position_after.map(Into::into),
+            market: MarketMovesDto {
+                price_at_moment_dec: dec_str(ctx.market.price_at_moment),
+                price_1h_later_dec: dec_str(ctx.market.price_1h_later),
+                price_24h_later_dec: dec_str(ctx.market.price_24h_later),
+                price_7d_later_dec: dec_str(ctx.market.price_7d_later),
+                volatility_24h: ctx.market.volatility_24h,
+            },
+            portfolio: PortfolioAtDto {
+                total_value_usd_dec: ctx.portfolio.total_value_usd.to_string(),
+                moment_mint_pct: ctx.portfolio.moment_mint_pct,
+                other_positions: ctx.portfolio.other_positions,
+                unpriced_positions: ctx.portfolio.unpriced_positions,
+                diversification: ctx.portfolio.diversification,
+            },
+            counterfactual: ctx.counterfactual.map(|c| CounterfactualDto {
+                action: c.action,
+                target_price_dec: c.target_price.to_string(),
+                target_ts: c.target_ts.and_then(|t| t.format(&Rfc3339).ok()),
+                value_usd_dec: c.value_usd.to_string(),
+                confidence: c.confidence,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use shared::{PriceConfidence, PriceRange, PriceSource};
+    use time::macros::datetime;
+
+    fn range(min_price: Decimal, max_price: Decimal) -> PriceRange {
+        PriceRange {
+            mint: "So11111111111111111111111111111111111111112".to_string(),
+            from: datetime!(2024-03-01 00:00 UTC),
+            to: datetime!(2024-03-08 00:00 UTC),
+            min_price,
+            min_timestamp: datetime!(2024-03-04 06:00 UTC),
+            max_price,
+            max_timestamp: datetime!(2024-03-02 12:00 UTC),
+            avg_price: (min_price + max_price) / Decimal::TWO,
+            source: PriceSource::Jupiter,
+            confidence: PriceConfidence::High,
+        }
+    }
+
+    #[test]
+    fn test_sold_too_early_holds_to_peak() {
+        let explain = detectors::sold_too_early_explain(
+            Decimal::new(150, 2),
+            Decimal::new(100, 0),
+            7,
+            &range(Decimal::ONE, Decimal::new(4, 0)),
+        );
+        let cf = counterfactual("S2E", &explain).unwrap();
+        assert_eq!(cf.action, "Hold to the peak at $4 on 2024-03-02");
+        assert_eq!(cf.value_usd, Decimal::new(250, 0));
+        assert_eq!(cf.confidence.as_deref(), Some("high"));
+        assert_eq!(cf.target_ts, Some(datetime!(2024-03-02 12:00 UTC)));
+    }
+
+    #[test]
+    fn test_drawdown_exits_at_entry() {
+        let explain = detectors::drawdown_explain(
+            Decimal::new(2, 0),
+            Decimal::new(10, 0),
+            7,
+            &range(Decimal::new(5, 1), Decimal::new(3, 0)),
+        );
+        let cf = counterfactual("BHD", &explain).unwrap();
+        assert_eq!(cf.action, "Exit at $2 before the drawdown");
+        assert_eq!(cf.value_usd, Decimal::new(15, 0));
+        assert_eq!(cf.target_ts, Some(datetime!(2024-03-04 06:00 UTC)));
+    }
+
+    #[test]
+    fn test_timestamps_stored_before_rfc3339_still_parse() {
+        let at = datetime!(2024-03-02 12:00 UTC);
+        let explain = json!({ "peak_timestamp": at });
+        assert_eq!(explain_ts(&explain, "peak_timestamp"), Some(at));
+    }
+
+    #[test]
+    fn test_no_counterfactual_without_upside() {
+        let explain = json!({ "exit_price": "2", "peak_price": "2", "qty_sold": "5" });
+        assert!(counterfactual("S2E", &explain).is_none());
+        assert!(counterfactual("IDLE_YIELD", &json!({})).is_none());
+        assert!(counterfactual("S2E", &json!({})).is_none());
+    }
+}
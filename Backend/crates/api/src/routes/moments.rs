@@ -12,6 +12,7 @@ use shared::{
     },
     validation::validate_pubkey,
 };
+use rust_decimal::{prelude::ToPrimitive, Decimal};
 use std::collections::HashMap;
 use time::OffsetDateTime;
 use tracing::{debug, info, instrument, warn};
@@ -27,6 +28,7 @@ use crate::{
         TrendingMoment, StatsPeriod, PositionInfo, MarketConditions, PortfolioContext,
         AlternativeAction, HistoricalParallel, TransactionRole,
     },
+    routes::moment_context::{self, Counterfactual, MarketMoves, PortfolioAt, PositionAt},
     state::AppState,
 };
 
@@ -439,25 +441,23 @@ async fn build_detailed_context(
     state: &AppState,
     moment: &Moment,
 ) -> ApiResult<DetailedContext> {
-    // Fetch position information before/after the moment
-    let position_before = get_position_at_time(state, &moment.wallet_address, moment.timestamp - time::Duration::hours(1)).await?;
-    let position_after = get_position_at_time(state, &moment.wallet_address, moment.timestamp + time::Duration::hours(1)).await?;
-
-    // Get market conditions
-    let market_conditions = get_market_conditions(state, &moment.token_mint, moment.timestamp).await?;
-
-    // Get portfolio context
-    let portfolio_context = get_portfolio_context(state, &moment.wallet_address, moment.timestamp).await?;
-
-    // Generate alternative action suggestion
-    let alternative_action = generate_alternative_action(moment, &market_conditions);
+    let context = moment_context::build_context(
+        &state.database.0,
+        state.price_provider.as_ref(),
+        &moment.wallet,
+        moment.mint.as_deref(),
+        moment.kind.as_str(),
+        moment.t_event,
+        &moment.explain_json,
+    )
+    .await?;
 
     Ok(DetailedContext {
-        position_before,
-        position_after,
-        market_conditions,
-        portfolio_context,
-        alternative_action,
+        position_before: context.position_before.map(position_info),
+        position_after: context.position_after.map(position_info),
+        market_conditions: market_conditions(&context.market),
+        portfolio_context: portfolio_context(&context.portfolio),
+        alternative_action: context.counterfactual.map(alternative_action),
     })
 }
 
@@ -570,10 +570,6 @@ fn generate_sharing_options(moment: &MomentDetail) -> SharingOptions {
 // - store_share_info
 // - fetch_top_moments
 // - start_moments_export
-// - get_position_at_time
-// - get_market_conditions
-// - get_portfolio_context
-// - generate_alternative_action
 
 // Placeholder implementations for now
 async fn calculate_overall_stats(state: &AppState, request: &MomentStatsRequest) -> ApiResult<OverallStats> {
@@ -639,41 +635,69 @@ async fn fetch_top_moments(state: &AppState, period: &str, moment_type: Option<M
 }
 
 async fn start_moments_export(state: &AppState, wallet: &str, format: &str, include_transactions: bool, user_id: &str) -> ApiResult<String> {
-    let export_id = Uuid::new_v4().to_string();
-    debug!("Starting export {} for wallet: {} in format: {}", export_id, wallet, format);
+    // Same pipeline as POST /v1/exports
+    let format = shared::export::ExportFormat::parse(format)
+        .ok_or_else(|| ApiError::BadRequest("format must be one of: csv, ndjson, parquet".to_string()))?;
+    let export_id = crate::routes::exports::queue_export(&state.database.0, user_id, wallet, format, include_transactions, None).await?;
+    debug!("Started export {} for wallet: {} in format: {}", export_id, wallet, format.as_str());
     Ok(export_id)
 }
 
-async fn get_position_at_time(state: &AppState, wallet: &str, timestamp: OffsetDateTime) -> ApiResult<Option<PositionInfo>> {
-    Ok(None)
+fn usd(d: Decimal) -> UsdAmount {
+    UsdAmount::from(d.to_f64().unwrap_or_default())
 }
 
-async fn get_market_conditions(state: &AppState, token_mint: &str, timestamp: OffsetDateTime) -> ApiResult<MarketConditions> {
-    Ok(MarketConditions {
-        price_at_moment: UsdAmount::from(50.0),
-        price_1h_later: Some(UsdAmount::from(52.0)),
-        price_24h_later: Some(UsdAmount::from(65.0)),
-        price_7d_later: Some(UsdAmount::from(85.0)),
-        volume_24h: Some(UsdAmount::from(10000000.0)),
-        market_cap: Some(UsdAmount::from(1000000000.0)),
-        volatility: Some(0.15),
-    })
+fn position_info(p: PositionAt) -> PositionInfo {
+    let avg_cost = p.avg_entry_px.unwrap_or_default();
+    let unrealized = p.unrealized_pnl_usd.unwrap_or_default();
+    let basis = avg_cost * p.qty;
+    PositionInfo {
+        amount: TokenAmount::from(p.qty.to_f64().unwrap_or_default()),
+        avg_cost: usd(avg_cost),
+        market_price: usd(p.price.unwrap_or_default()),
+        total_value: usd(p.value_usd.unwrap_or_default()),
+        unrealized_pnl: usd(unrealized),
+        pnl_pct: if basis > Decimal::ZERO {
+            (unrealized / basis * Decimal::ONE_HUNDRED).to_f64().unwrap_or_default()
+        } else {
+            0.0
+        },
+    }
 }
 
-async fn get_portfolio_context(state: &AppState, wallet: &str, timestamp: OffsetDateTime) -> ApiResult<PortfolioContext> {
-    Ok(PortfolioContext {
-        total_value: UsdAmount::from(50000.0),
-        portfolio_pct: 15.0,
-        other_positions: 12,
-        diversification: 0.7,
-    })
+fn market_conditions(market: &MarketMoves) -> MarketConditions {
+    MarketConditions {
+        price_at_moment: usd(market.price_at_moment.unwrap_or_default()),
+        price_1h_later: market.price_1h_later.map(usd),
+        price_24h_later: market.price_24h_later.map(usd),
+        price_7d_later: market.price_7d_later.map(usd),
+        // No volume or market cap history is stored
+        volume_24h: None,
+        market_cap: None,
+        volatility: market.volatility_24h,
+    }
 }
 
-fn generate_alternative_action(moment: &Moment, market_conditions: &MarketConditions) -> Option<AlternativeAction> {
-    Some(AlternativeAction {
-        action: "Hold for 30 more days".to_string(),
-        potential_value: UsdAmount::from(15000.0),
-        confidence: 0.85,
-        reasoning: "Strong technical indicators suggested continued uptrend".to_string(),
-    })
+fn portfolio_context(portfolio: &PortfolioAt) -> PortfolioContext {
+    PortfolioContext {
+        total_value: usd(portfolio.total_value_usd),
+        portfolio_pct: portfolio.moment_mint_pct.unwrap_or_default(),
+        other_positions: portfolio.other_positions,
+        diversification: portfolio.diversification.unwrap_or_default(),
+    }
+}
+
+fn alternative_action(cf: Counterfactual) -> AlternativeAction {
+    let confidence = match cf.confidence.as_deref() {
+        Some("high") => 0.9,
+        Some("medium") => 0.7,
+        Some("low") => 0.5,
+        _ => 0.3,
+    };
+    AlternativeAction {
+        reasoning: format!("Based on the detector's recorded target price of ${}", cf.target_price.normalize()),
+        action: cf.action,
+        potential_value: usd(cf.value_usd),
+        confidence,
+    }
 }
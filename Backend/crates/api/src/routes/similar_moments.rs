@@ -0,0 +1,177 @@
+//! "You and 3,412 others": moments from other wallets on the same mint and
+//! kind around a given moment, plus the moment's cohort (same mint, kind and
+//! UTC day) totals and where its wallet ranks in it.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use shared::{
+    moment_cohort::{load_cohort_day, load_cohort_rank},
+    moment_query::{MomentCursor, MomentFilter, MomentQuery, MomentSort},
+    validation::validate_pagination,
+    ApiError, ApiResult, MomentKind,
+};
+use time::{format_description::well_known::Rfc3339, Duration};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
+use crate::routes::{list_moments, AppState, MomentsListResponse};
+
+/// Default and largest distance from the moment, either side, for listed
+/// moments
+const DEFAULT_WINDOW_HOURS: i64 = 24;
+const MAX_WINDOW_HOURS: i64 = 7 * 24;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SimilarMomentsQuery {
+    pub window_hours: Option<i64>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CohortDto {
+    pub mint: String,
+    pub kind: String,
+    /// UTC day, `YYYY-MM-DD`
+    pub day: String,
+    pub moments: i64,
+    pub wallets: i64,
+    /// Wallets in the cohort besides this moment's
+    #[serde(rename = "otherWallets")]
+    pub other_wallets: i64,
+    #[serde(rename = "missedUsdTotalDec")]
+    pub missed_usd_total_dec: String,
+    #[serde(rename = "missedUsdMaxDec")]
+    pub missed_usd_max_dec: Option<String>,
+    #[serde(rename = "computedAt")]
+    pub computed_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CohortRankDto {
+    pub rank: i64,
+    pub of: i64,
+    #[serde(rename = "missedUsdDec")]
+    pub missed_usd_dec: String,
+    #[serde(rename = "topPct")]
+    pub top_pct: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SimilarMomentsResponse {
+    #[serde(rename = "momentId")]
+    pub moment_id: String,
+    pub cohort: CohortDto,
+    pub rank: Option<CohortRankDto>,
+    /// Other wallets' moments in the window, largest miss first
+    pub similar: MomentsListResponse,
+}
+
+/// GET /v1/moments/:id/similar - Same mint and kind moments from other
+/// wallets, with cohort totals and this wallet's rank
+#[utoipa::path(
+    get,
+    path = "/v1/moments/{id}/similar",
+    tag = "moments",
+    params(("id" = String, Path, description = "Moment ID (ULID)"), SimilarMomentsQuery),
+    responses(
+        (status = 200, description = "Cohort totals, rank and similar moments", body = SimilarMomentsResponse),
+        (status = 400, description = "Invalid moment ID, window or cursor", body = ErrorBody),
+        (status = 401, description = "Invalid credentials", body = ErrorBody),
+        (status = 402, description = "API key row quota used up", body = ErrorBody),
+        (status = 403, description = "API key lacks the read:moments scope", body = ErrorBody),
+        (status = 404, description = "Moment not found", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    ),
+    security((), ("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn similar_moments(
+    State(state): State<AppState>,
+    user: Option<AuthUser>,
+    Path(id): Path<String>,
+    Query(query): Query<SimilarMomentsQuery>,
+) -> ApiResult<Json<SimilarMomentsResponse>> {
+    if id.len() != 26 {
+        return Err(ApiError::BadRequest("Invalid moment ID format".to_string()));
+    }
+    let window_hours = query.window_hours.unwrap_or(DEFAULT_WINDOW_HOURS);
+    if !(1..=MAX_WINDOW_HOURS).contains(&window_hours) {
+        return Err(ApiError::BadRequest(format!(
+            "window_hours must be between 1 and {}",
+            MAX_WINDOW_HOURS
+        )));
+    }
+    let (limit, cursor) = validate_pagination(query.limit, query.cursor.as_deref())?;
+    let after = cursor
+        .as_deref()
+        .map(|c| MomentCursor::decode(c, MomentSort::MissedUsd))
+        .transpose()?;
+
+    let moment = sqlx::query!(
+        "SELECT wallet, mint, kind, t_event FROM oof_moments WHERE id = $1",
+        id
+    )
+    .fetch_optional(&state.pg.0)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Moment not found".to_string()))?;
+    let mint = moment
+        .mint
+        .ok_or_else(|| ApiError::BadRequest("Moment is not tied to a token".to_string()))?;
+    let kind: MomentKind = moment
+        .kind
+        .parse()
+        .map_err(|e: String| ApiError::Internal(anyhow::anyhow!(e)))?;
+
+    let cohort = load_cohort_day(&state.pg.0, &mint, kind.as_str(), moment.t_event).await?;
+    let rank = load_cohort_rank(&state.pg.0, &cohort, &moment.wallet).await?;
+
+    let window = Duration::hours(window_hours);
+    let filter = MomentFilter {
+        exclude_wallets: vec![moment.wallet.clone()],
+        mint: Some(mint),
+        kinds: vec![kind],
+        since: Some(moment.t_event - window),
+        until: Some(moment.t_event + window),
+        ..Default::default()
+    };
+    let moment_query = MomentQuery::new()
+        .with_filter(filter)
+        .with_sort(MomentSort::MissedUsd)
+        .with_cursor(after)
+        .with_limit(limit);
+    let similar = list_moments(&state, user.as_ref(), moment_query, cursor).await?;
+
+    state
+        .metrics
+        .increment_counter("similar_moments_requests_total");
+
+    let in_cohort = i64::from(rank.is_some());
+    Ok(Json(SimilarMomentsResponse {
+        moment_id: id,
+        rank: rank.map(|r| CohortRankDto {
+            top_pct: r.top_pct(),
+            rank: r.rank,
+            of: r.wallets,
+            missed_usd_dec: r.missed_usd.to_string(),
+        }),
+        cohort: CohortDto {
+            other_wallets: (cohort.wallets - in_cohort).max(0),
+            day: cohort.day.to_string(),
+            moments: cohort.moments,
+            wallets: cohort.wallets,
+            missed_usd_total_dec: cohort.missed_usd_total.to_string(),
+            missed_usd_max_dec: cohort.missed_usd_max.map(|d| d.to_string()),
+            computed_at: cohort.computed_at.format(&Rfc3339).unwrap_or_default(),
+            mint: cohort.mint,
+            kind: cohort.kind,
+        },
+        similar,
+    }))
+}
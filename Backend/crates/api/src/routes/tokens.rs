@@ -9,16 +9,19 @@ use shared::{
 };
 use sqlx::Row;
 use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
 
+use crate::openapi::ErrorBody;
 use crate::routes::AppState;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TrendingTokensQuery {
     pub limit: Option<usize>,
     pub cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TrendingToken {
     pub mint: String,
     pub symbol: Option<String>,
@@ -29,12 +32,24 @@ pub struct TrendingToken {
     pub volume_24h: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TrendingTokensResponse {
     pub data: Vec<TrendingToken>,
     pub cursor: Option<String>,
 }
 
+/// GET /v1/trending/tokens - Tokens with the most swap volume in the last
+/// 24 hours
+#[utoipa::path(
+    get,
+    path = "/v1/trending/tokens",
+    tag = "tokens",
+    params(TrendingTokensQuery),
+    responses(
+        (status = 200, description = "Tokens by 24h volume", body = TrendingTokensResponse),
+        (status = 400, description = "Invalid pagination", body = ErrorBody),
+    )
+)]
 pub async fn trending_tokens(
     State(state): State<AppState>,
     Query(query): Query<TrendingTokensQuery>,
@@ -0,0 +1,248 @@
+//! Head-to-head comparison of up to five wallets on realized performance,
+//! risk, fees and missed opportunities. Metrics come from
+//! `shared::wallet_metrics`; this replaces the placeholder `compare_wallets`
+//! in `routes/wallets.rs`, which is not part of the built router.
+
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use shared::{
+    validation::validate_wallet_address,
+    wallet_metrics::{
+        leaders, load_wallet_metrics, metrics_window, Leaders, WalletMetrics, DEFAULT_METRICS_DAYS,
+        MAX_COMPARE_WALLETS, MAX_METRICS_DAYS,
+    },
+    ApiError, ApiResult,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::openapi::ErrorBody;
+use crate::routes::AppState;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CompareWalletsQuery {
+    /// Comma separated wallet addresses
+    pub wallets: String,
+    pub days: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MissedByKindDto {
+    pub kind: String,
+    pub moments: i64,
+    #[serde(rename = "missedUsdDec")]
+    pub missed_usd_dec: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BiggestMomentDto {
+    pub id: String,
+    pub kind: String,
+    pub mint: Option<String>,
+    #[serde(rename = "tEvent")]
+    pub t_event: String,
+    #[serde(rename = "missedUsdDec")]
+    pub missed_usd_dec: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WalletMetricsDto {
+    pub wallet: String,
+    pub trades: i64,
+    #[serde(rename = "winningTrades")]
+    pub winning_trades: i64,
+    #[serde(rename = "winRate")]
+    pub win_rate: Option<f64>,
+    #[serde(rename = "realizedPnlUsdDec")]
+    pub realized_pnl_usd_dec: String,
+    #[serde(rename = "closedEpisodes")]
+    pub closed_episodes: i64,
+    #[serde(rename = "avgHoldSecs")]
+    pub avg_hold_secs: Option<i64>,
+    #[serde(rename = "maxDrawdown")]
+    pub max_drawdown: Option<f64>,
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    /// Days with money invested, which the risk metrics are computed over
+    #[serde(rename = "activeDays")]
+    pub active_days: usize,
+    #[serde(rename = "totalFeesSolDec")]
+    pub total_fees_sol_dec: String,
+    #[serde(rename = "missedUsdTotalDec")]
+    pub missed_usd_total_dec: String,
+    #[serde(rename = "missedByKind")]
+    pub missed_by_kind: Vec<MissedByKindDto>,
+    #[serde(rename = "biggestMoment")]
+    pub biggest_moment: Option<BiggestMomentDto>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LeadersDto {
+    #[serde(rename = "winRate")]
+    pub win_rate: Option<String>,
+    #[serde(rename = "realizedPnl")]
+    pub realized_pnl: Option<String>,
+    pub sharpe: Option<String>,
+    pub sortino: Option<String>,
+    #[serde(rename = "maxDrawdown")]
+    pub max_drawdown: Option<String>,
+    #[serde(rename = "totalFees")]
+    pub total_fees: Option<String>,
+    #[serde(rename = "missedUsd")]
+    pub missed_usd: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CompareWalletsResponse {
+    pub since: String,
+    pub until: String,
+    pub wallets: Vec<WalletMetricsDto>,
+    /// Best wallet per metric; lowest wins for drawdown, fees and missed USD
+    pub leaders: LeadersDto,
+}
+
+impl From<WalletMetrics> for WalletMetricsDto {
+    fn from(m: WalletMetrics) -> Self {
+        Self {
+            active_days: m.daily_returns.len(),
+            wallet: m.wallet,
+            trades: m.trades,
+            winning_trades: m.winning_trades,
+            win_rate: m.win_rate,
+            realized_pnl_usd_dec: m.realized_pnl_usd.to_string(),
+            closed_episodes: m.closed_episodes,
+            avg_hold_secs: m.avg_hold.map(|d| d.whole_seconds()),
+            max_drawdown: m.max_drawdown,
+            sharpe: m.sharpe,
+            sortino: m.sortino,
+            total_fees_sol_dec: m.total_fees_sol.to_string(),
+            missed_usd_total_dec: m.missed_usd_total.to_string(),
+            missed_by_kind: m
+                .missed_by_kind
+                .into_iter()
+                .map(|k| MissedByKindDto {
+                    kind: k.kind,
+                    moments: k.moments,
+                    missed_usd_dec: k.missed_usd.to_string(),
+                })
+                .collect(),
+            biggest_moment: m.biggest_moment.map(|b| BiggestMomentDto {
+                id: b.id,
+                kind: b.kind,
+                mint: b.mint,
+                t_event: b.t_event.format(&Rfc3339).unwrap_or_default(),
+                missed_usd_dec: b.missed_usd.to_string(),
+            }),
+        }
+    }
+}
+
+impl From<Leaders> for LeadersDto {
+    fn from(l: Leaders) -> Self {
+        Self {
+            win_rate: l.win_rate,
+            realized_pnl: l.realized_pnl,
+            sharpe: l.sharpe,
+            sortino: l.sortino,
+            max_drawdown: l.max_drawdown,
+            total_fees: l.total_fees,
+            missed_usd: l.missed_usd,
+        }
+    }
+}
+
+/// Split and validate the `wallets` parameter, dropping repeats
+fn parse_wallets(raw: &str) -> ApiResult<Vec<String>> {
+    let mut wallets: Vec<String> = Vec::new();
+    for wallet in raw.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+        validate_wallet_address(wallet)?;
+        if !wallets.iter().any(|w| w == wallet) {
+            wallets.push(wallet.to_string());
+        }
+    }
+    if wallets.is_empty() {
+        return Err(ApiError::BadRequest(
+            "At least one wallet is required".to_string(),
+        ));
+    }
+    if wallets.len() > MAX_COMPARE_WALLETS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} wallets can be compared",
+            MAX_COMPARE_WALLETS
+        )));
+    }
+    Ok(wallets)
+}
+
+/// GET /v1/wallets/compare?wallets=a,b - Metrics for each wallet over the
+/// last `days` days and which wallet leads each one
+#[utoipa::path(
+    get,
+    path = "/v1/wallets/compare",
+    tag = "wallets",
+    params(CompareWalletsQuery),
+    responses(
+        (status = 200, description = "Metrics per wallet and the leader of each", body = CompareWalletsResponse),
+        (status = 400, description = "Invalid wallets or days", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn compare_wallets(
+    State(state): State<AppState>,
+    Query(query): Query<CompareWalletsQuery>,
+) -> ApiResult<Json<CompareWalletsResponse>> {
+    let wallets = parse_wallets(&query.wallets)?;
+    let days = query.days.unwrap_or(DEFAULT_METRICS_DAYS);
+    if !(1..=MAX_METRICS_DAYS).contains(&days) {
+        return Err(ApiError::BadRequest(format!(
+            "days must be between 1 and {}",
+            MAX_METRICS_DAYS
+        )));
+    }
+    let (since, until) = metrics_window(OffsetDateTime::now_utc(), days);
+
+    let metrics = try_join_all(
+        wallets
+            .iter()
+            .map(|w| load_wallet_metrics(&state.pg.0, w, since, until)),
+    )
+    .await?;
+
+    state
+        .metrics
+        .increment_counter("wallet_compare_requests_total");
+
+    let leaders = leaders(&metrics).into();
+    Ok(Json(CompareWalletsResponse {
+        since: since.format(&Rfc3339).unwrap_or_default(),
+        until: until.format(&Rfc3339).unwrap_or_default(),
+        wallets: metrics.into_iter().map(Into::into).collect(),
+        leaders,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const B: &str = "So11111111111111111111111111111111111111112";
+
+    #[test]
+    fn test_parse_wallets_dedupes_and_limits() {
+        let raw = format!("{A}, {B},{A}");
+        assert_eq!(parse_wallets(&raw).unwrap(), vec![A, B]);
+        assert!(parse_wallets(" , ").is_err());
+        assert!(parse_wallets(&[A; 6].join(",")).is_ok());
+
+        let six: Vec<String> = (0..6).map(|i| format!("{}{}", &A[..43], i + 1)).collect();
+        assert!(parse_wallets(&six.join(",")).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Daily equity curve for a wallet alongside SOL, for charting. Both
+//! indexes are rebased to 1.0 at the first day shown; volatility and beta
+//! are computed over the days shown.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use shared::{
+    equity::{
+        annualized_volatility, beta, close_returns, load_daily_closes, load_equity_series, rebase,
+        DailyCloses, BENCHMARK_MINT, EQUITY_HISTORY_DAYS,
+    },
+    validation::validate_wallet_address,
+    ApiError, ApiResult,
+};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::openapi::ErrorBody;
+use crate::routes::AppState;
+
+const DEFAULT_EQUITY_DAYS: i64 = 90;
+
+/// Closes are carried forward a few days, so look back that far for the
+/// first benchmark point
+const BENCHMARK_LOOKBACK_DAYS: i64 = 3;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WalletEquityQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EquityPointDto {
+    /// UTC day, `YYYY-MM-DD`
+    pub day: String,
+    #[serde(rename = "equityUsdDec")]
+    pub equity_usd_dec: String,
+    /// Transfers in minus out at the day's close
+    #[serde(rename = "netFlowUsdDec")]
+    pub net_flow_usd_dec: String,
+    #[serde(rename = "costBasisUsdDec")]
+    pub cost_basis_usd_dec: String,
+    #[serde(rename = "unrealizedPnlUsdDec")]
+    pub unrealized_pnl_usd_dec: String,
+    /// Cumulative
+    #[serde(rename = "realizedPnlUsdDec")]
+    pub realized_pnl_usd_dec: String,
+    /// Flow-adjusted; null when nothing was invested
+    #[serde(rename = "dailyReturn")]
+    pub daily_return: Option<f64>,
+    /// Time-weighted return index
+    pub index: f64,
+    #[serde(rename = "unpricedMints")]
+    pub unpriced_mints: i32,
+    #[serde(rename = "benchmarkCloseUsdDec")]
+    pub benchmark_close_usd_dec: Option<String>,
+    #[serde(rename = "benchmarkIndex")]
+    pub benchmark_index: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WalletEquityResponse {
+    pub wallet: String,
+    #[serde(rename = "benchmarkMint")]
+    pub benchmark_mint: String,
+    /// Null until the equity job has run for this wallet
+    #[serde(rename = "computedAt")]
+    pub computed_at: Option<String>,
+    /// Annualised standard deviation of daily returns; null with fewer
+    /// than two returns
+    pub volatility: Option<f64>,
+    /// Beta of daily returns against SOL; null without enough overlap
+    pub beta: Option<f64>,
+    pub points: Vec<EquityPointDto>,
+}
+
+/// GET /v1/wallets/:wallet/equity - Daily equity, PnL and a SOL benchmark
+#[utoipa::path(
+    get,
+    path = "/v1/wallets/{wallet}/equity",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address"), WalletEquityQuery),
+    responses(
+        (status = 200, description = "Daily equity with a SOL benchmark", body = WalletEquityResponse),
+        (status = 400, description = "Invalid wallet or days", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    )
+)]
+#[instrument(skip(state))]
+pub async fn wallet_equity(
+    State(state): State<AppState>,
+    Path(wallet): Path<String>,
+    Query(query): Query<WalletEquityQuery>,
+) -> ApiResult<Json<WalletEquityResponse>> {
+    validate_wallet_address(&wallet)?;
+    let days = query.days.unwrap_or(DEFAULT_EQUITY_DAYS);
+    if !(1..=EQUITY_HISTORY_DAYS).contains(&days) {
+        return Err(ApiError::BadRequest(format!(
+            "days must be between 1 and {}",
+            EQUITY_HISTORY_DAYS
+        )));
+    }
+
+    let until = OffsetDateTime::now_utc().date();
+    let since = until - Duration::days(days - 1);
+    let (series, computed_at) = load_equity_series(&state.pg.0, &wallet, since, until).await?;
+
+    let benchmark = match series.first() {
+        Some(first) => {
+            load_daily_closes(
+                &state.pg.0,
+                &[BENCHMARK_MINT.to_string()],
+                (first.day - Duration::days(BENCHMARK_LOOKBACK_DAYS))
+                    .midnight()
+                    .assume_utc(),
+                until.midnight().assume_utc() + Duration::days(1),
+            )
+            .await?
+        }
+        None => DailyCloses::new(),
+    };
+    let benchmark_closes: Vec<_> = series
+        .iter()
+        .map(|d| benchmark.close(BENCHMARK_MINT, d.day))
+        .collect();
+    let benchmark_index = rebase(&benchmark_closes);
+
+    let returns: Vec<_> = series
+        .iter()
+        .filter_map(|d| Some((d.day, d.daily_return?)))
+        .collect();
+    let benchmark_returns = close_returns(
+        &series
+            .iter()
+            .zip(&benchmark_closes)
+            .filter_map(|(d, close)| Some((d.day, (*close)?)))
+            .collect::<Vec<_>>(),
+    );
+    let volatility = annualized_volatility(&returns.iter().map(|(_, r)| *r).collect::<Vec<_>>());
+    let beta = beta(&returns, &benchmark_returns);
+    let base_index = series
+        .first()
+        .map(|d| d.twr_index)
+        .filter(|i| *i > 0.0)
+        .unwrap_or(1.0);
+
+    state
+        .metrics
+        .increment_counter("wallet_equity_requests_total");
+
+    let points = series
+        .into_iter()
+        .zip(benchmark_closes)
+        .zip(benchmark_index)
+        .map(|((d, close), bench_index)| EquityPointDto {
+            day: d.day.to_string(),
+            equity_usd_dec: d.equity_usd.to_string(),
+            net_flow_usd_dec: d.net_flow_usd.to_string(),
+            cost_basis_usd_dec: d.cost_basis_usd.to_string(),
+            unrealized_pnl_usd_dec: d.unrealized_pnl_usd.to_string(),
+            realized_pnl_usd_dec: d.realized_pnl_usd.to_string(),
+            daily_return: d.daily_return,
+            index: d.twr_index / base_index,
+            unpriced_mints: d.unpriced_mints,
+            benchmark_close_usd_dec: close.map(|c| c.to_string()),
+            benchmark_index: bench_index,
+        })
+        .collect();
+
+    Ok(Json(WalletEquityResponse {
+        wallet,
+        benchmark_mint: BENCHMARK_MINT.to_string(),
+        computed_at: computed_at.map(|t| t.format(&Rfc3339).unwrap_or_default()),
+        volatility,
+        beta,
+        points,
+    }))
+}
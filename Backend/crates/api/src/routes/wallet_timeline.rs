@@ -0,0 +1,240 @@
+//! One chronological feed per wallet: buys, sells, swaps, transfers,
+//! realized trades, episode opens and closes, and OOF moments, newest
+//! first. See `shared::timeline` for how the sources are merged.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use shared::{
+    api_keys::ApiScope,
+    timeline::{TimelineCursor, TimelineEventType, TimelineQuery, TimelineRow},
+    validation::{validate_pagination, validate_wallet_address},
+    ApiError, ApiResult,
+};
+use time::format_description::well_known::Rfc3339;
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth_mw::AuthUser;
+use crate::openapi::ErrorBody;
+use crate::routes::{AppState, PaginationInfo};
+
+/// Keeps the `= ANY(...)` filters small enough for the per-wallet indexes
+const MAX_MINT_FILTERS: usize = 20;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WalletTimelineQuery {
+    /// Comma-separated; any of buy, sell, swap, transfer, trade,
+    /// episode_open, episode_close, moment. Defaults to all.
+    pub types: Option<String>,
+    /// Comma-separated mint addresses; SOL transfers match the wrapped SOL mint
+    pub mints: Option<String>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+impl WalletTimelineQuery {
+    fn to_timeline_query(&self, wallet: String) -> ApiResult<TimelineQuery> {
+        let (limit, cursor) = validate_pagination(self.limit, self.cursor.as_deref())?;
+        let cursor = cursor.map(|c| TimelineCursor::decode(&c)).transpose()?;
+        Ok(TimelineQuery::new(wallet)
+            .with_types(parse_types(self.types.as_deref())?)
+            .with_mints(parse_mints(self.mints.as_deref())?)
+            .with_cursor(cursor)
+            .with_limit(limit))
+    }
+}
+
+fn csv(raw: Option<&str>) -> impl Iterator<Item = &str> {
+    raw.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+fn parse_types(raw: Option<&str>) -> ApiResult<Vec<TimelineEventType>> {
+    let mut types = Vec::new();
+    for s in csv(raw) {
+        let event_type = TimelineEventType::parse(s)
+            .ok_or_else(|| ApiError::BadRequest(format!("Invalid event type: {}", s)))?;
+        if !types.contains(&event_type) {
+            types.push(event_type);
+        }
+    }
+    Ok(types)
+}
+
+fn parse_mints(raw: Option<&str>) -> ApiResult<Vec<String>> {
+    let mut mints: Vec<String> = Vec::new();
+    for mint in csv(raw) {
+        validate_wallet_address(mint)
+            .map_err(|_| ApiError::BadRequest(format!("Invalid mint: {}", mint)))?;
+        if !mints.iter().any(|m| m == mint) {
+            mints.push(mint.to_string());
+        }
+    }
+    if mints.len() > MAX_MINT_FILTERS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} mints can be filtered on",
+            MAX_MINT_FILTERS
+        )));
+    }
+    Ok(mints)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimelineEventDto {
+    /// Source-prefixed: `a:` action, `t:` trade, `eo:`/`ec:` episode open
+    /// and close, `m:` moment
+    pub id: String,
+    /// RFC 3339
+    pub ts: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub mint: Option<String>,
+    pub sig: Option<String>,
+    /// Token quantity; signed for transfers
+    #[serde(rename = "amountDec")]
+    pub amount_dec: Option<String>,
+    /// Execution price, or the last known price for transfers
+    #[serde(rename = "priceUsdDec")]
+    pub price_usd_dec: Option<String>,
+    /// Leg or trade value; basis for episode opens, proceeds for closes and
+    /// the missed amount for moments
+    #[serde(rename = "valueUsdDec")]
+    pub value_usd_dec: Option<String>,
+    #[serde(rename = "pnlUsdDec")]
+    pub pnl_usd_dec: Option<String>,
+    #[serde(rename = "tokenSymbol", skip_serializing_if = "Option::is_none")]
+    pub token_symbol: Option<String>,
+    #[serde(rename = "tokenLogoUrl", skip_serializing_if = "Option::is_none")]
+    pub token_logo_url: Option<String>,
+    /// Source-specific fields, e.g. swap quote, transfer direction or
+    /// moment kind
+    pub detail: serde_json::Value,
+}
+
+impl From<TimelineRow> for TimelineEventDto {
+    fn from(row: TimelineRow) -> Self {
+        Self {
+            id: row.id,
+            ts: row.ts.format(&Rfc3339).unwrap_or_default(),
+            event_type: row.event_type,
+            mint: row.mint,
+            sig: row.sig,
+            amount_dec: row.amount.map(|d| d.to_string()),
+            price_usd_dec: row.price_usd.map(|d| d.to_string()),
+            value_usd_dec: row.value_usd.map(|d| d.to_string()),
+            pnl_usd_dec: row.pnl_usd.map(|d| d.to_string()),
+            token_symbol: row.token_symbol,
+            token_logo_url: row.token_logo_url,
+            detail: row.detail.unwrap_or_else(|| serde_json::json!({})),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WalletTimelineResponse {
+    pub wallet: String,
+    pub data: Vec<TimelineEventDto>,
+    pub pagination: PaginationInfo,
+}
+
+/// GET /v1/wallets/:wallet/timeline - The wallet's activity and moments,
+/// newest first
+#[utoipa::path(
+    get,
+    path = "/v1/wallets/{wallet}/timeline",
+    tag = "wallets",
+    params(("wallet" = String, Path, description = "Wallet address"), WalletTimelineQuery),
+    responses(
+        (status = 200, description = "A page of the wallet's timeline", body = WalletTimelineResponse),
+        (status = 400, description = "Invalid wallet, filters or cursor", body = ErrorBody),
+        (status = 401, description = "Invalid credentials", body = ErrorBody),
+        (status = 402, description = "API key row quota used up", body = ErrorBody),
+        (status = 403, description = "API key lacks the read:moments scope", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    ),
+    security((), ("bearerAuth" = []), ("apiKey" = []))
+)]
+#[instrument(skip(state))]
+pub async fn wallet_timeline(
+    State(state): State<AppState>,
+    user: Option<AuthUser>,
+    Path(wallet): Path<String>,
+    Query(query): Query<WalletTimelineQuery>,
+) -> ApiResult<Json<WalletTimelineResponse>> {
+    validate_wallet_address(&wallet)?;
+    let mut timeline_query = query.to_timeline_query(wallet.clone())?;
+
+    // Same terms as the moment listings: keys need read:moments and their
+    // pages are cut to the rows they have left
+    if let Some(user) = &user {
+        user.require_scope(ApiScope::ReadMoments)?;
+        if let Some(key) = &user.api_key {
+            timeline_query.limit = timeline_query.limit.min(key.rows_remaining as usize);
+        }
+    }
+
+    let page = timeline_query.fetch_page(&state.pg.0).await?;
+    let data: Vec<TimelineEventDto> = page.rows.into_iter().map(Into::into).collect();
+    if let Some(user) = &user {
+        user.meter_rows(&state, data.len()).await?;
+    }
+
+    state
+        .metrics
+        .increment_counter("wallet_timeline_requests_total");
+
+    Ok(Json(WalletTimelineResponse {
+        wallet,
+        data,
+        pagination: PaginationInfo {
+            limit: timeline_query.limit,
+            cursor: query.cursor,
+            has_more: page.next_cursor.is_some(),
+            next_cursor: page.next_cursor.map(|c| c.encode()),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_types() {
+        assert!(parse_types(None).unwrap().is_empty());
+        assert_eq!(
+            parse_types(Some("moment, trade,moment")).unwrap(),
+            vec![TimelineEventType::Moment, TimelineEventType::Trade]
+        );
+        assert!(parse_types(Some("sol_transfer")).is_err());
+    }
+
+    #[test]
+    fn test_parse_mints() {
+        let sol = "So11111111111111111111111111111111111111112";
+        assert_eq!(
+            parse_mints(Some(&format!("{sol},{sol}"))).unwrap(),
+            vec![sol]
+        );
+        assert!(parse_mints(Some("not-a-mint")).is_err());
+
+        let many: Vec<String> = "abcdefghijkmnopqrstuvwxyz"
+            .chars()
+            .take(MAX_MINT_FILTERS + 1)
+            .map(|c| format!("{}{}", "1".repeat(43), c))
+            .collect();
+        assert!(parse_mints(Some(&many.join(","))).is_err());
+        assert_eq!(
+            parse_mints(Some(&many[..MAX_MINT_FILTERS].join(",")))
+                .unwrap()
+                .len(),
+            MAX_MINT_FILTERS
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use detectors::prices::CompositePriceProvider;
 use shared::{
     observability::{HealthChecker, MetricsRegistry},
     store::ObjectStore,
@@ -14,6 +15,8 @@ pub struct AppState {
     pub redis: MaybeRedis,
     pub object_store: Arc<dyn ObjectStore>,
     pub policy_service: PolicyService,
+    /// Shared so its cache is reused across requests
+    pub price_provider: Arc<CompositePriceProvider>,
     pub metrics_registry: Arc<MetricsRegistry>,
     pub health_checker: Arc<HealthChecker>,
     pub broadcast_tx: broadcast::Sender<String>,
@@ -29,6 +32,11 @@ impl AppState {
         health_checker: Arc<HealthChecker>,
     ) -> Self {
         let policy_service = PolicyService::new(database.0.clone());
+        let price_provider = Arc::new(CompositePriceProvider::new(
+            database.0.clone(),
+            redis.clone(),
+            config.jupiter_base_url.clone(),
+        ));
         let (broadcast_tx, _) = broadcast::channel(1000);
 
         Self {
@@ -37,6 +45,7 @@ impl AppState {
             redis,
             object_store,
             policy_service,
+            price_provider,
             metrics_registry: Arc::new(metrics_registry),
             health_checker,
             broadcast_tx,
@@ -2,8 +2,8 @@ use anyhow::Result;
 use async_trait::async_trait;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use shared::{ChainEvent, Moment, MomentContext, MomentKind, PriceProvider};
-use time::{Duration, OffsetDateTime};
+use shared::{ChainEvent, Moment, MomentContext, MomentKind, PriceProvider, PriceRange};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 
 pub mod position;
 pub mod prices;
@@ -33,6 +33,47 @@ pub trait Detector: Send + Sync {
     fn should_process(&self, event: &ChainEvent) -> bool;
 }
 
+/// Explain JSON for an S2E moment: the exit against the window's peak.
+/// Timestamps are RFC 3339 strings.
+pub fn sold_too_early_explain(
+    exit_price: Decimal,
+    qty_sold: Decimal,
+    window_days: i64,
+    range: &PriceRange,
+) -> serde_json::Value {
+    serde_json::json!({
+        "exit_price": exit_price,
+        "peak_price": range.max_price,
+        "peak_timestamp": range.max_timestamp.format(&Rfc3339).ok(),
+        "qty_sold": qty_sold,
+        "window_days": window_days,
+        "price_source": range.source,
+        "confidence": range.confidence
+    })
+}
+
+/// Explain JSON for a BHD moment: the entry against the window's trough.
+/// Timestamps are RFC 3339 strings.
+pub fn drawdown_explain(
+    entry_price: Decimal,
+    qty_bought: Decimal,
+    window_days: i64,
+    range: &PriceRange,
+) -> serde_json::Value {
+    let trough_price = range.min_price;
+    serde_json::json!({
+        "entry_price": entry_price,
+        "trough_price": trough_price,
+        "trough_timestamp": range.min_timestamp.format(&Rfc3339).ok(),
+        "qty_bought": qty_bought,
+        "drawdown_pct": (trough_price - entry_price) / entry_price,
+        "unrealized_loss_usd": qty_bought * (trough_price - entry_price),
+        "window_days": window_days,
+        "price_source": range.source,
+        "confidence": range.confidence
+    })
+}
+
 /// S2E (Sold Too Early) detector
 pub struct SoldTooEarlyDetector {
     min_percentage: Decimal,
@@ -112,15 +153,8 @@ impl Detector for SoldTooEarlyDetector {
                 moment.slot_ref = Some(event.slot);
                 moment.version = self.version().to_string();
 
-                moment.explain_json = serde_json::json!({
-                    "exit_price": exit_price,
-                    "peak_price": peak_price,
-                    "peak_timestamp": range.max_timestamp,
-                    "qty_sold": qty_sold,
-                    "window_days": self.window_days,
-                    "price_source": range.source,
-                    "confidence": range.confidence
-                });
+                moment.explain_json =
+                    sold_too_early_explain(exit_price, qty_sold, self.window_days, &range);
 
                 return Ok(Some(moment));
             }
@@ -207,17 +241,8 @@ impl Detector for BagHolderDrawdownDetector {
                 moment.slot_ref = Some(event.slot);
                 moment.version = self.version().to_string();
 
-                moment.explain_json = serde_json::json!({
-                    "entry_price": entry_price,
-                    "trough_price": trough_price,
-                    "trough_timestamp": range.min_timestamp,
-                    "qty_bought": qty_bought,
-                    "drawdown_pct": drawdown_pct,
-                    "unrealized_loss_usd": unrealized_loss,
-                    "window_days": self.window_days,
-                    "price_source": range.source,
-                    "confidence": range.confidence
-                });
+                moment.explain_json =
+                    drawdown_explain(entry_price, qty_bought, self.window_days, &range);
 
                 return Ok(Some(moment));
             }
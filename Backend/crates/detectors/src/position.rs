@@ -2,9 +2,9 @@ use anyhow::Result;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use shared::{ChainEvent, EventKind};
+use shared::{tax::DisposedLot, ChainEvent, EventKind};
 use sqlx::PgPool;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use time::OffsetDateTime;
 use ulid::Ulid;
 
@@ -16,6 +16,14 @@ pub struct Lot {
     pub qty_initial: Decimal,
     pub qty_remaining: Decimal,
     pub entry_px: Decimal,
+    /// Bought without a USD price; `entry_px` is zero and the lot has no
+    /// known cost basis. Before migration 0027 such buys opened no lot, so
+    /// sells matched older priced lots instead and realized PnL and
+    /// detector inputs came out differently. Lots and trades stored before
+    /// then are not re-analyzed; they only change when the wallet's
+    /// positions are rebuilt from its actions.
+    #[serde(default)]
+    pub entry_px_missing: bool,
 }
 
 impl Lot {
@@ -26,6 +34,15 @@ impl Lot {
             qty_initial: qty,
             qty_remaining: qty,
             entry_px,
+            entry_px_missing: false,
+        }
+    }
+
+    /// Lot for a buy whose USD price is unknown
+    pub fn unpriced(entry_ts: OffsetDateTime, qty: Decimal) -> Self {
+        Self {
+            entry_px_missing: true,
+            ..Self::new(entry_ts, qty, Decimal::ZERO)
         }
     }
 
@@ -36,6 +53,9 @@ impl Lot {
 
     /// Calculate unrealized PnL at current price
     pub fn unrealized_pnl(&self, current_px: Decimal) -> Decimal {
+        if self.entry_px_missing {
+            return Decimal::ZERO;
+        }
         (current_px - self.entry_px) * self.qty_remaining
     }
 }
@@ -90,6 +110,9 @@ pub struct RealizedTrade {
     pub vwavg_exit_px: Decimal,
     pub realized_pnl_usd: Decimal,
     pub sig: String,
+    /// Lots the sell consumed, for tax reporting
+    #[serde(default)]
+    pub lots: Vec<DisposedLot>,
 }
 
 impl RealizedTrade {
@@ -113,10 +136,19 @@ impl RealizedTrade {
             vwavg_exit_px,
             realized_pnl_usd,
             sig,
+            lots: Vec::new(),
         }
     }
+
+    pub fn with_lots(mut self, lots: Vec<DisposedLot>) -> Self {
+        self.lots = lots;
+        self
+    }
 }
 
+/// A wallet's position states keyed by mint
+pub type StatesByMint = HashMap<String, PositionState>;
+
 /// Position state for a wallet/mint pair
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct PositionState {
@@ -149,12 +181,18 @@ impl PositionState {
         self.lots.iter().map(|lot| lot.cost_basis()).sum()
     }
 
-    /// Calculate average entry price
+    /// Calculate average entry price over lots with a known price
     pub fn average_entry_price(&self) -> Option<Decimal> {
-        if self.exposure == Decimal::ZERO {
+        let priced: Decimal = self
+            .lots
+            .iter()
+            .filter(|lot| !lot.entry_px_missing)
+            .map(|lot| lot.qty_remaining)
+            .sum();
+        if self.exposure == Decimal::ZERO || priced == Decimal::ZERO {
             return None;
         }
-        Some(self.cost_basis() / self.exposure)
+        Some(self.cost_basis() / priced)
     }
 
     /// Calculate unrealized PnL at current price
@@ -174,11 +212,22 @@ impl PositionState {
 /// Position engine for processing chain events
 pub struct Engine {
     pool: PgPool,
+    persist: bool,
 }
 
 impl Engine {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            persist: true,
+        }
+    }
+
+    /// Whether lots, trades, episodes and snapshots are written back.
+    /// Replays that only inspect historical state turn this off.
+    pub fn with_persist(mut self, persist: bool) -> Self {
+        self.persist = persist;
+        self
     }
 
     /// Process a chain event and update position state
@@ -191,8 +240,11 @@ impl Engine {
 
         match event.kind {
             EventKind::Buy => {
-                if let (Some(qty), Some(px)) = (event.amount, event.price_usd) {
-                    self.on_buy(state, event.timestamp, qty, px).await?;
+                // Unpriced buys still open a lot so later sells match the
+                // right quantity; the lot is flagged as having no basis
+                if let Some(qty) = event.amount {
+                    self.on_buy(state, event.timestamp, qty, event.price_usd)
+                        .await?;
                 }
             }
             EventKind::Sell => {
@@ -219,7 +271,7 @@ impl Engine {
         state.snapshot_counter += 1;
 
         // Persist snapshots periodically
-        if state.should_snapshot() {
+        if self.persist && state.should_snapshot() {
             self.persist_snapshot(state).await?;
         }
 
@@ -232,7 +284,7 @@ impl Engine {
         state: &mut PositionState,
         ts: OffsetDateTime,
         qty: Decimal,
-        px: Decimal,
+        px: Option<Decimal>,
     ) -> Result<()> {
         // Start new episode if no exposure
         if state.exposure == Decimal::ZERO {
@@ -241,27 +293,33 @@ impl Engine {
         }
 
         // Add new lot
-        let lot = Lot::new(ts, qty, px);
+        let lot = match px {
+            Some(px) => Lot::new(ts, qty, px),
+            None => Lot::unpriced(ts, qty),
+        };
 
         // Persist lot to database
-        let episode_id = state.current_episode.as_ref().unwrap().episode_id.clone();
-        sqlx::query(include_str!("../../../../db/queries/upsert_lot.sql"))
-            .bind(&lot.lot_id)
-            .bind(&state.wallet)
-            .bind(&state.mint)
-            .bind(&episode_id)
-            .bind(lot.entry_ts)
-            .bind(lot.qty_initial)
-            .bind(lot.qty_remaining)
-            .bind(lot.entry_px)
-            .execute(&self.pool)
-            .await?;
+        if self.persist {
+            let episode_id = state.current_episode.as_ref().unwrap().episode_id.clone();
+            sqlx::query(include_str!("../../../../db/queries/upsert_lot.sql"))
+                .bind(&lot.lot_id)
+                .bind(&state.wallet)
+                .bind(&state.mint)
+                .bind(&episode_id)
+                .bind(lot.entry_ts)
+                .bind(lot.qty_initial)
+                .bind(lot.qty_remaining)
+                .bind(lot.entry_px)
+                .bind(lot.entry_px_missing)
+                .execute(&self.pool)
+                .await?;
+        }
 
         state.lots.push_back(lot);
         state.exposure += qty;
 
         // Update episode basis
-        if let Some(episode) = &mut state.current_episode {
+        if let (Some(episode), Some(px)) = (&mut state.current_episode, px) {
             episode.basis_usd += qty * px;
         }
 
@@ -280,18 +338,30 @@ impl Engine {
         let mut trades = Vec::new();
         let mut total_realized = Decimal::ZERO;
         let mut total_qty_sold = Decimal::ZERO;
+        let mut priced_qty_sold = Decimal::ZERO;
         let mut weighted_entry_px = Decimal::ZERO;
+        let mut disposed = Vec::new();
 
         // Process FIFO lot matching
         while qty_to_sell > Decimal::ZERO && !state.lots.is_empty() {
             let mut lot = state.lots.pop_front().unwrap();
             let qty_from_lot = qty_to_sell.min(lot.qty_remaining);
 
-            // Calculate realized PnL for this partial sell
-            let realized_pnl = (exit_px - lot.entry_px) * qty_from_lot;
-            total_realized += realized_pnl;
+            // Calculate realized PnL for this partial sell; unpriced lots
+            // have no basis to realize against or to average in
+            if !lot.entry_px_missing {
+                let realized_pnl = (exit_px - lot.entry_px) * qty_from_lot;
+                total_realized += realized_pnl;
+                priced_qty_sold += qty_from_lot;
+                weighted_entry_px += lot.entry_px * qty_from_lot;
+            }
             total_qty_sold += qty_from_lot;
-            weighted_entry_px += lot.entry_px * qty_from_lot;
+            disposed.push(DisposedLot {
+                lot_id: Some(lot.lot_id.clone()),
+                entry_ts: Some(lot.entry_ts),
+                qty: qty_from_lot,
+                entry_px_usd: (!lot.entry_px_missing).then_some(lot.entry_px),
+            });
 
             // Update lot
             lot.qty_remaining -= qty_from_lot;
@@ -299,20 +369,23 @@ impl Engine {
 
             // Update lot in database or remove if depleted
             if lot.qty_remaining > Decimal::ZERO {
-                sqlx::query(include_str!("../../../../db/queries/upsert_lot.sql"))
-                    .bind(&lot.lot_id)
-                    .bind(&state.wallet)
-                    .bind(&state.mint)
-                    .bind(state.current_episode.as_ref().unwrap().episode_id.clone())
-                    .bind(lot.entry_ts)
-                    .bind(lot.qty_initial)
-                    .bind(lot.qty_remaining)
-                    .bind(lot.entry_px)
-                    .execute(&self.pool)
-                    .await?;
+                if self.persist {
+                    sqlx::query(include_str!("../../../../db/queries/upsert_lot.sql"))
+                        .bind(&lot.lot_id)
+                        .bind(&state.wallet)
+                        .bind(&state.mint)
+                        .bind(state.current_episode.as_ref().unwrap().episode_id.clone())
+                        .bind(lot.entry_ts)
+                        .bind(lot.qty_initial)
+                        .bind(lot.qty_remaining)
+                        .bind(lot.entry_px)
+                        .bind(lot.entry_px_missing)
+                        .execute(&self.pool)
+                        .await?;
+                }
 
                 state.lots.push_front(lot);
-            } else {
+            } else if self.persist {
                 // Lot fully consumed, remove from database
                 sqlx::query!("DELETE FROM lots WHERE lot_id = $1", lot.lot_id)
                     .execute(&self.pool)
@@ -324,9 +397,29 @@ impl Engine {
         state.exposure = state.lots.iter().map(|l| l.qty_remaining).sum();
         state.total_realized_pnl += total_realized;
 
+        // Quantity sold beyond every known lot has no basis either
+        if total_qty_sold > Decimal::ZERO && qty_to_sell > Decimal::ZERO {
+            disposed.push(DisposedLot {
+                lot_id: None,
+                entry_ts: None,
+                qty: qty_to_sell,
+                entry_px_usd: None,
+            });
+        }
+
         // Create realized trade record
         if total_qty_sold > Decimal::ZERO {
-            let vwavg_entry_px = weighted_entry_px / total_qty_sold;
+            if priced_qty_sold < total_qty_sold {
+                let vwavg_entry_px =
+                    (priced_qty_sold > Decimal::ZERO).then(|| weighted_entry_px / priced_qty_sold);
+                tracing::debug!(
+                    wallet = %state.wallet,
+                    mint = %state.mint,
+                    ?vwavg_entry_px,
+                    unpriced_qty = %(total_qty_sold - priced_qty_sold),
+                    "Sell is only partially priced; PnL covers the priced lots"
+                );
+            }
             let episode_id = state.current_episode.as_ref().unwrap().episode_id.clone();
 
             let trade = RealizedTrade::new(
@@ -338,23 +431,27 @@ impl Engine {
                 exit_px,
                 total_realized,
                 sig.to_string(),
-            );
+            )
+            .with_lots(disposed);
 
             // Persist trade to database
-            sqlx::query(include_str!(
-                "../../../../db/queries/insert_realized_trade.sql"
-            ))
-            .bind(&trade.exit_id)
-            .bind(&trade.wallet)
-            .bind(&trade.mint)
-            .bind(&trade.episode_id)
-            .bind(trade.ts)
-            .bind(trade.qty)
-            .bind(trade.vwavg_exit_px)
-            .bind(trade.realized_pnl_usd)
-            .bind(&trade.sig)
-            .execute(&self.pool)
-            .await?;
+            if self.persist {
+                sqlx::query(include_str!(
+                    "../../../../db/queries/insert_realized_trade.sql"
+                ))
+                .bind(&trade.exit_id)
+                .bind(&trade.wallet)
+                .bind(&trade.mint)
+                .bind(&trade.episode_id)
+                .bind(trade.ts)
+                .bind(trade.qty)
+                .bind(trade.vwavg_exit_px)
+                .bind(trade.realized_pnl_usd)
+                .bind(&trade.sig)
+                .bind(serde_json::to_value(&trade.lots)?)
+                .execute(&self.pool)
+                .await?;
+            }
 
             trades.push(trade);
         }
@@ -366,17 +463,19 @@ impl Engine {
                 episode.close(ts);
 
                 // Persist episode to database
-                sqlx::query(include_str!("../../../../db/queries/upsert_episode.sql"))
-                    .bind(&episode.episode_id)
-                    .bind(&episode.wallet)
-                    .bind(&episode.mint)
-                    .bind(episode.start_ts)
-                    .bind(episode.end_ts)
-                    .bind(episode.basis_usd)
-                    .bind(episode.realized_pnl_usd)
-                    .bind(episode.roi_pct)
-                    .execute(&self.pool)
-                    .await?;
+                if self.persist {
+                    sqlx::query(include_str!("../../../../db/queries/upsert_episode.sql"))
+                        .bind(&episode.episode_id)
+                        .bind(&episode.wallet)
+                        .bind(&episode.mint)
+                        .bind(episode.start_ts)
+                        .bind(episode.end_ts)
+                        .bind(episode.basis_usd)
+                        .bind(episode.realized_pnl_usd)
+                        .bind(episode.roi_pct)
+                        .execute(&self.pool)
+                        .await?;
+                }
             }
         }
 
@@ -450,7 +549,7 @@ impl Engine {
         }
 
         // Fallback: reconstruct from lots
-        let lots = sqlx::query!("SELECT lot_id, episode_id, entry_ts, qty_initial, qty_remaining, entry_px_usd_dec FROM lots WHERE wallet = $1 AND mint = $2 ORDER BY entry_ts ASC", wallet, mint)
+        let lots = sqlx::query!("SELECT lot_id, episode_id, entry_ts, qty_initial, qty_remaining, entry_px_usd_dec, entry_px_missing FROM lots WHERE wallet = $1 AND mint = $2 ORDER BY entry_ts ASC", wallet, mint)
             .fetch_all(&self.pool)
             .await?;
 
@@ -463,6 +562,7 @@ impl Engine {
                 qty_initial: lot_row.qty_initial,
                 qty_remaining: lot_row.qty_remaining,
                 entry_px: lot_row.entry_px_usd_dec,
+                entry_px_missing: lot_row.entry_px_missing,
             };
 
             state.lots.push_back(lot);
@@ -503,9 +603,74 @@ impl Engine {
         mint: &str,
         from_ts: OffsetDateTime,
     ) -> Result<PositionState> {
-        let mut state = PositionState::new(wallet.to_string(), mint.to_string());
+        self.replay_between(wallet, mint, from_ts, None).await
+    }
 
-        // Get all actions for this wallet/mint from timestamp
+    /// Replay one mint's events in `[from_ts, until]` to rebuild its state
+    pub async fn replay_between(
+        &self,
+        wallet: &str,
+        mint: &str,
+        from_ts: OffsetDateTime,
+        until: Option<OffsetDateTime>,
+    ) -> Result<PositionState> {
+        let mut states = self
+            .replay_wallet_between(wallet, from_ts, until, Some(mint))
+            .await?;
+        Ok(states
+            .remove(mint)
+            .unwrap_or_else(|| PositionState::new(wallet.to_string(), mint.to_string())))
+    }
+
+    /// Replay a wallet's events in `[from_ts, until]`, keyed by mint; pass
+    /// `only_mint` to skip every other mint
+    pub async fn replay_wallet_between(
+        &self,
+        wallet: &str,
+        from_ts: OffsetDateTime,
+        until: Option<OffsetDateTime>,
+        only_mint: Option<&str>,
+    ) -> Result<HashMap<String, PositionState>> {
+        let (states, _) = self
+            .replay_wallet(wallet, from_ts, until, only_mint, None)
+            .await?;
+        Ok(states)
+    }
+
+    /// Replay a wallet's whole history up to `until` in one pass, returning
+    /// the states as they stood just before `checkpoint` and at `until`
+    pub async fn replay_wallet_around(
+        &self,
+        wallet: &str,
+        checkpoint: OffsetDateTime,
+        until: OffsetDateTime,
+    ) -> Result<(StatesByMint, StatesByMint)> {
+        let (after, before) = self
+            .replay_wallet(
+                wallet,
+                OffsetDateTime::UNIX_EPOCH,
+                Some(until),
+                None,
+                Some(checkpoint),
+            )
+            .await?;
+        Ok((before.unwrap_or_default(), after))
+    }
+
+    /// Shared replay loop. With a `checkpoint`, also returns a copy of the
+    /// states taken before the first event at or after it.
+    async fn replay_wallet(
+        &self,
+        wallet: &str,
+        from_ts: OffsetDateTime,
+        until: Option<OffsetDateTime>,
+        only_mint: Option<&str>,
+        checkpoint: Option<OffsetDateTime>,
+    ) -> Result<(StatesByMint, Option<StatesByMint>)> {
+        let mut states = StatesByMint::new();
+        let mut at_checkpoint = None;
+
+        // Get all actions for this wallet from timestamp
         let actions = sqlx::query!(
             include_str!("../../../../db/queries/select_wallet_actions.sql"),
             wallet,
@@ -515,28 +680,46 @@ impl Engine {
         .await?;
 
         for action in actions {
-            if action.mint.as_deref() == Some(mint) {
-                let event = shared::Action {
-                    id: action.id,
-                    signature: action.sig,
-                    log_idx: action.log_idx,
-                    slot: action.slot,
-                    timestamp: action.ts,
-                    program_id: action.program_id,
-                    kind: action.kind,
-                    mint: action.mint,
-                    amount_dec: action.amount_dec,
-                    exec_px_usd_dec: action.exec_px_usd_dec,
-                    route: action.route,
-                    flags_json: action.flags_json,
-                }
-                .to_chain_event(wallet);
+            if until.map_or(false, |until| action.ts > until) {
+                continue;
+            }
+            if at_checkpoint.is_none() && checkpoint.is_some_and(|c| action.ts >= c) {
+                at_checkpoint = Some(states.clone());
+            }
+            let Some(mint) = action.mint.clone() else {
+                continue;
+            };
+            if only_mint.map_or(false, |only| only != mint) {
+                continue;
+            }
 
-                self.process_event(&mut state, &event).await?;
+            let event = shared::Action {
+                id: action.id,
+                signature: action.sig,
+                log_idx: action.log_idx,
+                slot: action.slot,
+                timestamp: action.ts,
+                program_id: action.program_id,
+                kind: action.kind,
+                mint: action.mint,
+                amount_dec: action.amount_dec,
+                exec_px_usd_dec: action.exec_px_usd_dec,
+                route: action.route,
+                flags_json: action.flags_json,
             }
+            .to_chain_event(wallet);
+
+            let state = states
+                .entry(mint.clone())
+                .or_insert_with(|| PositionState::new(wallet.to_string(), mint));
+            self.process_event(state, &event).await?;
         }
 
-        Ok(state)
+        // Nothing reached the checkpoint, so the final states are it
+        if checkpoint.is_some() && at_checkpoint.is_none() {
+            at_checkpoint = Some(states.clone());
+        }
+        Ok((states, at_checkpoint))
     }
 }
 
@@ -571,6 +754,88 @@ mod tests {
         assert_eq!(state.unrealized_pnl(dec!(2.0)), dec!(5.0)); // (2.0 - 1.5) * 10
     }
 
+    #[test]
+    fn test_unpriced_lot() {
+        let mut state = PositionState::new("wallet123".to_string(), "mint456".to_string());
+        state.lots.push_back(Lot::new(
+            datetime!(2024-01-01 0:00 UTC),
+            dec!(10),
+            dec!(1.5),
+        ));
+        state
+            .lots
+            .push_back(Lot::unpriced(datetime!(2024-01-02 0:00 UTC), dec!(5)));
+        state.exposure = dec!(15);
+
+        assert!(state.lots[1].entry_px_missing);
+        assert_eq!(state.cost_basis(), dec!(15));
+        assert_eq!(state.average_entry_price(), Some(dec!(1.5)));
+        assert_eq!(state.unrealized_pnl(dec!(2.0)), dec!(5.0));
+    }
+
+    fn event(kind: EventKind, qty: Decimal, px: Option<Decimal>, ts: OffsetDateTime) -> ChainEvent {
+        ChainEvent {
+            id: Ulid::new().to_string(),
+            signature: format!("sig_{}", Ulid::new()),
+            log_idx: 0,
+            slot: 1,
+            timestamp: ts,
+            wallet: "wallet123".to_string(),
+            mint: Some("mint456".to_string()),
+            program_id: "program".to_string(),
+            kind,
+            amount: Some(qty),
+            price_usd: px,
+            route: None,
+            metadata: serde_json::Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sell_across_priced_and_unpriced_lots() {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let engine = Engine::new(pool).with_persist(false);
+        let mut state = PositionState::new("wallet123".to_string(), "mint456".to_string());
+
+        let buys = [
+            (dec!(10), Some(dec!(1.0)), datetime!(2024-01-01 0:00 UTC)),
+            (dec!(5), None, datetime!(2024-01-02 0:00 UTC)),
+            (dec!(5), Some(dec!(2.0)), datetime!(2024-01-03 0:00 UTC)),
+        ];
+        for (qty, px, ts) in buys {
+            let buy = event(EventKind::Buy, qty, px, ts);
+            engine.process_event(&mut state, &buy).await.unwrap();
+        }
+        assert_eq!(state.exposure, dec!(20));
+
+        // FIFO: all 10 priced, all 5 unpriced, then 3 of the second priced lot
+        let sell = event(
+            EventKind::Sell,
+            dec!(18),
+            Some(dec!(3.0)),
+            datetime!(2024-01-04 0:00 UTC),
+        );
+        let trades = engine.process_event(&mut state, &sell).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        assert_eq!(trade.qty, dec!(18));
+        // (3 - 1) * 10 + (3 - 2) * 3; the unpriced 5 realize nothing
+        assert_eq!(trade.realized_pnl_usd, dec!(23));
+        let bases: Vec<_> = trade.lots.iter().map(|l| (l.qty, l.entry_px_usd)).collect();
+        assert_eq!(
+            bases,
+            vec![
+                (dec!(10), Some(dec!(1.0))),
+                (dec!(5), None),
+                (dec!(3), Some(dec!(2.0))),
+            ]
+        );
+
+        assert_eq!(state.exposure, dec!(2));
+        assert_eq!(state.lots.len(), 1);
+        assert_eq!(state.lots[0].entry_px, dec!(2.0));
+    }
+
     #[test]
     fn test_episode_lifecycle() {
         let mut episode = Episode::new(
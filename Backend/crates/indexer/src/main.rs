@@ -7,6 +7,7 @@ use axum::{
 };
 use shared::{
     metrics_router,
+    normalize::{normalize_transaction, persist_normalized, IngestSource},
     observability::{init_observability, HealthChecker, MetricsRegistry, ObservabilityConfig},
     security::helius_hmac::{get_helius_sig_from_headers, verify_webhook_signature},
     store::{make_store, ObjectStore},
@@ -15,7 +16,6 @@ use shared::{
 use std::{sync::Arc, time::Duration};
 use tokio::{sync::broadcast, time::interval};
 use tracing::{error, info, instrument, warn};
-use ulid::Ulid;
 
 #[derive(Clone)]
 struct AppState {
@@ -29,55 +29,6 @@ struct AppState {
     health_checker: Arc<HealthChecker>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
-struct HeliusTransactionNotification {
-    signature: String,
-    slot: i64,
-    timestamp: i64,
-    #[serde(rename = "accountKeys")]
-    account_keys: Option<Vec<String>>,
-    #[serde(rename = "tokenTransfers")]
-    token_transfers: Option<Vec<HeliusTokenTransfer>>,
-    #[serde(rename = "nativeTransfers")]
-    native_transfers: Option<Vec<HeliusNativeTransfer>>,
-    meta: Option<serde_json::Value>,
-    description: Option<String>,
-    #[serde(rename = "type")]
-    tx_type: Option<String>,
-    source: Option<String>,
-    fee: Option<i64>,
-    #[serde(rename = "feePayer")]
-    fee_payer: Option<String>,
-    instructions: Option<Vec<serde_json::Value>>,
-    events: Option<serde_json::Value>,
-}
-
-#[derive(serde::Deserialize, serde::Serialize)]
-struct HeliusTokenTransfer {
-    #[serde(rename = "fromUserAccount")]
-    from_user_account: Option<String>,
-    #[serde(rename = "toUserAccount")]
-    to_user_account: Option<String>,
-    #[serde(rename = "fromTokenAccount")]
-    from_token_account: Option<String>,
-    #[serde(rename = "toTokenAccount")]
-    to_token_account: Option<String>,
-    mint: String,
-    #[serde(rename = "tokenAmount")]
-    token_amount: Option<String>,
-    #[serde(rename = "tokenStandard")]
-    token_standard: Option<String>,
-}
-
-#[derive(serde::Deserialize, serde::Serialize)]
-struct HeliusNativeTransfer {
-    #[serde(rename = "fromUserAccount")]
-    from_user_account: Option<String>,
-    #[serde(rename = "toUserAccount")]
-    to_user_account: Option<String>,
-    amount: i64,
-}
-
 #[derive(serde::Serialize)]
 struct WebhookStats {
     processed_count: u64,
@@ -178,8 +129,9 @@ async fn helius_webhook(
         return StatusCode::UNAUTHORIZED;
     }
 
-    // Parse webhook payload
-    let notifications: Vec<HeliusTransactionNotification> = match serde_json::from_slice(&body) {
+    // Parse webhook payload; raw and enhanced webhooks are both accepted and
+    // told apart by the shared normaliser
+    let notifications: Vec<serde_json::Value> = match serde_json::from_slice(&body) {
         Ok(notifications) => notifications,
         Err(e) => {
             error!("Failed to parse webhook payload: {}", e);
@@ -208,7 +160,8 @@ async fn helius_webhook(
             Err(e) => {
                 error!(
                     "Failed to process transaction {}: {}",
-                    notification.signature, e
+                    notification_signature(&notification),
+                    e
                 );
                 error_count += 1;
             }
@@ -278,172 +231,59 @@ async fn helius_webhook(
 /// Process a single transaction notification
 async fn process_transaction_notification(
     state: &AppState,
-    notification: &HeliusTransactionNotification,
+    notification: &serde_json::Value,
 ) -> anyhow::Result<Vec<String>> {
-    let mut discovered_mints = Vec::new();
+    let normalized = normalize_transaction(notification)?;
+    let signature = &normalized.signature;
 
     // Store raw compressed transaction data
-    let storage_key = format!(
-        "tx/{}/{}.json.zst",
-        &notification.signature[0..2],
-        notification.signature
-    );
+    let storage_key = format!("tx/{}/{}.json.zst", &signature[0..2], signature);
     let raw_data = serde_json::to_vec(notification)?;
     let compressed_data = zstd::encode_all(&raw_data[..], 3)?;
 
     state.store.put(&storage_key, &compressed_data).await?;
 
-    let timestamp = time::OffsetDateTime::from_unix_timestamp(notification.timestamp)
+    let timestamp = normalized
+        .timestamp()
         .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
 
+    let mut tx = state.pg.0.begin().await?;
+
     // Upsert transaction record
     sqlx::query!(
         include_str!("../../../db/queries/upsert_tx_raw.sql"),
-        notification.signature,
-        notification.slot,
+        signature,
+        normalized.slot,
         timestamp,
-        "confirmed", // Default status
+        if normalized.success { "confirmed" } else { "failed" },
         storage_key,
         compressed_data.len() as i32
     )
-    .execute(&state.pg.0)
+    .execute(&mut *tx)
     .await?;
 
-    // Process participants (wallet addresses involved)
-    if let Some(account_keys) = &notification.account_keys {
-        for account in account_keys {
-            sqlx::query!(
-                "INSERT INTO participants (sig, wallet) VALUES ($1, $2) ON CONFLICT DO NOTHING",
-                notification.signature,
-                account
-            )
-            .execute(&state.pg.0)
-            .await?;
-        }
-    }
+    // Participants and actions, classified exactly like backfilled data
+    persist_normalized(&mut *tx, &normalized, timestamp, IngestSource::Webhook).await?;
+    tx.commit().await?;
 
-    // Process token transfers
-    if let Some(transfers) = &notification.token_transfers {
-        for (idx, transfer) in transfers.iter().enumerate() {
-            let action_id = Ulid::new().to_string();
-            let amount = transfer
-                .token_amount
-                .as_ref()
-                .and_then(|s| rust_decimal::Decimal::from_str_exact(s).ok());
-
-            let action_type = determine_action_type(transfer);
-            let flags = create_transfer_flags(transfer);
-
-            discovered_mints.push(transfer.mint.clone());
-
-            sqlx::query!(
-                include_str!("../../../db/queries/insert_action.sql"),
-                action_id,
-                notification.signature,
-                idx as i32,
-                notification.slot,
-                timestamp,
-                "", // program_id - will be filled by backfill worker
-                action_type,
-                transfer.mint,
-                amount,
-                None::<rust_decimal::Decimal>, // sol_amount
-                None::<String>,                // counterparty
-                flags
-            )
-            .execute(&state.pg.0)
-            .await?;
-        }
-    }
-
-    // Process native SOL transfers
-    if let Some(native_transfers) = &notification.native_transfers {
-        for (idx, transfer) in native_transfers.iter().enumerate() {
-            let action_id = Ulid::new().to_string();
-            let sol_amount = rust_decimal::Decimal::new(transfer.amount, 9); // Convert lamports to SOL
-
-            let flags = serde_json::json!({
-                "from": transfer.from_user_account,
-                "to": transfer.to_user_account,
-                "amount_lamports": transfer.amount
-            });
-
-            let token_transfer_count = notification
-                .token_transfers
-                .as_ref()
-                .map(|t| t.len())
-                .unwrap_or(0);
-
-            sqlx::query!(
-                include_str!("../../../db/queries/insert_action.sql"),
-                action_id,
-                notification.signature,
-                (token_transfer_count + idx) as i32, // Offset by token transfers
-                notification.slot,
-                timestamp,
-                "", // program_id
-                "sol_transfer",
-                None::<String>,                // mint
-                None::<rust_decimal::Decimal>, // token_amount
-                sol_amount,
-                transfer.to_user_account.clone(),
-                flags
-            )
-            .execute(&state.pg.0)
-            .await?;
-        }
-    }
-
-    // If no token or native transfers, create a placeholder action
-    if notification.token_transfers.is_none() && notification.native_transfers.is_none() {
-        let action_id = Ulid::new().to_string();
-        let flags = serde_json::json!({
-            "description": notification.description,
-            "type": notification.tx_type,
-            "source": notification.source
-        });
-
-        sqlx::query!(
-            include_str!("../../../db/queries/insert_action.sql"),
-            action_id,
-            notification.signature,
-            0i32,
-            notification.slot,
-            timestamp,
-            "",
-            "unknown",
-            None::<String>,
-            None::<rust_decimal::Decimal>,
-            None::<rust_decimal::Decimal>,
-            None::<String>,
-            flags
-        )
-        .execute(&state.pg.0)
-        .await?;
-    }
+    let mut discovered_mints: Vec<String> = normalized
+        .token_deltas
+        .iter()
+        .map(|d| d.mint.clone())
+        .collect();
+    discovered_mints.sort_unstable();
+    discovered_mints.dedup();
 
     Ok(discovered_mints)
 }
 
-/// Determine the action type based on transfer details
-fn determine_action_type(transfer: &HeliusTokenTransfer) -> &'static str {
-    match (&transfer.from_user_account, &transfer.to_user_account) {
-        (Some(_), Some(_)) => "transfer",
-        (None, Some(_)) => "mint",
-        (Some(_), None) => "burn",
-        (None, None) => "unknown",
-    }
-}
-
-/// Create flags JSON for transfer metadata
-fn create_transfer_flags(transfer: &HeliusTokenTransfer) -> serde_json::Value {
-    serde_json::json!({
-        "from_user": transfer.from_user_account,
-        "to_user": transfer.to_user_account,
-        "from_token_account": transfer.from_token_account,
-        "to_token_account": transfer.to_token_account,
-        "token_standard": transfer.token_standard
-    })
+/// Best-effort signature of a raw or enhanced notification, for logging
+fn notification_signature(notification: &serde_json::Value) -> &str {
+    notification
+        .get("signature")
+        .or_else(|| notification.pointer("/transaction/signatures/0"))
+        .and_then(|s| s.as_str())
+        .unwrap_or("unknown")
 }
 
 /// Background worker for price refreshing
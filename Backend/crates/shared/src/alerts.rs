@@ -0,0 +1,144 @@
+//! Where a user's alerts are delivered.
+//!
+//! Workers queue alerts in `alert_queue`; the alerts dispatcher sends each
+//! one to every channel in the owner's `user_alert_configs` row. Users who
+//! have not configured a channel, or who turned alerts off, have their
+//! alerts marked `skipped`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Channels one user may configure
+pub const MAX_CHANNELS: usize = 5;
+
+const DISCORD_WEBHOOK_PREFIXES: [&str; 2] = [
+    "https://discord.com/api/webhooks/",
+    "https://discordapp.com/api/webhooks/",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AlertChannel {
+    /// JSON POST of the alert, signed with `X-OOF-Signature` when a secret
+    /// is set
+    Webhook { url: String, secret: Option<String> },
+    /// Discord incoming webhook
+    Discord { webhook_url: String },
+}
+
+impl AlertChannel {
+    /// Only public https endpoints are accepted, so a config cannot point
+    /// the dispatcher at plain-text or non-Discord URLs
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            AlertChannel::Webhook { url, .. } => {
+                if !url.starts_with("https://") {
+                    return Err(format!("Webhook URL must use https: {}", url));
+                }
+            }
+            AlertChannel::Discord { webhook_url } => {
+                if !DISCORD_WEBHOOK_PREFIXES
+                    .iter()
+                    .any(|p| webhook_url.starts_with(p))
+                {
+                    return Err("Discord channel needs a Discord webhook URL".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertConfig {
+    pub enabled: bool,
+    pub channels: Vec<AlertChannel>,
+}
+
+#[derive(Clone)]
+pub struct AlertConfigStore {
+    pub pg: PgPool,
+}
+
+impl AlertConfigStore {
+    pub fn new(pg: PgPool) -> Self {
+        Self { pg }
+    }
+
+    pub async fn get(&self, user_id: &str) -> anyhow::Result<Option<AlertConfig>> {
+        let row = sqlx::query!(
+            "SELECT channels_json, enabled FROM user_alert_configs WHERE user_id = $1",
+            user_id
+        )
+        .fetch_optional(&self.pg)
+        .await?;
+
+        row.map(|r| {
+            Ok(AlertConfig {
+                enabled: r.enabled,
+                channels: serde_json::from_value(r.channels_json)?,
+            })
+        })
+        .transpose()
+    }
+
+    /// Replace the user's channels and on/off switch
+    pub async fn put(&self, user_id: &str, config: &AlertConfig) -> anyhow::Result<()> {
+        sqlx::query!(
+            "INSERT INTO user_alert_configs (user_id, channels_json, enabled, updated_at)
+             VALUES ($1, $2, $3, NOW())
+             ON CONFLICT (user_id) DO UPDATE
+             SET channels_json = EXCLUDED.channels_json,
+                 enabled = EXCLUDED.enabled,
+                 updated_at = NOW()",
+            user_id,
+            serde_json::to_value(&config.channels)?,
+            config.enabled
+        )
+        .execute(&self.pg)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channels_round_trip_as_tagged_json() {
+        let channels = vec![
+            AlertChannel::Webhook {
+                url: "https://example.com/hook".to_string(),
+                secret: Some("s3cret".to_string()),
+            },
+            AlertChannel::Discord {
+                webhook_url: "https://discord.com/api/webhooks/1/abc".to_string(),
+            },
+        ];
+        let json = serde_json::to_value(&channels).unwrap();
+        assert_eq!(json[0]["type"], "webhook");
+        assert_eq!(json[1]["type"], "discord");
+        let back: Vec<AlertChannel> = serde_json::from_value(json).unwrap();
+        assert_eq!(back, channels);
+    }
+
+    #[test]
+    fn test_validate_rejects_plain_http_and_foreign_discord_urls() {
+        let http = AlertChannel::Webhook {
+            url: "http://example.com/hook".to_string(),
+            secret: None,
+        };
+        assert!(http.validate().is_err());
+
+        let not_discord = AlertChannel::Discord {
+            webhook_url: "https://example.com/api/webhooks/1/abc".to_string(),
+        };
+        assert!(not_discord.validate().is_err());
+
+        let ok = AlertChannel::Discord {
+            webhook_url: "https://discord.com/api/webhooks/1/abc".to_string(),
+        };
+        assert!(ok.validate().is_ok());
+    }
+}
@@ -0,0 +1,475 @@
+//! API keys for programmatic access.
+//!
+//! A key looks like `oof_<prefix>_<secret>`. Only the SHA-256 of the whole
+//! key is stored; the prefix is stored in the clear so a presented key is
+//! looked up by it and so listings can tell keys apart. The full key is
+//! shown once, when it is minted or rotated.
+//!
+//! Each key carries scopes, and rows served through keys are metered per
+//! calendar-month billing period against the plan's `api_rows` and the
+//! key's own optional `rows_limit`.
+
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use subtle::ConstantTimeEq;
+use time::{Date, Month, OffsetDateTime};
+use ulid::Ulid;
+
+/// Marks a bearer token as an API key rather than a JWT
+pub const KEY_PREFIX: &str = "oof_";
+
+/// Active keys one user may hold
+pub const MAX_ACTIVE_KEYS: i64 = 10;
+
+/// Random bytes behind the lookup prefix and the secret
+const PREFIX_BYTES: usize = 9;
+const SECRET_BYTES: usize = 32;
+
+/// `last_used_at` is only rewritten once it is this stale, so busy keys do
+/// not write on every request
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "read:moments")]
+    ReadMoments,
+    #[serde(rename = "analyze")]
+    Analyze,
+    #[serde(rename = "export")]
+    Export,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 3] = [ApiScope::ReadMoments, ApiScope::Analyze, ApiScope::Export];
+
+    /// Value stored in `api_keys.scopes` and accepted by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ReadMoments => "read:moments",
+            ApiScope::Analyze => "analyze",
+            ApiScope::Export => "export",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == s)
+    }
+}
+
+/// A freshly generated key; `key` is only ever returned to the caller once
+#[derive(Debug, Clone)]
+pub struct GeneratedKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+pub fn generate_key() -> GeneratedKey {
+    let mut prefix = [0u8; PREFIX_BYTES];
+    let mut secret = [0u8; SECRET_BYTES];
+    OsRng.fill_bytes(&mut prefix);
+    OsRng.fill_bytes(&mut secret);
+    let prefix = bs58::encode(prefix).into_string();
+    let key = format!(
+        "{}{}_{}",
+        KEY_PREFIX,
+        prefix,
+        bs58::encode(secret).into_string()
+    );
+    GeneratedKey {
+        hash: hash_key(&key),
+        key,
+        prefix,
+    }
+}
+
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Lookup prefix of a presented key, if it is shaped like one
+pub fn key_prefix(key: &str) -> Option<&str> {
+    let (prefix, secret) = key.strip_prefix(KEY_PREFIX)?.split_once('_')?;
+    (!prefix.is_empty() && !secret.is_empty()).then_some(prefix)
+}
+
+/// `[start, end)` of the calendar-month billing period containing `now`
+pub fn billing_period(now: OffsetDateTime) -> (Date, Date) {
+    let today = now.to_offset(time::UtcOffset::UTC).date();
+    let start = today.replace_day(1).unwrap_or(today);
+    let (year, month) = match start.month() {
+        Month::December => (start.year() + 1, Month::January),
+        month => (start.year(), month.next()),
+    };
+    let end = Date::from_calendar_date(year, month, 1).unwrap_or(start);
+    (start, end)
+}
+
+/// Rows a key may still serve this period, given the plan's allowance
+pub fn rows_remaining(
+    plan_rows: i64,
+    key_limit: Option<i64>,
+    user_rows_used: i64,
+    key_rows_used: i64,
+) -> i64 {
+    let plan_left = plan_rows - user_rows_used;
+    let key_left = key_limit.map_or(i64::MAX, |limit| limit - key_rows_used);
+    plan_left.min(key_left).max(0)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub rows_limit: Option<i64>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+    pub rotated_from: Option<String>,
+}
+
+impl ApiKeyRecord {
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.revoked_at.is_none() && self.expires_at.map_or(true, |t| t > now)
+    }
+}
+
+/// Rows metered in one billing period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ApiUsage {
+    pub period_start: Date,
+    pub period_end: Date,
+    pub rows_used: i64,
+    pub requests: i64,
+}
+
+/// What a new or rotated key is created with
+#[derive(Debug, Clone)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub rows_limit: Option<i64>,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+fn parse_scopes(scopes: &[String]) -> Vec<ApiScope> {
+    scopes.iter().filter_map(|s| ApiScope::parse(s)).collect()
+}
+
+fn scope_strings(scopes: &[ApiScope]) -> Vec<String> {
+    scopes.iter().map(|s| s.as_str().to_string()).collect()
+}
+
+macro_rules! record_from_row {
+    ($r:expr) => {
+        ApiKeyRecord {
+            scopes: parse_scopes(&$r.scopes),
+            id: $r.id,
+            user_id: $r.user_id,
+            name: $r.name,
+            prefix: $r.prefix,
+            rows_limit: $r.rows_limit,
+            created_at: $r.created_at,
+            expires_at: $r.expires_at,
+            last_used_at: $r.last_used_at,
+            revoked_at: $r.revoked_at,
+            rotated_from: $r.rotated_from,
+        }
+    };
+}
+
+async fn insert_key(
+    conn: &mut PgConnection,
+    user_id: &str,
+    new: &NewApiKey,
+    rotated_from: Option<&str>,
+) -> anyhow::Result<(ApiKeyRecord, String)> {
+    let generated = generate_key();
+    let row = sqlx::query!(
+        "INSERT INTO api_keys
+             (id, user_id, name, prefix, key_hash, scopes, rows_limit, expires_at, rotated_from)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         RETURNING id, user_id, name, prefix, scopes, rows_limit, created_at, expires_at,
+                   last_used_at, revoked_at, rotated_from",
+        Ulid::new().to_string(),
+        user_id,
+        new.name,
+        generated.prefix,
+        generated.hash,
+        &scope_strings(&new.scopes),
+        new.rows_limit,
+        new.expires_at,
+        rotated_from
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok((record_from_row!(row), generated.key))
+}
+
+/// Key storage and usage metering
+#[derive(Clone)]
+pub struct ApiKeyStore {
+    pg: PgPool,
+}
+
+impl ApiKeyStore {
+    pub fn new(pg: PgPool) -> Self {
+        Self { pg }
+    }
+
+    /// Store a new key for `user_id`; returns the record and the full key,
+    /// or `None` if the user already holds `MAX_ACTIVE_KEYS` active keys.
+    /// The count and insert run under a per-user lock, so concurrent
+    /// requests cannot both take the last slot.
+    pub async fn create(
+        &self,
+        user_id: &str,
+        new: &NewApiKey,
+    ) -> anyhow::Result<Option<(ApiKeyRecord, String)>> {
+        let mut tx = self.pg.begin().await?;
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended('api_keys:' || $1, 0))",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let active = sqlx::query_scalar!(
+            "SELECT COUNT(*) AS \"count!\" FROM api_keys
+             WHERE user_id = $1 AND revoked_at IS NULL
+               AND (expires_at IS NULL OR expires_at > NOW())",
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if active >= MAX_ACTIVE_KEYS {
+            return Ok(None);
+        }
+        let created = insert_key(&mut tx, user_id, new, None).await?;
+        tx.commit().await?;
+        Ok(Some(created))
+    }
+
+    /// Every key the user has minted, newest first
+    pub async fn list(&self, user_id: &str) -> anyhow::Result<Vec<ApiKeyRecord>> {
+        let rows = sqlx::query!(
+            "SELECT id, user_id, name, prefix, scopes, rows_limit, created_at, expires_at,
+                    last_used_at, revoked_at, rotated_from
+             FROM api_keys
+             WHERE user_id = $1
+             ORDER BY created_at DESC",
+            user_id
+        )
+        .fetch_all(&self.pg)
+        .await?;
+        Ok(rows.into_iter().map(|r| record_from_row!(r)).collect())
+    }
+
+    /// Revoke a key; `false` if the user has no such active key
+    pub async fn revoke(&self, user_id: &str, id: &str) -> anyhow::Result<bool> {
+        let revoked = sqlx::query!(
+            "UPDATE api_keys SET revoked_at = NOW()
+             WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+            id,
+            user_id
+        )
+        .execute(&self.pg)
+        .await?
+        .rows_affected();
+        Ok(revoked > 0)
+    }
+
+    /// Replace an active key with a new one carrying the same name, scopes,
+    /// limit and expiry, and revoke the old one. Both happen in one
+    /// transaction, so a failed insert leaves the old key working.
+    pub async fn rotate(
+        &self,
+        user_id: &str,
+        id: &str,
+    ) -> anyhow::Result<Option<(ApiKeyRecord, String)>> {
+        let mut tx = self.pg.begin().await?;
+        let Some(old) = sqlx::query!(
+            "UPDATE api_keys SET revoked_at = NOW()
+             WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+               AND (expires_at IS NULL OR expires_at > NOW())
+             RETURNING name, scopes, rows_limit, expires_at",
+            id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let new = NewApiKey {
+            name: old.name,
+            scopes: parse_scopes(&old.scopes),
+            rows_limit: old.rows_limit,
+            expires_at: old.expires_at,
+        };
+        let rotated = insert_key(&mut tx, user_id, &new, Some(id)).await?;
+        tx.commit().await?;
+        Ok(Some(rotated))
+    }
+
+    /// The active key matching `key`, recording it as used from `ip`
+    pub async fn authenticate(
+        &self,
+        key: &str,
+        ip: Option<&str>,
+    ) -> anyhow::Result<Option<ApiKeyRecord>> {
+        let Some(prefix) = key_prefix(key) else {
+            return Ok(None);
+        };
+        let Some(row) = sqlx::query!(
+            "SELECT id, user_id, name, prefix, key_hash, scopes, rows_limit, created_at,
+                    expires_at, last_used_at, revoked_at, rotated_from
+             FROM api_keys
+             WHERE prefix = $1",
+            prefix
+        )
+        .fetch_optional(&self.pg)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let presented = hash_key(key);
+        if !bool::from(presented.as_bytes().ct_eq(row.key_hash.as_bytes())) {
+            return Ok(None);
+        }
+        let now = OffsetDateTime::now_utc();
+        let record = record_from_row!(row);
+        if !record.is_active(now) {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            "UPDATE api_keys SET last_used_at = $2, last_used_ip = $3
+             WHERE id = $1
+               AND (last_used_at IS NULL
+                    OR last_used_at < $2 - make_interval(secs => $4::float8))",
+            record.id,
+            now,
+            ip,
+            LAST_USED_RESOLUTION_SECS as f64
+        )
+        .execute(&self.pg)
+        .await?;
+
+        Ok(Some(record))
+    }
+
+    /// Rows the user's keys, and this key, have served this period
+    pub async fn rows_used(&self, user_id: &str, key_id: &str) -> anyhow::Result<(i64, i64)> {
+        let (period_start, _) = billing_period(OffsetDateTime::now_utc());
+        let row = sqlx::query!(
+            "SELECT COALESCE(SUM(rows_used), 0)::bigint AS \"user_rows!\",
+                    COALESCE(SUM(rows_used) FILTER (WHERE key_id = $2), 0)::bigint AS \"key_rows!\"
+             FROM api_key_usage
+             WHERE user_id = $1 AND period_start = $3",
+            user_id,
+            key_id,
+            period_start
+        )
+        .fetch_one(&self.pg)
+        .await?;
+        Ok((row.user_rows, row.key_rows))
+    }
+
+    /// Meter `rows` served by one request through `key_id`
+    pub async fn record_rows(&self, user_id: &str, key_id: &str, rows: i64) -> anyhow::Result<()> {
+        let (period_start, _) = billing_period(OffsetDateTime::now_utc());
+        sqlx::query!(
+            "INSERT INTO api_key_usage (key_id, user_id, period_start, rows_used, requests)
+             VALUES ($1, $2, $3, $4, 1)
+             ON CONFLICT (key_id, period_start) DO UPDATE SET
+                 rows_used = api_key_usage.rows_used + EXCLUDED.rows_used,
+                 requests = api_key_usage.requests + 1,
+                 updated_at = NOW()",
+            key_id,
+            user_id,
+            period_start,
+            rows
+        )
+        .execute(&self.pg)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's usage across all keys for the current period
+    pub async fn usage(&self, user_id: &str) -> anyhow::Result<ApiUsage> {
+        let (period_start, period_end) = billing_period(OffsetDateTime::now_utc());
+        let row = sqlx::query!(
+            "SELECT COALESCE(SUM(rows_used), 0)::bigint AS \"rows!\",
+                    COALESCE(SUM(requests), 0)::bigint AS \"requests!\"
+             FROM api_key_usage
+             WHERE user_id = $1 AND period_start = $2",
+            user_id,
+            period_start
+        )
+        .fetch_one(&self.pg)
+        .await?;
+        Ok(ApiUsage {
+            period_start,
+            period_end,
+            rows_used: row.rows,
+            requests: row.requests,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn test_generated_key_roundtrip() {
+        let generated = generate_key();
+        assert!(generated.key.starts_with(KEY_PREFIX));
+        assert_eq!(key_prefix(&generated.key), Some(generated.prefix.as_str()));
+        assert_eq!(hash_key(&generated.key), generated.hash);
+        assert_ne!(generate_key().key, generated.key);
+
+        assert_eq!(key_prefix("eyJhbGciOi.jwt.token"), None);
+        assert_eq!(key_prefix("oof_abc"), None);
+        assert_eq!(key_prefix("oof__secret"), None);
+    }
+
+    #[test]
+    fn test_scopes_parse() {
+        for scope in ApiScope::ALL {
+            assert_eq!(ApiScope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(ApiScope::parse("admin"), None);
+    }
+
+    #[test]
+    fn test_billing_period_is_calendar_month() {
+        assert_eq!(
+            billing_period(datetime!(2024-12-31 23:59 UTC)),
+            (date!(2024 - 12 - 01), date!(2025 - 01 - 01))
+        );
+        assert_eq!(
+            billing_period(datetime!(2024-03-01 01:00 +05:00)),
+            (date!(2024 - 02 - 01), date!(2024 - 03 - 01))
+        );
+    }
+
+    #[test]
+    fn test_rows_remaining_takes_tighter_limit() {
+        assert_eq!(rows_remaining(10_000, None, 9_000, 0), 1_000);
+        assert_eq!(rows_remaining(10_000, Some(500), 1_000, 400), 100);
+        assert_eq!(rows_remaining(10_000, Some(500), 10_500, 0), 0);
+    }
+}
@@ -45,9 +45,15 @@ pub struct AppConfig {
     // Solana RPC endpoints
     pub rpc_primary: String,
     pub rpc_secondary: Option<String>,
+    pub rpc_max_concurrency: usize,
+    pub rpc_primary_rps: u32,
+    pub rpc_secondary_rps: u32,
 
     // External service integration
     pub helius_webhook_secret: String,
+    pub helius_api_base_url: String,
+    pub helius_api_key: Option<String>,
+    pub helius_webhook_id: Option<String>,
     pub jupiter_base_url: String,
     pub pyth_sse: String,
 
@@ -64,6 +70,25 @@ pub struct AppConfig {
     pub r2_bucket_name: String,
     pub r2_public_url: String,
 
+    // Worker runtime
+    pub worker_concurrency: usize,
+    pub worker_kind_limits: String,
+    pub worker_poll_interval_ms: u64,
+    pub worker_shutdown_grace_secs: u64,
+    pub worker_heartbeat_secs: u64,
+    pub worker_lock_timeout_secs: u64,
+    pub worker_lock_timeouts: String,
+
+    // Recurring job schedules (5-field cron, UTC); empty disables
+    pub schedule_refresh_prices: String,
+    pub schedule_refresh_views: String,
+    pub schedule_cleanup: String,
+    pub schedule_leaderboard: String,
+    pub schedule_webhook_sync: String,
+    pub schedule_plan_reanalysis: String,
+    pub schedule_equity: String,
+    pub schedule_alert_dispatch: String,
+
     // Server configuration
     pub api_bind: String,
     pub indexer_bind: String,
@@ -72,6 +97,9 @@ pub struct AppConfig {
     // Additional security
     pub app_secret: String,
     pub environment: String,
+    pub admin_user_ids: Vec<String>,
+    /// Lifetime of signed export download links
+    pub export_url_ttl_secs: u64,
 }
 
 impl AppConfig {
@@ -112,9 +140,25 @@ impl AppConfig {
             // Solana RPC endpoints
             rpc_primary: Self::get_required_var("RPC_PRIMARY")?,
             rpc_secondary: env::var("RPC_SECONDARY").ok(),
+            rpc_max_concurrency: env::var("RPC_MAX_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            rpc_primary_rps: env::var("RPC_PRIMARY_RPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            rpc_secondary_rps: env::var("RPC_SECONDARY_RPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
 
             // External service integration
             helius_webhook_secret: env::var("HELIUS_WEBHOOK_SECRET").unwrap_or_default(),
+            helius_api_base_url: env::var("HELIUS_API_BASE_URL")
+                .unwrap_or_else(|_| "https://api.helius.xyz".into()),
+            helius_api_key: env::var("HELIUS_API_KEY").ok(),
+            helius_webhook_id: env::var("HELIUS_WEBHOOK_ID").ok(),
             jupiter_base_url: env::var("JUPITER_BASE_URL")
                 .unwrap_or_else(|_| "https://price.jup.ag/v3".into()),
             pyth_sse: env::var("PYTH_HERMES_SSE").unwrap_or_default(),
@@ -134,6 +178,46 @@ impl AppConfig {
             r2_bucket_name: Self::get_required_var("R2_BUCKET_NAME")?,
             r2_public_url: Self::get_required_var("R2_PUBLIC_URL")?,
 
+            // Worker runtime
+            worker_concurrency: env::var("WORKER_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
+            worker_kind_limits: env::var("WORKER_KIND_LIMITS").unwrap_or_default(),
+            worker_poll_interval_ms: env::var("WORKER_POLL_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5000),
+            worker_shutdown_grace_secs: env::var("WORKER_SHUTDOWN_GRACE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            worker_heartbeat_secs: env::var("WORKER_HEARTBEAT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            worker_lock_timeout_secs: env::var("WORKER_LOCK_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            worker_lock_timeouts: env::var("WORKER_LOCK_TIMEOUTS").unwrap_or_default(),
+
+            // Recurring job schedules
+            schedule_refresh_prices: env::var("SCHEDULE_REFRESH_PRICES")
+                .unwrap_or_else(|_| "*/5 * * * *".into()),
+            schedule_refresh_views: env::var("SCHEDULE_REFRESH_VIEWS")
+                .unwrap_or_else(|_| "0 * * * *".into()),
+            schedule_cleanup: env::var("SCHEDULE_CLEANUP").unwrap_or_else(|_| "0 2 * * *".into()),
+            schedule_leaderboard: env::var("SCHEDULE_LEADERBOARD")
+                .unwrap_or_else(|_| "*/15 * * * *".into()),
+            schedule_webhook_sync: env::var("SCHEDULE_WEBHOOK_SYNC")
+                .unwrap_or_else(|_| "30 * * * *".into()),
+            schedule_plan_reanalysis: env::var("SCHEDULE_PLAN_REANALYSIS")
+                .unwrap_or_else(|_| "*/10 * * * *".into()),
+            schedule_equity: env::var("SCHEDULE_EQUITY").unwrap_or_else(|_| "15 0 * * *".into()),
+            schedule_alert_dispatch: env::var("SCHEDULE_ALERT_DISPATCH")
+                .unwrap_or_else(|_| "* * * * *".into()),
+
             // Server configuration
             api_bind,
             indexer_bind,
@@ -145,6 +229,10 @@ impl AppConfig {
             admin_user_ids: env::var("ADMIN_USER_IDS")
                 .map(|s| s.split(',').map(|s| s.trim().to_string()).collect())
                 .unwrap_or_default(),
+            export_url_ttl_secs: env::var("EXPORT_URL_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
 
             // Social API keys
             twitter_api_key: Self::get_required_var("TWITTER_API_KEY")?,
@@ -18,6 +18,10 @@ pub mod system {
     /// Maximum signatures per backfill batch
     pub const MAX_SIGNATURES_PER_BATCH: usize = 1000;
 
+    /// Version of the action classifier. Bump whenever classification
+    /// changes so the `renormalize` job can replay archived transactions.
+    pub const CLASSIFIER_VERSION: i32 = 2;
+
     /// Rate limiting window duration
     pub const RATE_LIMIT_WINDOW: Duration = Duration::minutes(1);
 
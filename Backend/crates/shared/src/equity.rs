@@ -0,0 +1,776 @@
+//! Daily equity curve per wallet.
+//!
+//! Balances come from the wallet's own swaps and transfers: a swap moves
+//! value between two of its balances, while a transfer in or out is a cash
+//! flow. Equity is every priced balance at the day's close. Unrealized PnL
+//! comes from the lot book (buys at their USD cost, less the basis each
+//! realized trade closed), and realized PnL is the running total of
+//! `realized_trades`.
+//!
+//! Each day's return is the Modified Dietz return, with flows weighted at
+//! mid-day: `(E1 - E0 - F) / (E0 + F / 2)`. Chaining those gives a
+//! time-weighted index in which deposits and withdrawals are not gains or
+//! losses. A return only covers the mints priced on both days, so a close
+//! going stale does not show up as a loss. The `compute_equity` job
+//! materialises the series into `wallet_equity_daily`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::constants::solana::SOL_MINT;
+use crate::normalize::is_usd_quote;
+
+/// Job kind that materialises equity curves
+pub const EQUITY_JOB_KIND: &str = "compute_equity";
+
+/// Benchmark the curve is charted against
+pub const BENCHMARK_MINT: &str = SOL_MINT;
+
+/// History replayed per run, matching the compute job
+pub const EQUITY_HISTORY_DAYS: i64 = 730;
+
+/// Days a close is carried forward before the mint counts as unpriced
+const CLOSE_MAX_AGE_DAYS: i64 = 3;
+
+const PERIODS_PER_YEAR: f64 = 365.0;
+
+/// A change to one of the wallet's balances
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceEvent {
+    pub ts: OffsetDateTime,
+    pub mint: String,
+    pub delta: Decimal,
+    /// Transfers in or out; swap legs are internal
+    pub external: bool,
+}
+
+/// A change to the lot book: buys open quantity, realized trades close it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LotEvent {
+    pub ts: OffsetDateTime,
+    pub mint: String,
+    pub qty: Decimal,
+    pub cost_usd: Decimal,
+    pub realized_pnl_usd: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EquityDay {
+    pub day: Date,
+    pub equity_usd: Decimal,
+    pub net_flow_usd: Decimal,
+    pub cost_basis_usd: Decimal,
+    pub unrealized_pnl_usd: Decimal,
+    pub realized_pnl_usd: Decimal,
+    /// `None` when nothing was invested over the day
+    pub daily_return: Option<f64>,
+    pub twr_index: f64,
+    pub unpriced_mints: i32,
+}
+
+/// Balance events for one `actions` row owned by the wallet
+pub fn balance_events(
+    ts: OffsetDateTime,
+    kind: &str,
+    mint: Option<&str>,
+    amount: Option<Decimal>,
+    flags: Option<&serde_json::Value>,
+) -> Vec<BalanceEvent> {
+    let event = |mint: &str, delta: Decimal, external: bool| BalanceEvent {
+        ts,
+        mint: mint.to_string(),
+        delta,
+        external,
+    };
+    let quote = || {
+        let flags = flags?;
+        let mint = flags.get("quote_mint")?.as_str()?;
+        let amount = flags
+            .get("quote_amount")?
+            .as_str()?
+            .parse::<Decimal>()
+            .ok()?;
+        Some((mint, amount.abs()))
+    };
+
+    match (kind, mint, amount) {
+        // A token-for-token swap records the bought mint as base and the
+        // sold one as quote, so its legs move like a buy's
+        ("buy" | "swap", Some(base), Some(qty)) => {
+            let mut events = vec![event(base, qty.abs(), false)];
+            if let Some((quote_mint, quote_qty)) = quote() {
+                events.push(event(quote_mint, -quote_qty, false));
+            }
+            events
+        }
+        ("sell", Some(base), Some(qty)) => {
+            let mut events = vec![event(base, -qty.abs(), false)];
+            if let Some((quote_mint, quote_qty)) = quote() {
+                events.push(event(quote_mint, quote_qty, false));
+            }
+            events
+        }
+        ("transfer", Some(mint), Some(delta)) => vec![event(mint, delta, true)],
+        ("sol_transfer", _, Some(delta)) => vec![event(SOL_MINT, delta, true)],
+        _ => Vec::new(),
+    }
+}
+
+/// Close per mint and UTC day; USD stablecoins always close at 1
+#[derive(Debug, Clone, Default)]
+pub struct DailyCloses {
+    closes: HashMap<String, BTreeMap<Date, Decimal>>,
+}
+
+impl DailyCloses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, mint: &str, day: Date, close: Decimal) {
+        self.closes
+            .entry(mint.to_string())
+            .or_default()
+            .insert(day, close);
+    }
+
+    /// Latest close on or up to a few days before `day`
+    pub fn close(&self, mint: &str, day: Date) -> Option<Decimal> {
+        if is_usd_quote(mint) {
+            return Some(Decimal::ONE);
+        }
+        let oldest = day - Duration::days(CLOSE_MAX_AGE_DAYS);
+        self.closes
+            .get(mint)?
+            .range(oldest..=day)
+            .next_back()
+            .map(|(_, close)| *close)
+    }
+}
+
+/// Value of the positive balances at `day`'s closes, with how many of them
+/// had no close
+fn value_balances(
+    balances: &BTreeMap<&str, Decimal>,
+    closes: &DailyCloses,
+    day: Date,
+) -> (Decimal, i32) {
+    let mut equity = Decimal::ZERO;
+    let mut unpriced_mints = 0;
+    for (mint, balance) in balances {
+        // A negative balance means history starts mid-position
+        if *balance <= Decimal::ZERO {
+            continue;
+        }
+        match closes.close(mint, day) {
+            Some(close) => equity += *balance * close,
+            None => unpriced_mints += 1,
+        }
+    }
+    (equity, unpriced_mints)
+}
+
+/// Modified Dietz return from `prev`'s close to `day`'s over the mints
+/// priced at both ends, so a mint whose close goes stale drops out of both
+/// instead of reading as a loss and then a gain. `flows` holds each mint's
+/// external quantity over the day.
+fn dietz_return(
+    opening: &BTreeMap<&str, Decimal>,
+    closing: &BTreeMap<&str, Decimal>,
+    flows: &BTreeMap<&str, Decimal>,
+    closes: &DailyCloses,
+    prev: Option<Date>,
+    day: Date,
+) -> Option<f64> {
+    let (mut start, mut end, mut flow) = (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+    for mint in closing.keys() {
+        // Negative balances are left out, as in the equity itself
+        let held = opening
+            .get(mint)
+            .copied()
+            .unwrap_or_default()
+            .max(Decimal::ZERO);
+        let holds = closing[mint].max(Decimal::ZERO);
+        let moved = flows.get(mint).copied().unwrap_or_default();
+        // A close is only needed at the end that holds or moves something
+        let start_close = if held > Decimal::ZERO {
+            match prev.and_then(|prev| closes.close(mint, prev)) {
+                Some(close) => close,
+                None => continue,
+            }
+        } else {
+            Decimal::ZERO
+        };
+        let end_close = if holds > Decimal::ZERO || !moved.is_zero() {
+            match closes.close(mint, day) {
+                Some(close) => close,
+                None => continue,
+            }
+        } else {
+            Decimal::ZERO
+        };
+        start += held * start_close;
+        end += holds * end_close;
+        flow += moved * end_close;
+    }
+
+    let invested = start + flow / Decimal::TWO;
+    (invested > Decimal::ZERO)
+        .then(|| ((end - start - flow) / invested).to_f64())
+        .flatten()
+        .map(|r| r.max(-1.0))
+}
+
+/// Equity for each day from `first` to `last`, inclusive. Events before
+/// `first` set the opening balances, lots and realized PnL, valued at the
+/// day before's closes; both slices must be oldest first.
+pub fn build_series(
+    first: Date,
+    last: Date,
+    balance_events: &[BalanceEvent],
+    lot_events: &[LotEvent],
+    closes: &DailyCloses,
+) -> Vec<EquityDay> {
+    let mut balances: BTreeMap<&str, Decimal> = BTreeMap::new();
+    let mut lots: BTreeMap<&str, (Decimal, Decimal)> = BTreeMap::new();
+    let mut realized = Decimal::ZERO;
+    let (mut next_balance, mut next_lot) = (0, 0);
+
+    let opening = first.midnight().assume_utc();
+    while let Some(e) = balance_events.get(next_balance).filter(|e| e.ts < opening) {
+        *balances.entry(e.mint.as_str()).or_default() += e.delta;
+        next_balance += 1;
+    }
+    while let Some(e) = lot_events.get(next_lot).filter(|e| e.ts < opening) {
+        let (qty, cost) = lots.entry(e.mint.as_str()).or_default();
+        *qty += e.qty;
+        *cost += e.cost_usd;
+        realized += e.realized_pnl_usd;
+        next_lot += 1;
+    }
+    let mut prev_day = first.previous_day();
+    let mut index = 1.0_f64;
+    let mut series = Vec::new();
+
+    let mut day = first;
+    while day <= last {
+        let day_end = day.midnight().assume_utc() + Duration::days(1);
+
+        let opening_balances = balances.clone();
+        let mut flows: BTreeMap<&str, Decimal> = BTreeMap::new();
+        let mut flow = Decimal::ZERO;
+        while let Some(e) = balance_events.get(next_balance).filter(|e| e.ts < day_end) {
+            *balances.entry(e.mint.as_str()).or_default() += e.delta;
+            if e.external {
+                *flows.entry(e.mint.as_str()).or_default() += e.delta;
+                // Unpriced flows are left out, as is the balance they move
+                if let Some(close) = closes.close(&e.mint, day) {
+                    flow += e.delta * close;
+                }
+            }
+            next_balance += 1;
+        }
+        while let Some(e) = lot_events.get(next_lot).filter(|e| e.ts < day_end) {
+            let (qty, cost) = lots.entry(e.mint.as_str()).or_default();
+            *qty += e.qty;
+            *cost += e.cost_usd;
+            realized += e.realized_pnl_usd;
+            next_lot += 1;
+        }
+
+        let (equity, unpriced_mints) = value_balances(&balances, closes, day);
+
+        let mut cost_basis = Decimal::ZERO;
+        let mut unrealized = Decimal::ZERO;
+        for (mint, (qty, cost)) in &lots {
+            if *qty <= Decimal::ZERO {
+                continue;
+            }
+            cost_basis += *cost;
+            if let Some(close) = closes.close(mint, day) {
+                unrealized += *qty * close - *cost;
+            }
+        }
+
+        let daily_return =
+            dietz_return(&opening_balances, &balances, &flows, closes, prev_day, day);
+        if let Some(r) = daily_return {
+            index *= 1.0 + r;
+        }
+
+        series.push(EquityDay {
+            day,
+            equity_usd: equity,
+            net_flow_usd: flow,
+            cost_basis_usd: cost_basis,
+            unrealized_pnl_usd: unrealized,
+            realized_pnl_usd: realized,
+            daily_return,
+            twr_index: index,
+            unpriced_mints,
+        });
+        prev_day = Some(day);
+        day = match day.next_day() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    series
+}
+
+/// Closes rebased to 1.0 at the first day that has one
+pub fn rebase(closes: &[Option<Decimal>]) -> Vec<Option<f64>> {
+    let base = closes.iter().flatten().next().and_then(|c| c.to_f64());
+    closes
+        .iter()
+        .map(|c| {
+            let base = base.filter(|b| *b > 0.0)?;
+            Some(c.as_ref()?.to_f64()? / base)
+        })
+        .collect()
+}
+
+/// Day-over-day returns of consecutive closes
+pub fn close_returns(closes: &[(Date, Decimal)]) -> Vec<(Date, f64)> {
+    closes
+        .windows(2)
+        .filter(|w| w[1].0 - w[0].0 == Duration::days(1) && w[0].1 > Decimal::ZERO)
+        .filter_map(|w| Some((w[1].0, ((w[1].1 - w[0].1) / w[0].1).to_f64()?)))
+        .collect()
+}
+
+/// Annualised standard deviation of daily returns
+pub fn annualized_volatility(returns: &[f64]) -> Option<f64> {
+    if returns.len() < 2 {
+        return None;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    Some(var.sqrt() * PERIODS_PER_YEAR.sqrt())
+}
+
+/// Beta of `returns` against `benchmark` over the days both have
+pub fn beta(returns: &[(Date, f64)], benchmark: &[(Date, f64)]) -> Option<f64> {
+    let benchmark: BTreeMap<Date, f64> = benchmark.iter().copied().collect();
+    let (xs, ys): (Vec<f64>, Vec<f64>) = returns
+        .iter()
+        .filter_map(|(day, r)| benchmark.get(day).map(|b| (*r, *b)))
+        .unzip();
+    if xs.len() < 2 {
+        return None;
+    }
+    let n = xs.len() as f64;
+    let (mx, my) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let cov: f64 = xs.iter().zip(&ys).map(|(x, y)| (x - mx) * (y - my)).sum();
+    let var: f64 = ys.iter().map(|y| (y - my).powi(2)).sum();
+    (var > 0.0).then(|| cov / var)
+}
+
+/// Recompute and store the equity curve for `wallet`; returns the rows
+/// written. The whole history is replayed so balances and lots opened
+/// before the window are held, but only the last `EQUITY_HISTORY_DAYS`
+/// are written.
+pub async fn compute_wallet_equity(pg: &PgPool, wallet: &str) -> anyhow::Result<usize> {
+    let now = OffsetDateTime::now_utc();
+    let window_start = (now - Duration::days(EQUITY_HISTORY_DAYS)).date();
+
+    let mut balances = Vec::new();
+    for row in sqlx::query!(
+        include_str!("../../../db/queries/select_wallet_balance_events.sql"),
+        wallet
+    )
+    .fetch_all(pg)
+    .await?
+    {
+        balances.extend(balance_events(
+            row.ts,
+            &row.kind,
+            row.mint.as_deref(),
+            row.amount_dec,
+            row.flags_json.as_ref(),
+        ));
+    }
+
+    let lots: Vec<LotEvent> = sqlx::query!(
+        include_str!("../../../db/queries/select_wallet_lot_events.sql"),
+        wallet
+    )
+    .fetch_all(pg)
+    .await?
+    .into_iter()
+    .map(|r| LotEvent {
+        ts: r.ts,
+        mint: r.mint,
+        qty: r.qty,
+        cost_usd: r.cost_usd,
+        realized_pnl_usd: r.realized_pnl_usd,
+    })
+    .collect();
+
+    let first_ts = match (balances.first(), lots.first()) {
+        (Some(b), Some(l)) => b.ts.min(l.ts),
+        (Some(b), None) => b.ts,
+        (None, Some(l)) => l.ts,
+        (None, None) => return Ok(0),
+    };
+    let first = first_ts
+        .to_offset(time::UtcOffset::UTC)
+        .date()
+        .max(window_start);
+    let last = now.date();
+
+    let mut mints: Vec<String> = balances
+        .iter()
+        .map(|e| e.mint.clone())
+        .chain(lots.iter().map(|e| e.mint.clone()))
+        .collect();
+    mints.sort();
+    mints.dedup();
+    let closes = load_daily_closes(
+        pg,
+        &mints,
+        first.midnight().assume_utc() - Duration::days(CLOSE_MAX_AGE_DAYS + 1),
+        last.midnight().assume_utc() + Duration::days(1),
+    )
+    .await?;
+
+    let series = build_series(first, last, &balances, &lots, &closes);
+    let days: Vec<Date> = series.iter().map(|d| d.day).collect();
+    let equity: Vec<Decimal> = series.iter().map(|d| d.equity_usd).collect();
+    let flows: Vec<Decimal> = series.iter().map(|d| d.net_flow_usd).collect();
+    let basis: Vec<Decimal> = series.iter().map(|d| d.cost_basis_usd).collect();
+    let unrealized: Vec<Decimal> = series.iter().map(|d| d.unrealized_pnl_usd).collect();
+    let realized: Vec<Decimal> = series.iter().map(|d| d.realized_pnl_usd).collect();
+    let returns: Vec<Option<f64>> = series.iter().map(|d| d.daily_return).collect();
+    let index: Vec<f64> = series.iter().map(|d| d.twr_index).collect();
+    let unpriced: Vec<i32> = series.iter().map(|d| d.unpriced_mints).collect();
+    sqlx::query!(
+        include_str!("../../../db/queries/upsert_wallet_equity_daily.sql"),
+        wallet,
+        &days,
+        &equity,
+        &flows,
+        &basis,
+        &unrealized,
+        &realized,
+        &returns as &[Option<f64>],
+        &index,
+        &unpriced,
+        now
+    )
+    .execute(pg)
+    .await?;
+
+    // Days before the written window would chain onto a different index
+    sqlx::query!(
+        "DELETE FROM wallet_equity_daily WHERE wallet = $1 AND day < $2",
+        wallet,
+        first
+    )
+    .execute(pg)
+    .await?;
+
+    Ok(series.len())
+}
+
+/// Daily closes for `mints` over `[from, to)`
+pub async fn load_daily_closes(
+    pg: &PgPool,
+    mints: &[String],
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+) -> anyhow::Result<DailyCloses> {
+    let mut closes = DailyCloses::new();
+    for row in sqlx::query!(
+        include_str!("../../../db/queries/select_daily_closes.sql"),
+        mints,
+        from,
+        to
+    )
+    .fetch_all(pg)
+    .await?
+    {
+        closes.insert(&row.mint, row.day, row.close);
+    }
+    Ok(closes)
+}
+
+/// Stored equity curve for `wallet` over `[since, until]`, oldest first,
+/// with when it was last computed
+pub async fn load_equity_series(
+    pg: &PgPool,
+    wallet: &str,
+    since: Date,
+    until: Date,
+) -> anyhow::Result<(Vec<EquityDay>, Option<OffsetDateTime>)> {
+    let rows = sqlx::query!(
+        "SELECT day, equity_usd, net_flow_usd, cost_basis_usd, unrealized_pnl_usd,
+                realized_pnl_usd, daily_return, twr_index, unpriced_mints, computed_at
+         FROM wallet_equity_daily
+         WHERE wallet = $1 AND day >= $2 AND day <= $3
+         ORDER BY day ASC",
+        wallet,
+        since,
+        until
+    )
+    .fetch_all(pg)
+    .await?;
+
+    let computed_at = rows.iter().map(|r| r.computed_at).max();
+    let series = rows
+        .into_iter()
+        .map(|r| EquityDay {
+            day: r.day,
+            equity_usd: r.equity_usd,
+            net_flow_usd: r.net_flow_usd,
+            cost_basis_usd: r.cost_basis_usd,
+            unrealized_pnl_usd: r.unrealized_pnl_usd,
+            realized_pnl_usd: r.realized_pnl_usd,
+            daily_return: r.daily_return,
+            twr_index: r.twr_index,
+            unpriced_mints: r.unpriced_mints,
+        })
+        .collect();
+    Ok((series, computed_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::solana::USDC_MINT;
+    use time::macros::{date, datetime};
+
+    const TOKEN: &str = "TokenMint1111111111111111111111111111111111";
+    const OTHER: &str = "OtherMint1111111111111111111111111111111111";
+
+    fn dec(v: i64) -> Decimal {
+        Decimal::new(v, 0)
+    }
+
+    fn closes() -> DailyCloses {
+        let mut closes = DailyCloses::new();
+        closes.insert(SOL_MINT, date!(2024 - 01 - 01), dec(100));
+        closes.insert(SOL_MINT, date!(2024 - 01 - 02), dec(110));
+        closes.insert(TOKEN, date!(2024 - 01 - 01), dec(1));
+        closes.insert(TOKEN, date!(2024 - 01 - 02), dec(2));
+        closes
+    }
+
+    #[test]
+    fn test_swap_flags_give_both_legs() {
+        let flags =
+            serde_json::json!({ "owner": "w", "quote_mint": SOL_MINT, "quote_amount": "1.5" });
+        let events = balance_events(
+            datetime!(2024-01-01 12:00 UTC),
+            "buy",
+            Some(TOKEN),
+            Some(dec(100)),
+            Some(&flags),
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].delta, dec(100));
+        assert_eq!(events[1].mint, SOL_MINT);
+        assert_eq!(events[1].delta, Decimal::new(-15, 1));
+        assert!(events.iter().all(|e| !e.external));
+
+        let flags = serde_json::json!({ "owner": "w", "quote_mint": OTHER, "quote_amount": "40" });
+        let swap = balance_events(
+            datetime!(2024-01-01 12:00 UTC),
+            "swap",
+            Some(TOKEN),
+            Some(dec(100)),
+            Some(&flags),
+        );
+        assert_eq!(swap.len(), 2);
+        assert_eq!((swap[0].mint.as_str(), swap[0].delta), (TOKEN, dec(100)));
+        assert_eq!((swap[1].mint.as_str(), swap[1].delta), (OTHER, dec(-40)));
+
+        let sol = balance_events(
+            datetime!(2024-01-01 12:00 UTC),
+            "sol_transfer",
+            None,
+            Some(dec(-2)),
+            None,
+        );
+        assert!(sol[0].external);
+        assert!(balance_events(datetime!(2024-01-01 12:00 UTC), "tx", None, None, None).is_empty());
+    }
+
+    #[test]
+    fn test_deposit_is_not_a_gain() {
+        let deposit = BalanceEvent {
+            ts: datetime!(2024-01-01 09:00 UTC),
+            mint: SOL_MINT.to_string(),
+            delta: dec(10),
+            external: true,
+        };
+        let top_up = BalanceEvent {
+            ts: datetime!(2024-01-02 09:00 UTC),
+            ..deposit.clone()
+        };
+        let series = build_series(
+            date!(2024 - 01 - 01),
+            date!(2024 - 01 - 02),
+            &[deposit, top_up],
+            &[],
+            &closes(),
+        );
+
+        assert_eq!(series[0].equity_usd, dec(1000));
+        assert_eq!(series[0].daily_return, Some(0.0));
+        // 1000 -> 2200 with 1100 deposited: 100 gain on 1000 + 550 invested
+        assert_eq!(series[1].net_flow_usd, dec(1100));
+        let r = series[1].daily_return.unwrap();
+        assert!((r - 100.0 / 1550.0).abs() < 1e-12);
+        assert!((series[1].twr_index - (1.0 + r)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_events_before_first_open_the_series() {
+        let deposit = BalanceEvent {
+            ts: datetime!(2024-01-01 09:00 UTC),
+            mint: SOL_MINT.to_string(),
+            delta: dec(10),
+            external: true,
+        };
+        let buy = LotEvent {
+            ts: datetime!(2024-01-01 10:00 UTC),
+            mint: TOKEN.to_string(),
+            qty: dec(100),
+            cost_usd: dec(100),
+            realized_pnl_usd: Decimal::ZERO,
+        };
+        let series = build_series(
+            date!(2024 - 01 - 02),
+            date!(2024 - 01 - 02),
+            &[deposit],
+            &[buy],
+            &closes(),
+        );
+
+        // The opening 10 SOL is held at 100, not deposited at 110
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].net_flow_usd, Decimal::ZERO);
+        assert_eq!(series[0].equity_usd, dec(1100));
+        assert_eq!(series[0].daily_return, Some(0.1));
+        assert_eq!(series[0].cost_basis_usd, dec(100));
+    }
+
+    #[test]
+    fn test_unrealized_and_realized_from_lot_book() {
+        let buy = LotEvent {
+            ts: datetime!(2024-01-01 10:00 UTC),
+            mint: TOKEN.to_string(),
+            qty: dec(100),
+            cost_usd: dec(100),
+            realized_pnl_usd: Decimal::ZERO,
+        };
+        // Sell half at 2: proceeds 100, basis 50, PnL 50
+        let sell = LotEvent {
+            ts: datetime!(2024-01-02 10:00 UTC),
+            mint: TOKEN.to_string(),
+            qty: dec(-50),
+            cost_usd: dec(-50),
+            realized_pnl_usd: dec(50),
+        };
+        let series = build_series(
+            date!(2024 - 01 - 01),
+            date!(2024 - 01 - 02),
+            &[],
+            &[buy, sell],
+            &closes(),
+        );
+
+        assert_eq!(series[0].unrealized_pnl_usd, Decimal::ZERO);
+        assert_eq!(series[1].cost_basis_usd, dec(50));
+        assert_eq!(series[1].unrealized_pnl_usd, dec(50));
+        assert_eq!(series[1].realized_pnl_usd, dec(50));
+    }
+
+    #[test]
+    fn test_closes_carry_forward_and_unpriced() {
+        let closes = closes();
+        assert_eq!(
+            closes.close(SOL_MINT, date!(2024 - 01 - 04)),
+            Some(dec(110))
+        );
+        assert_eq!(closes.close(SOL_MINT, date!(2024 - 01 - 06)), None);
+        assert_eq!(
+            closes.close(USDC_MINT, date!(2020 - 01 - 01)),
+            Some(Decimal::ONE)
+        );
+
+        let airdrop = BalanceEvent {
+            ts: datetime!(2024-01-01 09:00 UTC),
+            mint: "Unpriced111111111111111111111111111111111111".to_string(),
+            delta: dec(5),
+            external: true,
+        };
+        let series = build_series(
+            date!(2024 - 01 - 01),
+            date!(2024 - 01 - 01),
+            &[airdrop],
+            &[],
+            &closes,
+        );
+        assert_eq!(series[0].unpriced_mints, 1);
+        assert_eq!(series[0].net_flow_usd, Decimal::ZERO);
+        assert_eq!(series[0].daily_return, None);
+    }
+
+    #[test]
+    fn test_stale_mint_drops_out_of_the_return() {
+        let mut closes = DailyCloses::new();
+        let mut day = date!(2024 - 01 - 01);
+        while day <= date!(2024 - 01 - 08) {
+            closes.insert(SOL_MINT, day, dec(100));
+            day = day.next_day().unwrap();
+        }
+        // TOKEN is priced on the 1st, stale from the 5th and priced again
+        // on the 8th, at a higher close
+        closes.insert(TOKEN, date!(2024 - 01 - 01), dec(1));
+        closes.insert(TOKEN, date!(2024 - 01 - 08), dec(2));
+        let deposit = |mint: &str, delta| BalanceEvent {
+            ts: datetime!(2024-01-01 09:00 UTC),
+            mint: mint.to_string(),
+            delta,
+            external: true,
+        };
+        let series = build_series(
+            date!(2024 - 01 - 01),
+            date!(2024 - 01 - 08),
+            &[deposit(SOL_MINT, dec(10)), deposit(TOKEN, dec(100))],
+            &[],
+            &closes,
+        );
+
+        assert_eq!(series[4].unpriced_mints, 1);
+        assert_eq!(series[4].equity_usd, dec(1000));
+        assert_eq!(series[7].equity_usd, dec(1200));
+        // Only SOL is priced on both days around each gap, and it is flat
+        assert!(series.iter().all(|d| d.daily_return == Some(0.0)));
+        assert_eq!(series[7].twr_index, 1.0);
+    }
+
+    #[test]
+    fn test_benchmark_and_beta() {
+        let rebased = rebase(&[None, Some(dec(100)), Some(dec(150))]);
+        assert_eq!(rebased, vec![None, Some(1.0), Some(1.5)]);
+
+        let sol = close_returns(&[
+            (date!(2024 - 01 - 01), dec(100)),
+            (date!(2024 - 01 - 02), dec(110)),
+            (date!(2024 - 01 - 03), dec(99)),
+            (date!(2024 - 01 - 05), dec(120)),
+        ]);
+        assert_eq!(sol.len(), 2);
+        let doubled: Vec<_> = sol.iter().map(|(d, r)| (*d, r * 2.0)).collect();
+        assert!((beta(&doubled, &sol).unwrap() - 2.0).abs() < 1e-9);
+        assert!(annualized_volatility(&[0.01]).is_none());
+    }
+}
@@ -0,0 +1,255 @@
+//! Wallet data exports.
+//!
+//! The `export_moments` job writes one file per dataset to the object
+//! store under [`object_key`]. Files are downloaded through the API with a
+//! link signed by [`sign_download`]: the signature covers the export, the
+//! file and the expiry, so a link cannot be reused for another file or
+//! kept alive past its expiry.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Job kind that produces exports
+pub const EXPORT_JOB_KIND: &str = "export_moments";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Value stored in `exports.format` and accepted by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csv" => Some(ExportFormat::Csv),
+            // "jsonl" is the other common name for the same format
+            "ndjson" | "jsonl" => Some(ExportFormat::Ndjson),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+/// Table an export file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportDataset {
+    Moments,
+    Trades,
+    Episodes,
+    /// Normalized actions; only when transactions were requested
+    Transactions,
+}
+
+impl ExportDataset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportDataset::Moments => "moments",
+            ExportDataset::Trades => "trades",
+            ExportDataset::Episodes => "episodes",
+            ExportDataset::Transactions => "transactions",
+        }
+    }
+
+    /// Datasets included in an export
+    pub fn for_export(include_transactions: bool) -> Vec<ExportDataset> {
+        let mut datasets = vec![
+            ExportDataset::Moments,
+            ExportDataset::Trades,
+            ExportDataset::Episodes,
+        ];
+        if include_transactions {
+            datasets.push(ExportDataset::Transactions);
+        }
+        datasets
+    }
+
+    pub fn file_name(&self, format: ExportFormat) -> String {
+        format!("{}.{}", self.as_str(), format.extension())
+    }
+}
+
+/// One uploaded file, as recorded in `exports.files`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportFile {
+    pub name: String,
+    pub dataset: ExportDataset,
+    pub key: String,
+    pub rows: u64,
+    pub bytes: u64,
+    /// Cut short at the API key's row quota
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// API key an export was queued with, and the rows it may still serve
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportQuota {
+    pub api_key_id: String,
+    pub max_rows: u64,
+}
+
+/// Payload of an `export_moments` job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRequest {
+    pub export_id: String,
+    pub user_id: String,
+    pub wallet: String,
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub include_transactions: bool,
+    /// Set for exports queued through an API key; the rows written are
+    /// capped at `max_rows` and metered against the key once done
+    #[serde(default)]
+    pub quota: Option<ExportQuota>,
+}
+
+/// Object store key for an export file
+pub fn object_key(user_id: &str, export_id: &str, file_name: &str) -> String {
+    format!("exports/{}/{}/{}", user_id, export_id, file_name)
+}
+
+fn signature_mac(secret: &str, export_id: &str, file_name: &str, expires: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}\n{}\n{}", export_id, file_name, expires).as_bytes());
+    mac
+}
+
+/// Hex signature for downloading `file_name` of `export_id` until the unix
+/// time `expires`
+pub fn sign_download(secret: &str, export_id: &str, file_name: &str, expires: i64) -> String {
+    hex::encode(
+        signature_mac(secret, export_id, file_name, expires)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+/// Check a download signature and that it has not expired at `now`
+pub fn verify_download(
+    secret: &str,
+    export_id: &str,
+    file_name: &str,
+    expires: i64,
+    signature: &str,
+    now: i64,
+) -> bool {
+    if now > expires {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    signature_mac(secret, export_id, file_name, expires)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+/// API path of a signed download link
+pub fn download_path(secret: &str, export_id: &str, file_name: &str, expires: i64) -> String {
+    format!(
+        "/v1/exports/{}/files/{}?expires={}&sig={}",
+        export_id,
+        file_name,
+        expires,
+        sign_download(secret, export_id, file_name, expires)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn verify(export_id: &str, file_name: &str, expires: i64, sig: &str, now: i64) -> bool {
+        verify_download(SECRET, export_id, file_name, expires, sig, now)
+    }
+
+    #[test]
+    fn test_signed_download_round_trip() {
+        let sig = sign_download(SECRET, "exp1", "moments.csv", 1_000);
+        assert!(verify("exp1", "moments.csv", 1_000, &sig, 999));
+        assert!(verify("exp1", "moments.csv", 1_000, &sig, 1_000));
+    }
+
+    #[test]
+    fn test_signed_download_rejects_tampering_and_expiry() {
+        let sig = sign_download(SECRET, "exp1", "moments.csv", 1_000);
+        assert!(!verify("exp1", "moments.csv", 1_000, &sig, 1_001));
+        assert!(!verify("exp1", "trades.csv", 1_000, &sig, 0));
+        assert!(!verify("exp2", "moments.csv", 1_000, &sig, 0));
+        assert!(!verify("exp1", "moments.csv", 2_000, &sig, 0));
+        assert!(!verify("exp1", "moments.csv", 1_000, "zz", 0));
+        let other = sign_download("another-secret", "exp1", "moments.csv", 1_000);
+        assert!(!verify("exp1", "moments.csv", 1_000, &other, 0));
+    }
+
+    #[test]
+    fn test_export_datasets_and_formats() {
+        assert_eq!(ExportDataset::for_export(false).len(), 3);
+        assert_eq!(
+            ExportDataset::for_export(true).last(),
+            Some(&ExportDataset::Transactions)
+        );
+        assert_eq!(ExportFormat::parse("jsonl"), Some(ExportFormat::Ndjson));
+        assert_eq!(ExportFormat::parse("xlsx"), None);
+        assert_eq!(
+            ExportDataset::Trades.file_name(ExportFormat::Parquet),
+            "trades.parquet"
+        );
+    }
+
+    #[test]
+    fn test_jobs_queued_before_quotas_still_parse() {
+        let request: ExportRequest = serde_json::from_value(serde_json::json!({
+            "export_id": "exp1",
+            "user_id": "u1",
+            "wallet": "w",
+            "format": "csv",
+        }))
+        .unwrap();
+        assert_eq!(request.quota, None);
+
+        let file: ExportFile = serde_json::from_value(serde_json::json!({
+            "name": "moments.csv",
+            "dataset": "moments",
+            "key": "exports/u1/exp1/moments.csv",
+            "rows": 3,
+            "bytes": 10,
+        }))
+        .unwrap();
+        assert!(!file.truncated);
+    }
+}
@@ -0,0 +1,296 @@
+//! Client for the Helius webhook management API.
+//!
+//! Only the calls needed to keep a webhook's `accountAddresses` in sync
+//! with `tracked_wallets` are implemented. The base URL is configurable so
+//! tests (and staging) can point it at a local stub.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::config::AppConfig;
+
+/// Helius caps a single webhook at this many addresses
+pub const MAX_WEBHOOK_ADDRESSES: usize = 100_000;
+
+/// Webhook definition as returned by `GET /v0/webhooks/{id}`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HeliusWebhook {
+    #[serde(rename = "webhookID")]
+    pub webhook_id: String,
+    #[serde(rename = "webhookURL")]
+    pub webhook_url: String,
+    #[serde(rename = "transactionTypes", default)]
+    pub transaction_types: Vec<String>,
+    #[serde(rename = "accountAddresses", default)]
+    pub account_addresses: Vec<String>,
+    #[serde(rename = "webhookType")]
+    pub webhook_type: String,
+    #[serde(rename = "authHeader", skip_serializing_if = "Option::is_none")]
+    pub auth_header: Option<String>,
+}
+
+/// Addresses to add to and remove from a webhook
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl AddressDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compare the webhook's current addresses with the desired set
+pub fn diff_addresses(current: &[String], desired: &[String]) -> AddressDiff {
+    let current: BTreeSet<&String> = current.iter().collect();
+    let desired: BTreeSet<&String> = desired.iter().collect();
+    AddressDiff {
+        added: desired
+            .difference(&current)
+            .map(|s| s.to_string())
+            .collect(),
+        removed: current
+            .difference(&desired)
+            .map(|s| s.to_string())
+            .collect(),
+    }
+}
+
+pub struct HeliusWebhookClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    webhook_id: String,
+}
+
+impl HeliusWebhookClient {
+    pub fn new(base_url: &str, api_key: &str, webhook_id: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            webhook_id: webhook_id.to_string(),
+        }
+    }
+
+    /// Build a client from config; `None` when webhook sync is not configured
+    pub fn from_config(cfg: &AppConfig) -> Option<Self> {
+        match (&cfg.helius_api_key, &cfg.helius_webhook_id) {
+            (Some(key), Some(id)) if !key.is_empty() && !id.is_empty() => {
+                Some(Self::new(&cfg.helius_api_base_url, key, id))
+            }
+            _ => None,
+        }
+    }
+
+    fn webhook_url(&self) -> String {
+        format!("{}/v0/webhooks/{}", self.base_url, self.webhook_id)
+    }
+
+    pub async fn get_webhook(&self) -> Result<HeliusWebhook> {
+        let response = self
+            .http
+            .get(self.webhook_url())
+            .query(&[("api-key", &self.api_key)])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Helius get webhook failed with status {}",
+                response.status()
+            ));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Replace the webhook's account addresses, keeping its other settings
+    pub async fn set_account_addresses(
+        &self,
+        webhook: &HeliusWebhook,
+        addresses: Vec<String>,
+    ) -> Result<HeliusWebhook> {
+        if addresses.len() > MAX_WEBHOOK_ADDRESSES {
+            return Err(anyhow!(
+                "{} addresses exceeds the Helius webhook limit of {}",
+                addresses.len(),
+                MAX_WEBHOOK_ADDRESSES
+            ));
+        }
+
+        let body = HeliusWebhook {
+            account_addresses: addresses,
+            ..webhook.clone()
+        };
+        let response = self
+            .http
+            .put(self.webhook_url())
+            .query(&[("api-key", &self.api_key)])
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Helius update webhook failed with status {}",
+                response.status()
+            ));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Make the webhook watch exactly `desired`; returns what changed
+    pub async fn sync_addresses(&self, desired: &[String]) -> Result<AddressDiff> {
+        let webhook = self.get_webhook().await?;
+        let diff = diff_addresses(&webhook.account_addresses, desired);
+        if !diff.is_empty() {
+            let mut addresses: Vec<String> = desired.to_vec();
+            addresses.sort();
+            addresses.dedup();
+            self.set_account_addresses(&webhook, addresses).await?;
+        }
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn s(v: &[&str]) -> Vec<String> {
+        v.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn webhook(addresses: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "webhookID": "wh_test",
+            "webhookURL": "https://indexer.example.com/webhooks/helius",
+            "transactionTypes": ["ANY"],
+            "accountAddresses": addresses,
+            "webhookType": "enhanced",
+            "authHeader": "secret"
+        })
+    }
+
+    /// Answers one connection per entry of `replies`, in order, and hands
+    /// back the raw requests it saw
+    async fn stub_server(
+        replies: Vec<(u16, serde_json::Value)>,
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for (status, body) in replies {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = socket.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let len = text[..end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if buf.len() >= end + 4 + len || n == 0 {
+                            break;
+                        }
+                    }
+                }
+                let body = body.to_string();
+                let reply = format!(
+                    "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(reply.as_bytes()).await.unwrap();
+                requests.push(String::from_utf8(buf).unwrap());
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_diff_addresses() {
+        let diff = diff_addresses(&s(&["a", "b", "c"]), &s(&["b", "c", "d", "d"]));
+        assert_eq!(diff.added, s(&["d"]));
+        assert_eq!(diff.removed, s(&["a"]));
+        assert!(diff_addresses(&s(&["a"]), &s(&["a"])).is_empty());
+    }
+
+    #[test]
+    fn test_webhook_roundtrip_uses_helius_field_names() {
+        let raw = serde_json::json!({
+            "webhookID": "wh_1",
+            "wallet": "owner",
+            "webhookURL": "https://indexer.example.com/webhooks/helius",
+            "transactionTypes": ["ANY"],
+            "accountAddresses": ["a"],
+            "webhookType": "enhanced"
+        });
+        let webhook: HeliusWebhook = serde_json::from_value(raw).unwrap();
+        assert_eq!(webhook.account_addresses, s(&["a"]));
+
+        let out = serde_json::to_value(&webhook).unwrap();
+        assert_eq!(
+            out["webhookURL"],
+            "https://indexer.example.com/webhooks/helius"
+        );
+        assert!(out.get("authHeader").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_replaces_webhook_addresses() {
+        let (url, server) = stub_server(vec![
+            (200, webhook(&["old", "kept"])),
+            (200, webhook(&["kept", "new"])),
+        ])
+        .await;
+
+        let client = HeliusWebhookClient::new(&url, "key", "wh_test");
+        let diff = client.sync_addresses(&s(&["new", "kept"])).await.unwrap();
+        assert_eq!(diff.added, s(&["new"]));
+        assert_eq!(diff.removed, s(&["old"]));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("GET /v0/webhooks/wh_test?api-key=key "));
+        assert!(requests[1].starts_with("PUT /v0/webhooks/wh_test?api-key=key "));
+        let (_, body) = requests[1].split_once("\r\n\r\n").unwrap();
+        let sent: HeliusWebhook = serde_json::from_str(body).unwrap();
+        assert_eq!(sent.account_addresses, s(&["kept", "new"]));
+        assert_eq!(sent.auth_header.as_deref(), Some("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_skips_update_when_unchanged() {
+        let (url, server) = stub_server(vec![(200, webhook(&["a", "b"]))]).await;
+
+        let client = HeliusWebhookClient::new(&url, "key", "wh_test");
+        let diff = client.sync_addresses(&s(&["b", "a"])).await.unwrap();
+        assert!(diff.is_empty());
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET "));
+    }
+
+    #[tokio::test]
+    async fn test_sync_surfaces_management_api_errors() {
+        let (url, server) = stub_server(vec![(401, serde_json::json!({}))]).await;
+
+        let client = HeliusWebhookClient::new(&url, "bad", "wh_test");
+        assert!(client.sync_addresses(&s(&["a"])).await.is_err());
+        server.await.unwrap();
+    }
+}
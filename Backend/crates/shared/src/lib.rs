@@ -1,17 +1,32 @@
+pub mod alerts;
+pub mod api_keys;
 pub mod auth;
 pub mod config;
 pub mod constants;
 pub mod db;
+pub mod equity;
 pub mod errors;
+pub mod export;
+pub mod helius;
 pub mod metrics;
+pub mod moment_cohort;
+pub mod moment_query;
+pub mod normalize;
 pub mod observability;
 pub mod policy;
+pub mod progress;
+pub mod queue;
 pub mod redis;
+pub mod rpc;
 pub mod security;
 pub mod store;
+pub mod tax;
 pub mod telemetry;
+pub mod timeline;
+pub mod tracking;
 pub mod types;
 pub mod utils;
+pub mod wallet_metrics;
 
 // Re-export commonly used types
 pub use config::AppConfig;
@@ -20,14 +35,18 @@ pub use errors::{ApiError, ApiResult};
 pub use metrics::{metrics_router, Metrics};
 pub use policy::PolicyService;
 pub use redis::{MaybeRedis, RedisClient};
+pub use queue::{CancelToken, JobError, JobSpec, ParentFailure, RetryPolicy};
+pub use rpc::{RpcBudget, RpcEndpointConfig, RpcError, RpcPool};
 pub use store::{make_store, ObjectStore};
 pub use telemetry::{init_telemetry, service_name, service_version};
+pub use tracking::{TrackReason, TrackedWalletRegistry};
 pub use types::{
     chain::{Action, ChainEvent, EventKind, Participant, TxContext, TxRaw},
+    leaderboard::{LeaderboardMetric, LeaderboardPeriod},
     moment::{ExtremeEntry, Moment, MomentContext, MomentKind, WalletExtremes},
     policy::{
-        AuthMethod, Plan, PolicyState, QuotaCheck, RateLimitConfig, StakingBoost, UserContext,
-        UserPlan,
+        AuthMethod, JobPriority, Plan, PlanCadence, PolicyState, QuotaCheck, RateLimitConfig,
+        StakingBoost, UserContext, UserPlan,
     },
     price::{
         Candle, PriceBucket, PriceConfidence, PricePoint, PriceProvider, PriceRange, PriceSource,
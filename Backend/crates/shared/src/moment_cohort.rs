@@ -0,0 +1,195 @@
+//! Moment cohorts: every moment on the same mint and kind on the same UTC
+//! day. Totals are cached in `moment_cohort_daily` and recomputed once
+//! stale; days still collecting moments go stale sooner than settled ones.
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime};
+
+/// Cache lifetime for a day that may still be collecting moments
+const OPEN_DAY_TTL: Duration = Duration::minutes(10);
+
+/// Cache lifetime for older days, which only change on backfills
+const SETTLED_DAY_TTL: Duration = Duration::hours(6);
+
+/// Days after which a cohort day counts as settled
+const SETTLE_AFTER: Duration = Duration::days(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CohortDay {
+    pub mint: String,
+    pub kind: String,
+    pub day: Date,
+    pub moments: i64,
+    pub wallets: i64,
+    pub missed_usd_total: Decimal,
+    pub missed_usd_max: Option<Decimal>,
+    pub computed_at: OffsetDateTime,
+}
+
+/// One wallet's standing within a cohort
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CohortRank {
+    /// 1 missed the most; ties share a rank
+    pub rank: i64,
+    pub wallets: i64,
+    pub missed_usd: Decimal,
+}
+
+impl CohortRank {
+    /// Share of the cohort at or above this rank, in percent
+    pub fn top_pct(&self) -> f64 {
+        if self.wallets <= 0 {
+            return 100.0;
+        }
+        (self.rank as f64 / self.wallets as f64 * 100.0).min(100.0)
+    }
+}
+
+/// `[start, end)` of the UTC day containing `ts`
+pub fn day_bounds(ts: OffsetDateTime) -> (OffsetDateTime, OffsetDateTime) {
+    let start = ts
+        .to_offset(time::UtcOffset::UTC)
+        .date()
+        .midnight()
+        .assume_utc();
+    (start, start + Duration::days(1))
+}
+
+/// Whether a cache row computed at `computed_at` for the day starting at
+/// `day_start` should be recomputed
+pub fn is_stale(
+    day_start: OffsetDateTime,
+    computed_at: OffsetDateTime,
+    now: OffsetDateTime,
+) -> bool {
+    let ttl = if now - day_start >= SETTLE_AFTER {
+        SETTLED_DAY_TTL
+    } else {
+        OPEN_DAY_TTL
+    };
+    now - computed_at >= ttl
+}
+
+/// Cohort totals for the UTC day containing `ts`, from the cache when fresh
+pub async fn load_cohort_day(
+    pg: &PgPool,
+    mint: &str,
+    kind: &str,
+    ts: OffsetDateTime,
+) -> anyhow::Result<CohortDay> {
+    let (start, end) = day_bounds(ts);
+    let day = start.date();
+    let now = OffsetDateTime::now_utc();
+
+    let cached = sqlx::query!(
+        "SELECT moments, wallets, missed_usd_total, missed_usd_max, computed_at
+         FROM moment_cohort_daily
+         WHERE mint = $1 AND kind = $2 AND day = ($3::timestamptz AT TIME ZONE 'UTC')::date",
+        mint,
+        kind,
+        start
+    )
+    .fetch_optional(pg)
+    .await?;
+
+    if let Some(row) = cached.filter(|r| !is_stale(start, r.computed_at, now)) {
+        return Ok(CohortDay {
+            mint: mint.to_string(),
+            kind: kind.to_string(),
+            day,
+            moments: row.moments,
+            wallets: row.wallets,
+            missed_usd_total: row.missed_usd_total,
+            missed_usd_max: row.missed_usd_max,
+            computed_at: row.computed_at,
+        });
+    }
+
+    let row = sqlx::query!(
+        include_str!("../../../db/queries/upsert_moment_cohort_day.sql"),
+        mint,
+        kind,
+        start,
+        end
+    )
+    .fetch_one(pg)
+    .await?;
+
+    Ok(CohortDay {
+        mint: mint.to_string(),
+        kind: kind.to_string(),
+        day,
+        moments: row.moments,
+        wallets: row.wallets,
+        missed_usd_total: row.missed_usd_total,
+        missed_usd_max: row.missed_usd_max,
+        computed_at: row.computed_at,
+    })
+}
+
+/// Where `wallet` ranks by missed USD in `cohort`; `None` when it has no
+/// moment in the cohort
+pub async fn load_cohort_rank(
+    pg: &PgPool,
+    cohort: &CohortDay,
+    wallet: &str,
+) -> anyhow::Result<Option<CohortRank>> {
+    let start = cohort.day.midnight().assume_utc();
+    let end = start + Duration::days(1);
+    let row = sqlx::query!(
+        include_str!("../../../db/queries/select_moment_cohort_rank.sql"),
+        cohort.mint,
+        cohort.kind,
+        start,
+        end,
+        wallet
+    )
+    .fetch_optional(pg)
+    .await?;
+
+    Ok(row.map(|r| CohortRank {
+        rank: r.rank,
+        // The cache can trail the live ranking by up to its TTL
+        wallets: cohort.wallets.max(r.rank),
+        missed_usd: r.missed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_day_bounds_use_utc() {
+        let (start, end) = day_bounds(datetime!(2024-03-02 23:30 -05:00));
+        assert_eq!(start, datetime!(2024-03-03 0:00 UTC));
+        assert_eq!(end, datetime!(2024-03-04 0:00 UTC));
+    }
+
+    #[test]
+    fn test_open_days_go_stale_sooner() {
+        let now = datetime!(2024-03-10 12:00 UTC);
+        let today = datetime!(2024-03-10 0:00 UTC);
+        let last_week = datetime!(2024-03-03 0:00 UTC);
+        let computed = now - Duration::minutes(30);
+
+        assert!(is_stale(today, computed, now));
+        assert!(!is_stale(last_week, computed, now));
+        assert!(is_stale(last_week, now - Duration::hours(7), now));
+    }
+
+    #[test]
+    fn test_top_pct() {
+        let rank = CohortRank {
+            rank: 1,
+            wallets: 3413,
+            missed_usd: Decimal::ONE_HUNDRED,
+        };
+        assert!(rank.top_pct() < 0.03);
+        let last = CohortRank { rank: 3413, ..rank };
+        assert_eq!(last.top_pct(), 100.0);
+    }
+}
@@ -0,0 +1,431 @@
+//! Typed query layer over `oof_moments`.
+//!
+//! [`MomentQuery`] composes filters, a sort order and a keyset cursor into
+//! one parameterised statement with [`sqlx::QueryBuilder`]; every value is
+//! bound, never interpolated.
+//!
+//! Cursors encode the sort they were issued for together with the last
+//! row's sort key and id, so pages stay stable while new moments arrive.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::str::FromStr;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::errors::ApiError;
+use crate::types::moment::MomentKind;
+
+/// Columns every moment listing returns
+const MOMENT_COLUMNS: &str = "SELECT m.id, m.wallet, m.mint, m.kind, m.t_event, m.pct_dec, \
+     m.missed_usd_dec, m.severity_dec, m.sig_ref, m.slot_ref, m.version, m.explain_json, \
+     m.preview_png_url, tf.symbol AS token_symbol, tf.logo_url AS token_logo_url \
+     FROM oof_moments m LEFT JOIN token_facts tf ON tf.mint = m.mint";
+
+/// Listing order; always descending, ties broken by id
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MomentSort {
+    /// Newest first
+    #[default]
+    Time,
+    /// Most severe first; moments without a severity are skipped
+    Severity,
+    /// Largest miss first; moments without a USD figure are skipped
+    MissedUsd,
+}
+
+impl MomentSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MomentSort::Time => "time",
+            MomentSort::Severity => "severity",
+            MomentSort::MissedUsd => "missed_usd",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "time" => Some(MomentSort::Time),
+            "severity" => Some(MomentSort::Severity),
+            "missed_usd" => Some(MomentSort::MissedUsd),
+            _ => None,
+        }
+    }
+
+    fn column(&self) -> &'static str {
+        match self {
+            MomentSort::Time => "m.t_event",
+            MomentSort::Severity => "m.severity_dec",
+            MomentSort::MissedUsd => "m.missed_usd_dec",
+        }
+    }
+}
+
+/// Sort key of the last row of a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorKey {
+    Time(OffsetDateTime),
+    Amount(Decimal),
+}
+
+/// Keyset position: resume after the row with this key and id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MomentCursor {
+    pub sort: MomentSort,
+    pub key: CursorKey,
+    pub id: String,
+}
+
+impl MomentCursor {
+    /// Cursor positioned after `row` in `sort` order. `None` when the row
+    /// has no value for the sort column (such rows are never listed).
+    pub fn after(row: &MomentRow, sort: MomentSort) -> Option<Self> {
+        let key = match sort {
+            MomentSort::Time => CursorKey::Time(row.t_event),
+            MomentSort::Severity => CursorKey::Amount(row.severity_dec?),
+            MomentSort::MissedUsd => CursorKey::Amount(row.missed_usd_dec?),
+        };
+        Some(Self {
+            sort,
+            key,
+            id: row.id.clone(),
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let key = match &self.key {
+            CursorKey::Time(ts) => ts.format(&Rfc3339).unwrap_or_default(),
+            CursorKey::Amount(amount) => amount.to_string(),
+        };
+        URL_SAFE_NO_PAD.encode(format!("{}|{}|{}", self.sort.as_str(), key, self.id))
+    }
+
+    /// Decode a cursor issued for `sort`
+    pub fn decode(cursor: &str, sort: MomentSort) -> Result<Self, ApiError> {
+        let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+        let raw = URL_SAFE_NO_PAD
+            .decode(cursor.trim_end_matches('='))
+            .map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let mut parts = raw.splitn(3, '|');
+        let (Some(issued_for), Some(key), Some(id)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if MomentSort::parse(issued_for) != Some(sort) || id.is_empty() {
+            return Err(ApiError::BadRequest(
+                "Cursor does not match the requested sort".to_string(),
+            ));
+        }
+        let key = match sort {
+            MomentSort::Time => {
+                CursorKey::Time(OffsetDateTime::parse(key, &Rfc3339).map_err(|_| invalid())?)
+            }
+            MomentSort::Severity | MomentSort::MissedUsd => {
+                CursorKey::Amount(Decimal::from_str(key).map_err(|_| invalid())?)
+            }
+        };
+        Ok(Self {
+            sort,
+            key,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Row filters; unset fields do not constrain the result
+#[derive(Debug, Clone, Default)]
+pub struct MomentFilter {
+    pub wallets: Vec<String>,
+    /// Wallets left out, e.g. the viewer's own when listing others
+    pub exclude_wallets: Vec<String>,
+    pub mint: Option<String>,
+    pub kinds: Vec<MomentKind>,
+    /// Inclusive lower bound on `t_event`
+    pub since: Option<OffsetDateTime>,
+    /// Exclusive upper bound on `t_event`
+    pub until: Option<OffsetDateTime>,
+    pub min_usd: Option<Decimal>,
+    pub max_usd: Option<Decimal>,
+    pub min_severity: Option<Decimal>,
+    /// Detector version that produced the moment
+    pub version: Option<String>,
+}
+
+impl MomentFilter {
+    /// Append these filters as clauses; the moments table must be aliased
+    /// `m`
+    fn push_clauses(&self, qb: &mut QueryBuilder<'_, Postgres>, sep: &mut Clauses) {
+        if !self.wallets.is_empty() {
+            sep.next(qb).push("m.wallet = ANY(");
+            qb.push_bind(self.wallets.clone()).push(")");
+        }
+        if !self.exclude_wallets.is_empty() {
+            sep.next(qb).push("m.wallet <> ALL(");
+            qb.push_bind(self.exclude_wallets.clone()).push(")");
+        }
+        if let Some(mint) = &self.mint {
+            sep.next(qb).push("m.mint = ");
+            qb.push_bind(mint.clone());
+        }
+        if !self.kinds.is_empty() {
+            let kinds: Vec<String> = self.kinds.iter().map(|k| k.as_str().to_string()).collect();
+            sep.next(qb).push("m.kind = ANY(");
+            qb.push_bind(kinds).push(")");
+        }
+        if let Some(since) = self.since {
+            sep.next(qb).push("m.t_event >= ");
+            qb.push_bind(since);
+        }
+        if let Some(until) = self.until {
+            sep.next(qb).push("m.t_event < ");
+            qb.push_bind(until);
+        }
+        if let Some(min_usd) = self.min_usd {
+            sep.next(qb).push("m.missed_usd_dec >= ");
+            qb.push_bind(min_usd);
+        }
+        if let Some(max_usd) = self.max_usd {
+            sep.next(qb).push("m.missed_usd_dec <= ");
+            qb.push_bind(max_usd);
+        }
+        if let Some(min_severity) = self.min_severity {
+            sep.next(qb).push("m.severity_dec >= ");
+            qb.push_bind(min_severity);
+        }
+        if let Some(version) = &self.version {
+            sep.next(qb).push("m.version = ");
+            qb.push_bind(version.clone());
+        }
+    }
+}
+
+/// Emits ` WHERE ` before the first clause and ` AND ` before the rest
+#[derive(Default)]
+struct Clauses {
+    started: bool,
+}
+
+impl Clauses {
+    fn next<'q, 'a>(
+        &mut self,
+        qb: &'q mut QueryBuilder<'a, Postgres>,
+    ) -> &'q mut QueryBuilder<'a, Postgres> {
+        qb.push(if self.started { " AND " } else { " WHERE " });
+        self.started = true;
+        qb
+    }
+}
+
+/// A moment as listed by the API, with its token's display facts
+#[derive(Debug, Clone, FromRow)]
+pub struct MomentRow {
+    pub id: String,
+    pub wallet: String,
+    pub mint: Option<String>,
+    pub kind: String,
+    pub t_event: OffsetDateTime,
+    pub pct_dec: Option<Decimal>,
+    pub missed_usd_dec: Option<Decimal>,
+    pub severity_dec: Option<Decimal>,
+    pub sig_ref: Option<String>,
+    pub slot_ref: Option<i64>,
+    pub version: Option<String>,
+    pub explain_json: Option<serde_json::Value>,
+    pub preview_png_url: Option<String>,
+    pub token_symbol: Option<String>,
+    pub token_logo_url: Option<String>,
+}
+
+/// One page of moments and the cursor for the next, if any
+#[derive(Debug, Clone)]
+pub struct MomentPage {
+    pub rows: Vec<MomentRow>,
+    pub next_cursor: Option<MomentCursor>,
+}
+
+/// Composable moment listing
+#[derive(Debug, Clone)]
+pub struct MomentQuery {
+    pub filter: MomentFilter,
+    pub sort: MomentSort,
+    pub after: Option<MomentCursor>,
+    pub limit: usize,
+}
+
+impl MomentQuery {
+    pub fn new() -> Self {
+        Self {
+            filter: MomentFilter::default(),
+            sort: MomentSort::default(),
+            after: None,
+            limit: crate::constants::api::DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: MomentFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wallet(mut self, wallet: impl Into<String>) -> Self {
+        self.filter.wallets = vec![wallet.into()];
+        self
+    }
+
+    pub fn with_kinds(mut self, kinds: Vec<MomentKind>) -> Self {
+        self.filter.kinds = kinds;
+        self
+    }
+
+    pub fn with_sort(mut self, sort: MomentSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Resume after `cursor`, which must have been issued for this sort
+    pub fn with_cursor(mut self, cursor: Option<MomentCursor>) -> Self {
+        self.after = cursor;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    /// Build the listing statement; fetches one row past `limit` so the
+    /// caller can tell whether another page exists
+    pub fn build(&self) -> QueryBuilder<'static, Postgres> {
+        let mut qb = QueryBuilder::new(MOMENT_COLUMNS);
+        let mut sep = Clauses::default();
+        self.filter.push_clauses(&mut qb, &mut sep);
+
+        let column = self.sort.column();
+        if self.sort != MomentSort::Time {
+            sep.next(&mut qb).push(column).push(" IS NOT NULL");
+        }
+        if let Some(cursor) = &self.after {
+            sep.next(&mut qb).push("(").push(column).push(", m.id) < (");
+            match &cursor.key {
+                CursorKey::Time(ts) => qb.push_bind(*ts),
+                CursorKey::Amount(amount) => qb.push_bind(*amount),
+            };
+            qb.push(", ").push_bind(cursor.id.clone()).push(")");
+        }
+
+        qb.push(" ORDER BY ")
+            .push(column)
+            .push(" DESC, m.id DESC LIMIT ")
+            .push_bind(self.limit as i64 + 1);
+        qb
+    }
+
+    pub async fn fetch_page(&self, pg: &PgPool) -> anyhow::Result<MomentPage> {
+        let mut rows: Vec<MomentRow> = self.build().build_query_as().fetch_all(pg).await?;
+        let has_more = rows.len() > self.limit;
+        rows.truncate(self.limit);
+        let next_cursor = match rows.last() {
+            Some(last) if has_more => MomentCursor::after(last, self.sort),
+            _ => None,
+        };
+        Ok(MomentPage { rows, next_cursor })
+    }
+}
+
+impl Default for MomentQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let time_cursor = MomentCursor {
+            sort: MomentSort::Time,
+            key: CursorKey::Time(OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap()),
+            id: "01HF0000000000000000000000".to_string(),
+        };
+        let decoded = MomentCursor::decode(&time_cursor.encode(), MomentSort::Time).unwrap();
+        assert_eq!(decoded, time_cursor);
+
+        let usd_cursor = MomentCursor {
+            sort: MomentSort::MissedUsd,
+            key: CursorKey::Amount(Decimal::from_str("1234.5678").unwrap()),
+            id: "01HF0000000000000000000001".to_string(),
+        };
+        let decoded = MomentCursor::decode(&usd_cursor.encode(), MomentSort::MissedUsd).unwrap();
+        assert_eq!(decoded, usd_cursor);
+    }
+
+    #[test]
+    fn test_cursor_rejects_other_sort_and_garbage() {
+        let cursor = MomentCursor {
+            sort: MomentSort::Severity,
+            key: CursorKey::Amount(Decimal::ONE),
+            id: "01HF0000000000000000000000".to_string(),
+        };
+        assert!(MomentCursor::decode(&cursor.encode(), MomentSort::Time).is_err());
+        assert!(MomentCursor::decode("not a cursor", MomentSort::Time).is_err());
+        assert!(MomentCursor::decode(&URL_SAFE_NO_PAD.encode("time|x"), MomentSort::Time).is_err());
+    }
+
+    #[test]
+    fn test_build_without_filters() {
+        let qb = MomentQuery::new().with_limit(10).build();
+        assert!(qb
+            .sql()
+            .ends_with(" ORDER BY m.t_event DESC, m.id DESC LIMIT $1"));
+        assert!(!qb.sql().contains("WHERE"));
+    }
+
+    #[test]
+    fn test_build_binds_every_filter() {
+        let filter = MomentFilter {
+            wallets: vec!["w1".to_string(), "w2".to_string()],
+            kinds: vec![MomentKind::SoldTooEarly],
+            since: Some(OffsetDateTime::from_unix_timestamp(0).unwrap()),
+            min_usd: Some(Decimal::ONE_HUNDRED),
+            ..Default::default()
+        };
+        let cursor = MomentCursor {
+            sort: MomentSort::Severity,
+            key: CursorKey::Amount(Decimal::ONE),
+            id: "01HF0000000000000000000000".to_string(),
+        };
+        let qb = MomentQuery::new()
+            .with_filter(filter)
+            .with_sort(MomentSort::Severity)
+            .with_cursor(Some(cursor))
+            .build();
+        let sql = qb.sql();
+        assert!(sql.contains(
+            " WHERE m.wallet = ANY($1) AND m.kind = ANY($2) AND m.t_event >= $3 \
+             AND m.missed_usd_dec >= $4 AND m.severity_dec IS NOT NULL \
+             AND (m.severity_dec, m.id) < ($5, $6)"
+        ));
+        assert!(sql.ends_with(" ORDER BY m.severity_dec DESC, m.id DESC LIMIT $7"));
+    }
+
+    #[test]
+    fn test_build_excludes_wallets() {
+        let filter = MomentFilter {
+            exclude_wallets: vec!["w1".to_string()],
+            mint: Some("mint".to_string()),
+            ..Default::default()
+        };
+        let qb = MomentQuery::new()
+            .with_filter(filter)
+            .with_sort(MomentSort::MissedUsd)
+            .build();
+        assert!(qb.sql().contains(
+            " WHERE m.wallet <> ALL($1) AND m.mint = $2 AND m.missed_usd_dec IS NOT NULL"
+        ));
+    }
+}
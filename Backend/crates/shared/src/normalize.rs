@@ -0,0 +1,926 @@
+//! Transaction normaliser shared by the webhook (live) and backfill paths.
+//!
+//! Turns a standard `jsonParsed` RPC transaction (or an archived Helius
+//! enhanced webhook payload) into per-owner token balance deltas, SOL
+//! deltas net of fees and classified swaps, then into the rows we store in
+//! `actions`. Keeping one implementation means historical and live data are
+//! classified identically.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use crate::constants::solana::{SOL_MINT, USDC_MINT};
+
+/// Programs that never identify what a transaction "did"
+const INFRA_PROGRAMS: [&str; 2] = [
+    "11111111111111111111111111111111",
+    "ComputeBudget111111111111111111111111111111",
+];
+
+/// USDT is treated as a USD quote alongside USDC
+const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+
+/// Native SOL changes smaller than this are rent/dust, not a swap leg
+const SOL_DUST_LAMPORTS: i64 = 100_000;
+
+/// Decimal places of a SOL amount in lamports, as a `Decimal` scale
+pub(crate) const SOL_DECIMALS: u32 = 9;
+
+/// Path through which a transaction reached `actions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestSource {
+    Webhook,
+    RpcWs,
+    Backfill,
+    Renormalize,
+}
+
+impl IngestSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestSource::Webhook => "webhook",
+            IngestSource::RpcWs => "rpc_ws",
+            IngestSource::Backfill => "backfill",
+            IngestSource::Renormalize => "renormalize",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            IngestSource::Webhook,
+            IngestSource::RpcWs,
+            IngestSource::Backfill,
+            IngestSource::Renormalize,
+        ]
+        .into_iter()
+        .find(|source| source.as_str() == s)
+    }
+
+    /// Source to record when a signature's actions are rebuilt: the path
+    /// that first ingested it, or `Renormalize` if nothing was stored
+    pub fn for_replay(previous: Option<&str>) -> Self {
+        previous
+            .and_then(Self::parse)
+            .unwrap_or(IngestSource::Renormalize)
+    }
+}
+
+/// Replay-safe action ID: the same (sig, log_idx, kind) always maps to the
+/// same ID regardless of which path ingested it. Mirrors the backfill in
+/// migration 0016.
+pub fn action_id(sig: &str, log_idx: i32, kind: &str) -> String {
+    let digest = Sha256::digest(format!("{sig}:{log_idx}:{kind}").as_bytes());
+    hex::encode(&digest[..16])
+}
+
+#[derive(Debug, Error)]
+pub enum NormalizeError {
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+    #[error("unrecognised transaction format")]
+    UnknownFormat,
+}
+
+/// Net change of one mint held by one owner, in UI units
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenDelta {
+    pub owner: String,
+    pub mint: String,
+    pub decimals: u32,
+    pub delta: Decimal,
+}
+
+/// Net lamport change of one account; the fee payer's fee is added back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolDelta {
+    pub account: String,
+    pub lamports: i64,
+}
+
+impl SolDelta {
+    pub fn sol(&self) -> Decimal {
+        Decimal::new(self.lamports, SOL_DECIMALS)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapKind {
+    /// Quote (SOL or a stablecoin) spent for `base_mint`
+    Buy,
+    /// `base_mint` sold for the quote
+    Sell,
+    /// Token-for-token swap without a recognised quote
+    Swap,
+}
+
+impl SwapKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwapKind::Buy => "buy",
+            SwapKind::Sell => "sell",
+            SwapKind::Swap => "swap",
+        }
+    }
+}
+
+/// A classified swap for one owner. Amounts are positive UI units.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Swap {
+    pub owner: String,
+    pub kind: SwapKind,
+    pub base_mint: String,
+    pub base_amount: Decimal,
+    pub quote_mint: String,
+    pub quote_amount: Decimal,
+}
+
+impl Swap {
+    /// Execution price in USD when the quote is a stablecoin
+    pub fn exec_px_usd(&self) -> Option<Decimal> {
+        if is_usd_quote(&self.quote_mint) && !self.base_amount.is_zero() {
+            Some(self.quote_amount / self.base_amount)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalizedTx {
+    pub signature: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+    pub success: bool,
+    pub fee_lamports: u64,
+    pub fee_payer: Option<String>,
+    /// Every account referenced by the transaction, in message order
+    pub accounts: Vec<String>,
+    /// Top-level instruction programs, in order, without duplicates
+    pub program_ids: Vec<String>,
+    pub token_deltas: Vec<TokenDelta>,
+    pub sol_deltas: Vec<SolDelta>,
+    pub swaps: Vec<Swap>,
+}
+
+/// One row destined for the `actions` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedAction {
+    pub log_idx: i32,
+    pub kind: &'static str,
+    pub program_id: String,
+    pub mint: Option<String>,
+    pub amount: Option<Decimal>,
+    pub exec_px_usd: Option<Decimal>,
+    pub route: Option<String>,
+    pub flags: Value,
+}
+
+/// Normalise either a `jsonParsed`/`json` RPC transaction or a Helius
+/// enhanced payload, detected from its shape.
+pub fn normalize_transaction(tx: &Value) -> Result<NormalizedTx, NormalizeError> {
+    if tx.get("meta").is_some() && tx.get("transaction").is_some() {
+        normalize_rpc_transaction(tx)
+    } else if tx.get("accountData").is_some() {
+        normalize_helius_enhanced(tx)
+    } else {
+        Err(NormalizeError::UnknownFormat)
+    }
+}
+
+/// Normalise a standard RPC `getTransaction` result
+pub fn normalize_rpc_transaction(tx: &Value) -> Result<NormalizedTx, NormalizeError> {
+    let meta = tx.get("meta").ok_or(NormalizeError::MissingField("meta"))?;
+    let message = tx
+        .pointer("/transaction/message")
+        .ok_or(NormalizeError::MissingField("transaction.message"))?;
+    let signature = tx
+        .pointer("/transaction/signatures/0")
+        .and_then(Value::as_str)
+        .ok_or(NormalizeError::MissingField("transaction.signatures"))?
+        .to_string();
+
+    let accounts = rpc_account_keys(message, meta);
+    let fee_lamports = meta.get("fee").and_then(Value::as_u64).unwrap_or(0);
+    let fee_payer = accounts.first().cloned();
+    let success = meta.get("err").map(Value::is_null).unwrap_or(true);
+
+    // SOL deltas from pre/post lamport balances
+    let pre = u64_array(meta.get("preBalances"));
+    let post = u64_array(meta.get("postBalances"));
+    let mut sol: BTreeMap<String, i64> = BTreeMap::new();
+    for (idx, account) in accounts.iter().enumerate() {
+        let (Some(pre), Some(post)) = (pre.get(idx), post.get(idx)) else {
+            continue;
+        };
+        *sol.entry(account.clone()).or_default() += *post as i64 - *pre as i64;
+    }
+    if let Some(payer) = &fee_payer {
+        *sol.entry(payer.clone()).or_default() += fee_lamports as i64;
+    }
+
+    // Token deltas from pre/post token balances, aggregated per owner+mint
+    let mut raw: BTreeMap<(String, String), (i128, u32)> = BTreeMap::new();
+    let mut apply = |balances: Option<&Value>, sign: i128| {
+        for balance in balances.and_then(Value::as_array).into_iter().flatten() {
+            let Some(mint) = balance.get("mint").and_then(Value::as_str) else {
+                continue;
+            };
+            let owner = balance
+                .get("owner")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| {
+                    balance
+                        .get("accountIndex")
+                        .and_then(Value::as_u64)
+                        .and_then(|i| accounts.get(i as usize).cloned())
+                });
+            let Some(owner) = owner else { continue };
+            let amount = balance
+                .pointer("/uiTokenAmount/amount")
+                .and_then(Value::as_str)
+                .and_then(|s| s.parse::<i128>().ok())
+                .unwrap_or(0);
+            let decimals = balance
+                .pointer("/uiTokenAmount/decimals")
+                .and_then(Value::as_u64)
+                .unwrap_or(0) as u32;
+            let entry = raw
+                .entry((owner, mint.to_string()))
+                .or_insert((0, decimals));
+            entry.0 += sign * amount;
+            entry.1 = decimals;
+        }
+    };
+    apply(meta.get("preTokenBalances"), -1);
+    apply(meta.get("postTokenBalances"), 1);
+
+    let program_ids = message
+        .get("instructions")
+        .and_then(Value::as_array)
+        .map(|ixs| {
+            ixs.iter()
+                .filter_map(|ix| {
+                    ix.get("programId")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                        .or_else(|| {
+                            ix.get("programIdIndex")
+                                .and_then(Value::as_u64)
+                                .and_then(|i| accounts.get(i as usize).cloned())
+                        })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    Ok(build(
+        signature,
+        tx.get("slot").and_then(Value::as_i64).unwrap_or(0),
+        tx.get("blockTime").and_then(Value::as_i64),
+        success,
+        fee_lamports,
+        fee_payer,
+        accounts,
+        program_ids,
+        raw,
+        sol,
+    ))
+}
+
+/// Normalise an archived Helius enhanced webhook payload using its
+/// `accountData` balance changes, so it matches the RPC path.
+pub fn normalize_helius_enhanced(tx: &Value) -> Result<NormalizedTx, NormalizeError> {
+    let signature = tx
+        .get("signature")
+        .and_then(Value::as_str)
+        .ok_or(NormalizeError::MissingField("signature"))?
+        .to_string();
+    let fee_lamports = tx.get("fee").and_then(Value::as_u64).unwrap_or(0);
+    let fee_payer = tx
+        .get("feePayer")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let success = tx
+        .get("transactionError")
+        .map(Value::is_null)
+        .unwrap_or(true);
+
+    let mut accounts = Vec::new();
+    let mut sol: BTreeMap<String, i64> = BTreeMap::new();
+    let mut raw: BTreeMap<(String, String), (i128, u32)> = BTreeMap::new();
+
+    for data in tx
+        .get("accountData")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let Some(account) = data.get("account").and_then(Value::as_str) else {
+            continue;
+        };
+        accounts.push(account.to_string());
+        let change = data
+            .get("nativeBalanceChange")
+            .and_then(Value::as_i64)
+            .unwrap_or(0);
+        *sol.entry(account.to_string()).or_default() += change;
+
+        for token in data
+            .get("tokenBalanceChanges")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let (Some(owner), Some(mint)) = (
+                token.get("userAccount").and_then(Value::as_str),
+                token.get("mint").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            let amount = token
+                .pointer("/rawTokenAmount/tokenAmount")
+                .and_then(Value::as_str)
+                .and_then(|s| s.parse::<i128>().ok())
+                .unwrap_or(0);
+            let decimals = token
+                .pointer("/rawTokenAmount/decimals")
+                .and_then(Value::as_u64)
+                .unwrap_or(0) as u32;
+            let entry = raw
+                .entry((owner.to_string(), mint.to_string()))
+                .or_insert((0, decimals));
+            entry.0 += amount;
+        }
+    }
+    if let Some(payer) = &fee_payer {
+        *sol.entry(payer.clone()).or_default() += fee_lamports as i64;
+    }
+
+    let program_ids = tx
+        .get("instructions")
+        .and_then(Value::as_array)
+        .map(|ixs| {
+            ixs.iter()
+                .filter_map(|ix| ix.get("programId").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(build(
+        signature,
+        tx.get("slot").and_then(Value::as_i64).unwrap_or(0),
+        tx.get("timestamp").and_then(Value::as_i64),
+        success,
+        fee_lamports,
+        fee_payer,
+        accounts,
+        program_ids,
+        raw,
+        sol,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build(
+    signature: String,
+    slot: i64,
+    block_time: Option<i64>,
+    success: bool,
+    fee_lamports: u64,
+    fee_payer: Option<String>,
+    accounts: Vec<String>,
+    mut program_ids: Vec<String>,
+    raw: BTreeMap<(String, String), (i128, u32)>,
+    sol: BTreeMap<String, i64>,
+) -> NormalizedTx {
+    let token_deltas: Vec<TokenDelta> = raw
+        .into_iter()
+        .filter(|(_, (amount, _))| *amount != 0)
+        .filter_map(|((owner, mint), (amount, decimals))| {
+            Decimal::try_from_i128_with_scale(amount, decimals)
+                .ok()
+                .map(|delta| TokenDelta {
+                    owner,
+                    mint,
+                    decimals,
+                    delta,
+                })
+        })
+        .collect();
+
+    let sol_deltas: Vec<SolDelta> = sol
+        .into_iter()
+        .filter(|(_, lamports)| *lamports != 0)
+        .map(|(account, lamports)| SolDelta { account, lamports })
+        .collect();
+
+    let mut seen = std::collections::HashSet::new();
+    program_ids.retain(|p| seen.insert(p.clone()));
+
+    let swaps = if success {
+        classify_swaps(&token_deltas, &sol_deltas)
+    } else {
+        Vec::new()
+    };
+
+    NormalizedTx {
+        signature,
+        slot,
+        block_time,
+        success,
+        fee_lamports,
+        fee_payer,
+        accounts,
+        program_ids,
+        token_deltas,
+        sol_deltas,
+        swaps,
+    }
+}
+
+/// Classify each owner's deltas into at most one swap. An owner swapped if
+/// exactly one token went out and one came in, counting native SOL plus
+/// wrapped SOL as a single SOL leg.
+pub fn classify_swaps(token_deltas: &[TokenDelta], sol_deltas: &[SolDelta]) -> Vec<Swap> {
+    let mut by_owner: BTreeMap<&str, Vec<(String, Decimal)>> = BTreeMap::new();
+    let mut owner_sol: BTreeMap<&str, Decimal> = BTreeMap::new();
+
+    for d in token_deltas {
+        if d.mint == SOL_MINT {
+            *owner_sol.entry(d.owner.as_str()).or_default() += d.delta;
+        } else {
+            by_owner
+                .entry(d.owner.as_str())
+                .or_default()
+                .push((d.mint.clone(), d.delta));
+        }
+    }
+    for d in sol_deltas {
+        if d.lamports.abs() >= SOL_DUST_LAMPORTS || owner_sol.contains_key(d.account.as_str()) {
+            *owner_sol.entry(d.account.as_str()).or_default() += d.sol();
+        }
+    }
+
+    let mut swaps = Vec::new();
+    for (owner, legs) in by_owner {
+        let ins: Vec<_> = legs.iter().filter(|(_, d)| d.is_sign_positive()).collect();
+        let outs: Vec<_> = legs.iter().filter(|(_, d)| d.is_sign_negative()).collect();
+        let sol = owner_sol.get(owner).copied().unwrap_or_default();
+        let sol_dust = Decimal::new(SOL_DUST_LAMPORTS, SOL_DECIMALS);
+
+        let swap = match (ins.as_slice(), outs.as_slice()) {
+            ([(bought, b_amt)], [(sold, s_amt)]) => {
+                let (kind, base, base_amt, quote, quote_amt) = if is_usd_quote(sold) {
+                    (SwapKind::Buy, bought, *b_amt, sold, s_amt.abs())
+                } else if is_usd_quote(bought) {
+                    (SwapKind::Sell, sold, s_amt.abs(), bought, *b_amt)
+                } else {
+                    (SwapKind::Swap, bought, *b_amt, sold, s_amt.abs())
+                };
+                Some(Swap {
+                    owner: owner.to_string(),
+                    kind,
+                    base_mint: base.clone(),
+                    base_amount: base_amt,
+                    quote_mint: quote.clone(),
+                    quote_amount: quote_amt,
+                })
+            }
+            ([(bought, amt)], []) if sol <= -sol_dust => Some(Swap {
+                owner: owner.to_string(),
+                kind: SwapKind::Buy,
+                base_mint: bought.clone(),
+                base_amount: *amt,
+                quote_mint: SOL_MINT.to_string(),
+                quote_amount: sol.abs(),
+            }),
+            ([], [(sold, amt)]) if sol >= sol_dust => Some(Swap {
+                owner: owner.to_string(),
+                kind: SwapKind::Sell,
+                base_mint: sold.clone(),
+                base_amount: amt.abs(),
+                quote_mint: SOL_MINT.to_string(),
+                quote_amount: sol,
+            }),
+            _ => None,
+        };
+        swaps.extend(swap);
+    }
+    swaps
+}
+
+impl NormalizedTx {
+    pub fn timestamp(&self) -> Option<OffsetDateTime> {
+        self.block_time
+            .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok())
+    }
+
+    /// The first top-level program that is not system/compute-budget
+    pub fn primary_program(&self) -> Option<&str> {
+        self.program_ids
+            .iter()
+            .map(String::as_str)
+            .find(|p| !INFRA_PROGRAMS.contains(p))
+    }
+
+    /// Deterministic action rows: swaps, then token transfers not explained
+    /// by a swap, then native SOL transfers not explained by a swap.
+    pub fn actions(&self) -> Vec<NormalizedAction> {
+        let program_id = self.primary_program().unwrap_or_default().to_string();
+        let mut actions = Vec::new();
+
+        let swapped = |owner: &str, mint: &str| {
+            self.swaps
+                .iter()
+                .any(|s| s.owner == owner && (s.base_mint == mint || s.quote_mint == mint))
+        };
+        let swapped_sol = |owner: &str| {
+            self.swaps
+                .iter()
+                .any(|s| s.owner == owner && s.quote_mint == SOL_MINT)
+        };
+
+        for swap in &self.swaps {
+            actions.push(NormalizedAction {
+                log_idx: 0,
+                kind: swap.kind.as_str(),
+                program_id: program_id.clone(),
+                mint: Some(swap.base_mint.clone()),
+                amount: Some(swap.base_amount),
+                exec_px_usd: swap.exec_px_usd(),
+                route: None,
+                flags: serde_json::json!({
+                    "owner": swap.owner,
+                    "quote_mint": swap.quote_mint,
+                    "quote_amount": swap.quote_amount.to_string(),
+                }),
+            });
+        }
+
+        for delta in &self.token_deltas {
+            if swapped(&delta.owner, &delta.mint) {
+                continue;
+            }
+            actions.push(NormalizedAction {
+                log_idx: 0,
+                kind: "transfer",
+                program_id: program_id.clone(),
+                mint: Some(delta.mint.clone()),
+                amount: Some(delta.delta),
+                exec_px_usd: None,
+                route: None,
+                flags: serde_json::json!({
+                    "owner": delta.owner,
+                    "direction": if delta.delta.is_sign_positive() { "in" } else { "out" },
+                }),
+            });
+        }
+
+        for delta in &self.sol_deltas {
+            if delta.lamports.abs() < SOL_DUST_LAMPORTS || swapped_sol(&delta.account) {
+                continue;
+            }
+            actions.push(NormalizedAction {
+                log_idx: 0,
+                kind: "sol_transfer",
+                program_id: program_id.clone(),
+                mint: None,
+                amount: Some(delta.sol()),
+                exec_px_usd: None,
+                route: None,
+                flags: serde_json::json!({
+                    "owner": delta.account,
+                    "amount_lamports": delta.lamports,
+                }),
+            });
+        }
+
+        if actions.is_empty() {
+            // Mark transaction presence so coverage checks see it
+            actions.push(NormalizedAction {
+                log_idx: 0,
+                kind: "tx",
+                program_id,
+                mint: None,
+                amount: None,
+                exec_px_usd: None,
+                route: None,
+                flags: serde_json::json!({
+                    "success": self.success,
+                    "fee_lamports": self.fee_lamports,
+                }),
+            });
+        }
+
+        for (idx, action) in actions.iter_mut().enumerate() {
+            action.log_idx = idx as i32;
+        }
+        actions
+    }
+}
+
+/// Insert the normalised actions of `tx` and its participants.
+///
+/// Idempotent: redelivering the same signature inserts nothing new. Returns
+/// the number of action rows actually inserted.
+pub async fn persist_normalized(
+    conn: &mut sqlx::PgConnection,
+    tx: &NormalizedTx,
+    ts: OffsetDateTime,
+    source: IngestSource,
+) -> anyhow::Result<usize> {
+    for account in &tx.accounts {
+        sqlx::query!(
+            "INSERT INTO participants (sig, wallet) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            tx.signature,
+            account
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let mut inserted = 0;
+    for action in &tx.actions() {
+        let res = sqlx::query!(
+            include_str!("../../../db/queries/insert_action.sql"),
+            action_id(&tx.signature, action.log_idx, action.kind),
+            tx.signature,
+            action.log_idx,
+            tx.slot,
+            ts,
+            action.program_id,
+            action.kind,
+            action.mint,
+            action.amount,
+            action.exec_px_usd,
+            action.route,
+            action.flags,
+            source.as_str()
+        )
+        .execute(&mut *conn)
+        .await?;
+        inserted += res.rows_affected() as usize;
+    }
+
+    sqlx::query!(
+        "UPDATE tx_raw SET classifier_version = $2, fee_lamports = $3, fee_payer = $4
+         WHERE sig = $1",
+        tx.signature,
+        crate::constants::system::CLASSIFIER_VERSION,
+        tx.fee_lamports as i64,
+        tx.fee_payer
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(inserted)
+}
+
+pub(crate) fn is_usd_quote(mint: &str) -> bool {
+    mint == USDC_MINT || mint == USDT_MINT
+}
+
+fn u64_array(value: Option<&Value>) -> Vec<u64> {
+    value
+        .and_then(Value::as_array)
+        .map(|a| a.iter().map(|v| v.as_u64().unwrap_or(0)).collect())
+        .unwrap_or_default()
+}
+
+/// Account keys in message order. `jsonParsed` returns objects that already
+/// include lookup-table accounts; `json` returns strings plus
+/// `meta.loadedAddresses`.
+fn rpc_account_keys(message: &Value, meta: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = message
+        .get("accountKeys")
+        .and_then(Value::as_array)
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|k| {
+                    k.as_str()
+                        .or_else(|| k.get("pubkey").and_then(Value::as_str))
+                        .map(str::to_string)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let parsed = message
+        .pointer("/accountKeys/0")
+        .map(Value::is_object)
+        .unwrap_or(false);
+    if !parsed {
+        for group in ["writable", "readonly"] {
+            for key in meta
+                .pointer(&format!("/loadedAddresses/{}", group))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const WALLET: &str = "Wa11et1111111111111111111111111111111111111";
+    const POOL: &str = "Poo1111111111111111111111111111111111111111";
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+    const DEX: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+    fn token_balance(idx: u64, owner: &str, mint: &str, amount: &str, decimals: u64) -> Value {
+        json!({
+            "accountIndex": idx,
+            "mint": mint,
+            "owner": owner,
+            "uiTokenAmount": {"amount": amount, "decimals": decimals}
+        })
+    }
+
+    fn buy_with_sol() -> Value {
+        json!({
+            "slot": 250_000_000,
+            "blockTime": 1_700_000_000,
+            "meta": {
+                "err": null,
+                "fee": 5000,
+                "preBalances": [2_000_005_000u64, 10_000_000_000u64, 1],
+                "postBalances": [1_000_000_000u64, 11_000_000_000u64, 1],
+                "preTokenBalances": [
+                    token_balance(1, POOL, BONK, "900000000", 5),
+                ],
+                "postTokenBalances": [
+                    token_balance(1, POOL, BONK, "400000000", 5),
+                    token_balance(3, WALLET, BONK, "500000000", 5),
+                ]
+            },
+            "transaction": {
+                "signatures": ["sig1"],
+                "message": {
+                    "accountKeys": [
+                        {"pubkey": WALLET, "signer": true, "writable": true},
+                        {"pubkey": POOL, "signer": false, "writable": true},
+                        {"pubkey": DEX, "signer": false, "writable": false}
+                    ],
+                    "instructions": [
+                        {"programId": "ComputeBudget111111111111111111111111111111"},
+                        {"programId": DEX}
+                    ]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_rpc_buy_is_classified_net_of_fees() {
+        let tx = normalize_transaction(&buy_with_sol()).unwrap();
+
+        assert_eq!(tx.signature, "sig1");
+        assert_eq!(tx.fee_payer.as_deref(), Some(WALLET));
+        let wallet_sol = tx.sol_deltas.iter().find(|d| d.account == WALLET).unwrap();
+        assert_eq!(wallet_sol.lamports, -1_000_000_000);
+
+        let wallet_bonk = tx
+            .token_deltas
+            .iter()
+            .find(|d| d.owner == WALLET && d.mint == BONK)
+            .unwrap();
+        assert_eq!(wallet_bonk.delta, Decimal::new(5000, 0));
+
+        let swap = tx.swaps.iter().find(|s| s.owner == WALLET).unwrap();
+        assert_eq!(swap.kind, SwapKind::Buy);
+        assert_eq!(swap.base_mint, BONK);
+        assert_eq!(swap.quote_mint, SOL_MINT);
+        assert_eq!(swap.quote_amount, Decimal::ONE);
+        assert_eq!(tx.primary_program(), Some(DEX));
+    }
+
+    #[test]
+    fn test_actions_are_deterministic() {
+        let tx = normalize_transaction(&buy_with_sol()).unwrap();
+        let first = tx.actions();
+        let second = normalize_transaction(&buy_with_sol()).unwrap().actions();
+        assert_eq!(first, second);
+
+        let buy = first.iter().find(|a| a.flags["owner"] == WALLET).unwrap();
+        assert_eq!(buy.kind, "buy");
+        assert_eq!(buy.mint.as_deref(), Some(BONK));
+        assert_eq!(
+            first.iter().map(|a| a.log_idx).collect::<Vec<_>>(),
+            (0..first.len() as i32).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_replay_keeps_the_first_ingest_source() {
+        for source in [
+            IngestSource::Webhook,
+            IngestSource::RpcWs,
+            IngestSource::Backfill,
+        ] {
+            assert_eq!(IngestSource::for_replay(Some(source.as_str())), source);
+        }
+        assert_eq!(IngestSource::for_replay(None), IngestSource::Renormalize);
+        assert_eq!(
+            IngestSource::for_replay(Some("bogus")),
+            IngestSource::Renormalize
+        );
+    }
+
+    #[test]
+    fn test_action_id_is_stable_and_distinct() {
+        let id = action_id("sig1", 0, "buy");
+        assert_eq!(id, action_id("sig1", 0, "buy"));
+        assert_eq!(id.len(), 32);
+        assert_ne!(id, action_id("sig1", 1, "buy"));
+        assert_ne!(id, action_id("sig1", 0, "sell"));
+        assert_ne!(id, action_id("sig2", 0, "buy"));
+    }
+
+    #[test]
+    fn test_stable_quote_sell_has_usd_price() {
+        let deltas = vec![
+            TokenDelta {
+                owner: WALLET.into(),
+                mint: BONK.into(),
+                decimals: 5,
+                delta: Decimal::new(-1000, 0),
+            },
+            TokenDelta {
+                owner: WALLET.into(),
+                mint: USDC_MINT.into(),
+                decimals: 6,
+                delta: Decimal::new(25, 0),
+            },
+        ];
+        let swaps = classify_swaps(&deltas, &[]);
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0].kind, SwapKind::Sell);
+        assert_eq!(swaps[0].exec_px_usd(), Some(Decimal::new(25, 3)));
+    }
+
+    #[test]
+    fn test_failed_transaction_only_pays_fee() {
+        let mut raw = buy_with_sol();
+        raw["meta"]["err"] = json!({"InstructionError": [1, "Custom"]});
+        raw["meta"]["preBalances"] = json!([1_000_005_000u64, 0, 1]);
+        raw["meta"]["postBalances"] = json!([1_000_000_000u64, 0, 1]);
+        raw["meta"]["postTokenBalances"] = raw["meta"]["preTokenBalances"].clone();
+
+        let tx = normalize_transaction(&raw).unwrap();
+        assert!(!tx.success);
+        assert!(tx.sol_deltas.is_empty());
+        assert!(tx.swaps.is_empty());
+        assert_eq!(tx.actions()[0].kind, "tx");
+    }
+
+    #[test]
+    fn test_helius_enhanced_matches_rpc() {
+        let enhanced = json!({
+            "signature": "sig1",
+            "slot": 250_000_000,
+            "timestamp": 1_700_000_000,
+            "fee": 5000,
+            "feePayer": WALLET,
+            "transactionError": null,
+            "instructions": [{"programId": DEX}],
+            "accountData": [
+                {"account": WALLET, "nativeBalanceChange": -1_000_005_000i64, "tokenBalanceChanges": [
+                    {"userAccount": WALLET, "mint": BONK, "rawTokenAmount": {"tokenAmount": "500000000", "decimals": 5}}
+                ]},
+                {"account": POOL, "nativeBalanceChange": 1_000_000_000i64, "tokenBalanceChanges": [
+                    {"userAccount": POOL, "mint": BONK, "rawTokenAmount": {"tokenAmount": "-500000000", "decimals": 5}}
+                ]}
+            ]
+        });
+
+        let live = normalize_transaction(&enhanced).unwrap();
+        let backfill = normalize_transaction(&buy_with_sol()).unwrap();
+        assert_eq!(live.token_deltas, backfill.token_deltas);
+        assert_eq!(live.swaps, backfill.swaps);
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(matches!(
+            normalize_transaction(&json!({"foo": 1})),
+            Err(NormalizeError::UnknownFormat)
+        ));
+    }
+}
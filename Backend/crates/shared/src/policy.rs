@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use time::{OffsetDateTime, Date};
 
+/// Per-key API rate when the plan row is missing, as the column default
+const API_REQUESTS_PER_MINUTE: i32 = 10;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Plan {
     pub code: String,
@@ -11,27 +14,30 @@ pub struct Plan {
     pub cadence: String,
     pub alerts: i32,
     pub api_rows: i64,
+    pub max_signatures_per_run: i64,
+    pub max_enhanced_tx_per_run: i64,
 }
 
 #[derive(Debug, Clone)]
 pub struct UserContext { pub user_id: String, pub plan_code: String }
 
-pub struct PolicyService<'a> { pub pg: &'a PgPool }
+#[derive(Clone)]
+pub struct PolicyService { pub pg: PgPool }
 
-impl<'a> PolicyService<'a> {
-    pub fn new(pg: &'a PgPool) -> Self { Self { pg } }
+impl PolicyService {
+    pub fn new(pg: PgPool) -> Self { Self { pg } }
 
     pub async fn get_user_plan(&self, user_id: &str) -> anyhow::Result<Plan> {
-        if let Some(rec) = sqlx::query!("SELECT p.code, p.price_usd_dec, p.daily_wallets, p.backfill_days, p.cadence, p.alerts, p.api_rows FROM user_plans up JOIN plans p ON p.code=up.plan_code WHERE up.user_id=$1 AND (up.expires_at IS NULL OR up.expires_at>NOW()) ORDER BY up.started_at DESC LIMIT 1", user_id)
-            .fetch_optional(self.pg).await? {
-            return Ok(Plan { code: rec.code, price_usd: rec.price_usd_dec.to_string(), daily_wallets: rec.daily_wallets, backfill_days: rec.backfill_days, cadence: rec.cadence.unwrap_or_default(), alerts: rec.alerts.unwrap_or(0), api_rows: rec.api_rows.unwrap_or(0) });
+        if let Some(rec) = sqlx::query!("SELECT p.code, p.price_usd_dec, p.daily_wallets, p.backfill_days, p.cadence, p.alerts, p.api_rows, p.max_signatures_per_run, p.max_enhanced_tx_per_run FROM user_plans up JOIN plans p ON p.code=up.plan_code WHERE up.user_id=$1 AND (up.expires_at IS NULL OR up.expires_at>NOW()) ORDER BY up.started_at DESC LIMIT 1", user_id)
+            .fetch_optional(&self.pg).await? {
+            return Ok(Plan { code: rec.code, price_usd: rec.price_usd_dec.to_string(), daily_wallets: rec.daily_wallets, backfill_days: rec.backfill_days, cadence: rec.cadence.unwrap_or_default(), alerts: rec.alerts.unwrap_or(0), api_rows: rec.api_rows.unwrap_or(0), max_signatures_per_run: rec.max_signatures_per_run, max_enhanced_tx_per_run: rec.max_enhanced_tx_per_run });
         }
         // default FREE
-        if let Some(rec) = sqlx::query!("SELECT code, price_usd_dec, daily_wallets, backfill_days, cadence, alerts, api_rows FROM plans WHERE code='FREE'")
-            .fetch_optional(self.pg).await? {
-            return Ok(Plan { code: rec.code.unwrap_or("FREE".into()), price_usd: rec.price_usd_dec.unwrap_or_default().to_string(), daily_wallets: rec.daily_wallets.unwrap_or(2), backfill_days: rec.backfill_days.unwrap_or(180), cadence: rec.cadence.unwrap_or_default(), alerts: rec.alerts.unwrap_or(0), api_rows: rec.api_rows.unwrap_or(0) });
+        if let Some(rec) = sqlx::query!("SELECT code, price_usd_dec, daily_wallets, backfill_days, cadence, alerts, api_rows, max_signatures_per_run, max_enhanced_tx_per_run FROM plans WHERE code='FREE'")
+            .fetch_optional(&self.pg).await? {
+            return Ok(Plan { code: rec.code.unwrap_or("FREE".into()), price_usd: rec.price_usd_dec.unwrap_or_default().to_string(), daily_wallets: rec.daily_wallets.unwrap_or(2), backfill_days: rec.backfill_days.unwrap_or(180), cadence: rec.cadence.unwrap_or_default(), alerts: rec.alerts.unwrap_or(0), api_rows: rec.api_rows.unwrap_or(0), max_signatures_per_run: rec.max_signatures_per_run.unwrap_or(1000), max_enhanced_tx_per_run: rec.max_enhanced_tx_per_run.unwrap_or(100) });
         }
-        Ok(Plan { code: "FREE".into(), price_usd: "0.00".into(), daily_wallets: 2, backfill_days: 180, cadence: "manual".into(), alerts: 0, api_rows: 0 })
+        Ok(Plan { code: "FREE".into(), price_usd: "0.00".into(), daily_wallets: 2, backfill_days: 180, cadence: "manual".into(), alerts: 0, api_rows: 0, max_signatures_per_run: 1000, max_enhanced_tx_per_run: 100 })
     }
 
     pub async fn check_and_consume_quota(&self, user_id: &str, wallets: i32) -> anyhow::Result<bool> {
@@ -55,5 +61,74 @@ impl<'a> PolicyService<'a> {
         tx.commit().await?;
         Ok(true)
     }
-}
 
+    /// Plan limits and perks, today's quota usage and any active staking
+    /// boost, with the boost already applied to the plan
+    pub async fn get_user_context(&self, user_id: &str) -> anyhow::Result<crate::types::policy::UserContext> {
+        use crate::types::policy::{AuthMethod, BoostPerks, Plan as PlanLimits, PlanCadence, PlanPerks, PolicyState, StakingBoost, UserContext};
+
+        let plan = self.get_user_plan(user_id).await?;
+        let plan_row = sqlx::query!("SELECT perks_json, api_requests_per_minute FROM plans WHERE code = $1", plan.code)
+            .fetch_optional(&self.pg).await?;
+        let api_requests_per_minute = plan_row.as_ref().map_or(API_REQUESTS_PER_MINUTE, |r| r.api_requests_per_minute);
+        let perks: PlanPerks = plan_row
+            .and_then(|r| r.perks_json)
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+        let mut limits = PlanLimits {
+            name: plan.code.clone(),
+            price_usd: plan.price_usd.parse().unwrap_or_default(),
+            daily_wallets: plan.daily_wallets,
+            backfill_days: plan.backfill_days,
+            cadence: PlanCadence::parse(&plan.cadence).unwrap_or(PlanCadence::Manual),
+            alerts: plan.alerts,
+            api_rows: plan.api_rows,
+            max_signatures_per_run: plan.max_signatures_per_run,
+            max_enhanced_tx_per_run: plan.max_enhanced_tx_per_run,
+            api_requests_per_minute,
+            perks,
+            code: plan.code,
+        };
+
+        let mut policy_state = PolicyState::new(user_id.to_string());
+        if let Some(st) = sqlx::query!("SELECT analyses_today, last_reset_at FROM policy_state WHERE user_id = $1", user_id)
+            .fetch_optional(&self.pg).await? {
+            // Counters from an earlier day no longer count against the quota
+            if let Some(last_reset_at) = st.last_reset_at.filter(|t| t.date() == OffsetDateTime::now_utc().date()) {
+                policy_state.analyses_today = st.analyses_today.unwrap_or(0);
+                policy_state.last_reset_at = last_reset_at;
+            }
+        }
+
+        let staking_boost = sqlx::query!(
+            "SELECT wallet_address, staked_amount, boost_multiplier, boost_expires_at, perks_json
+             FROM staking_boosts
+             WHERE user_id = $1 AND boost_expires_at > NOW()
+             ORDER BY staked_amount DESC
+             LIMIT 1",
+            user_id
+        )
+        .fetch_optional(&self.pg).await?
+        .map(|r| StakingBoost {
+            wallet_address: r.wallet_address,
+            staked_amount: r.staked_amount,
+            boost_multiplier: r.boost_multiplier,
+            boost_expires_at: r.boost_expires_at,
+            perks: serde_json::from_value::<BoostPerks>(r.perks_json).unwrap_or_default(),
+        })
+        .filter(StakingBoost::is_active);
+        if let Some(boost) = &staking_boost {
+            limits = boost.apply_to_plan(limits);
+        }
+
+        Ok(UserContext {
+            user_id: user_id.to_string(),
+            wallet_address: staking_boost.as_ref().map(|b| b.wallet_address.clone()),
+            plan: limits,
+            policy_state,
+            is_authenticated: true,
+            auth_method: AuthMethod::JWT,
+            staking_boost,
+        })
+    }
+}
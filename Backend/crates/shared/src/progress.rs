@@ -0,0 +1,341 @@
+//! Analysis progress events.
+//!
+//! Workers append [`BackfillProgress`] snapshots to `job_progress` through a
+//! [`ProgressReporter`]; an insert trigger sends `NOTIFY job_progress` with
+//! the DAG's root id. The API runs [`relay_notifications`] to fan those
+//! notifications out to SSE streams, which then read the new rows with
+//! [`events_since`]. Each root's events are numbered by a per-root `seq`
+//! handed out under a row lock in `job_progress_seq`, so they commit in
+//! `seq` order; `seq` doubles as the SSE event id and a reconnecting client
+//! resumes from its `Last-Event-ID` without gaps.
+
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// Channel the `trg_job_progress_notify` trigger publishes on
+pub const PROGRESS_CHANNEL: &str = "job_progress";
+
+/// Most events returned by one [`events_since`] call
+const EVENTS_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackfillProgress {
+    pub job_id: String,
+    pub wallet_address: String,
+    pub status: BackfillStatus,
+    pub progress_pct: f64,
+    pub current_stage: BackfillStage,
+    pub signatures_fetched: u64,
+    pub transactions_parsed: u64,
+    pub moments_found: u64,
+    pub started_at: OffsetDateTime,
+    pub estimated_completion: Option<OffsetDateTime>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillStatus {
+    Queued,
+    InProgress,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillStage {
+    Initializing,
+    FetchingSignatures,
+    FetchingTransactions,
+    ProcessingTransactions,
+    BuildingPositions,
+    DetectingMoments,
+    Finalizing,
+}
+
+impl BackfillStage {
+    /// Share of the whole analysis, in percent, this stage spans
+    pub fn pct_range(&self) -> (f64, f64) {
+        match self {
+            Self::Initializing => (0.0, 5.0),
+            Self::FetchingSignatures => (5.0, 15.0),
+            Self::FetchingTransactions => (15.0, 55.0),
+            Self::ProcessingTransactions => (55.0, 65.0),
+            Self::BuildingPositions => (65.0, 85.0),
+            Self::DetectingMoments => (85.0, 95.0),
+            Self::Finalizing => (95.0, 100.0),
+        }
+    }
+
+    /// Overall percentage for `fraction` (0..=1) of the way through this stage
+    pub fn pct_at(&self, fraction: f64) -> f64 {
+        let (lo, hi) = self.pct_range();
+        lo + (hi - lo) * fraction.clamp(0.0, 1.0)
+    }
+}
+
+/// Linear ETA from elapsed time and percentage done
+pub fn estimate_completion(
+    started_at: OffsetDateTime,
+    now: OffsetDateTime,
+    progress_pct: f64,
+) -> Option<OffsetDateTime> {
+    if progress_pct <= 0.0 || progress_pct >= 100.0 {
+        return None;
+    }
+    let elapsed = (now - started_at).as_seconds_f64().max(0.0);
+    let remaining = elapsed * (100.0 - progress_pct) / progress_pct;
+    Some(now + time::Duration::seconds_f64(remaining))
+}
+
+/// Publishes progress for one job of an analysis DAG. Updates within a
+/// stage are throttled; stage changes and the final status always go out.
+/// Publishing is best effort and never fails the job.
+pub struct ProgressReporter {
+    pg: PgPool,
+    root_id: String,
+    job_id: String,
+    min_interval: Duration,
+    last_sent: Option<Instant>,
+    pub progress: BackfillProgress,
+}
+
+impl ProgressReporter {
+    pub fn new(pg: PgPool, root_id: &str, job_id: &str, wallet: &str) -> Self {
+        Self {
+            pg,
+            root_id: root_id.to_string(),
+            job_id: job_id.to_string(),
+            min_interval: Duration::from_secs(2),
+            last_sent: None,
+            progress: BackfillProgress {
+                job_id: root_id.to_string(),
+                wallet_address: wallet.to_string(),
+                status: BackfillStatus::InProgress,
+                progress_pct: 0.0,
+                current_stage: BackfillStage::Initializing,
+                signatures_fetched: 0,
+                transactions_parsed: 0,
+                moments_found: 0,
+                started_at: OffsetDateTime::now_utc(),
+                estimated_completion: None,
+                error_message: None,
+            },
+        }
+    }
+
+    /// Like [`ProgressReporter::new`], but carries counters and start time
+    /// over from the DAG's latest event so a later job (e.g. compute after
+    /// backfill) continues the same progress line
+    pub async fn resume(pg: PgPool, root_id: &str, job_id: &str, wallet: &str) -> Self {
+        let mut reporter = Self::new(pg, root_id, job_id, wallet);
+        let latest = sqlx::query_scalar!(
+            "SELECT payload FROM job_progress WHERE root_id = $1 ORDER BY seq DESC LIMIT 1",
+            root_id
+        )
+        .fetch_optional(&reporter.pg)
+        .await;
+        match latest {
+            Ok(Some(payload)) => match serde_json::from_value::<BackfillProgress>(payload) {
+                Ok(previous) => {
+                    reporter.progress = BackfillProgress {
+                        status: BackfillStatus::InProgress,
+                        estimated_completion: None,
+                        error_message: None,
+                        ..previous
+                    };
+                }
+                Err(e) => warn!(root_id, error = %e, "Ignoring malformed progress event"),
+            },
+            Ok(None) => {}
+            Err(e) => warn!(root_id, error = %e, "Failed to load previous progress"),
+        }
+        reporter
+    }
+
+    /// Minimum time between updates within one stage
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// Move to `stage` and publish immediately
+    pub async fn stage(&mut self, stage: BackfillStage) {
+        self.progress.current_stage = stage;
+        self.set_pct(stage.pct_at(0.0));
+        self.publish().await;
+    }
+
+    /// Record `fraction` of the current stage done; published if the
+    /// throttle interval has passed
+    pub async fn advance(&mut self, fraction: f64) {
+        self.set_pct(self.progress.current_stage.pct_at(fraction));
+        let due = self
+            .last_sent
+            .map_or(true, |at| at.elapsed() >= self.min_interval);
+        if due {
+            self.publish().await;
+        }
+    }
+
+    /// Publish the job's final status
+    pub async fn finish(&mut self, status: BackfillStatus, error_message: Option<String>) {
+        self.progress.status = status;
+        self.progress.error_message = error_message;
+        if status == BackfillStatus::Completed {
+            self.set_pct(self.progress.current_stage.pct_range().1);
+        }
+        self.progress.estimated_completion = None;
+        self.publish().await;
+    }
+
+    fn set_pct(&mut self, pct: f64) {
+        // Never move backwards within a job
+        self.progress.progress_pct = self.progress.progress_pct.max(pct);
+        self.progress.estimated_completion = estimate_completion(
+            self.progress.started_at,
+            OffsetDateTime::now_utc(),
+            self.progress.progress_pct,
+        );
+    }
+
+    async fn publish(&mut self) {
+        self.last_sent = Some(Instant::now());
+        let payload = match serde_json::to_value(&self.progress) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!(job_id = %self.job_id, error = %e, "Failed to serialize progress");
+                return;
+            }
+        };
+        if let Err(e) = self.insert_event(payload).await {
+            warn!(job_id = %self.job_id, error = %e, "Failed to publish progress");
+        }
+    }
+
+    /// Take the root's next `seq` and write the event in one transaction;
+    /// the `job_progress_seq` row stays locked until commit, so a later seq
+    /// can never become visible before an earlier one
+    async fn insert_event(&self, payload: serde_json::Value) -> Result<(), sqlx::Error> {
+        let mut tx = self.pg.begin().await?;
+        let seq = sqlx::query_scalar!(
+            "INSERT INTO job_progress_seq (root_id, last_seq) VALUES ($1, 1)
+             ON CONFLICT (root_id) DO UPDATE
+             SET last_seq = job_progress_seq.last_seq + 1, updated_at = NOW()
+             RETURNING last_seq",
+            self.root_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO job_progress (root_id, job_id, payload, seq) VALUES ($1, $2, $3, $4)",
+            self.root_id,
+            self.job_id,
+            payload,
+            seq
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+}
+
+/// Progress events for `root_id` after sequence number `after`, oldest first
+pub async fn events_since(
+    pg: &PgPool,
+    root_id: &str,
+    after: i64,
+) -> anyhow::Result<Vec<(i64, BackfillProgress)>> {
+    let rows = sqlx::query!(
+        "SELECT seq, payload FROM job_progress
+         WHERE root_id = $1 AND seq > $2
+         ORDER BY seq ASC
+         LIMIT $3",
+        root_id,
+        after,
+        EVENTS_PAGE_SIZE
+    )
+    .fetch_all(pg)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| match serde_json::from_value(r.payload) {
+            Ok(progress) => Some((r.seq, progress)),
+            Err(e) => {
+                warn!(event_id = r.seq, error = %e, "Skipping malformed progress event");
+                None
+            }
+        })
+        .collect())
+}
+
+/// Forward `job_progress` notifications (root ids) to `tx`, reconnecting
+/// on failure. Subscribers re-read the table, so a missed notification
+/// only delays an update.
+pub async fn relay_notifications(pg: PgPool, tx: broadcast::Sender<String>) {
+    loop {
+        match PgListener::connect_with(&pg).await {
+            Ok(mut listener) => match listener.listen(PROGRESS_CHANNEL).await {
+                Ok(()) => {
+                    debug!("Listening for progress notifications");
+                    loop {
+                        match listener.recv().await {
+                            Ok(n) => {
+                                // No subscribers is fine
+                                let _ = tx.send(n.payload().to_string());
+                            }
+                            Err(e) => {
+                                warn!(error = %e, "Progress notification listener failed");
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!(error = %e, "Failed to LISTEN on job progress"),
+            },
+            Err(e) => warn!(error = %e, "Failed to connect progress notification listener"),
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_pct() {
+        assert_eq!(BackfillStage::Initializing.pct_at(0.0), 0.0);
+        assert_eq!(BackfillStage::FetchingTransactions.pct_at(0.5), 35.0);
+        assert_eq!(BackfillStage::Finalizing.pct_at(2.0), 100.0);
+        assert_eq!(BackfillStage::DetectingMoments.pct_at(-1.0), 85.0);
+    }
+
+    #[test]
+    fn test_estimate_completion() {
+        let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let now = start + time::Duration::seconds(30);
+        assert_eq!(
+            estimate_completion(start, now, 25.0),
+            Some(now + time::Duration::seconds(90))
+        );
+        assert_eq!(estimate_completion(start, now, 0.0), None);
+        assert_eq!(estimate_completion(start, now, 100.0), None);
+    }
+
+    #[test]
+    fn test_progress_serialization() {
+        let stage = serde_json::to_value(BackfillStage::FetchingSignatures).unwrap();
+        assert_eq!(stage, "fetching_signatures");
+        let status = serde_json::to_value(BackfillStatus::InProgress).unwrap();
+        assert_eq!(status, "in_progress");
+    }
+}
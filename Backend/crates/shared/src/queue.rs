@@ -0,0 +1,936 @@
+//! Job queue DAG support.
+//!
+//! A job may declare parent jobs; it is inserted as `blocked` and only
+//! becomes `queued` once every parent has resolved. What "resolved" means
+//! for a failed parent is the child's [`ParentFailure`] policy. All jobs of
+//! one request share a `root_id`, which is the ID handed back to clients and
+//! whose status is rolled up across the whole graph.
+//!
+//! Running jobs heartbeat `locked_at`; [`reap_stale_locks`] hands back jobs
+//! whose worker stopped heartbeating. Failed attempts are classified with
+//! [`JobError`] and retried on a per-kind [`RetryPolicy`].
+//!
+//! Cancellation is cooperative: [`request_cancel`] stops queued and blocked
+//! jobs outright, but only flags running ones. The worker learns of the
+//! flag on its next heartbeat and trips the job's [`CancelToken`], which
+//! long-running handlers check between units of work.
+
+use once_cell::sync::Lazy;
+use prometheus::{IntCounter, IntCounterVec, Opts};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use crate::types::policy::JobPriority;
+
+static JOBS_REAPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "jobs_reaped_total",
+            "Jobs reclaimed from workers that stopped heartbeating",
+        ),
+        &["kind", "outcome"],
+    )
+    .expect("metric");
+    crate::metrics::REGISTRY
+        .register(Box::new(counter.clone()))
+        .ok();
+    counter
+});
+
+static REAPER_RUNS: Lazy<IntCounter> = Lazy::new(|| {
+    let counter =
+        IntCounter::new("job_reaper_runs_total", "Stale-lock reaper passes").expect("metric");
+    crate::metrics::REGISTRY
+        .register(Box::new(counter.clone()))
+        .ok();
+    counter
+});
+
+static HEARTBEATS_LOST: Lazy<IntCounter> = Lazy::new(|| {
+    let counter = IntCounter::new(
+        "job_heartbeats_lost_total",
+        "Heartbeats that found the job no longer locked by this worker",
+    )
+    .expect("metric");
+    crate::metrics::REGISTRY
+        .register(Box::new(counter.clone()))
+        .ok();
+    counter
+});
+
+/// What a blocked job does when one of its parents fails permanently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParentFailure {
+    /// Cancel this job and, transitively, everything depending on it
+    Cancel,
+    /// Treat the failed parent as resolved and run anyway
+    Continue,
+}
+
+impl ParentFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParentFailure::Cancel => "cancel",
+            ParentFailure::Continue => "continue",
+        }
+    }
+}
+
+/// A job to insert as part of a DAG
+#[derive(Debug, Clone)]
+pub struct JobSpec {
+    pub id: String,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub max_attempts: i32,
+    pub priority: JobPriority,
+    pub tenant: Option<String>,
+    pub parents: Vec<String>,
+    pub on_parent_failure: ParentFailure,
+}
+
+impl JobSpec {
+    pub fn new(kind: &str, payload: serde_json::Value) -> Self {
+        Self {
+            id: crate::utils::new_id(),
+            kind: kind.to_string(),
+            payload,
+            max_attempts: 5,
+            priority: JobPriority::Normal,
+            tenant: None,
+            parents: Vec::new(),
+            on_parent_failure: ParentFailure::Cancel,
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: i32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: JobPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    pub fn after(mut self, parents: &[String], on_failure: ParentFailure) -> Self {
+        self.parents.extend_from_slice(parents);
+        self.on_parent_failure = on_failure;
+        self
+    }
+}
+
+/// Insert a set of jobs sharing `root_id`. Parents must be earlier in
+/// `jobs` or already exist in the queue.
+pub async fn enqueue_dag(pg: &PgPool, root_id: &str, jobs: &[JobSpec]) -> anyhow::Result<()> {
+    let mut tx = pg.begin().await?;
+    let now = OffsetDateTime::now_utc();
+
+    for job in jobs {
+        let status = if job.parents.is_empty() {
+            "queued"
+        } else {
+            "blocked"
+        };
+        sqlx::query!(
+            "INSERT INTO job_queue (id, kind, payload_json, run_after, max_attempts, priority, tenant,
+                                    status, root_id, on_parent_failure)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+            job.id,
+            job.kind,
+            job.payload,
+            now,
+            job.max_attempts,
+            job.priority.as_i16(),
+            job.tenant,
+            status,
+            root_id,
+            job.on_parent_failure.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for parent in &job.parents {
+            sqlx::query!(
+                "INSERT INTO job_dependencies (job_id, parent_id) VALUES ($1, $2)",
+                job.id,
+                parent
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    // A parent may have finished before its child was inserted
+    for job in jobs.iter().filter(|j| !j.parents.is_empty()) {
+        promote_if_ready(&mut *tx, &job.id).await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Unblock children of a job that just succeeded; returns how many
+pub async fn on_job_succeeded(conn: &mut PgConnection, job_id: &str) -> anyhow::Result<u64> {
+    promote_children(conn, job_id).await
+}
+
+/// Apply each child's failure policy after `job_id` failed permanently or
+/// was cancelled. Returns the number of jobs cancelled by the cascade.
+pub async fn on_job_failed(conn: &mut PgConnection, job_id: &str) -> anyhow::Result<u64> {
+    let mut cancelled = 0;
+    let mut frontier = vec![job_id.to_string()];
+
+    while let Some(parent) = frontier.pop() {
+        let doomed = sqlx::query_scalar!(
+            "UPDATE job_queue c
+             SET status = 'cancelled', error_message = 'parent job ' || $1 || ' did not succeed',
+                 completed_at = NOW()
+             WHERE c.status = 'blocked'
+               AND c.on_parent_failure = 'cancel'
+               AND c.id IN (SELECT job_id FROM job_dependencies WHERE parent_id = $1)
+             RETURNING c.id",
+            parent
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        promote_children(conn, &parent).await?;
+        cancelled += doomed.len() as u64;
+        frontier.extend(doomed);
+    }
+
+    Ok(cancelled)
+}
+
+async fn promote_children(conn: &mut PgConnection, parent_id: &str) -> anyhow::Result<u64> {
+    let children = sqlx::query_scalar!(
+        "SELECT job_id FROM job_dependencies WHERE parent_id = $1",
+        parent_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut promoted = 0;
+    for child in children {
+        promoted += promote_if_ready(conn, &child).await?;
+    }
+    Ok(promoted)
+}
+
+async fn promote_if_ready(conn: &mut PgConnection, job_id: &str) -> anyhow::Result<u64> {
+    let res = sqlx::query!(
+        "UPDATE job_queue c
+         SET status = 'queued', run_after = GREATEST(c.run_after, NOW())
+         WHERE c.id = $1
+           AND c.status = 'blocked'
+           AND NOT EXISTS (
+             SELECT 1
+             FROM job_dependencies d
+             JOIN job_queue p ON p.id = d.parent_id
+             WHERE d.job_id = c.id
+               AND NOT (
+                 p.status = 'done'
+                 OR (c.on_parent_failure = 'continue' AND p.status IN ('failed', 'cancelled'))
+               )
+           )",
+        job_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// Rolled-up state of a DAG (or of a single job without a root)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DagStatus {
+    pub status: String,
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub running: usize,
+    pub pending: usize,
+}
+
+/// One job's contribution to a rollup; `is_sink` means nothing depends on it
+#[derive(Debug, Clone)]
+pub struct DagNode {
+    pub status: String,
+    pub is_sink: bool,
+}
+
+/// Roll a DAG up to one status.
+///
+/// The graph is `done` once every sink (a job nothing depends on) is done,
+/// and `failed` once nothing is left to run and some sink did not succeed,
+/// or `cancelled` if nothing failed outright. Until then it is `running` if
+/// any work has started, otherwise `queued`.
+pub fn rollup(nodes: &[DagNode]) -> Option<DagStatus> {
+    if nodes.is_empty() {
+        return None;
+    }
+
+    let count = |f: &dyn Fn(&str) -> bool| nodes.iter().filter(|n| f(&n.status)).count();
+    let done = count(&|s| s == "done");
+    let failed = count(&|s| s == "failed" || s == "cancelled");
+    let running = count(&|s| s == "running");
+    let pending = count(&|s| s == "queued" || s == "blocked");
+
+    let sinks: Vec<&DagNode> = nodes.iter().filter(|n| n.is_sink).collect();
+    let status = if sinks.iter().all(|n| n.status == "done") {
+        "done"
+    } else if running == 0 && pending == 0 {
+        if nodes.iter().any(|n| n.status == "failed") {
+            "failed"
+        } else {
+            "cancelled"
+        }
+    } else if running > 0 || done > 0 || failed > 0 {
+        "running"
+    } else {
+        "queued"
+    };
+
+    Some(DagStatus {
+        status: status.to_string(),
+        total: nodes.len(),
+        done,
+        failed,
+        running,
+        pending,
+    })
+}
+
+/// Status of the DAG rooted at `id`, or of the single job `id`
+pub async fn dag_status(pg: &PgPool, id: &str) -> anyhow::Result<Option<DagStatus>> {
+    let rows = sqlx::query!(
+        "SELECT j.status AS \"status!\",
+                NOT EXISTS (SELECT 1 FROM job_dependencies d WHERE d.parent_id = j.id) AS \"is_sink!\"
+         FROM job_queue j
+         WHERE j.root_id = $1 OR j.id = $1",
+        id
+    )
+    .fetch_all(pg)
+    .await?;
+
+    let nodes: Vec<DagNode> = rows
+        .into_iter()
+        .map(|r| DagNode {
+            status: r.status,
+            is_sink: r.is_sink,
+        })
+        .collect();
+    Ok(rollup(&nodes))
+}
+
+/// How long a running job may go without a heartbeat, per kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockTimeouts {
+    pub default: Duration,
+    pub per_kind: HashMap<String, Duration>,
+}
+
+impl LockTimeouts {
+    /// Parse `kind=seconds` pairs, e.g. `backfill=1800,renormalize=900`;
+    /// malformed entries are ignored
+    pub fn parse(default: Duration, spec: &str) -> Self {
+        let per_kind = spec
+            .split(',')
+            .filter_map(|entry| {
+                let (kind, secs) = entry.split_once('=')?;
+                let secs: u64 = secs.trim().parse().ok()?;
+                Some((kind.trim().to_string(), Duration::from_secs(secs)))
+            })
+            .filter(|(kind, _)| !kind.is_empty())
+            .collect();
+        Self { default, per_kind }
+    }
+
+    pub fn for_kind(&self, kind: &str) -> Duration {
+        self.per_kind.get(kind).copied().unwrap_or(self.default)
+    }
+}
+
+/// Refresh the lock on a running job. Returns false if the job is no longer
+/// ours, e.g. because it was reaped; the caller should stop work on it.
+pub async fn heartbeat(pg: &PgPool, job_id: &str, worker_id: &str) -> anyhow::Result<Heartbeat> {
+    let cancel_requested = sqlx::query_scalar!(
+        "UPDATE job_queue SET locked_at = NOW()
+         WHERE id = $1 AND locked_by = $2 AND status = 'running'
+         RETURNING cancel_requested_at IS NOT NULL AS \"cancel_requested!\"",
+        job_id,
+        worker_id
+    )
+    .fetch_optional(pg)
+    .await?;
+
+    Ok(match cancel_requested {
+        Some(false) => Heartbeat::Held,
+        Some(true) => Heartbeat::CancelRequested,
+        None => {
+            HEARTBEATS_LOST.inc();
+            Heartbeat::Lost
+        }
+    })
+}
+
+/// Lock a running job's row before writing its final status. Returns false
+/// if the job is no longer ours, in which case its new owner records the
+/// outcome and this worker must not.
+pub async fn holds_lock(
+    conn: &mut PgConnection,
+    job_id: &str,
+    worker_id: &str,
+) -> anyhow::Result<bool> {
+    let held = sqlx::query_scalar!(
+        "SELECT id FROM job_queue
+         WHERE id = $1 AND locked_by = $2 AND status = 'running'
+         FOR UPDATE",
+        job_id,
+        worker_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(held.is_some())
+}
+
+/// Result of refreshing a running job's lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heartbeat {
+    Held,
+    /// Still held, but someone asked for the job to stop
+    CancelRequested,
+    /// Another worker or the reaper has taken the job
+    Lost,
+}
+
+/// Shared flag a running job's handler polls to stop early
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// `Err(JobError::Cancelled)` once cancelled; use with `?` in handlers
+    pub fn check(&self) -> Result<(), JobError> {
+        if self.is_cancelled() {
+            Err(JobError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// What [`request_cancel`] did to a job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", content = "status", rename_all = "snake_case")]
+pub enum CancelOutcome {
+    /// Was queued or blocked and is now cancelled
+    Cancelled,
+    /// Is running; its worker will stop it at the next checkpoint
+    Requested,
+    /// Already in a terminal state
+    AlreadyFinished(String),
+    NotFound,
+}
+
+/// Cancel a job now if it has not started, otherwise flag it for its worker
+pub async fn request_cancel(
+    pg: &PgPool,
+    job_id: &str,
+    reason: &str,
+) -> anyhow::Result<CancelOutcome> {
+    let mut tx = pg.begin().await?;
+    let status = sqlx::query_scalar!(
+        "SELECT status AS \"status!\" FROM job_queue WHERE id = $1 FOR UPDATE",
+        job_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let outcome = match status.as_deref() {
+        None => CancelOutcome::NotFound,
+        Some("queued" | "blocked") => {
+            mark_cancelled(&mut *tx, job_id, reason).await?;
+            CancelOutcome::Cancelled
+        }
+        Some("running") => {
+            sqlx::query!(
+                "UPDATE job_queue
+                 SET cancel_requested_at = COALESCE(cancel_requested_at, NOW()), error_message = $2
+                 WHERE id = $1",
+                job_id,
+                reason
+            )
+            .execute(&mut *tx)
+            .await?;
+            CancelOutcome::Requested
+        }
+        Some(other) => CancelOutcome::AlreadyFinished(other.to_string()),
+    };
+    tx.commit().await?;
+    Ok(outcome)
+}
+
+/// Cancel every unfinished job of a DAG; returns (cancelled, requested)
+pub async fn cancel_dag(pg: &PgPool, root_id: &str, reason: &str) -> anyhow::Result<(u64, u64)> {
+    let ids = sqlx::query_scalar!(
+        "SELECT id FROM job_queue
+         WHERE (root_id = $1 OR id = $1) AND status IN ('queued', 'blocked', 'running')
+         ORDER BY created_at ASC",
+        root_id
+    )
+    .fetch_all(pg)
+    .await?;
+
+    let (mut cancelled, mut requested) = (0, 0);
+    for id in ids {
+        match request_cancel(pg, &id, reason).await? {
+            CancelOutcome::Cancelled => cancelled += 1,
+            CancelOutcome::Requested => requested += 1,
+            // Cascaded from an earlier sibling
+            CancelOutcome::AlreadyFinished(_) | CancelOutcome::NotFound => {}
+        }
+    }
+    Ok((cancelled, requested))
+}
+
+/// Move a job to `cancelled` and cancel dependents that require it.
+/// Returns how many dependents were cancelled.
+pub async fn mark_cancelled(
+    conn: &mut PgConnection,
+    job_id: &str,
+    reason: &str,
+) -> anyhow::Result<u64> {
+    sqlx::query!(
+        "UPDATE job_queue
+         SET status = 'cancelled', locked_by = NULL, locked_at = NULL, completed_at = NOW(),
+             error_message = COALESCE(error_message, $2)
+         WHERE id = $1",
+        job_id,
+        reason
+    )
+    .execute(&mut *conn)
+    .await?;
+    on_job_failed(conn, job_id).await
+}
+
+/// Put a failed or cancelled job back in the queue with fresh attempts,
+/// reopening dependents that were cancelled because of it. A job whose own
+/// parents have not succeeded goes back to `blocked`.
+pub async fn retry_job(pg: &PgPool, job_id: &str) -> anyhow::Result<bool> {
+    let mut tx = pg.begin().await?;
+    let reset = sqlx::query!(
+        "UPDATE job_queue j
+         SET status = CASE
+               WHEN EXISTS (SELECT 1 FROM job_dependencies d WHERE d.job_id = j.id) THEN 'blocked'
+               ELSE 'queued'
+             END,
+             attempts = 0, run_after = NOW(), error_message = NULL, completed_at = NULL,
+             cancel_requested_at = NULL, locked_by = NULL, locked_at = NULL
+         WHERE j.id = $1 AND j.status IN ('failed', 'cancelled')",
+        job_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if reset.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        "WITH RECURSIVE descendants AS (
+           SELECT job_id FROM job_dependencies WHERE parent_id = $1
+           UNION
+           SELECT d.job_id FROM job_dependencies d JOIN descendants ON d.parent_id = descendants.job_id
+         )
+         UPDATE job_queue
+         SET status = 'blocked', attempts = 0, error_message = NULL, completed_at = NULL,
+             cancel_requested_at = NULL
+         WHERE id IN (SELECT job_id FROM descendants) AND status = 'cancelled'",
+        job_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    promote_if_ready(&mut *tx, job_id).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Outcome of one reaper pass
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReapReport {
+    pub requeued: u64,
+    pub failed: u64,
+    pub cancelled: u64,
+}
+
+/// Return jobs whose heartbeat is older than their kind's timeout to
+/// `queued`, or to `failed` once out of attempts. `attempts` was already
+/// incremented when the job was locked, so the lost run counts, and it is
+/// written to `job_attempts` like any other.
+pub async fn reap_stale_locks(pg: &PgPool, timeouts: &LockTimeouts) -> anyhow::Result<ReapReport> {
+    let (kinds, secs): (Vec<String>, Vec<i64>) = timeouts
+        .per_kind
+        .iter()
+        .map(|(kind, timeout)| (kind.clone(), timeout.as_secs() as i64))
+        .unzip();
+
+    let mut tx = pg.begin().await?;
+    let reaped = sqlx::query!(
+        "WITH stale AS (
+           SELECT j.id, j.locked_by, j.locked_at
+           FROM job_queue j
+           LEFT JOIN UNNEST($1::text[], $2::bigint[]) AS t(kind, secs) ON t.kind = j.kind
+           WHERE j.status = 'running'
+             AND j.locked_at < NOW() - make_interval(secs => COALESCE(t.secs, $3)::double precision)
+           FOR UPDATE OF j SKIP LOCKED
+         )
+         UPDATE job_queue j
+         SET status = CASE
+               WHEN j.cancel_requested_at IS NOT NULL THEN 'cancelled'
+               WHEN j.attempts >= j.max_attempts THEN 'failed'
+               ELSE 'queued'
+             END,
+             error_message = 'lock expired: worker ' || COALESCE(j.locked_by, 'unknown') || ' stopped heartbeating',
+             completed_at = CASE
+               WHEN j.cancel_requested_at IS NOT NULL OR j.attempts >= j.max_attempts THEN NOW()
+             END,
+             locked_by = NULL,
+             locked_at = NULL,
+             run_after = NOW()
+         FROM stale
+         WHERE j.id = stale.id
+         RETURNING j.id, j.kind, j.status AS \"status!\", j.attempts,
+                   stale.locked_by AS \"locked_by?\", stale.locked_at AS \"locked_at!\"",
+        &kinds,
+        &secs,
+        timeouts.default.as_secs() as i64
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut report = ReapReport::default();
+    for row in &reaped {
+        let outcome = if row.status == "failed" {
+            report.failed += 1;
+            report.cancelled += on_job_failed(&mut *tx, &row.id).await?;
+            AttemptOutcome::Failed
+        } else if row.status == "cancelled" {
+            report.cancelled += 1 + on_job_failed(&mut *tx, &row.id).await?;
+            AttemptOutcome::Cancelled
+        } else {
+            report.requeued += 1;
+            AttemptOutcome::Retrying
+        };
+        // The dead worker never recorded its attempt. Its start time is not
+        // kept, so the last heartbeat stands in for it.
+        let worker_id = row.locked_by.as_deref().unwrap_or("unknown");
+        let error = JobError::retryable(anyhow::anyhow!(
+            "lock expired: worker {} stopped heartbeating",
+            worker_id
+        ));
+        record_attempt(
+            &mut *tx,
+            &row.id,
+            row.attempts,
+            worker_id,
+            row.locked_at,
+            outcome,
+            Some(&error),
+        )
+        .await?;
+        JOBS_REAPED
+            .with_label_values(&[&row.kind, &row.status])
+            .inc();
+    }
+    tx.commit().await?;
+
+    REAPER_RUNS.inc();
+    Ok(report)
+}
+
+/// Why a job attempt failed, and whether another attempt can help
+#[derive(Debug, Error)]
+pub enum JobError {
+    /// Transient: network, RPC, database contention. Retry with backoff.
+    #[error("{0:#}")]
+    Retryable(anyhow::Error),
+    /// Permanent: bad payload, unknown kind, invariant violation. Never retry.
+    #[error("{0:#}")]
+    Fatal(anyhow::Error),
+    /// The handler stopped because cancellation was requested
+    #[error("job cancelled")]
+    Cancelled,
+}
+
+impl JobError {
+    pub fn retryable(e: impl Into<anyhow::Error>) -> Self {
+        JobError::Retryable(e.into())
+    }
+
+    pub fn fatal(e: impl Into<anyhow::Error>) -> Self {
+        JobError::Fatal(e.into())
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, JobError::Fatal(_))
+    }
+
+    pub fn class(&self) -> &'static str {
+        match self {
+            JobError::Retryable(_) => "retryable",
+            JobError::Fatal(_) => "fatal",
+            JobError::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Handlers return `anyhow::Result`; classify what they return. An explicit
+/// `JobError` anywhere in the chain wins, payload decoding errors are fatal,
+/// RPC errors follow the node's verdict, everything else is retried.
+impl From<anyhow::Error> for JobError {
+    fn from(e: anyhow::Error) -> Self {
+        if let Some(job_err) = e.downcast_ref::<JobError>() {
+            return match job_err {
+                JobError::Fatal(_) => JobError::Fatal(e),
+                JobError::Retryable(_) => JobError::Retryable(e),
+                JobError::Cancelled => JobError::Cancelled,
+            };
+        }
+        if e.downcast_ref::<serde_json::Error>().is_some() {
+            return JobError::Fatal(e);
+        }
+        if let Some(rpc) = e.downcast_ref::<crate::rpc::RpcError>() {
+            return match rpc {
+                crate::rpc::RpcError::Rpc { .. } if !rpc.is_retryable() => JobError::Fatal(e),
+                _ => JobError::Retryable(e),
+            };
+        }
+        JobError::Retryable(e)
+    }
+}
+
+/// Exponential backoff with jitter for one job kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl RetryPolicy {
+    pub const fn new(base: Duration, max: Duration) -> Self {
+        Self { base, max }
+    }
+
+    /// Policy for a job kind; unknown kinds get the default
+    pub fn for_kind(kind: &str) -> Self {
+        match kind {
+            // RPC-heavy: give providers time to recover
+            "backfill" | "renormalize" => {
+                Self::new(Duration::from_secs(30), Duration::from_secs(1800))
+            }
+            // Cheap and time-sensitive
+            "refresh_prices" | "sync_webhook_addresses" => {
+                Self::new(Duration::from_secs(5), Duration::from_secs(120))
+            }
+            // On-chain: avoid hammering while the cluster is congested
+            "mint_nft" => Self::new(Duration::from_secs(60), Duration::from_secs(3600)),
+            _ => Self::new(Duration::from_secs(15), Duration::from_secs(900)),
+        }
+    }
+
+    /// Delay before retrying after failed attempt number `attempt` (1-based).
+    /// `jitter` in [0, 1) picks a point in the upper half of the window
+    /// ("equal jitter"), so retries spread out but never collapse to zero.
+    pub fn delay(&self, attempt: i32, jitter: f64) -> Duration {
+        let exp = (attempt.max(1) - 1).min(30) as u32;
+        let window = self
+            .base
+            .checked_mul(2u32.saturating_pow(exp))
+            .unwrap_or(self.max)
+            .min(self.max);
+        let half = window / 2;
+        half + half.mul_f64(jitter.clamp(0.0, 1.0))
+    }
+}
+
+/// Outcome of one attempt, stored in `job_attempts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    Succeeded,
+    Retrying,
+    Failed,
+    Cancelled,
+}
+
+impl AttemptOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttemptOutcome::Succeeded => "succeeded",
+            AttemptOutcome::Retrying => "retrying",
+            AttemptOutcome::Failed => "failed",
+            AttemptOutcome::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Append one attempt to the job's history
+pub async fn record_attempt(
+    conn: &mut PgConnection,
+    job_id: &str,
+    attempt: i32,
+    worker_id: &str,
+    started_at: OffsetDateTime,
+    outcome: AttemptOutcome,
+    error: Option<&JobError>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "INSERT INTO job_attempts (job_id, attempt, worker_id, started_at, finished_at, outcome,
+                                   error_class, error_message)
+         VALUES ($1, $2, $3, $4, NOW(), $5, $6, $7)
+         ON CONFLICT (job_id, attempt) DO UPDATE SET
+           worker_id = EXCLUDED.worker_id,
+           started_at = EXCLUDED.started_at,
+           finished_at = EXCLUDED.finished_at,
+           outcome = EXCLUDED.outcome,
+           error_class = EXCLUDED.error_class,
+           error_message = EXCLUDED.error_message",
+        job_id,
+        attempt,
+        worker_id,
+        started_at,
+        outcome.as_str(),
+        error.map(|e| e.class()),
+        error.map(|e| e.to_string())
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(status: &str, is_sink: bool) -> DagNode {
+        DagNode {
+            status: status.to_string(),
+            is_sink,
+        }
+    }
+
+    #[test]
+    fn test_rollup_waits_for_sinks() {
+        let nodes = [
+            node("done", false),
+            node("done", false),
+            node("blocked", true),
+        ];
+        let status = rollup(&nodes).unwrap();
+        assert_eq!(status.status, "running");
+        assert_eq!(status.done, 2);
+        assert_eq!(status.pending, 1);
+
+        let nodes = [node("done", false), node("done", true)];
+        assert_eq!(rollup(&nodes).unwrap().status, "done");
+    }
+
+    #[test]
+    fn test_rollup_fails_when_a_sink_is_cancelled() {
+        let nodes = [
+            node("failed", false),
+            node("done", false),
+            node("cancelled", true),
+        ];
+        let status = rollup(&nodes).unwrap();
+        assert_eq!(status.status, "failed");
+        assert_eq!(status.failed, 2);
+
+        let nodes = [node("cancelled", false), node("cancelled", true)];
+        assert_eq!(rollup(&nodes).unwrap().status, "cancelled");
+    }
+
+    #[test]
+    fn test_rollup_continue_policy_can_still_succeed() {
+        // A failed backfill under `continue` does not fail a finished compute
+        let nodes = [node("failed", false), node("done", true)];
+        assert_eq!(rollup(&nodes).unwrap().status, "done");
+    }
+
+    #[test]
+    fn test_retry_delay_grows_and_caps() {
+        let policy = RetryPolicy::new(Duration::from_secs(10), Duration::from_secs(60));
+        assert_eq!(policy.delay(1, 0.0), Duration::from_secs(5));
+        assert_eq!(policy.delay(1, 1.0), Duration::from_secs(10));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_secs(10));
+        assert_eq!(policy.delay(3, 0.5), Duration::from_secs(30));
+        // Capped at `max`, and huge attempt counts do not overflow
+        assert_eq!(policy.delay(10, 1.0), Duration::from_secs(60));
+        assert_eq!(policy.delay(i32::MAX, 1.0), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_job_error_classification() {
+        let bad_payload = serde_json::from_str::<u32>("nope").unwrap_err();
+        assert!(JobError::from(anyhow::Error::new(bad_payload)).is_fatal());
+
+        let explicit = anyhow::Error::new(JobError::fatal(anyhow::anyhow!("unknown kind")));
+        assert!(JobError::from(explicit.context("dispatch")).is_fatal());
+
+        let transient = anyhow::anyhow!("connection reset");
+        assert_eq!(JobError::from(transient).class(), "retryable");
+
+        let rpc = crate::rpc::RpcError::Rpc {
+            code: -32602,
+            message: "invalid params".into(),
+        };
+        assert!(JobError::from(anyhow::Error::new(rpc)).is_fatal());
+
+        let token = CancelToken::new();
+        assert!(token.check().is_ok());
+        token.clone().cancel();
+        let stopped = anyhow::Error::new(token.check().unwrap_err()).context("backfill");
+        assert_eq!(JobError::from(stopped).class(), "cancelled");
+    }
+
+    #[test]
+    fn test_lock_timeouts() {
+        let timeouts = LockTimeouts::parse(
+            Duration::from_secs(300),
+            "backfill=1800, renormalize = 900,junk,compute=x",
+        );
+        assert_eq!(timeouts.for_kind("backfill"), Duration::from_secs(1800));
+        assert_eq!(timeouts.for_kind("renormalize"), Duration::from_secs(900));
+        assert_eq!(timeouts.for_kind("compute"), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_rollup_queued_and_empty() {
+        let nodes = [node("queued", false), node("blocked", true)];
+        assert_eq!(rollup(&nodes).unwrap().status, "queued");
+        assert!(rollup(&[]).is_none());
+    }
+}
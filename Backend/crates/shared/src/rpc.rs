@@ -0,0 +1,708 @@
+//! Pooled Solana JSON-RPC client.
+//!
+//! `RpcPool` spreads calls over every configured endpoint, keeps each one
+//! inside its own concurrency and requests-per-second limits, scores
+//! endpoints by recent health and fails over on 429/5xx/transport errors.
+//! Every call is charged to a per-method credit counter, and callers that
+//! run on behalf of a plan can pass an `RpcBudget` so the plan's
+//! `max_signatures_per_run` / `max_enhanced_tx_per_run` limits are enforced
+//! in one place.
+
+use once_cell::sync::Lazy;
+use prometheus::{IntCounterVec, Opts};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+
+static RPC_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "rpc_requests_total",
+            "Solana RPC requests by endpoint and outcome",
+        ),
+        &["endpoint", "method", "outcome"],
+    )
+    .expect("metric");
+    crate::metrics::REGISTRY
+        .register(Box::new(counter.clone()))
+        .ok();
+    counter
+});
+
+static RPC_CREDITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new("rpc_credits_total", "Solana RPC credits consumed by method"),
+        &["endpoint", "method"],
+    )
+    .expect("metric");
+    crate::metrics::REGISTRY
+        .register(Box::new(counter.clone()))
+        .ok();
+    counter
+});
+
+/// JSON-RPC error codes that indicate a transient node-side condition
+/// (node behind, slot skipped/unavailable) rather than a bad request.
+const RETRYABLE_RPC_CODES: [i64; 3] = [-32004, -32005, -32014];
+
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error("no RPC endpoints configured")]
+    NoEndpoints,
+    #[error("HTTP {status} from {endpoint}")]
+    Http {
+        endpoint: String,
+        status: u16,
+        retry_after: Option<Duration>,
+    },
+    #[error("transport error from {endpoint}: {message}")]
+    Transport { endpoint: String, message: String },
+    #[error("RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    #[error("invalid RPC response: {0}")]
+    InvalidResponse(String),
+    #[error("plan budget exhausted: {0}")]
+    BudgetExhausted(&'static str),
+    #[error("all {attempts} attempts failed, last error: {last}")]
+    Exhausted { attempts: u32, last: Box<RpcError> },
+}
+
+impl RpcError {
+    /// Whether another endpoint (or the same one later) may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RpcError::Http { status, .. } => *status == 429 || *status >= 500,
+            RpcError::Transport { .. } => true,
+            RpcError::Rpc { code, .. } => RETRYABLE_RPC_CODES.contains(code),
+            _ => false,
+        }
+    }
+}
+
+pub type RpcResult<T> = Result<T, RpcError>;
+
+/// Static configuration for a single RPC endpoint
+#[derive(Debug, Clone)]
+pub struct RpcEndpointConfig {
+    pub url: String,
+    pub max_concurrency: usize,
+    pub requests_per_second: u32,
+}
+
+impl RpcEndpointConfig {
+    pub fn new(url: impl Into<String>, max_concurrency: usize, requests_per_second: u32) -> Self {
+        Self {
+            url: url.into(),
+            max_concurrency: max_concurrency.max(1),
+            requests_per_second: requests_per_second.max(1),
+        }
+    }
+
+    /// Label used in logs and metrics; never includes the query string so
+    /// API keys passed as `?api-key=` do not leak.
+    pub fn label(&self) -> String {
+        url::Url::parse(&self.url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// Token bucket refilled at `rate` tokens per second
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, or return how long to wait until one is available.
+    fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+/// Rolling health of an endpoint used to rank failover candidates
+#[derive(Debug, Clone)]
+pub struct EndpointHealth {
+    /// Exponentially weighted success rate in `[0, 1]`
+    pub score: f64,
+    /// Exponentially weighted latency of successful calls
+    pub latency_ms: f64,
+    pub consecutive_failures: u32,
+    pub cooldown_until: Option<Instant>,
+}
+
+impl Default for EndpointHealth {
+    fn default() -> Self {
+        Self {
+            score: 1.0,
+            latency_ms: 0.0,
+            consecutive_failures: 0,
+            cooldown_until: None,
+        }
+    }
+}
+
+impl EndpointHealth {
+    const ALPHA: f64 = 0.2;
+    const MAX_COOLDOWN: Duration = Duration::from_secs(30);
+
+    fn record_success(&mut self, latency: Duration) {
+        self.score = self.score * (1.0 - Self::ALPHA) + Self::ALPHA;
+        let ms = latency.as_secs_f64() * 1000.0;
+        self.latency_ms = if self.latency_ms == 0.0 {
+            ms
+        } else {
+            self.latency_ms * (1.0 - Self::ALPHA) + ms * Self::ALPHA
+        };
+        self.consecutive_failures = 0;
+        self.cooldown_until = None;
+    }
+
+    fn record_failure(&mut self, now: Instant, retry_after: Option<Duration>) {
+        self.score *= 1.0 - Self::ALPHA;
+        self.consecutive_failures += 1;
+        let backoff = retry_after.unwrap_or_else(|| {
+            Duration::from_millis(250 * 2u64.pow(self.consecutive_failures.min(7)))
+        });
+        self.cooldown_until = Some(now + backoff.min(Self::MAX_COOLDOWN));
+    }
+
+    fn is_cooling_down(&self, now: Instant) -> bool {
+        self.cooldown_until.map(|t| t > now).unwrap_or(false)
+    }
+}
+
+struct Endpoint {
+    config: RpcEndpointConfig,
+    label: String,
+    permits: Arc<Semaphore>,
+    bucket: Mutex<TokenBucket>,
+    health: Mutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    fn new(config: RpcEndpointConfig) -> Self {
+        Self {
+            label: config.label(),
+            permits: Arc::new(Semaphore::new(config.max_concurrency)),
+            bucket: Mutex::new(TokenBucket::new(config.requests_per_second)),
+            health: Mutex::new(EndpointHealth::default()),
+            config,
+        }
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        loop {
+            let wait = match self.bucket.lock().unwrap().try_take(Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => wait,
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn health(&self) -> EndpointHealth {
+        self.health.lock().unwrap().clone()
+    }
+}
+
+/// Per-run limits on RPC usage, usually derived from the caller's plan
+#[derive(Debug)]
+pub struct RpcBudget {
+    max_signatures: u64,
+    max_enhanced_tx: u64,
+    signatures_used: AtomicU64,
+    enhanced_tx_used: AtomicU64,
+}
+
+impl RpcBudget {
+    pub fn new(max_signatures: u64, max_enhanced_tx: u64) -> Self {
+        Self {
+            max_signatures,
+            max_enhanced_tx,
+            signatures_used: AtomicU64::new(0),
+            enhanced_tx_used: AtomicU64::new(0),
+        }
+    }
+
+    /// Budget for system work that is not billed to a plan
+    pub fn unlimited() -> Self {
+        Self::new(u64::MAX, u64::MAX)
+    }
+
+    pub fn signatures_remaining(&self) -> u64 {
+        self.max_signatures
+            .saturating_sub(self.signatures_used.load(Ordering::Relaxed))
+    }
+
+    pub fn enhanced_tx_remaining(&self) -> u64 {
+        self.max_enhanced_tx
+            .saturating_sub(self.enhanced_tx_used.load(Ordering::Relaxed))
+    }
+
+    pub fn signatures_used(&self) -> u64 {
+        self.signatures_used.load(Ordering::Relaxed)
+    }
+
+    pub fn enhanced_tx_used(&self) -> u64 {
+        self.enhanced_tx_used.load(Ordering::Relaxed)
+    }
+
+    fn record_signatures(&self, count: u64) {
+        self.signatures_used.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn reserve_enhanced_tx(&self) -> RpcResult<()> {
+        let prev = self.enhanced_tx_used.fetch_add(1, Ordering::Relaxed);
+        if prev >= self.max_enhanced_tx {
+            self.enhanced_tx_used.fetch_sub(1, Ordering::Relaxed);
+            return Err(RpcError::BudgetExhausted("max_enhanced_tx_per_run"));
+        }
+        Ok(())
+    }
+}
+
+/// Pool of RPC endpoints with failover, rate limiting and credit accounting
+pub struct RpcPool {
+    endpoints: Vec<Endpoint>,
+    http: reqwest::Client,
+    max_attempts: u32,
+    request_timeout: Duration,
+    method_costs: HashMap<String, u64>,
+    credits: Mutex<HashMap<String, u64>>,
+}
+
+impl RpcPool {
+    pub fn new(endpoints: Vec<RpcEndpointConfig>) -> Self {
+        let max_attempts = (endpoints.len() as u32 * 2).max(3);
+        Self {
+            endpoints: endpoints.into_iter().map(Endpoint::new).collect(),
+            http: reqwest::Client::new(),
+            max_attempts,
+            request_timeout: Duration::from_secs(30),
+            method_costs: HashMap::new(),
+            credits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Build a pool from `RPC_PRIMARY` and the optional `RPC_SECONDARY`
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut endpoints = vec![RpcEndpointConfig::new(
+            &config.rpc_primary,
+            config.rpc_max_concurrency,
+            config.rpc_primary_rps,
+        )];
+        if let Some(secondary) = config.rpc_secondary.as_deref().filter(|s| !s.is_empty()) {
+            endpoints.push(RpcEndpointConfig::new(
+                secondary,
+                config.rpc_max_concurrency,
+                config.rpc_secondary_rps,
+            ));
+        }
+        Self::new(endpoints)
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Override the credit cost of a method (defaults to 1 credit per call)
+    pub fn with_method_cost(mut self, method: &str, credits: u64) -> Self {
+        self.method_costs.insert(method.to_string(), credits);
+        self
+    }
+
+    /// Snapshot of endpoint health keyed by endpoint label
+    pub fn health_snapshot(&self) -> Vec<(String, EndpointHealth)> {
+        self.endpoints
+            .iter()
+            .map(|e| (e.label.clone(), e.health()))
+            .collect()
+    }
+
+    /// Credits consumed so far, keyed by RPC method
+    pub fn credit_usage(&self) -> HashMap<String, u64> {
+        self.credits.lock().unwrap().clone()
+    }
+
+    /// Perform a JSON-RPC call and return its `result` field
+    pub async fn call(&self, method: &str, params: Value) -> RpcResult<Value> {
+        if self.endpoints.is_empty() {
+            return Err(RpcError::NoEndpoints);
+        }
+
+        let mut tried: Vec<usize> = Vec::new();
+        let mut last_error = None;
+
+        for attempt in 1..=self.max_attempts {
+            let now = Instant::now();
+            let ranked = self.ranked_endpoints(now, &tried);
+            let idx = match ranked.first() {
+                Some(&idx) => idx,
+                None => {
+                    // Every endpoint has been tried; start another round.
+                    tried.clear();
+                    self.ranked_endpoints(now, &tried)[0]
+                }
+            };
+            tried.push(idx);
+            let endpoint = &self.endpoints[idx];
+
+            // Respect cooldown when we have nowhere else to go.
+            let cooldown = endpoint.health().cooldown_until;
+            if let Some(until) = cooldown.filter(|t| *t > now) {
+                tokio::time::sleep(until - now).await;
+            }
+
+            match self.call_endpoint(endpoint, method, &params).await {
+                Ok(result) => return Ok(result),
+                Err(e) if e.is_retryable() => {
+                    warn!(
+                        endpoint = %endpoint.label,
+                        method,
+                        attempt,
+                        error = %e,
+                        "RPC call failed, failing over"
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(RpcError::Exhausted {
+            attempts: self.max_attempts,
+            last: Box::new(last_error.unwrap_or(RpcError::NoEndpoints)),
+        })
+    }
+
+    /// `getSignaturesForAddress`, clamped to the remaining signature budget.
+    /// Fails with `RpcError::BudgetExhausted` once the budget is spent.
+    pub async fn get_signatures_for_address(
+        &self,
+        address: &str,
+        before: Option<&str>,
+        limit: usize,
+        budget: &RpcBudget,
+    ) -> RpcResult<Vec<Value>> {
+        let remaining = budget.signatures_remaining();
+        if remaining == 0 {
+            return Err(RpcError::BudgetExhausted("max_signatures_per_run"));
+        }
+        let limit = (limit as u64).min(remaining).min(1000);
+
+        let mut config = json!({ "limit": limit });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+
+        let result = self
+            .call("getSignaturesForAddress", json!([address, config]))
+            .await?;
+        let page = result
+            .as_array()
+            .cloned()
+            .ok_or_else(|| RpcError::InvalidResponse("expected array".to_string()))?;
+        budget.record_signatures(page.len() as u64);
+        Ok(page)
+    }
+
+    /// `getTransaction` with `jsonParsed` encoding. Each call counts against
+    /// the enhanced-transaction budget whether or not the node has the tx.
+    pub async fn get_transaction(
+        &self,
+        signature: &str,
+        budget: &RpcBudget,
+    ) -> RpcResult<Option<Value>> {
+        budget.reserve_enhanced_tx()?;
+        let result = self
+            .call(
+                "getTransaction",
+                json!([
+                    signature,
+                    {
+                        "encoding": "jsonParsed",
+                        "commitment": "confirmed",
+                        "maxSupportedTransactionVersion": 0
+                    }
+                ]),
+            )
+            .await?;
+        Ok(if result.is_null() { None } else { Some(result) })
+    }
+
+    /// `getBalance` in lamports
+    pub async fn get_balance(&self, address: &str) -> RpcResult<u64> {
+        let result = self.call("getBalance", json!([address])).await?;
+        result
+            .get("value")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| RpcError::InvalidResponse("missing balance value".to_string()))
+    }
+
+    /// Endpoint indices ordered best-first: endpoints not cooling down come
+    /// before those that are, then by health score, then by free capacity.
+    fn ranked_endpoints(&self, now: Instant, exclude: &[usize]) -> Vec<usize> {
+        let mut ranked: Vec<(usize, EndpointHealth, usize)> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !exclude.contains(idx))
+            .map(|(idx, e)| (idx, e.health(), e.permits.available_permits()))
+            .collect();
+
+        ranked.sort_by(|a, b| {
+            a.1.is_cooling_down(now)
+                .cmp(&b.1.is_cooling_down(now))
+                .then_with(|| b.1.score.total_cmp(&a.1.score))
+                .then_with(|| b.2.cmp(&a.2))
+        });
+
+        ranked.into_iter().map(|(idx, _, _)| idx).collect()
+    }
+
+    async fn call_endpoint(
+        &self,
+        endpoint: &Endpoint,
+        method: &str,
+        params: &Value,
+    ) -> RpcResult<Value> {
+        let _permit = endpoint
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        endpoint.wait_for_rate_limit().await;
+
+        self.charge_credits(&endpoint.label, method);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        let started = Instant::now();
+        let outcome = self.send(endpoint, &request).await;
+
+        let mut health = endpoint.health.lock().unwrap();
+        match &outcome {
+            Ok(_) => {
+                health.record_success(started.elapsed());
+                RPC_REQUESTS
+                    .with_label_values(&[&endpoint.label, method, "ok"])
+                    .inc();
+            }
+            Err(e) => {
+                let retry_after = match e {
+                    RpcError::Http { retry_after, .. } => *retry_after,
+                    _ => None,
+                };
+                if e.is_retryable() {
+                    health.record_failure(Instant::now(), retry_after);
+                }
+                RPC_REQUESTS
+                    .with_label_values(&[&endpoint.label, method, "error"])
+                    .inc();
+            }
+        }
+        debug!(
+            endpoint = %endpoint.label,
+            method,
+            score = health.score,
+            latency_ms = started.elapsed().as_millis() as u64,
+            "RPC call finished"
+        );
+
+        outcome
+    }
+
+    async fn send(&self, endpoint: &Endpoint, request: &Value) -> RpcResult<Value> {
+        let response = self
+            .http
+            .post(&endpoint.config.url)
+            .json(request)
+            .timeout(self.request_timeout)
+            .send()
+            .await
+            .map_err(|e| RpcError::Transport {
+                endpoint: endpoint.label.clone(),
+                message: e.to_string(),
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs);
+            return Err(RpcError::Http {
+                endpoint: endpoint.label.clone(),
+                status: status.as_u16(),
+                retry_after,
+            });
+        }
+
+        let body: Value = response.json().await.map_err(|e| RpcError::Transport {
+            endpoint: endpoint.label.clone(),
+            message: e.to_string(),
+        })?;
+
+        parse_rpc_response(body)
+    }
+
+    fn charge_credits(&self, endpoint_label: &str, method: &str) {
+        let cost = self.method_costs.get(method).copied().unwrap_or(1);
+        *self
+            .credits
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_insert(0) += cost;
+        RPC_CREDITS
+            .with_label_values(&[endpoint_label, method])
+            .inc_by(cost);
+    }
+}
+
+/// Extract `result` from a JSON-RPC response body, mapping `error` objects
+fn parse_rpc_response(mut body: Value) -> RpcResult<Value> {
+    if let Some(error) = body.get("error").filter(|e| !e.is_null()) {
+        return Err(RpcError::Rpc {
+            code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+            message: error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    body.get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| RpcError::InvalidResponse("missing result".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retryable_classification() {
+        let http = |status| RpcError::Http {
+            endpoint: "x".into(),
+            status,
+            retry_after: None,
+        };
+        assert!(http(429).is_retryable());
+        assert!(http(503).is_retryable());
+        assert!(!http(400).is_retryable());
+        assert!(RpcError::Rpc {
+            code: -32005,
+            message: String::new()
+        }
+        .is_retryable());
+        assert!(!RpcError::Rpc {
+            code: -32602,
+            message: String::new()
+        }
+        .is_retryable());
+        assert!(!RpcError::BudgetExhausted("max_enhanced_tx_per_run").is_retryable());
+    }
+
+    #[test]
+    fn test_budget_enforcement() {
+        let budget = RpcBudget::new(10, 2);
+        assert!(budget.reserve_enhanced_tx().is_ok());
+        assert!(budget.reserve_enhanced_tx().is_ok());
+        assert!(matches!(
+            budget.reserve_enhanced_tx(),
+            Err(RpcError::BudgetExhausted(_))
+        ));
+        assert_eq!(budget.enhanced_tx_used(), 2);
+
+        budget.record_signatures(7);
+        assert_eq!(budget.signatures_remaining(), 3);
+    }
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2);
+        assert!(bucket.try_take(start).is_ok());
+        assert!(bucket.try_take(start).is_ok());
+        assert!(bucket.try_take(start).is_err());
+        assert!(bucket.try_take(start + Duration::from_millis(600)).is_ok());
+    }
+
+    #[test]
+    fn test_ranking_prefers_healthy_endpoints() {
+        let pool = RpcPool::new(vec![
+            RpcEndpointConfig::new("https://a.example.com/?api-key=secret", 4, 10),
+            RpcEndpointConfig::new("https://b.example.com", 4, 10),
+        ]);
+        let now = Instant::now();
+        pool.endpoints[0]
+            .health
+            .lock()
+            .unwrap()
+            .record_failure(now, Some(Duration::from_secs(5)));
+
+        assert_eq!(pool.ranked_endpoints(now, &[]), vec![1, 0]);
+        assert_eq!(pool.endpoints[0].label, "a.example.com");
+    }
+
+    #[test]
+    fn test_parse_rpc_response() {
+        let ok = parse_rpc_response(json!({"jsonrpc": "2.0", "result": [1, 2], "id": 1}));
+        assert_eq!(ok.unwrap(), json!([1, 2]));
+
+        let err = parse_rpc_response(json!({
+            "jsonrpc": "2.0",
+            "error": {"code": -32005, "message": "Node is behind"},
+            "id": 1
+        }));
+        assert!(matches!(err, Err(RpcError::Rpc { code: -32005, .. })));
+    }
+}
@@ -1,10 +1,63 @@
 use anyhow::Result;
-use std::{fs, path::PathBuf};
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+};
+use tokio::io::AsyncWriteExt;
 use url::Url;
 
+/// Smallest part S3-compatible stores accept, except for the last one
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// An object's body, read in chunks
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
 #[async_trait::async_trait]
 pub trait ObjectStore: Send + Sync {
     async fn put(&self, key: &str, bytes: &[u8]) -> Result<String>;
+
+    /// Start an upload written part by part, for objects too large to hold
+    /// in memory. Nothing is visible under `key` until it completes.
+    async fn put_multipart(&self, key: &str) -> Result<Box<dyn MultipartUpload>>;
+
+    /// Read an object back; errors if the key does not exist
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Read an object back without buffering it; errors if the key does
+    /// not exist
+    async fn get_stream(&self, key: &str) -> Result<ByteStream>;
+
+    /// List keys starting with `prefix`, sorted lexicographically. With
+    /// `start_after`, only keys strictly after it are returned; with
+    /// `limit`, at most that many, so large prefixes can be paged through.
+    async fn list(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<String>>;
+
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Remove an object; removing a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// An object being uploaded in parts
+#[async_trait::async_trait]
+pub trait MultipartUpload: Send {
+    /// Append the next part. Every part but the last must be at least
+    /// `MIN_PART_SIZE` bytes.
+    async fn put_part(&mut self, bytes: Vec<u8>) -> Result<()>;
+
+    /// Publish the object from the parts written so far
+    async fn complete(self: Box<Self>) -> Result<()>;
+
+    /// Discard the parts written so far
+    async fn abort(self: Box<Self>) -> Result<()>;
 }
 
 pub struct FileStore {
@@ -23,6 +76,18 @@ impl FileStore {
         fs::create_dir_all(&p).ok();
         Ok(Self { base: p })
     }
+
+    /// Resolve a key to a path under `base`, rejecting keys that escape it
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        let rel = Path::new(key);
+        if rel
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            anyhow::bail!("invalid object key: {}", key)
+        }
+        Ok(self.base.join(rel))
+    }
 }
 
 #[async_trait::async_trait]
@@ -36,6 +101,136 @@ impl ObjectStore for FileStore {
         tokio::fs::write(&path, bytes).await?;
         Ok(format!("{}", key))
     }
+
+    async fn put_multipart(&self, key: &str) -> Result<Box<dyn MultipartUpload>> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let file = tokio::fs::File::create(&partial).await?;
+        Ok(Box::new(FileUpload {
+            file,
+            partial,
+            path,
+        }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path_for(key)?;
+        Ok(tokio::fs::read(&path).await?)
+    }
+
+    async fn get_stream(&self, key: &str) -> Result<ByteStream> {
+        let path = self.path_for(key)?;
+        let file = tokio::fs::File::open(&path).await?;
+        Ok(Box::pin(
+            tokio_util::io::ReaderStream::new(file).map_err(anyhow::Error::from),
+        ))
+    }
+
+    async fn list(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<String>> {
+        // Walk from the deepest directory fully named by the prefix, then
+        // filter on the full prefix so partial file names also match.
+        let dir_part = match prefix.rfind('/') {
+            Some(idx) => &prefix[..idx],
+            None => "",
+        };
+        let start = self.path_for(dir_part)?;
+
+        let mut keys = Vec::new();
+        let mut stack = vec![start];
+        while let Some(dir) = stack.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let key = path
+                    .strip_prefix(&self.base)?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if entry.file_type().await?.is_dir() {
+                    // Every key under a directory that sorts before
+                    // `start_after` without being a prefix of it is skipped
+                    let dir = format!("{}/", key);
+                    if start_after
+                        .is_some_and(|after| dir.as_str() < after && !after.starts_with(&dir))
+                    {
+                        continue;
+                    }
+                    stack.push(path);
+                    continue;
+                }
+                if key.starts_with(prefix)
+                    && !start_after.is_some_and(|after| key.as_str() <= after)
+                {
+                    keys.push(key);
+                }
+            }
+        }
+
+        keys.sort();
+        if let Some(limit) = limit {
+            keys.truncate(limit);
+        }
+        Ok(keys)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let path = self.path_for(key)?;
+        Ok(tokio::fs::metadata(&path)
+            .await
+            .map(|m| m.is_file())
+            .unwrap_or(false))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parts are appended to a sibling `.partial` file that is renamed into
+/// place on completion
+struct FileUpload {
+    file: tokio::fs::File,
+    partial: PathBuf,
+    path: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl MultipartUpload for FileUpload {
+    async fn put_part(&mut self, bytes: Vec<u8>) -> Result<()> {
+        self.file.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    async fn complete(mut self: Box<Self>) -> Result<()> {
+        self.file.flush().await?;
+        tokio::fs::rename(&self.partial, &self.path).await?;
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) -> Result<()> {
+        drop(self.file);
+        tokio::fs::remove_file(&self.partial).await?;
+        Ok(())
+    }
 }
 
 #[cfg(feature = "with-r2")]
@@ -131,17 +326,31 @@ impl R2Store {
 
     /// Get public URL for an object
     pub fn get_public_url(&self, key: &str) -> String {
-        let full_key = if self.prefix.is_empty() {
+        format!(
+            "{}/{}",
+            self.public_url_base.trim_end_matches('/'),
+            self.full_key(key)
+        )
+    }
+
+    fn full_key(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
             key.to_string()
         } else {
             format!("{}/{}", self.prefix, key)
-        };
+        }
+    }
 
-        format!(
-            "{}/{}",
-            self.public_url_base.trim_end_matches('/'),
+    /// Strip the store prefix from a bucket key
+    fn relative_key<'k>(&self, full_key: &'k str) -> &'k str {
+        if self.prefix.is_empty() {
             full_key
-        )
+        } else {
+            full_key
+                .strip_prefix(&self.prefix)
+                .map(|k| k.trim_start_matches('/'))
+                .unwrap_or(full_key)
+        }
     }
 }
 
@@ -149,11 +358,7 @@ impl R2Store {
 #[async_trait::async_trait]
 impl ObjectStore for R2Store {
     async fn put(&self, key: &str, bytes: &[u8]) -> Result<String> {
-        let full_key = if self.prefix.is_empty() {
-            key.to_string()
-        } else {
-            format!("{}/{}", self.prefix, key)
-        };
+        let full_key = self.full_key(key);
 
         self.client
             .put_object()
@@ -168,6 +373,230 @@ impl ObjectStore for R2Store {
         // Return the public URL instead of just the key
         Ok(self.get_public_url(key))
     }
+
+    async fn put_multipart(&self, key: &str) -> Result<Box<dyn MultipartUpload>> {
+        let full_key = self.full_key(key);
+        let output = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&full_key)
+            .content_type("application/octet-stream")
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to start R2 upload of {}: {}", key, e))?;
+        let upload_id = output
+            .upload_id()
+            .ok_or_else(|| anyhow::anyhow!("R2 returned no upload id for {}", key))?
+            .to_string();
+
+        Ok(Box::new(R2Upload {
+            client: self.client.clone(),
+            bucket: self.bucket.clone(),
+            key: full_key,
+            upload_id,
+            parts: Vec::new(),
+        }))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {} from R2: {}", key, e))?;
+
+        let body = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read R2 body for {}: {}", key, e))?;
+        Ok(body.into_bytes().to_vec())
+    }
+
+    async fn get_stream(&self, key: &str) -> Result<ByteStream> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {} from R2: {}", key, e))?;
+
+        let key = key.to_string();
+        Ok(Box::pin(futures::stream::try_unfold(
+            output.body,
+            move |mut body| {
+                let key = key.clone();
+                async move {
+                    let chunk = body.try_next().await.map_err(|e| {
+                        anyhow::anyhow!("Failed to read R2 body for {}: {}", key, e)
+                    })?;
+                    Ok(chunk.map(|chunk| (chunk, body)))
+                }
+            },
+        )))
+    }
+
+    async fn list(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            // R2 returns at most 1000 keys per page
+            let remaining = limit.map_or(1000, |limit| (limit - keys.len()).min(1000));
+            if remaining == 0 {
+                break;
+            }
+            let output = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(self.full_key(prefix))
+                .set_start_after(start_after.map(|after| self.full_key(after)))
+                .set_continuation_token(continuation.take())
+                .max_keys(remaining as i32)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to list R2 prefix {}: {}", prefix, e))?;
+
+            for object in output.contents() {
+                if let Some(key) = object.key() {
+                    keys.push(self.relative_key(key).to_string());
+                }
+            }
+
+            match output.next_continuation_token() {
+                Some(token) if output.is_truncated().unwrap_or(false) => {
+                    continuation = Some(token.to_string());
+                }
+                _ => break,
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let not_found = e
+                    .as_service_error()
+                    .map(|se| se.is_not_found())
+                    .unwrap_or(false);
+                if not_found {
+                    Ok(false)
+                } else {
+                    Err(anyhow::anyhow!("Failed to stat {} in R2: {}", key, e))
+                }
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.full_key(key))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to delete {} from R2: {}", key, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "with-r2")]
+struct R2Upload {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    parts: Vec<aws_sdk_s3::types::CompletedPart>,
+}
+
+#[cfg(feature = "with-r2")]
+#[async_trait::async_trait]
+impl MultipartUpload for R2Upload {
+    async fn put_part(&mut self, bytes: Vec<u8>) -> Result<()> {
+        let part_number = self.parts.len() as i32 + 1;
+        let output = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .part_number(part_number)
+            .body(bytes.into())
+            .send()
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to upload part {} of {}: {}",
+                    part_number,
+                    self.key,
+                    e
+                )
+            })?;
+
+        self.parts.push(
+            aws_sdk_s3::types::CompletedPart::builder()
+                .set_e_tag(output.e_tag().map(str::to_string))
+                .part_number(part_number)
+                .build(),
+        );
+        Ok(())
+    }
+
+    async fn complete(mut self: Box<Self>) -> Result<()> {
+        // An upload needs at least one part, even for an empty object
+        if self.parts.is_empty() {
+            self.put_part(Vec::new()).await?;
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .multipart_upload(
+                aws_sdk_s3::types::CompletedMultipartUpload::builder()
+                    .set_parts(Some(std::mem::take(&mut self.parts)))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to complete upload of {}: {}", self.key, e))?;
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) -> Result<()> {
+        self.client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to abort upload of {}: {}", self.key, e))?;
+        Ok(())
+    }
 }
 
 /// Create object store from configuration
@@ -206,3 +635,108 @@ pub async fn make_store(uri: &str) -> Result<std::sync::Arc<dyn ObjectStore>> {
         anyhow::bail!("unsupported store uri")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (FileStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("oof-store-{}", crate::utils::new_id()));
+        let store = FileStore::new(&format!("file://{}", dir.display())).unwrap();
+        (store, dir)
+    }
+
+    #[tokio::test]
+    async fn test_file_store_roundtrip() {
+        let (store, dir) = temp_store();
+
+        store.put("tx/ab/abc.json.zst", b"one").await.unwrap();
+        store.put("tx/ab/abd.json.zst", b"two").await.unwrap();
+        store.put("tx/cd/cde.json.zst", b"three").await.unwrap();
+
+        assert_eq!(store.get("tx/ab/abd.json.zst").await.unwrap(), b"two");
+        assert!(store.exists("tx/cd/cde.json.zst").await.unwrap());
+        assert!(!store.exists("tx/cd/missing.json.zst").await.unwrap());
+        assert!(store.get("tx/cd/missing.json.zst").await.is_err());
+
+        assert_eq!(
+            store.list("tx/", None, None).await.unwrap(),
+            vec![
+                "tx/ab/abc.json.zst",
+                "tx/ab/abd.json.zst",
+                "tx/cd/cde.json.zst"
+            ]
+        );
+        assert_eq!(
+            store.list("tx/ab/abc", None, None).await.unwrap(),
+            vec!["tx/ab/abc.json.zst"]
+        );
+        assert!(store.list("nothing/", None, None).await.unwrap().is_empty());
+
+        assert_eq!(
+            store.list("tx/", None, Some(2)).await.unwrap(),
+            vec!["tx/ab/abc.json.zst", "tx/ab/abd.json.zst"]
+        );
+        assert_eq!(
+            store
+                .list("tx/", Some("tx/ab/abc.json.zst"), Some(2))
+                .await
+                .unwrap(),
+            vec!["tx/ab/abd.json.zst", "tx/cd/cde.json.zst"]
+        );
+        assert_eq!(
+            store
+                .list("tx/", Some("tx/ab/abd.json.zst"), None)
+                .await
+                .unwrap(),
+            vec!["tx/cd/cde.json.zst"]
+        );
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_file_store_multipart_and_delete() {
+        let (store, dir) = temp_store();
+
+        let mut upload = store
+            .put_multipart("exports/u/e/moments.csv")
+            .await
+            .unwrap();
+        upload.put_part(b"id,kind\n".to_vec()).await.unwrap();
+        upload.put_part(b"1,s2e\n".to_vec()).await.unwrap();
+        assert!(!store.exists("exports/u/e/moments.csv").await.unwrap());
+        upload.complete().await.unwrap();
+
+        let streamed: Vec<Bytes> = store
+            .get_stream("exports/u/e/moments.csv")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(streamed.concat(), b"id,kind\n1,s2e\n");
+
+        let mut aborted = store.put_multipart("exports/u/e/trades.csv").await.unwrap();
+        aborted.put_part(b"partial".to_vec()).await.unwrap();
+        aborted.abort().await.unwrap();
+        assert_eq!(
+            store.list("exports/", None, None).await.unwrap(),
+            vec!["exports/u/e/moments.csv"]
+        );
+
+        store.delete("exports/u/e/moments.csv").await.unwrap();
+        store.delete("exports/u/e/moments.csv").await.unwrap();
+        assert!(!store.exists("exports/u/e/moments.csv").await.unwrap());
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_file_store_rejects_escaping_keys() {
+        let (store, dir) = temp_store();
+        assert!(store.get("../etc/passwd").await.is_err());
+        assert!(store.exists("/etc/passwd").await.is_err());
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,562 @@
+//! Capital gains reports built from `realized_trades`.
+//!
+//! A realized trade records the lots its sell consumed (`lots_json`, FIFO
+//! order), so a report lists one disposal per consumed lot with that lot's
+//! acquisition date, basis and holding period. Trades recorded before lot
+//! breakdowns existed become a single disposal acquired at the start of
+//! their episode. Lots bought without a USD price, and quantity sold beyond
+//! every known lot, have no basis: those disposals are flagged and left out
+//! of basis and gain totals.
+
+use anyhow::Context;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::PgPool;
+use time::{Date, Month, OffsetDateTime};
+
+/// Part of a sell matched against one lot, as stored in
+/// `realized_trades.lots_json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisposedLot {
+    /// `None` for quantity sold beyond every known lot
+    pub lot_id: Option<String>,
+    pub entry_ts: Option<OffsetDateTime>,
+    pub qty: Decimal,
+    /// `None` when the buy had no USD price
+    pub entry_px_usd: Option<Decimal>,
+}
+
+impl DisposedLot {
+    pub fn cost_basis(&self) -> Option<Decimal> {
+        self.entry_px_usd.map(|px| px * self.qty)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldingPeriod {
+    /// Held one year or less
+    Short,
+    /// Held more than one year
+    Long,
+    /// Acquisition date unknown
+    Unknown,
+}
+
+impl HoldingPeriod {
+    /// Long-term once sold after the first anniversary of the acquisition
+    /// date (the holding period starts the day after acquisition)
+    pub fn classify(acquired: Option<OffsetDateTime>, disposed: OffsetDateTime) -> Self {
+        let Some(acquired) = acquired else {
+            return HoldingPeriod::Unknown;
+        };
+        let acquired = acquired.date();
+        let anniversary =
+            Date::from_calendar_date(acquired.year() + 1, acquired.month(), acquired.day())
+                // Feb 29 has no anniversary; the year is up at the end of Feb 28
+                .unwrap_or_else(|_| {
+                    Date::from_calendar_date(acquired.year() + 1, Month::February, 28)
+                        .expect("Feb 28 exists every year")
+                });
+        if disposed.date() > anniversary {
+            HoldingPeriod::Long
+        } else {
+            HoldingPeriod::Short
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HoldingPeriod::Short => "short",
+            HoldingPeriod::Long => "long",
+            HoldingPeriod::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaxReportFormat {
+    /// One row per disposal followed by totals rows
+    Csv,
+    /// Generic Form 8949 layout: short and long-term parts with totals
+    Form8949,
+    Json,
+}
+
+impl TaxReportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csv" => Some(TaxReportFormat::Csv),
+            "form8949" | "8949" => Some(TaxReportFormat::Form8949),
+            "json" => Some(TaxReportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A realized trade as read for a report
+#[derive(Debug, Clone)]
+pub struct TradeRecord {
+    pub exit_id: String,
+    pub wallet: String,
+    pub mint: String,
+    pub symbol: Option<String>,
+    pub ts: OffsetDateTime,
+    pub qty: Decimal,
+    pub exit_px_usd: Decimal,
+    pub realized_pnl_usd: Decimal,
+    pub sig: Option<String>,
+    /// Start of the trade's episode, used when `lots` is missing
+    pub episode_start: Option<OffsetDateTime>,
+    pub lots: Option<Vec<DisposedLot>>,
+}
+
+fn serialize_date<S: Serializer>(ts: &OffsetDateTime, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&iso_date(*ts))
+}
+
+fn serialize_opt_date<S: Serializer>(ts: &Option<OffsetDateTime>, s: S) -> Result<S::Ok, S::Error> {
+    match ts {
+        Some(ts) => serialize_date(ts, s),
+        None => s.serialize_none(),
+    }
+}
+
+fn iso_date(ts: OffsetDateTime) -> String {
+    let d = ts.date();
+    format!("{:04}-{:02}-{:02}", d.year(), u8::from(d.month()), d.day())
+}
+
+fn us_date(ts: OffsetDateTime) -> String {
+    let d = ts.date();
+    format!("{:02}/{:02}/{:04}", u8::from(d.month()), d.day(), d.year())
+}
+
+fn usd(amount: Decimal) -> String {
+    format!("{:.2}", amount.round_dp(2))
+}
+
+/// One disposal row of a report
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxDisposal {
+    pub exit_id: String,
+    pub wallet: String,
+    pub mint: String,
+    pub symbol: Option<String>,
+    pub quantity: Decimal,
+    #[serde(serialize_with = "serialize_opt_date")]
+    pub acquired_at: Option<OffsetDateTime>,
+    #[serde(serialize_with = "serialize_date")]
+    pub disposed_at: OffsetDateTime,
+    pub proceeds_usd: Decimal,
+    pub cost_basis_usd: Option<Decimal>,
+    pub gain_usd: Option<Decimal>,
+    pub holding_period: HoldingPeriod,
+    /// Cost basis unknown because of a missing price
+    pub basis_unknown: bool,
+    pub sig: Option<String>,
+}
+
+impl TaxDisposal {
+    /// Disposals of one realized trade, one per consumed lot
+    pub fn from_trade(trade: &TradeRecord) -> Vec<TaxDisposal> {
+        let row =
+            |quantity: Decimal, acquired_at: Option<OffsetDateTime>, basis: Option<Decimal>| {
+                let proceeds_usd = quantity * trade.exit_px_usd;
+                TaxDisposal {
+                    exit_id: trade.exit_id.clone(),
+                    wallet: trade.wallet.clone(),
+                    mint: trade.mint.clone(),
+                    symbol: trade.symbol.clone(),
+                    quantity,
+                    acquired_at,
+                    disposed_at: trade.ts,
+                    proceeds_usd,
+                    cost_basis_usd: basis,
+                    gain_usd: basis.map(|b| proceeds_usd - b),
+                    holding_period: HoldingPeriod::classify(acquired_at, trade.ts),
+                    basis_unknown: basis.is_none(),
+                    sig: trade.sig.clone(),
+                }
+            };
+
+        match trade.lots.as_deref() {
+            Some(lots) if !lots.is_empty() => lots
+                .iter()
+                .map(|lot| row(lot.qty, lot.entry_ts, lot.cost_basis()))
+                .collect(),
+            // Recorded before lot breakdowns: realized PnL implies the basis
+            _ => {
+                let basis = trade.qty * trade.exit_px_usd - trade.realized_pnl_usd;
+                vec![row(trade.qty, trade.episode_start, Some(basis))]
+            }
+        }
+    }
+}
+
+/// Totals for one holding period. Proceeds cover every disposal; basis and
+/// gain only those with a known basis.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TermTotals {
+    pub disposals: usize,
+    pub proceeds_usd: Decimal,
+    pub cost_basis_usd: Decimal,
+    pub gain_usd: Decimal,
+    pub basis_unknown: usize,
+}
+
+impl TermTotals {
+    fn add(&mut self, d: &TaxDisposal) {
+        self.disposals += 1;
+        self.proceeds_usd += d.proceeds_usd;
+        match (d.cost_basis_usd, d.gain_usd) {
+            (Some(basis), Some(gain)) => {
+                self.cost_basis_usd += basis;
+                self.gain_usd += gain;
+            }
+            _ => self.basis_unknown += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaxTotals {
+    pub short_term: TermTotals,
+    pub long_term: TermTotals,
+    pub unknown_term: TermTotals,
+    pub net_gain_usd: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxReport {
+    pub tax_year: i32,
+    pub wallets: Vec<String>,
+    pub disposals: Vec<TaxDisposal>,
+    pub totals: TaxTotals,
+}
+
+/// `[start, end)` of a calendar tax year in UTC
+pub fn tax_year_bounds(year: i32) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let start = Date::from_calendar_date(year, Month::January, 1).ok()?;
+    let end = Date::from_calendar_date(year + 1, Month::January, 1).ok()?;
+    Some((start.midnight().assume_utc(), end.midnight().assume_utc()))
+}
+
+impl TaxReport {
+    pub fn new(tax_year: i32, wallets: Vec<String>, trades: &[TradeRecord]) -> Self {
+        let disposals: Vec<TaxDisposal> = trades.iter().flat_map(TaxDisposal::from_trade).collect();
+        let mut totals = TaxTotals::default();
+        for d in &disposals {
+            match d.holding_period {
+                HoldingPeriod::Short => totals.short_term.add(d),
+                HoldingPeriod::Long => totals.long_term.add(d),
+                HoldingPeriod::Unknown => totals.unknown_term.add(d),
+            }
+        }
+        totals.net_gain_usd =
+            totals.short_term.gain_usd + totals.long_term.gain_usd + totals.unknown_term.gain_usd;
+        Self {
+            tax_year,
+            wallets,
+            disposals,
+            totals,
+        }
+    }
+
+    fn terms(&self) -> [(HoldingPeriod, &TermTotals); 3] {
+        [
+            (HoldingPeriod::Short, &self.totals.short_term),
+            (HoldingPeriod::Long, &self.totals.long_term),
+            (HoldingPeriod::Unknown, &self.totals.unknown_term),
+        ]
+    }
+
+    /// Flat CSV: a `disposal` row per disposal, then a `total` row per
+    /// holding period
+    pub fn to_csv(&self) -> anyhow::Result<String> {
+        let mut w = csv::Writer::from_writer(Vec::new());
+        w.write_record([
+            "row_type",
+            "wallet",
+            "mint",
+            "symbol",
+            "quantity",
+            "date_acquired",
+            "date_sold",
+            "proceeds_usd",
+            "cost_basis_usd",
+            "gain_usd",
+            "holding_period",
+            "basis_unknown",
+            "exit_id",
+            "sig",
+        ])?;
+        for d in &self.disposals {
+            w.write_record([
+                "disposal".to_string(),
+                d.wallet.clone(),
+                d.mint.clone(),
+                d.symbol.clone().unwrap_or_default(),
+                d.quantity.normalize().to_string(),
+                d.acquired_at.map(iso_date).unwrap_or_default(),
+                iso_date(d.disposed_at),
+                usd(d.proceeds_usd),
+                d.cost_basis_usd.map(usd).unwrap_or_default(),
+                d.gain_usd.map(usd).unwrap_or_default(),
+                d.holding_period.as_str().to_string(),
+                d.basis_unknown.to_string(),
+                d.exit_id.clone(),
+                d.sig.clone().unwrap_or_default(),
+            ])?;
+        }
+        for (term, totals) in self.terms() {
+            if totals.disposals == 0 {
+                continue;
+            }
+            w.write_record([
+                "total".to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                usd(totals.proceeds_usd),
+                usd(totals.cost_basis_usd),
+                usd(totals.gain_usd),
+                term.as_str().to_string(),
+                (totals.basis_unknown > 0).to_string(),
+                String::new(),
+                String::new(),
+            ])?;
+        }
+        Ok(String::from_utf8(w.into_inner()?)?)
+    }
+
+    /// Form 8949 style CSV: one part per holding period with the form's
+    /// (a)–(h) columns and a totals line. Unknown bases are written as
+    /// `UNKNOWN` with no gain.
+    pub fn to_form8949(&self) -> anyhow::Result<String> {
+        let mut w = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(Vec::new());
+        w.write_record([format!(
+            "Capital gains and losses, tax year {}",
+            self.tax_year
+        )])?;
+        w.write_record([format!("Wallets: {}", self.wallets.join(" "))])?;
+
+        for (term, totals) in self.terms() {
+            if totals.disposals == 0 {
+                continue;
+            }
+            w.write_record([""])?;
+            w.write_record([match term {
+                HoldingPeriod::Short => "Part I - Short-term (held one year or less)",
+                HoldingPeriod::Long => "Part II - Long-term (held more than one year)",
+                HoldingPeriod::Unknown => "Holding period unknown (acquisition date missing)",
+            }])?;
+            w.write_record([
+                "(a) Description of property",
+                "(b) Date acquired",
+                "(c) Date sold or disposed of",
+                "(d) Proceeds",
+                "(e) Cost or other basis",
+                "(f) Code(s)",
+                "(g) Amount of adjustment",
+                "(h) Gain or (loss)",
+            ])?;
+            for d in self.disposals.iter().filter(|d| d.holding_period == term) {
+                w.write_record([
+                    format!(
+                        "{} {}",
+                        d.quantity.normalize(),
+                        d.symbol.as_deref().unwrap_or(&d.mint)
+                    ),
+                    d.acquired_at
+                        .map(us_date)
+                        .unwrap_or_else(|| "VARIOUS".to_string()),
+                    us_date(d.disposed_at),
+                    usd(d.proceeds_usd),
+                    d.cost_basis_usd
+                        .map(usd)
+                        .unwrap_or_else(|| "UNKNOWN".to_string()),
+                    String::new(),
+                    String::new(),
+                    d.gain_usd.map(usd).unwrap_or_default(),
+                ])?;
+            }
+            w.write_record([
+                "Totals".to_string(),
+                String::new(),
+                String::new(),
+                usd(totals.proceeds_usd),
+                usd(totals.cost_basis_usd),
+                String::new(),
+                String::new(),
+                usd(totals.gain_usd),
+            ])?;
+            if totals.basis_unknown > 0 {
+                w.write_record([format!(
+                    "{} disposal(s) with unknown cost basis are excluded from basis and gain totals",
+                    totals.basis_unknown
+                )])?;
+            }
+        }
+        Ok(String::from_utf8(w.into_inner()?)?)
+    }
+}
+
+/// Build the report for `wallets` over calendar year `tax_year` (UTC)
+pub async fn load_report(
+    pg: &PgPool,
+    wallets: &[String],
+    tax_year: i32,
+) -> anyhow::Result<TaxReport> {
+    let (start, end) = tax_year_bounds(tax_year)
+        .ok_or_else(|| anyhow::anyhow!("invalid tax year {}", tax_year))?;
+    let rows = sqlx::query!(
+        include_str!("../../../db/queries/select_tax_disposals.sql"),
+        wallets,
+        start,
+        end
+    )
+    .fetch_all(pg)
+    .await?;
+
+    let trades = rows
+        .into_iter()
+        .map(|r| {
+            // A trade that can't be split into lots would silently fall back
+            // to the episode-level holding period, so refuse the report
+            let lots = r
+                .lots_json
+                .map(serde_json::from_value)
+                .transpose()
+                .with_context(|| format!("malformed lots_json on realized trade {}", r.exit_id))?;
+            Ok(TradeRecord {
+                exit_id: r.exit_id,
+                wallet: r.wallet,
+                mint: r.mint,
+                symbol: r.symbol,
+                ts: r.ts,
+                qty: r.qty,
+                exit_px_usd: r.vwavg_exit_px_usd_dec,
+                realized_pnl_usd: r.realized_pnl_usd_dec,
+                sig: r.sig,
+                episode_start: r.episode_start,
+                lots,
+            })
+        })
+        .collect::<anyhow::Result<Vec<TradeRecord>>>()?;
+    Ok(TaxReport::new(tax_year, wallets.to_vec(), &trades))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn trade(lots: Option<Vec<DisposedLot>>) -> TradeRecord {
+        TradeRecord {
+            exit_id: "exit1".to_string(),
+            wallet: "wallet1".to_string(),
+            mint: "mint1".to_string(),
+            symbol: Some("BONK".to_string()),
+            ts: datetime!(2024-06-01 12:00 UTC),
+            qty: Decimal::from(30),
+            exit_px_usd: Decimal::from(2),
+            realized_pnl_usd: Decimal::from(10),
+            sig: None,
+            episode_start: Some(datetime!(2024-01-10 0:00 UTC)),
+            lots,
+        }
+    }
+
+    fn lot(entry_ts: Option<OffsetDateTime>, qty: i64, px: Option<i64>) -> DisposedLot {
+        DisposedLot {
+            lot_id: entry_ts.map(|_| "lot".to_string()),
+            entry_ts,
+            qty: Decimal::from(qty),
+            entry_px_usd: px.map(Decimal::from),
+        }
+    }
+
+    #[test]
+    fn test_holding_period() {
+        let bought = Some(datetime!(2023-01-05 15:00 UTC));
+        assert_eq!(
+            HoldingPeriod::classify(bought, datetime!(2024-01-05 23:00 UTC)),
+            HoldingPeriod::Short
+        );
+        assert_eq!(
+            HoldingPeriod::classify(bought, datetime!(2024-01-06 0:00 UTC)),
+            HoldingPeriod::Long
+        );
+        let leap = Some(datetime!(2024-02-29 0:00 UTC));
+        assert_eq!(
+            HoldingPeriod::classify(leap, datetime!(2025-02-28 0:00 UTC)),
+            HoldingPeriod::Short
+        );
+        assert_eq!(
+            HoldingPeriod::classify(leap, datetime!(2025-03-01 0:00 UTC)),
+            HoldingPeriod::Long
+        );
+        assert_eq!(
+            HoldingPeriod::classify(None, datetime!(2025-03-01 0:00 UTC)),
+            HoldingPeriod::Unknown
+        );
+    }
+
+    #[test]
+    fn test_disposals_split_by_lot() {
+        let t = trade(Some(vec![
+            lot(Some(datetime!(2023-01-01 0:00 UTC)), 10, Some(1)),
+            lot(Some(datetime!(2024-05-01 0:00 UTC)), 15, None),
+            lot(None, 5, None),
+        ]));
+        let report = TaxReport::new(2024, vec!["wallet1".to_string()], &[t]);
+        assert_eq!(report.disposals.len(), 3);
+
+        let long = &report.totals.long_term;
+        assert_eq!(long.disposals, 1);
+        assert_eq!(long.proceeds_usd, Decimal::from(20));
+        assert_eq!(long.gain_usd, Decimal::from(10));
+
+        let short = &report.totals.short_term;
+        assert_eq!(short.disposals, 1);
+        assert_eq!(short.proceeds_usd, Decimal::from(30));
+        assert_eq!(short.basis_unknown, 1);
+        assert_eq!(short.gain_usd, Decimal::ZERO);
+
+        assert_eq!(report.totals.unknown_term.basis_unknown, 1);
+        assert!(report.disposals[2].basis_unknown);
+        assert_eq!(report.totals.net_gain_usd, Decimal::from(10));
+    }
+
+    #[test]
+    fn test_legacy_trade_uses_realized_pnl() {
+        let report = TaxReport::new(2024, vec![], &[trade(None)]);
+        let d = &report.disposals[0];
+        assert_eq!(d.proceeds_usd, Decimal::from(60));
+        assert_eq!(d.cost_basis_usd, Some(Decimal::from(50)));
+        assert_eq!(d.acquired_at, Some(datetime!(2024-01-10 0:00 UTC)));
+        assert_eq!(d.holding_period, HoldingPeriod::Short);
+    }
+
+    #[test]
+    fn test_form8949_layout() {
+        let t = trade(Some(vec![lot(
+            Some(datetime!(2024-05-01 0:00 UTC)),
+            30,
+            None,
+        )]));
+        let out = TaxReport::new(2024, vec!["wallet1".to_string()], &[t])
+            .to_form8949()
+            .unwrap();
+        assert!(out.contains("Part I - Short-term"));
+        assert!(!out.contains("Part II"));
+        assert!(out.contains("30 BONK,05/01/2024,06/01/2024,60.00,UNKNOWN,,,\n"));
+        assert!(out.contains("Totals,,,60.00,0.00,,,0.00\n"));
+    }
+}
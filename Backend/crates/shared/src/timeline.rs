@@ -0,0 +1,478 @@
+//! One chronological feed per wallet.
+//!
+//! [`TimelineQuery`] merges the wallet's own action legs (buys, sells,
+//! swaps, transfers), realized trades, episode opens and closes, and OOF
+//! moments. Each source is read newest-first through its per-wallet index,
+//! cut at the keyset cursor and capped at one page, then the branches are
+//! combined with `UNION ALL` and only the merged page is joined to
+//! `token_facts` and, for unpriced transfers, the last `token_prices`
+//! bucket at or before the event.
+//!
+//! Event ids carry their source as a prefix (`a:`, `t:`, `eo:`, `ec:`,
+//! `m:`) so `(ts, id)` is unique across sources and cursors stay stable.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::constants::solana::SOL_MINT;
+use crate::errors::ApiError;
+
+/// Kind of timeline entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEventType {
+    Buy,
+    Sell,
+    Swap,
+    /// Token or SOL moved in or out without a swap
+    Transfer,
+    /// A realized exit from the lot book
+    Trade,
+    EpisodeOpen,
+    EpisodeClose,
+    Moment,
+}
+
+impl TimelineEventType {
+    pub const ALL: [TimelineEventType; 8] = [
+        TimelineEventType::Buy,
+        TimelineEventType::Sell,
+        TimelineEventType::Swap,
+        TimelineEventType::Transfer,
+        TimelineEventType::Trade,
+        TimelineEventType::EpisodeOpen,
+        TimelineEventType::EpisodeClose,
+        TimelineEventType::Moment,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelineEventType::Buy => "buy",
+            TimelineEventType::Sell => "sell",
+            TimelineEventType::Swap => "swap",
+            TimelineEventType::Transfer => "transfer",
+            TimelineEventType::Trade => "trade",
+            TimelineEventType::EpisodeOpen => "episode_open",
+            TimelineEventType::EpisodeClose => "episode_close",
+            TimelineEventType::Moment => "moment",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+
+    /// `actions.kind` values listed under this type
+    fn action_kinds(&self) -> &'static [&'static str] {
+        match self {
+            TimelineEventType::Buy => &["buy"],
+            TimelineEventType::Sell => &["sell"],
+            TimelineEventType::Swap => &["swap"],
+            TimelineEventType::Transfer => &["transfer", "sol_transfer"],
+            _ => &[],
+        }
+    }
+}
+
+/// Keyset position: resume after the event with this timestamp and id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineCursor {
+    pub ts: OffsetDateTime,
+    pub id: String,
+}
+
+impl TimelineCursor {
+    pub fn after(row: &TimelineRow) -> Self {
+        Self {
+            ts: row.ts,
+            id: row.id.clone(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let ts = self.ts.format(&Rfc3339).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(format!("{}|{}", ts, self.id))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, ApiError> {
+        let invalid = || ApiError::BadRequest("Invalid cursor".to_string());
+        let raw = URL_SAFE_NO_PAD
+            .decode(cursor.trim_end_matches('='))
+            .map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let (ts, id) = raw.split_once('|').ok_or_else(invalid)?;
+        if id.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            ts: OffsetDateTime::parse(ts, &Rfc3339).map_err(|_| invalid())?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// A timeline entry with its token's display facts. Columns a source does
+/// not have are `None`.
+#[derive(Debug, Clone, FromRow)]
+pub struct TimelineRow {
+    pub ts: OffsetDateTime,
+    pub id: String,
+    pub event_type: String,
+    /// SOL transfers are reported under the wrapped SOL mint
+    pub mint: Option<String>,
+    pub sig: Option<String>,
+    /// Token quantity; signed for transfers
+    pub amount: Option<Decimal>,
+    pub price_usd: Option<Decimal>,
+    /// Trade or leg value; basis for episode opens, proceeds for closes and
+    /// the missed amount for moments
+    pub value_usd: Option<Decimal>,
+    pub pnl_usd: Option<Decimal>,
+    /// Source-specific fields, e.g. the action's flags or the moment kind
+    pub detail: Option<serde_json::Value>,
+    pub token_symbol: Option<String>,
+    pub token_logo_url: Option<String>,
+}
+
+/// One page of the timeline and the cursor for the next, if any
+#[derive(Debug, Clone)]
+pub struct TimelinePage {
+    pub rows: Vec<TimelineRow>,
+    pub next_cursor: Option<TimelineCursor>,
+}
+
+/// The tables merged into the timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Actions,
+    Trades,
+    EpisodeOpens,
+    EpisodeCloses,
+    Moments,
+}
+
+impl Source {
+    const ALL: [Source; 5] = [
+        Source::Actions,
+        Source::Trades,
+        Source::EpisodeOpens,
+        Source::EpisodeCloses,
+        Source::Moments,
+    ];
+
+    fn lists(&self, event_type: TimelineEventType) -> bool {
+        match self {
+            Source::Actions => !event_type.action_kinds().is_empty(),
+            Source::Trades => event_type == TimelineEventType::Trade,
+            Source::EpisodeOpens => event_type == TimelineEventType::EpisodeOpen,
+            Source::EpisodeCloses => event_type == TimelineEventType::EpisodeClose,
+            Source::Moments => event_type == TimelineEventType::Moment,
+        }
+    }
+
+    /// Select list and table. For actions this stops before the mint
+    /// column, which needs a bound value; see [`Source::push_mint`].
+    fn select(&self) -> &'static str {
+        match self {
+            Source::Actions => {
+                "SELECT a.ts, 'a:' || a.id AS id, \
+                 CASE WHEN a.kind = 'sol_transfer' THEN 'transfer' ELSE a.kind END AS event_type, "
+            }
+            Source::Trades => {
+                "SELECT rt.ts, 't:' || rt.exit_id AS id, 'trade'::text AS event_type, rt.mint, \
+                 rt.sig, rt.qty AS amount, rt.vwavg_exit_px_usd_dec AS price_usd, \
+                 rt.qty * rt.vwavg_exit_px_usd_dec AS value_usd, \
+                 rt.realized_pnl_usd_dec AS pnl_usd, \
+                 jsonb_strip_nulls(jsonb_build_object('episodeId', rt.episode_id)) AS detail \
+                 FROM realized_trades rt"
+            }
+            Source::EpisodeOpens => {
+                "SELECT ep.start_ts AS ts, 'eo:' || ep.episode_id AS id, \
+                 'episode_open'::text AS event_type, ep.mint, NULL::text AS sig, \
+                 NULL::numeric AS amount, NULL::numeric AS price_usd, \
+                 ep.basis_usd_dec AS value_usd, NULL::numeric AS pnl_usd, \
+                 jsonb_build_object('episodeId', ep.episode_id) AS detail \
+                 FROM episodes ep"
+            }
+            Source::EpisodeCloses => {
+                "SELECT ep.end_ts AS ts, 'ec:' || ep.episode_id AS id, \
+                 'episode_close'::text AS event_type, ep.mint, NULL::text AS sig, \
+                 NULL::numeric AS amount, NULL::numeric AS price_usd, \
+                 ep.basis_usd_dec + ep.realized_pnl_usd_dec AS value_usd, \
+                 ep.realized_pnl_usd_dec AS pnl_usd, \
+                 jsonb_strip_nulls(jsonb_build_object('episodeId', ep.episode_id, \
+                 'openedAt', ep.start_ts, 'roiPctDec', ep.roi_pct_dec::text)) AS detail \
+                 FROM episodes ep"
+            }
+            Source::Moments => {
+                "SELECT m.t_event AS ts, 'm:' || m.id AS id, 'moment'::text AS event_type, \
+                 m.mint, m.sig_ref AS sig, NULL::numeric AS amount, NULL::numeric AS price_usd, \
+                 m.missed_usd_dec AS value_usd, NULL::numeric AS pnl_usd, \
+                 jsonb_strip_nulls(jsonb_build_object('momentId', m.id, 'kind', m.kind, \
+                 'pctDec', m.pct_dec::text, 'severityDec', m.severity_dec::text)) AS detail \
+                 FROM oof_moments m"
+            }
+        }
+    }
+
+    /// `(timestamp, wallet, id)` column expressions
+    fn columns(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Source::Actions => ("a.ts", "a.flags_json->>'owner'", "'a:' || a.id"),
+            Source::Trades => ("rt.ts", "rt.wallet", "'t:' || rt.exit_id"),
+            Source::EpisodeOpens => ("ep.start_ts", "ep.wallet", "'eo:' || ep.episode_id"),
+            Source::EpisodeCloses => ("ep.end_ts", "ep.wallet", "'ec:' || ep.episode_id"),
+            Source::Moments => ("m.t_event", "m.wallet", "'m:' || m.id"),
+        }
+    }
+
+    /// The event's mint; SOL transfers have none in `actions`
+    fn push_mint(&self, qb: &mut QueryBuilder<'static, Postgres>) {
+        match self {
+            Source::Actions => {
+                qb.push("COALESCE(a.mint, ").push_bind(SOL_MINT).push(")");
+            }
+            Source::Trades => {
+                qb.push("rt.mint");
+            }
+            Source::EpisodeOpens | Source::EpisodeCloses => {
+                qb.push("ep.mint");
+            }
+            Source::Moments => {
+                qb.push("m.mint");
+            }
+        }
+    }
+}
+
+/// Action columns after the mint; the owner is dropped from the flags
+/// since it is the wallet itself
+const ACTION_COLUMNS: &str = " AS mint, a.sig, a.amount_dec AS amount, \
+     a.exec_px_usd_dec AS price_usd, ABS(a.amount_dec) * a.exec_px_usd_dec AS value_usd, \
+     NULL::numeric AS pnl_usd, jsonb_strip_nulls(jsonb_build_object('programId', a.program_id, \
+     'route', a.route)) || COALESCE(a.flags_json - 'owner', '{}'::jsonb) AS detail \
+     FROM actions a";
+
+/// Composable timeline listing for one wallet
+#[derive(Debug, Clone)]
+pub struct TimelineQuery {
+    pub wallet: String,
+    /// Empty lists every type
+    pub types: Vec<TimelineEventType>,
+    /// Empty lists every mint
+    pub mints: Vec<String>,
+    pub after: Option<TimelineCursor>,
+    pub limit: usize,
+}
+
+impl TimelineQuery {
+    pub fn new(wallet: impl Into<String>) -> Self {
+        Self {
+            wallet: wallet.into(),
+            types: Vec::new(),
+            mints: Vec::new(),
+            after: None,
+            limit: crate::constants::api::DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    pub fn with_types(mut self, types: Vec<TimelineEventType>) -> Self {
+        self.types = types;
+        self
+    }
+
+    pub fn with_mints(mut self, mints: Vec<String>) -> Self {
+        self.mints = mints;
+        self
+    }
+
+    pub fn with_cursor(mut self, cursor: Option<TimelineCursor>) -> Self {
+        self.after = cursor;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    fn types(&self) -> &[TimelineEventType] {
+        if self.types.is_empty() {
+            &TimelineEventType::ALL
+        } else {
+            &self.types
+        }
+    }
+
+    fn action_kinds(&self) -> Vec<String> {
+        self.types()
+            .iter()
+            .flat_map(|t| t.action_kinds())
+            .map(|k| k.to_string())
+            .collect()
+    }
+
+    /// Build the listing statement; fetches one row past `limit` so the
+    /// caller can tell whether another page exists
+    pub fn build(&self) -> QueryBuilder<'static, Postgres> {
+        let mut qb = QueryBuilder::new(
+            "SELECT e.ts, e.id, e.event_type, e.mint, e.sig, e.amount, \
+             COALESCE(e.price_usd, px.price) AS price_usd, \
+             COALESCE(e.value_usd, ABS(e.amount) * px.price) AS value_usd, \
+             e.pnl_usd, e.detail, tf.symbol AS token_symbol, tf.logo_url AS token_logo_url \
+             FROM (",
+        );
+        let sources = Source::ALL
+            .into_iter()
+            .filter(|s| self.types().iter().any(|t| s.lists(*t)));
+        for (i, source) in sources.enumerate() {
+            if i > 0 {
+                qb.push(" UNION ALL ");
+            }
+            qb.push("(");
+            self.push_source(&mut qb, source);
+            qb.push(")");
+        }
+        qb.push(
+            ") e LEFT JOIN token_facts tf ON tf.mint = e.mint \
+             LEFT JOIN LATERAL (SELECT tp.price FROM token_prices tp \
+             WHERE e.value_usd IS NULL AND e.amount IS NOT NULL \
+             AND tp.mint = e.mint AND tp.ts <= e.ts \
+             ORDER BY tp.ts DESC LIMIT 1) px ON TRUE \
+             ORDER BY e.ts DESC, e.id DESC LIMIT ",
+        )
+        .push_bind(self.limit as i64 + 1);
+        qb
+    }
+
+    /// One source's page: this wallet's rows matching the filters, after the
+    /// cursor, newest first
+    fn push_source(&self, qb: &mut QueryBuilder<'static, Postgres>, source: Source) {
+        let (ts, wallet, id) = source.columns();
+        qb.push(source.select());
+        if source == Source::Actions {
+            source.push_mint(qb);
+            qb.push(ACTION_COLUMNS);
+        }
+
+        qb.push(" WHERE ").push(wallet).push(" = ");
+        qb.push_bind(self.wallet.clone());
+        match source {
+            Source::Actions => {
+                qb.push(" AND a.kind = ANY(");
+                qb.push_bind(self.action_kinds()).push(")");
+            }
+            Source::EpisodeCloses => {
+                qb.push(" AND ep.end_ts IS NOT NULL");
+            }
+            _ => {}
+        }
+        if !self.mints.is_empty() {
+            qb.push(" AND ");
+            source.push_mint(qb);
+            qb.push(" = ANY(");
+            qb.push_bind(self.mints.clone()).push(")");
+        }
+        if let Some(cursor) = &self.after {
+            // The plain bound lets the planner walk the timestamp index
+            qb.push(" AND ").push(ts).push(" <= ");
+            qb.push_bind(cursor.ts);
+            qb.push(" AND (").push(ts).push(", ").push(id).push(") < (");
+            qb.push_bind(cursor.ts)
+                .push(", ")
+                .push_bind(cursor.id.clone())
+                .push(")");
+        }
+        qb.push(" ORDER BY ")
+            .push(ts)
+            .push(" DESC, ")
+            .push(id)
+            .push(" DESC LIMIT ")
+            .push_bind(self.limit as i64 + 1);
+    }
+
+    pub async fn fetch_page(&self, pg: &PgPool) -> anyhow::Result<TimelinePage> {
+        let mut rows: Vec<TimelineRow> = self.build().build_query_as().fetch_all(pg).await?;
+        let has_more = rows.len() > self.limit;
+        rows.truncate(self.limit);
+        let next_cursor = match rows.last() {
+            Some(last) if has_more => Some(TimelineCursor::after(last)),
+            _ => None,
+        };
+        Ok(TimelinePage { rows, next_cursor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = TimelineCursor {
+            ts: OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap(),
+            id: "ec:01HF0000000000000000000000".to_string(),
+        };
+        assert_eq!(TimelineCursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        assert!(TimelineCursor::decode("not a cursor").is_err());
+        assert!(TimelineCursor::decode(&URL_SAFE_NO_PAD.encode("2024-01-01T00:00:00Z|")).is_err());
+        assert!(TimelineCursor::decode(&URL_SAFE_NO_PAD.encode("yesterday|m:1")).is_err());
+    }
+
+    #[test]
+    fn test_event_type_round_trip() {
+        for t in TimelineEventType::ALL {
+            assert_eq!(TimelineEventType::parse(t.as_str()), Some(t));
+        }
+        assert_eq!(TimelineEventType::parse("sol_transfer"), None);
+    }
+
+    #[test]
+    fn test_build_merges_every_source() {
+        let qb = TimelineQuery::new("w").with_limit(10).build();
+        let sql = qb.sql();
+        assert_eq!(sql.matches(" UNION ALL ").count(), 4);
+        assert!(
+            sql.contains("FROM actions a WHERE a.flags_json->>'owner' = $2 AND a.kind = ANY($3)")
+        );
+        assert!(sql.contains("FROM oof_moments m WHERE m.wallet = $11 ORDER BY m.t_event DESC"));
+        assert!(sql.ends_with(" ORDER BY e.ts DESC, e.id DESC LIMIT $13"));
+        assert!(!sql.contains("ep.end_ts <="));
+    }
+
+    #[test]
+    fn test_build_binds_types_mints_and_cursor() {
+        let cursor = TimelineCursor {
+            ts: OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap(),
+            id: "t:01HF0000000000000000000000".to_string(),
+        };
+        let qb = TimelineQuery::new("w")
+            .with_types(vec![TimelineEventType::Trade, TimelineEventType::Transfer])
+            .with_mints(vec!["mint".to_string()])
+            .with_cursor(Some(cursor))
+            .build();
+        let sql = qb.sql();
+        assert_eq!(sql.matches(" UNION ALL ").count(), 1);
+        assert!(!sql.contains("FROM episodes"));
+        assert!(sql.contains(
+            " AND COALESCE(a.mint, $4) = ANY($5) AND a.ts <= $6 \
+             AND (a.ts, 'a:' || a.id) < ($7, $8) ORDER BY a.ts DESC, 'a:' || a.id DESC LIMIT $9"
+        ));
+        assert!(sql.contains(
+            "FROM realized_trades rt WHERE rt.wallet = $10 AND rt.mint = ANY($11) \
+             AND rt.ts <= $12 AND (rt.ts, 't:' || rt.exit_id) < ($13, $14)"
+        ));
+        assert!(sql.ends_with(" LIMIT $16"));
+    }
+
+    #[test]
+    fn test_action_kinds_follow_types() {
+        let query = TimelineQuery::new("w").with_types(vec![TimelineEventType::Transfer]);
+        assert_eq!(query.action_kinds(), vec!["transfer", "sol_transfer"]);
+        assert_eq!(TimelineQuery::new("w").action_kinds().len(), 5);
+    }
+}
@@ -0,0 +1,217 @@
+//! Registry of wallets the live webhook should watch.
+//!
+//! Rows are keyed by (wallet, user, reason) so one customer unsubscribing
+//! does not stop watching a wallet another customer still pays for. Each
+//! row expires `plans.tracking_ttl_days` after its last activity.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::queue::JobSpec;
+use crate::types::policy::JobPriority;
+
+/// Why a wallet is being tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackReason {
+    Analyze,
+    Alerts,
+}
+
+impl TrackReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrackReason::Analyze => "analyze",
+            TrackReason::Alerts => "alerts",
+        }
+    }
+}
+
+/// Kind of the job that reconciles the registry with the webhook
+pub const SYNC_JOB_KIND: &str = "sync_webhook_addresses";
+
+/// Default TTL when the plan is unknown
+const DEFAULT_TTL_DAYS: i32 = 7;
+
+/// Fingerprint of a webhook address set, independent of order
+pub fn address_set_hash(addresses: &[String]) -> String {
+    let mut sorted: Vec<&str> = addresses.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+    hex::encode(Sha256::digest(sorted.join(",").as_bytes()))
+}
+
+#[derive(Clone)]
+pub struct TrackedWalletRegistry {
+    pub pg: PgPool,
+}
+
+impl TrackedWalletRegistry {
+    pub fn new(pg: PgPool) -> Self {
+        Self { pg }
+    }
+
+    /// Start tracking a wallet, or refresh its TTL if already tracked
+    pub async fn track(
+        &self,
+        wallet: &str,
+        user_id: &str,
+        reason: TrackReason,
+        plan_code: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query!(
+            "INSERT INTO tracked_wallets (wallet, user_id, reason, plan_code, last_active_at, expires_at)
+             VALUES ($1, $2, $3, $4, NOW(),
+                     NOW() + make_interval(days => COALESCE(
+                         (SELECT tracking_ttl_days FROM plans WHERE code = $4), $5)))
+             ON CONFLICT (wallet, user_id, reason) DO UPDATE SET
+               plan_code = EXCLUDED.plan_code,
+               last_active_at = EXCLUDED.last_active_at,
+               expires_at = GREATEST(tracked_wallets.expires_at, EXCLUDED.expires_at)",
+            wallet,
+            user_id,
+            reason.as_str(),
+            plan_code,
+            DEFAULT_TTL_DAYS
+        )
+        .execute(&self.pg)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn untrack(
+        &self,
+        wallet: &str,
+        user_id: &str,
+        reason: TrackReason,
+    ) -> anyhow::Result<bool> {
+        let res = sqlx::query!(
+            "DELETE FROM tracked_wallets WHERE wallet = $1 AND user_id = $2 AND reason = $3",
+            wallet,
+            user_id,
+            reason.as_str()
+        )
+        .execute(&self.pg)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Unexpired wallets a user tracks for `reason`, not counting `wallet`
+    /// itself, so re-subscribing to the same wallet is never over the limit
+    pub async fn count_other_tracked(
+        &self,
+        user_id: &str,
+        reason: TrackReason,
+        wallet: &str,
+    ) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) AS \"count!\" FROM tracked_wallets
+             WHERE user_id = $1 AND reason = $2 AND wallet <> $3 AND expires_at > NOW()",
+            user_id,
+            reason.as_str(),
+            wallet
+        )
+        .fetch_one(&self.pg)
+        .await?;
+        Ok(count)
+    }
+
+    /// Remove rows idle past their TTL; returns how many were removed
+    pub async fn expire_idle(&self) -> anyhow::Result<u64> {
+        let res = sqlx::query!("DELETE FROM tracked_wallets WHERE expires_at <= NOW()")
+            .execute(&self.pg)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Distinct wallets that should currently be watched. When there are
+    /// more than `limit`, alert subscriptions win over analyses and then the
+    /// most recently active wallets, so the idlest ones are dropped.
+    pub async fn active_wallets(&self, limit: i64) -> anyhow::Result<Vec<String>> {
+        let rows = sqlx::query_scalar!(
+            "SELECT wallet AS \"wallet!\" FROM tracked_wallets
+             WHERE expires_at > NOW()
+             GROUP BY wallet
+             ORDER BY BOOL_OR(reason = 'alerts') DESC, MAX(last_active_at) DESC, wallet
+             LIMIT $1",
+            limit
+        )
+        .fetch_all(&self.pg)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Fingerprint and time of the last address set pushed to the webhook
+    pub async fn last_sync(&self) -> anyhow::Result<Option<(String, OffsetDateTime)>> {
+        let row = sqlx::query!("SELECT addresses_hash, synced_at FROM webhook_sync_state")
+            .fetch_optional(&self.pg)
+            .await?;
+        Ok(row.map(|r| (r.addresses_hash, r.synced_at)))
+    }
+
+    pub async fn mark_synced(&self, wallets: &[String]) -> anyhow::Result<()> {
+        let mut tx = self.pg.begin().await?;
+        sqlx::query!(
+            "UPDATE tracked_wallets SET synced_at = NOW()
+             WHERE wallet = ANY($1) AND synced_at IS NULL",
+            wallets
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO webhook_sync_state (id, addresses_hash, address_count, synced_at)
+             VALUES (TRUE, $1, $2, NOW())
+             ON CONFLICT (id) DO UPDATE SET
+               addresses_hash = EXCLUDED.addresses_hash,
+               address_count = EXCLUDED.address_count,
+               synced_at = EXCLUDED.synced_at",
+            address_set_hash(wallets),
+            wallets.len() as i32
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Enqueue a webhook sync on behalf of `tenant` unless one is already
+    /// waiting; the waiting job then covers this change too
+    pub async fn request_sync(&self, tenant: &str, priority: JobPriority) -> anyhow::Result<()> {
+        let job = JobSpec::new(SYNC_JOB_KIND, serde_json::json!({}))
+            .with_max_attempts(3)
+            .with_priority(priority)
+            .with_tenant(tenant);
+        sqlx::query!(
+            "INSERT INTO job_queue (id, kind, payload_json, run_after, max_attempts, priority, tenant,
+                                    status, root_id)
+             SELECT $1, $2, $3, NOW(), $4, $5, $6, 'queued', $1
+             WHERE NOT EXISTS (
+               SELECT 1 FROM job_queue WHERE kind = $2 AND status = 'queued'
+             )",
+            job.id,
+            job.kind,
+            job.payload,
+            job.max_attempts,
+            job.priority.as_i16(),
+            job.tenant
+        )
+        .execute(&self.pg)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_set_hash_ignores_order_and_duplicates() {
+        let a = vec!["w2".to_string(), "w1".to_string(), "w1".to_string()];
+        let b = vec!["w1".to_string(), "w2".to_string()];
+        assert_eq!(address_set_hash(&a), address_set_hash(&b));
+        assert_ne!(address_set_hash(&a), address_set_hash(&b[..1]));
+    }
+}
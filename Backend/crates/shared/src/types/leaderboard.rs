@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use super::moment::MomentKind;
+
+/// Ranking a leaderboard snapshot is computed for. Every metric ranks
+/// wallets by a descending value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardMetric {
+    /// Total realized gains in USD
+    HighestGains,
+    /// Number of sold-too-early moments
+    MostS2e,
+    /// Number of bag-holder drawdowns
+    WorstBhd,
+    /// Largest single sold-too-early miss in USD
+    BiggestS2e,
+    /// Largest single bad-route loss in USD
+    WorstBadRoute,
+    /// Share of exits that turned into sold-too-early moments
+    MostPaperHanded,
+    /// USD missed across every moment kind
+    TotalMissed,
+}
+
+impl LeaderboardMetric {
+    pub const ALL: [LeaderboardMetric; 7] = [
+        LeaderboardMetric::HighestGains,
+        LeaderboardMetric::MostS2e,
+        LeaderboardMetric::WorstBhd,
+        LeaderboardMetric::BiggestS2e,
+        LeaderboardMetric::WorstBadRoute,
+        LeaderboardMetric::MostPaperHanded,
+        LeaderboardMetric::TotalMissed,
+    ];
+
+    /// Value stored in `leaderboard_snapshots.metric` and accepted by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::HighestGains => "highest_gains",
+            LeaderboardMetric::MostS2e => "most_s2e",
+            LeaderboardMetric::WorstBhd => "worst_bhd",
+            LeaderboardMetric::BiggestS2e => "biggest_s2e",
+            LeaderboardMetric::WorstBadRoute => "worst_bad_route",
+            LeaderboardMetric::MostPaperHanded => "most_paper_handed",
+            LeaderboardMetric::TotalMissed => "total_missed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == s)
+    }
+
+    /// Name of the value field in API responses
+    pub fn value_name(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::HighestGains => "total_gains_usd",
+            LeaderboardMetric::MostS2e => "s2e_count",
+            LeaderboardMetric::WorstBhd => "bhd_count",
+            LeaderboardMetric::BiggestS2e => "biggest_s2e_usd",
+            LeaderboardMetric::WorstBadRoute => "worst_bad_route_usd",
+            LeaderboardMetric::MostPaperHanded => "paper_hand_pct",
+            LeaderboardMetric::TotalMissed => "total_missed_usd",
+        }
+    }
+
+    /// Moment kind the metric is computed from, if it is moment based
+    pub fn moment_kind(&self) -> Option<MomentKind> {
+        match self {
+            LeaderboardMetric::MostS2e
+            | LeaderboardMetric::BiggestS2e
+            | LeaderboardMetric::MostPaperHanded => Some(MomentKind::SoldTooEarly),
+            LeaderboardMetric::WorstBhd => Some(MomentKind::BagHolderDrawdown),
+            LeaderboardMetric::WorstBadRoute => Some(MomentKind::BadRoute),
+            LeaderboardMetric::HighestGains | LeaderboardMetric::TotalMissed => None,
+        }
+    }
+}
+
+/// Trailing window a leaderboard covers
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum LeaderboardPeriod {
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+    #[serde(rename = "90d")]
+    Quarter,
+}
+
+impl LeaderboardPeriod {
+    pub const ALL: [LeaderboardPeriod; 3] = [
+        LeaderboardPeriod::Week,
+        LeaderboardPeriod::Month,
+        LeaderboardPeriod::Quarter,
+    ];
+
+    /// Value stored in `leaderboard_snapshots.period` and accepted by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardPeriod::Week => "7d",
+            LeaderboardPeriod::Month => "30d",
+            LeaderboardPeriod::Quarter => "90d",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == s)
+    }
+
+    pub fn days(&self) -> i32 {
+        match self {
+            LeaderboardPeriod::Week => 7,
+            LeaderboardPeriod::Month => 30,
+            LeaderboardPeriod::Quarter => 90,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metric_round_trip() {
+        for metric in LeaderboardMetric::ALL {
+            assert_eq!(LeaderboardMetric::parse(metric.as_str()), Some(metric));
+            assert_eq!(
+                serde_json::to_value(metric).unwrap(),
+                serde_json::Value::from(metric.as_str())
+            );
+        }
+        assert_eq!(LeaderboardMetric::parse("bogus"), None);
+    }
+
+    #[test]
+    fn test_period_round_trip() {
+        for period in LeaderboardPeriod::ALL {
+            assert_eq!(LeaderboardPeriod::parse(period.as_str()), Some(period));
+            assert_eq!(
+                serde_json::to_value(period).unwrap(),
+                serde_json::Value::from(period.as_str())
+            );
+        }
+        assert_eq!(LeaderboardPeriod::Quarter.days(), 90);
+        assert_eq!(LeaderboardPeriod::parse("1y"), None);
+    }
+}
@@ -13,6 +13,10 @@ pub struct Plan {
     pub cadence: PlanCadence,
     pub alerts: i32,
     pub api_rows: i64,
+    pub max_signatures_per_run: i64,
+    pub max_enhanced_tx_per_run: i64,
+    /// Requests per minute each of the user's API keys may make
+    pub api_requests_per_minute: i32,
     pub perks: PlanPerks,
 }
 
@@ -25,7 +29,42 @@ pub enum PlanCadence {
     Monthly,
 }
 
+impl PlanCadence {
+    pub const ALL: [PlanCadence; 4] = [
+        PlanCadence::Manual,
+        PlanCadence::Daily,
+        PlanCadence::Weekly,
+        PlanCadence::Monthly,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    /// Value stored in `plans.cadence`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanCadence::Manual => "manual",
+            PlanCadence::Daily => "daily",
+            PlanCadence::Weekly => "weekly",
+            PlanCadence::Monthly => "monthly",
+        }
+    }
+
+    /// Days between automatic re-analyses; `None` for manual plans
+    pub fn refresh_interval_days(&self) -> Option<i32> {
+        match self {
+            PlanCadence::Manual => None,
+            PlanCadence::Daily => Some(1),
+            PlanCadence::Weekly => Some(7),
+            PlanCadence::Monthly => Some(30),
+        }
+    }
+}
+
+/// Perks missing from a plan's `perks_json` are off
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PlanPerks {
     pub priority_queue: bool,
     pub custom_cards: bool,
@@ -115,6 +154,7 @@ pub struct UserContext {
     pub policy_state: PolicyState,
     pub is_authenticated: bool,
     pub auth_method: AuthMethod,
+    pub staking_boost: Option<StakingBoost>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -141,6 +181,11 @@ impl UserContext {
     pub fn remaining_api_calls(&self) -> i64 {
         (self.plan.api_rows - self.policy_state.api_calls_today).max(0)
     }
+
+    /// Queue priority for jobs this user enqueues
+    pub fn job_priority(&self) -> JobPriority {
+        JobPriority::for_plan(&self.plan, self.staking_boost.as_ref())
+    }
 }
 
 /// Rate limiting configuration
@@ -178,6 +223,7 @@ pub struct StakingBoost {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BoostPerks {
     pub extra_daily_wallets_pct: Decimal, // e.g., 0.50 for +50%
     pub card_mint_fee_discount_pct: Decimal, // e.g., 0.50 for -50%
@@ -228,6 +274,77 @@ impl Default for JobPriority {
     }
 }
 
+impl JobPriority {
+    const ALL: [JobPriority; 4] = [
+        JobPriority::Low,
+        JobPriority::Normal,
+        JobPriority::High,
+        JobPriority::Critical,
+    ];
+
+    /// Value stored in `job_queue.priority`
+    pub fn as_i16(&self) -> i16 {
+        self.clone() as i16
+    }
+
+    pub fn from_i16(value: i16) -> Self {
+        match value {
+            i16::MIN..=0 => JobPriority::Low,
+            1 => JobPriority::Normal,
+            2 => JobPriority::High,
+            _ => JobPriority::Critical,
+        }
+    }
+
+    /// Share of dequeues this level gets while other levels are also waiting
+    pub fn weight(&self) -> u32 {
+        match self {
+            JobPriority::Low => 1,
+            JobPriority::Normal => 4,
+            JobPriority::High => 12,
+            JobPriority::Critical => 48,
+        }
+    }
+
+    /// Priority for user-initiated work: plan perk or an active staking
+    /// boost lifts it to High, a fast-lane boost to Critical
+    pub fn for_plan(plan: &Plan, boost: Option<&StakingBoost>) -> Self {
+        let boost = boost.filter(|b| b.is_active());
+        if boost.map_or(false, |b| b.perks.fast_lane) {
+            JobPriority::Critical
+        } else if plan.perks.priority_queue || boost.map_or(false, |b| b.perks.priority_queue) {
+            JobPriority::High
+        } else {
+            JobPriority::Normal
+        }
+    }
+
+    /// Weighted pick among the levels that currently have ready jobs.
+    ///
+    /// `roll` is any random number; lower levels keep a small share so a
+    /// steady stream of high-priority work cannot starve them.
+    pub fn pick_weighted(ready: &[JobPriority], roll: u32) -> Option<JobPriority> {
+        let levels: Vec<JobPriority> = Self::ALL
+            .iter()
+            .filter(|p| ready.contains(*p))
+            .cloned()
+            .collect();
+        let total: u32 = levels.iter().map(|p| p.weight()).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut point = roll % total;
+        for level in levels.into_iter().rev() {
+            if point < level.weight() {
+                return Some(level);
+            }
+            point -= level.weight();
+        }
+        None
+    }
+}
+
 /// Quota enforcement result
 #[derive(Debug, Clone)]
 pub enum QuotaCheck {
@@ -253,3 +370,47 @@ impl QuotaCheck {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_weighted_only_returns_ready_levels() {
+        assert_eq!(JobPriority::pick_weighted(&[], 7), None);
+        for roll in 0..100 {
+            assert_eq!(
+                JobPriority::pick_weighted(&[JobPriority::Low], roll),
+                Some(JobPriority::Low)
+            );
+        }
+    }
+
+    #[test]
+    fn test_pick_weighted_shares_by_weight() {
+        let ready = [JobPriority::Normal, JobPriority::High];
+        let total = JobPriority::Normal.weight() + JobPriority::High.weight();
+        let high = (0..total)
+            .filter(|roll| JobPriority::pick_weighted(&ready, *roll) == Some(JobPriority::High))
+            .count() as u32;
+        assert_eq!(high, JobPriority::High.weight());
+    }
+
+    #[test]
+    fn test_cadence_intervals() {
+        assert_eq!(PlanCadence::Manual.refresh_interval_days(), None);
+        assert_eq!(PlanCadence::Weekly.refresh_interval_days(), Some(7));
+        for cadence in PlanCadence::ALL {
+            let parsed: PlanCadence =
+                serde_json::from_value(serde_json::json!(cadence.as_str())).unwrap();
+            assert_eq!(parsed, cadence);
+        }
+    }
+
+    #[test]
+    fn test_priority_roundtrips_through_column_value() {
+        for p in JobPriority::ALL {
+            assert_eq!(JobPriority::from_i16(p.as_i16()), p);
+        }
+    }
+}
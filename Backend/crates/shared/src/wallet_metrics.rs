@@ -0,0 +1,472 @@
+//! Trading metrics for a wallet, computed from `realized_trades`,
+//! `episodes`, `oof_moments`, transaction fees and the stored equity curve
+//! (`wallet_equity_daily`), plus head-to-head comparison of several wallets.
+//!
+//! Drawdown, Sharpe and Sortino use the curve's flow-adjusted daily returns,
+//! the same series volatility and beta come from, so every risk figure
+//! covers unrealized moves too. Days with nothing invested are left out of
+//! the series rather than counted as flat.
+
+use std::collections::BTreeMap;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::equity::{load_equity_series, EquityDay, EQUITY_HISTORY_DAYS};
+use crate::normalize::SOL_DECIMALS;
+
+/// Most wallets one comparison may cover
+pub const MAX_COMPARE_WALLETS: usize = 5;
+
+/// Default and longest lookback for metrics; returns come from the stored
+/// equity curve, which only keeps `EQUITY_HISTORY_DAYS`
+pub const DEFAULT_METRICS_DAYS: i64 = 365;
+pub const MAX_METRICS_DAYS: i64 = EQUITY_HISTORY_DAYS;
+
+/// Tokens trade every day, so returns annualise over calendar days
+const PERIODS_PER_YEAR: f64 = 365.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissedByKind {
+    pub kind: String,
+    pub moments: i64,
+    pub missed_usd: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiggestMoment {
+    pub id: String,
+    pub kind: String,
+    pub mint: Option<String>,
+    pub t_event: OffsetDateTime,
+    pub missed_usd: Decimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalletMetrics {
+    pub wallet: String,
+    pub since: OffsetDateTime,
+    pub until: OffsetDateTime,
+    pub trades: i64,
+    pub winning_trades: i64,
+    /// Share of trades with positive realized PnL
+    pub win_rate: Option<f64>,
+    pub realized_pnl_usd: Decimal,
+    pub closed_episodes: i64,
+    /// Over episodes closed in the window
+    pub avg_hold: Option<Duration>,
+    /// Largest peak-to-trough fall of the compounded daily returns, 0..1
+    pub max_drawdown: Option<f64>,
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    pub total_fees_sol: Decimal,
+    pub missed_by_kind: Vec<MissedByKind>,
+    pub missed_usd_total: Decimal,
+    pub biggest_moment: Option<BiggestMoment>,
+    pub daily_returns: Vec<(Date, f64)>,
+}
+
+/// Daily returns of an equity curve; days with nothing invested are skipped
+pub fn daily_returns(series: &[EquityDay]) -> Vec<(Date, f64)> {
+    series
+        .iter()
+        .filter_map(|d| Some((d.day, d.daily_return?)))
+        .collect()
+}
+
+fn mean(xs: &[f64]) -> f64 {
+    xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+/// Largest fall from a running peak of the compounded returns
+pub fn max_drawdown(returns: &[f64]) -> Option<f64> {
+    if returns.is_empty() {
+        return None;
+    }
+    let mut index = 1.0_f64;
+    let mut peak = 1.0_f64;
+    let mut worst = 0.0_f64;
+    for r in returns {
+        index *= 1.0 + r;
+        peak = peak.max(index);
+        if peak > 0.0 {
+            worst = worst.max((peak - index) / peak);
+        }
+    }
+    Some(worst.min(1.0))
+}
+
+/// Annualised Sharpe ratio with a zero risk-free rate
+pub fn sharpe_ratio(returns: &[f64]) -> Option<f64> {
+    if returns.len() < 2 {
+        return None;
+    }
+    let m = mean(returns);
+    let var = returns.iter().map(|r| (r - m).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    let sd = var.sqrt();
+    (sd > 0.0).then(|| m / sd * PERIODS_PER_YEAR.sqrt())
+}
+
+/// Annualised Sortino ratio: like Sharpe, but only losing days count as risk
+pub fn sortino_ratio(returns: &[f64]) -> Option<f64> {
+    if returns.len() < 2 {
+        return None;
+    }
+    let m = mean(returns);
+    let downside =
+        (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / returns.len() as f64).sqrt();
+    (downside > 0.0).then(|| m / downside * PERIODS_PER_YEAR.sqrt())
+}
+
+/// Daily returns compounded into calendar months, oldest first
+pub fn monthly_returns(returns: &[(Date, f64)]) -> Vec<((i32, time::Month), f64)> {
+    let mut months: BTreeMap<(i32, u8), f64> = BTreeMap::new();
+    for (day, r) in returns {
+        let index = months.entry((day.year(), day.month() as u8)).or_insert(1.0);
+        *index *= 1.0 + r;
+    }
+    months
+        .into_iter()
+        .filter_map(|((year, month), index)| {
+            Some(((year, time::Month::try_from(month).ok()?), index - 1.0))
+        })
+        .collect()
+}
+
+/// Pearson correlation of two return series over the days both have
+#[derive(Debug, Clone, PartialEq)]
+pub struct Correlation {
+    pub r: f64,
+    pub sample_size: usize,
+    /// 95% interval from the Fisher transformation
+    pub confidence_interval: (f64, f64),
+    /// Two-sided, against no correlation
+    pub p_value: f64,
+}
+
+pub fn correlation(a: &[(Date, f64)], b: &[(Date, f64)]) -> Option<Correlation> {
+    let b: BTreeMap<Date, f64> = b.iter().copied().collect();
+    let (xs, ys): (Vec<f64>, Vec<f64>) = a
+        .iter()
+        .filter_map(|(day, x)| b.get(day).map(|y| (*x, *y)))
+        .unzip();
+    let n = xs.len();
+    if n < 4 {
+        return None;
+    }
+    let (mx, my) = (mean(&xs), mean(&ys));
+    let cov: f64 = xs.iter().zip(&ys).map(|(x, y)| (x - mx) * (y - my)).sum();
+    let sx = xs.iter().map(|x| (x - mx).powi(2)).sum::<f64>().sqrt();
+    let sy = ys.iter().map(|y| (y - my).powi(2)).sum::<f64>().sqrt();
+    if sx == 0.0 || sy == 0.0 {
+        return None;
+    }
+    let r = (cov / (sx * sy)).clamp(-1.0, 1.0);
+
+    // Fisher z, with r kept off +-1 so atanh stays finite
+    let z = r.clamp(-0.999_999, 0.999_999).atanh();
+    let se = 1.0 / ((n - 3) as f64).sqrt();
+    let confidence_interval = ((z - 1.96 * se).tanh(), (z + 1.96 * se).tanh());
+    let p_value = erfc((z / se).abs() / std::f64::consts::SQRT_2);
+
+    Some(Correlation {
+        r,
+        sample_size: n,
+        confidence_interval,
+        p_value,
+    })
+}
+
+/// Complementary error function (Abramowitz & Stegun 7.1.26, |error| <
+/// 1.5e-7), for x >= 0
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    poly * (-x * x).exp()
+}
+
+/// Which wallet leads each metric; `None` when no wallet has a value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Leaders {
+    pub win_rate: Option<String>,
+    pub realized_pnl: Option<String>,
+    pub sharpe: Option<String>,
+    pub sortino: Option<String>,
+    /// Smallest drawdown
+    pub max_drawdown: Option<String>,
+    /// Lowest fees
+    pub total_fees: Option<String>,
+    /// Least missed across moments
+    pub missed_usd: Option<String>,
+}
+
+fn best_by(
+    metrics: &[WalletMetrics],
+    value: impl Fn(&WalletMetrics) -> Option<f64>,
+    higher_is_better: bool,
+) -> Option<String> {
+    metrics
+        .iter()
+        .filter_map(|m| value(m).filter(|v| v.is_finite()).map(|v| (m, v)))
+        .max_by(|(_, a), (_, b)| {
+            let ord = a.total_cmp(b);
+            if higher_is_better {
+                ord
+            } else {
+                ord.reverse()
+            }
+        })
+        .map(|(m, _)| m.wallet.clone())
+}
+
+pub fn leaders(metrics: &[WalletMetrics]) -> Leaders {
+    Leaders {
+        win_rate: best_by(metrics, |m| m.win_rate, true),
+        realized_pnl: best_by(metrics, |m| m.realized_pnl_usd.to_f64(), true),
+        sharpe: best_by(metrics, |m| m.sharpe, true),
+        sortino: best_by(metrics, |m| m.sortino, true),
+        max_drawdown: best_by(metrics, |m| m.max_drawdown, false),
+        total_fees: best_by(metrics, |m| m.total_fees_sol.to_f64(), false),
+        missed_usd: best_by(metrics, |m| m.missed_usd_total.to_f64(), false),
+    }
+}
+
+/// `[start, end)` covering the last `days` whole UTC days up to today
+pub fn metrics_window(now: OffsetDateTime, days: i64) -> (OffsetDateTime, OffsetDateTime) {
+    let today = now.to_offset(time::UtcOffset::UTC).date();
+    let end = today.next_day().unwrap_or(today).midnight().assume_utc();
+    (end - Duration::days(days), end)
+}
+
+/// Metrics for `wallet` over `[since, until)`; both must be UTC midnights
+pub async fn load_wallet_metrics(
+    pg: &PgPool,
+    wallet: &str,
+    since: OffsetDateTime,
+    until: OffsetDateTime,
+) -> anyhow::Result<WalletMetrics> {
+    let trades = sqlx::query!(
+        r#"SELECT COUNT(*) AS "trades!",
+                  COUNT(*) FILTER (WHERE realized_pnl_usd_dec > 0) AS "wins!",
+                  COALESCE(SUM(realized_pnl_usd_dec), 0) AS "pnl!"
+           FROM realized_trades
+           WHERE wallet = $1 AND ts >= $2 AND ts < $3"#,
+        wallet,
+        since,
+        until
+    )
+    .fetch_one(pg)
+    .await?;
+
+    let episodes = sqlx::query!(
+        r#"SELECT COUNT(*) AS "closed!",
+                  AVG(EXTRACT(EPOCH FROM (end_ts - start_ts)))::float8 AS avg_hold_secs
+           FROM episodes
+           WHERE wallet = $1 AND end_ts IS NOT NULL AND end_ts >= $2 AND end_ts < $3"#,
+        wallet,
+        since,
+        until
+    )
+    .fetch_one(pg)
+    .await?;
+
+    let fees = sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(fee_lamports), 0)::bigint AS "lamports!"
+           FROM tx_raw
+           WHERE fee_payer = $1 AND ts >= $2 AND ts < $3"#,
+        wallet,
+        since,
+        until
+    )
+    .fetch_one(pg)
+    .await?;
+
+    let missed_by_kind: Vec<MissedByKind> = sqlx::query!(
+        r#"SELECT kind, COUNT(*) AS "moments!", COALESCE(SUM(missed_usd_dec), 0) AS "missed!"
+           FROM oof_moments
+           WHERE wallet = $1 AND t_event >= $2 AND t_event < $3
+           GROUP BY kind
+           ORDER BY 3 DESC"#,
+        wallet,
+        since,
+        until
+    )
+    .fetch_all(pg)
+    .await?
+    .into_iter()
+    .map(|r| MissedByKind {
+        kind: r.kind,
+        moments: r.moments,
+        missed_usd: r.missed,
+    })
+    .collect();
+
+    let biggest_moment = sqlx::query!(
+        r#"SELECT id, kind, mint, t_event, missed_usd_dec AS "missed_usd!"
+           FROM oof_moments
+           WHERE wallet = $1 AND t_event >= $2 AND t_event < $3
+             AND missed_usd_dec IS NOT NULL
+           ORDER BY missed_usd_dec DESC, id DESC
+           LIMIT 1"#,
+        wallet,
+        since,
+        until
+    )
+    .fetch_optional(pg)
+    .await?
+    .map(|r| BiggestMoment {
+        id: r.id,
+        kind: r.kind,
+        mint: r.mint,
+        t_event: r.t_event,
+        missed_usd: r.missed_usd,
+    });
+
+    let last_day = (until - Duration::days(1)).date();
+    let (equity, _) = load_equity_series(pg, wallet, since.date(), last_day).await?;
+    let returns = daily_returns(&equity);
+    let values: Vec<f64> = returns.iter().map(|(_, r)| *r).collect();
+
+    Ok(WalletMetrics {
+        wallet: wallet.to_string(),
+        since,
+        until,
+        trades: trades.trades,
+        winning_trades: trades.wins,
+        win_rate: (trades.trades > 0).then(|| trades.wins as f64 / trades.trades as f64),
+        realized_pnl_usd: trades.pnl,
+        closed_episodes: episodes.closed,
+        avg_hold: episodes.avg_hold_secs.map(Duration::seconds_f64),
+        max_drawdown: max_drawdown(&values),
+        sharpe: sharpe_ratio(&values),
+        sortino: sortino_ratio(&values),
+        total_fees_sol: Decimal::new(fees, SOL_DECIMALS),
+        missed_usd_total: missed_by_kind.iter().map(|k| k.missed_usd).sum(),
+        missed_by_kind,
+        biggest_moment,
+        daily_returns: returns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    fn metrics(wallet: &str, win_rate: Option<f64>, drawdown: Option<f64>) -> WalletMetrics {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        WalletMetrics {
+            wallet: wallet.to_string(),
+            since: now,
+            until: now,
+            trades: 0,
+            winning_trades: 0,
+            win_rate,
+            realized_pnl_usd: Decimal::ZERO,
+            closed_episodes: 0,
+            avg_hold: None,
+            max_drawdown: drawdown,
+            sharpe: None,
+            sortino: None,
+            total_fees_sol: Decimal::ZERO,
+            missed_by_kind: Vec::new(),
+            missed_usd_total: Decimal::ZERO,
+            biggest_moment: None,
+            daily_returns: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_daily_returns_skip_days_without_capital() {
+        let day = |day: Date, daily_return: Option<f64>| EquityDay {
+            day,
+            equity_usd: Decimal::ZERO,
+            net_flow_usd: Decimal::ZERO,
+            cost_basis_usd: Decimal::ZERO,
+            unrealized_pnl_usd: Decimal::ZERO,
+            realized_pnl_usd: Decimal::ZERO,
+            daily_return,
+            twr_index: 1.0,
+            unpriced_mints: 0,
+        };
+        let series = [
+            day(date!(2024 - 01 - 01), Some(0.1)),
+            day(date!(2024 - 01 - 02), None),
+        ];
+        assert_eq!(daily_returns(&series), vec![(date!(2024 - 01 - 01), 0.1)]);
+    }
+
+    #[test]
+    fn test_max_drawdown_compounds() {
+        // 1.0 -> 1.5 -> 0.75 -> 0.9: worst fall is 1.5 -> 0.75
+        let dd = max_drawdown(&[0.5, -0.5, 0.2]).unwrap();
+        assert!((dd - 0.5).abs() < 1e-9);
+        assert_eq!(max_drawdown(&[0.1, 0.2]), Some(0.0));
+        assert_eq!(max_drawdown(&[]), None);
+    }
+
+    #[test]
+    fn test_sharpe_and_sortino() {
+        let returns = [0.02, -0.01, 0.03, -0.02, 0.01];
+        let sharpe = sharpe_ratio(&returns).unwrap();
+        let sortino = sortino_ratio(&returns).unwrap();
+        assert!(sharpe > 0.0);
+        assert!(sortino > sharpe);
+        assert_eq!(sharpe_ratio(&[0.01, 0.01]), None);
+        assert_eq!(sortino_ratio(&[0.01, 0.02]), None);
+    }
+
+    #[test]
+    fn test_monthly_returns_compound() {
+        let returns = [
+            (date!(2024 - 01 - 30), 0.1),
+            (date!(2024 - 01 - 31), 0.1),
+            (date!(2024 - 02 - 01), -0.5),
+        ];
+        let months = monthly_returns(&returns);
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].0, (2024, time::Month::January));
+        assert!((months[0].1 - 0.21).abs() < 1e-9);
+        assert!((months[1].1 + 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_correlation_aligns_days() {
+        let days = [
+            date!(2024 - 01 - 01),
+            date!(2024 - 01 - 02),
+            date!(2024 - 01 - 03),
+            date!(2024 - 01 - 04),
+            date!(2024 - 01 - 05),
+        ];
+        let a: Vec<_> = days
+            .iter()
+            .zip([0.01, 0.02, -0.01, 0.03, 0.0])
+            .map(|(d, r)| (*d, r))
+            .collect();
+        let b: Vec<_> = a.iter().map(|(d, r)| (*d, r * 2.0)).collect();
+        let c = correlation(&a, &b).unwrap();
+        assert!((c.r - 1.0).abs() < 1e-9);
+        assert_eq!(c.sample_size, 5);
+        assert!(c.p_value < 0.01);
+        assert!(correlation(&a, &b[..2]).is_none());
+    }
+
+    #[test]
+    fn test_leaders() {
+        let all = [
+            metrics("a", Some(0.6), Some(0.3)),
+            metrics("b", Some(0.4), Some(0.1)),
+            metrics("c", None, None),
+        ];
+        let leaders = leaders(&all);
+        assert_eq!(leaders.win_rate.as_deref(), Some("a"));
+        assert_eq!(leaders.max_drawdown.as_deref(), Some("b"));
+        assert_eq!(leaders.sharpe, None);
+    }
+}
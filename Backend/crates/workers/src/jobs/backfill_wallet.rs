@@ -1,26 +1,24 @@
 use serde::{Deserialize, Serialize};
 use shared::{
     error::{ApiError, ApiResult},
+    normalize::{normalize_transaction, persist_normalized, IngestSource},
+    progress::{BackfillStage, BackfillStatus},
     types::{
-        chain::{Action, ChainEvent, EventKind},
-        common::{SolAmount, TokenAmount, UsdAmount},
+        chain::{ChainEvent, EventKind},
         wallet::WalletAnalysis,
     },
+    RpcBudget, RpcPool,
 };
 use sqlx::PgPool;
-use std::{
-    collections::{HashMap, VecDeque},
-    time::Duration,
-};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 use time::OffsetDateTime;
 use tokio::time::sleep;
 use tracing::{debug, error, info, instrument, warn};
-use uuid::Uuid;
 
 /// Backfill job for comprehensive wallet historical analysis
 pub struct BackfillWalletJob {
     pool: PgPool,
-    rpc_client: solana_client::rpc_client::RpcClient,
+    rpc_pool: Arc<RpcPool>,
     redis: Option<redis::Client>,
     max_concurrent_wallets: usize,
 }
@@ -46,45 +44,11 @@ pub enum BackfillPriority {
     Critical,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BackfillProgress {
-    pub job_id: String,
-    pub wallet_address: String,
-    pub status: BackfillStatus,
-    pub progress_pct: f64,
-    pub transactions_processed: u32,
-    pub total_transactions: u32,
-    pub current_stage: BackfillStage,
-    pub started_at: OffsetDateTime,
-    pub estimated_completion: Option<OffsetDateTime>,
-    pub error_message: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum BackfillStatus {
-    Queued,
-    InProgress,
-    Completed,
-    Failed,
-    Cancelled,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum BackfillStage {
-    Initializing,
-    FetchingSignatures,
-    FetchingTransactions,
-    ProcessingTransactions,
-    BuildingPositions,
-    DetectingMoments,
-    Finalizing,
-}
-
 impl BackfillWalletJob {
-    pub fn new(pool: PgPool, rpc_endpoint: String, redis: Option<redis::Client>) -> Self {
+    pub fn new(pool: PgPool, rpc_pool: Arc<RpcPool>, redis: Option<redis::Client>) -> Self {
         Self {
             pool,
-            rpc_client: solana_client::rpc_client::RpcClient::new(rpc_endpoint),
+            rpc_pool,
             redis,
             max_concurrent_wallets: 5,
         }
@@ -124,13 +88,13 @@ impl BackfillWalletJob {
 
             // Start new job
             let job_pool = self.pool.clone();
-            let job_rpc = self.rpc_client.clone();
+            let job_rpc = Arc::clone(&self.rpc_pool);
             let job_redis = self.redis.clone();
 
             let handle = tokio::spawn(async move {
                 let job = BackfillWalletJob {
                     pool: job_pool,
-                    rpc_client: job_rpc,
+                    rpc_pool: job_rpc,
                     redis: job_redis,
                     max_concurrent_wallets: 1, // Individual job doesn't need concurrency
                 };
@@ -182,6 +146,8 @@ impl BackfillWalletJob {
             request.wallet_address, start_date, end_date
         );
 
+        let budget = RpcBudget::unlimited();
+
         // Step 1: Fetch transaction signatures
         self.update_progress(
             &request.job_id,
@@ -191,7 +157,7 @@ impl BackfillWalletJob {
         )
         .await?;
         let signatures = self
-            .fetch_transaction_signatures(&wallet_pubkey, start_date, end_date)
+            .fetch_transaction_signatures(&wallet_pubkey, start_date, end_date, &budget)
             .await?;
 
         info!(
@@ -208,7 +174,7 @@ impl BackfillWalletJob {
         )
         .await?;
         let transactions = self
-            .fetch_transaction_details(&signatures, &request.job_id)
+            .fetch_transaction_details(&signatures, &request.job_id, &budget)
             .await?;
 
         info!("Fetched {} transaction details", transactions.len());
@@ -286,37 +252,44 @@ impl BackfillWalletJob {
         wallet: &solana_sdk::pubkey::Pubkey,
         start_date: OffsetDateTime,
         end_date: OffsetDateTime,
+        budget: &RpcBudget,
     ) -> ApiResult<Vec<solana_sdk::signature::Signature>> {
         debug!("Fetching transaction signatures for wallet: {}", wallet);
 
         let mut all_signatures = Vec::new();
-        let mut before = None;
+        let mut before: Option<solana_sdk::signature::Signature> = None;
+        let wallet = wallet.to_string();
 
-        // Fetch signatures in batches
+        // Fetch signatures in batches; the pool handles rate limiting
         loop {
-            let signatures = self
-                .rpc_client
-                .get_signatures_for_address_with_config(
-                    wallet,
-                    solana_client::rpc_config::RpcTransactionHistoryConfig {
-                        limit: Some(1000),
-                        before,
-                        until: None,
-                        commitment: Some(
-                            solana_sdk::commitment_config::CommitmentConfig::confirmed(),
-                        ),
-                    },
-                )
+            let before_str = before.map(|s| s.to_string());
+            let page = match self
+                .rpc_pool
+                .get_signatures_for_address(&wallet, before_str.as_deref(), 1000, budget)
                 .await
-                .map_err(|e| ApiError::ExternalService {
-                    service: "Solana RPC".to_string(),
-                    error: e.to_string(),
-                })?;
+            {
+                Ok(page) => page,
+                Err(shared::RpcError::BudgetExhausted(limit)) => {
+                    info!("Stopping signature fetch, plan limit {} reached", limit);
+                    break;
+                }
+                Err(e) => {
+                    return Err(ApiError::ExternalService {
+                        service: "Solana RPC".to_string(),
+                        error: e.to_string(),
+                    })
+                }
+            };
 
-            if signatures.is_empty() {
+            if page.is_empty() {
                 break;
             }
 
+            let signatures: Vec<
+                solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature,
+            > = serde_json::from_value(serde_json::Value::Array(page))
+                .map_err(|e| ApiError::Internal(format!("Invalid signature page: {}", e)))?;
+
             let mut batch_signatures = Vec::new();
 
             for sig_info in signatures {
@@ -346,9 +319,6 @@ impl BackfillWalletJob {
             }
 
             all_signatures.extend(batch_signatures);
-
-            // Rate limiting
-            sleep(Duration::from_millis(100)).await;
         }
 
         Ok(all_signatures)
@@ -360,7 +330,8 @@ impl BackfillWalletJob {
         &self,
         signatures: &[solana_sdk::signature::Signature],
         job_id: &str,
-    ) -> ApiResult<Vec<solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta>> {
+        budget: &RpcBudget,
+    ) -> ApiResult<Vec<serde_json::Value>> {
         debug!("Fetching {} transaction details", signatures.len());
 
         let mut transactions = Vec::new();
@@ -371,22 +342,19 @@ impl BackfillWalletJob {
 
             for signature in batch {
                 match self
-                    .rpc_client
-                    .get_transaction_with_config(
-                        signature,
-                        solana_client::rpc_config::RpcTransactionConfig {
-                            encoding: Some(
-                                solana_transaction_status::UiTransactionEncoding::JsonParsed,
-                            ),
-                            commitment: Some(
-                                solana_sdk::commitment_config::CommitmentConfig::confirmed(),
-                            ),
-                            max_supported_transaction_version: Some(0),
-                        },
-                    )
+                    .rpc_pool
+                    .get_transaction(&signature.to_string(), budget)
                     .await
                 {
-                    Ok(transaction) => batch_transactions.push(transaction),
+                    Ok(Some(transaction)) => batch_transactions.push(transaction),
+                    Ok(None) => {
+                        warn!("Transaction {} not found", signature);
+                    }
+                    Err(shared::RpcError::BudgetExhausted(limit)) => {
+                        info!("Stopping transaction fetch, plan limit {} reached", limit);
+                        transactions.extend(batch_transactions);
+                        return Ok(transactions);
+                    }
                     Err(e) => {
                         warn!("Failed to fetch transaction {}: {}", signature, e);
                         continue;
@@ -405,19 +373,17 @@ impl BackfillWalletJob {
                 progress,
             )
             .await?;
-
-            // Rate limiting
-            sleep(Duration::from_millis(200)).await;
         }
 
         Ok(transactions)
     }
 
-    /// Process transactions into chain events
+    /// Normalise transactions, store their actions and return the wallet's
+    /// chain events
     #[instrument(skip(self, transactions))]
     async fn process_transactions(
         &self,
-        transactions: &[solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta],
+        transactions: &[serde_json::Value],
         wallet_address: &str,
     ) -> ApiResult<Vec<ChainEvent>> {
         debug!(
@@ -443,134 +409,65 @@ impl BackfillWalletJob {
         // Sort events by timestamp
         chain_events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
 
-        // Store events in database
-        self.store_chain_events(&chain_events).await?;
-
         Ok(chain_events)
     }
 
-    /// Parse a single transaction into chain events
+    /// Normalise a single transaction with the shared normaliser (the same
+    /// one the webhook path uses), persist its actions and return the events
+    /// that belong to `wallet_address`
     async fn parse_transaction_to_events(
         &self,
-        transaction: &solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta,
+        transaction: &serde_json::Value,
         wallet_address: &str,
     ) -> ApiResult<Vec<ChainEvent>> {
-        let mut events = Vec::new();
+        let normalized = normalize_transaction(transaction)
+            .map_err(|e| ApiError::Internal(format!("Failed to normalise transaction: {}", e)))?;
 
-        // Extract basic transaction info
-        let signature = transaction
-            .transaction
-            .signatures
-            .first()
-            .ok_or_else(|| ApiError::Internal("Transaction missing signature".to_string()))?;
-
-        let block_time = transaction
-            .block_time
+        let timestamp = normalized
+            .timestamp()
             .ok_or_else(|| ApiError::Internal("Transaction missing block time".to_string()))?;
 
-        let timestamp = OffsetDateTime::from_unix_timestamp(block_time)
-            .map_err(|e| ApiError::Internal(format!("Invalid block time: {}", e)))?;
-
-        // Parse pre and post token balances to detect transfers
-        if let (Some(pre_balances), Some(post_balances)) = (
-            &transaction.meta.as_ref()?.pre_token_balances,
-            &transaction.meta.as_ref()?.post_token_balances,
-        ) {
-            // Detect token transfers by comparing balances
-            let balance_changes = self
-                .calculate_balance_changes(pre_balances, post_balances, wallet_address)
-                .await?;
-
-            for change in balance_changes {
-                let event = ChainEvent {
-                    id: Uuid::new_v4().to_string(),
-                    signature: signature.clone(),
-                    wallet_address: wallet_address.to_string(),
-                    timestamp,
-                    kind: EventKind::Transfer,
-                    action: if change.amount > 0.0 {
-                        Action::Buy
-                    } else {
-                        Action::Sell
-                    },
-                    token_mint: change.token_mint,
-                    token_symbol: change.token_symbol,
-                    amount: TokenAmount::from(change.amount.abs()),
-                    price_usd: UsdAmount::from(0.0), // Would fetch from price service
-                    fee_sol: SolAmount::from(0.0),   // Would calculate from transaction
-                    metadata: serde_json::json!({
-                        "balance_change": change.amount,
-                        "account_index": change.account_index
-                    }),
-                };
-                events.push(event);
-            }
-        }
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| ApiError::Database(e.to_string()))?;
+        persist_normalized(&mut conn, &normalized, timestamp, IngestSource::Backfill)
+            .await
+            .map_err(|e| ApiError::Database(format!("Failed to store actions: {}", e)))?;
+
+        let events = normalized
+            .actions()
+            .into_iter()
+            .filter(|a| a.flags.get("owner").and_then(|o| o.as_str()) == Some(wallet_address))
+            .map(|a| ChainEvent {
+                id: format!("{}:{}", normalized.signature, a.log_idx),
+                signature: normalized.signature.clone(),
+                log_idx: a.log_idx,
+                slot: normalized.slot,
+                timestamp,
+                wallet: wallet_address.to_string(),
+                mint: a.mint,
+                program_id: a.program_id,
+                kind: match a.kind {
+                    "buy" => EventKind::Buy,
+                    "sell" => EventKind::Sell,
+                    "swap" => EventKind::Swap,
+                    "transfer" | "sol_transfer" => EventKind::Transfer,
+                    _ => EventKind::Transaction,
+                },
+                amount: a.amount,
+                price_usd: a.exec_px_usd,
+                route: a.route,
+                metadata: a.flags,
+            })
+            .collect();
 
         Ok(events)
     }
 
-    /// Calculate balance changes from token balance arrays
-    async fn calculate_balance_changes(
-        &self,
-        pre_balances: &[solana_transaction_status::UiTransactionTokenBalance],
-        post_balances: &[solana_transaction_status::UiTransactionTokenBalance],
-        wallet_address: &str,
-    ) -> ApiResult<Vec<BalanceChange>> {
-        let mut changes = Vec::new();
-
-        // Create maps for easier comparison
-        let pre_map: HashMap<String, &solana_transaction_status::UiTransactionTokenBalance> =
-            pre_balances
-                .iter()
-                .map(|b| (format!("{}:{}", b.account_index, b.mint.clone()), b))
-                .collect();
-
-        for post_balance in post_balances {
-            let key = format!("{}:{}", post_balance.account_index, post_balance.mint);
-
-            let pre_amount = pre_map
-                .get(&key)
-                .and_then(|b| b.ui_token_amount.ui_amount)
-                .unwrap_or(0.0);
-
-            let post_amount = post_balance.ui_token_amount.ui_amount.unwrap_or(0.0);
-            let amount_change = post_amount - pre_amount;
-
-            if amount_change.abs() > 0.000001 {
-                // Ignore dust
-                changes.push(BalanceChange {
-                    token_mint: post_balance.mint.clone(),
-                    token_symbol: "UNKNOWN".to_string(), // Would resolve from token registry
-                    amount: amount_change,
-                    account_index: post_balance.account_index,
-                });
-            }
-        }
-
-        Ok(changes)
-    }
-
     // Helper methods for remaining functionality
 
-    async fn store_chain_events(&self, events: &[ChainEvent]) -> ApiResult<()> {
-        debug!("Storing {} chain events", events.len());
-
-        for event in events {
-            sqlx::query!(
-                "INSERT INTO chain_events (id, signature, wallet_address, timestamp, kind, action, token_mint, token_symbol, amount, price_usd, fee_sol, metadata) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) ON CONFLICT (id) DO NOTHING",
-                event.id, event.signature, event.wallet_address, event.timestamp,
-                event.kind as _, event.action as _, event.token_mint, event.token_symbol,
-                event.amount.0, event.price_usd.0, event.fee_sol.0, event.metadata
-            )
-            .execute(&self.pool)
-            .await
-            .map_err(|e| ApiError::Database(format!("Failed to store chain event: {}", e)))?;
-        }
-
-        Ok(())
-    }
-
     async fn build_position_history(
         &self,
         _wallet_address: &str,
@@ -662,12 +559,3 @@ impl BackfillWalletJob {
         Ok(())
     }
 }
-
-// Supporting types
-#[derive(Debug, Clone)]
-struct BalanceChange {
-    token_mint: String,
-    token_symbol: String,
-    amount: f64,
-    account_index: u8,
-}
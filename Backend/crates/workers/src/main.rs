@@ -7,21 +7,36 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use shared::
     init_telemetry, job_span,
+    api_keys::ApiKeyStore,
+    equity::{compute_wallet_equity, EQUITY_JOB_KIND},
     observability::{init_observability, HealthChecker, MetricsRegistry, ObservabilityConfig},
     store::{make_store, ObjectStore},
-    ApiResult, AppConfig, MaybeRedis, Metrics, Pg, PriceProvider,
+    helius::{HeliusWebhookClient, MAX_WEBHOOK_ADDRESSES},
+    export::{ExportFile, ExportRequest, EXPORT_JOB_KIND},
+    normalize::IngestSource,
+    progress::{BackfillStage, BackfillStatus, ProgressReporter},
+    queue::{holds_lock, mark_cancelled, record_attempt, AttemptOutcome, CancelToken, JobError, RetryPolicy},
+    JobPriority, JobSpec, LeaderboardMetric, LeaderboardPeriod, ParentFailure,
+    PlanCadence,
+    tracking::{address_set_hash, SYNC_JOB_KIND},
+    ApiResult, AppConfig, MaybeRedis, Metrics, Pg, PriceProvider, RpcBudget, RpcPool,
+    TrackedWalletRegistry,
 };
 use sqlx::PgPool;
 use std::sync::Arc;
 use time::{Duration, OffsetDateTime};
-use tokio::time::{interval, Duration as TokioDuration};
+use tokio::sync::watch;
 use tracing::{debug, error, info, instrument, warn, Instrument};
 use ulid::Ulid;
 
+mod runtime;
+mod scheduler;
+
 // Import the new mint_nft worker
 mod jobs {
     pub mod alerts_dispatch;
     pub mod backfill_wallet;
+    pub mod export_moments;
     pub mod campaign_publish_root;
     pub mod nightly_compact;
     pub mod price_snapshots;
@@ -29,6 +44,24 @@ mod jobs {
     pub mod mint_nft; // Add the new mint_nft module
 }
 
+/// Wallets queued per plan-cadence re-analysis run
+const REANALYZE_BATCH_SIZE: i64 = 500;
+
+/// Entries stored per leaderboard snapshot
+const LEADERBOARD_SNAPSHOT_SIZE: i64 = 100;
+
+/// Days of leaderboard snapshots kept as rank history
+const LEADERBOARD_HISTORY_DAYS: i64 = 14;
+
+/// Hours after which webhook addresses are reconciled even if unchanged
+const WEBHOOK_RESYNC_HOURS: i64 = 24;
+
+/// Days export files stay downloadable
+const EXPORT_RETENTION_DAYS: i64 = 7;
+
+/// Tracked wallets refreshed per scheduled equity run
+const EQUITY_REFRESH_BATCH_SIZE: i64 = 5_000;
+
 /// Job structure from database
 #[derive(Debug, Deserialize)]
 struct Job {
@@ -37,6 +70,15 @@ struct Job {
     payload_json: serde_json::Value,
     attempts: i32,
     max_attempts: i32,
+    /// Analysis DAG this job belongs to, if any
+    root_id: Option<String>,
+}
+
+impl Job {
+    /// Key progress events are published under
+    fn progress_root(&self) -> &str {
+        self.root_id.as_deref().unwrap_or(&self.id)
+    }
 }
 
 /// Worker application state
@@ -47,6 +89,7 @@ struct WorkerState {
     metrics: Metrics,
     object_store: Arc<dyn ObjectStore>,
     http_client: Client,
+    rpc_pool: Arc<RpcPool>,
     price_provider: Arc<CompositePriceProvider>,
     detector_engine: DetectorEngine,
     worker_id: String,
@@ -62,6 +105,7 @@ impl WorkerState {
         let metrics = Metrics::new();
         let object_store = make_store(&config.asset_bucket).await?;
         let http_client = Client::new();
+        let rpc_pool = Arc::new(RpcPool::from_config(&config));
 
         // Initialize observability
         let observability_config = ObservabilityConfig::default();
@@ -94,6 +138,7 @@ impl WorkerState {
             metrics,
             object_store,
             http_client,
+            rpc_pool,
             price_provider,
             detector_engine,
             worker_id,
@@ -107,111 +152,113 @@ impl WorkerState {
 async fn main() -> Result<()> {
     init_telemetry();
 
-    let state = WorkerState::new().await?;
+    let state = Arc::new(WorkerState::new().await?);
     info!(worker_id = %state.worker_id, "Workers starting up");
 
+    let schedules = scheduler::schedules_from_config(&state.config)?;
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
     // Start background tasks
-    let mut job_processor_handle = tokio::spawn(job_processor(state.clone()));
-    let mut price_refresher_handle = tokio::spawn(price_refresher(state.clone()));
-    let mut cleanup_handle = tokio::spawn(cleanup_tasks(state.clone()));
+    let mut runtime_handle = tokio::spawn(runtime::run(
+        state.clone(),
+        runtime::RuntimeConfig::from_config(&state.config),
+        shutdown_rx.clone(),
+    ));
+    let mut scheduler_handle = tokio::spawn(scheduler::run(state.clone(), schedules, shutdown_rx));
 
     // Wait for any task to complete (which indicates an error)
     tokio::select! {
-        result = &mut job_processor_handle => {
-            error!("Job processor exited: {:?}", result);
-        }
-        result = &mut price_refresher_handle => {
-            error!("Price refresher exited: {:?}", result);
+        result = &mut runtime_handle => {
+            error!("Job runtime exited: {:?}", result);
         }
-        result = &mut cleanup_handle => {
-            error!("Cleanup task exited: {:?}", result);
+        result = &mut scheduler_handle => {
+            error!("Scheduler exited: {:?}", result);
         }
-        _ = tokio::signal::ctrl_c() => {
+        _ = shutdown_signal() => {
             info!("Shutdown signal received");
         }
     }
 
-    // Graceful shutdown
+    // Graceful shutdown: stop the scheduler, then let in-flight jobs finish
     info!("Shutting down workers");
-    job_processor_handle.abort();
-    price_refresher_handle.abort();
-    cleanup_handle.abort();
+    let _ = shutdown_tx.send(true);
+    if !scheduler_handle.is_finished() {
+        let _ = scheduler_handle.await;
+    }
+
+    if !runtime_handle.is_finished() {
+        match runtime_handle.await {
+            Ok(Err(e)) => error!(error = %e, "Job runtime shutdown failed"),
+            Err(e) => error!(error = %e, "Job runtime panicked"),
+            Ok(Ok(())) => {}
+        }
+    }
 
     Ok(())
 }
 
-/// Main job processing loop
-async fn job_processor(state: WorkerState) -> Result<()> {
-    let mut interval = interval(TokioDuration::from_millis(500));
-
-    loop {
-        interval.tick().await;
-
-        match process_next_job(&state).await {
-            Ok(true) => {
-                // Job processed successfully
-                state.metrics.increment_counter("jobs_processed_total");
-                state.metrics_registry.jobs_processed
-                    .with_label_values(&["unknown", "success"])
-                    .inc();
-            }
-            Ok(false) => {
-                // No jobs available
-            }
-            Err(e) => {
-                error!(error = %e, "Error processing job");
-                state.metrics.increment_counter("job_errors_total");
-                state.metrics_registry.jobs_failed
-                    .with_label_values(&["unknown", "processing_error"])
-                    .inc();
-
-                // Update health status
-                state.health_checker.update_check(
-                    "job_processing".to_string(),
-                    shared::observability::HealthStatus::Degraded,
-                    Some(format!("Job processing error: {}", e)),
-                    None,
-                    None,
-                ).await;
-            }
+/// Resolve on Ctrl-C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut term =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term.recv() => {}
         }
     }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
 }
 
-/// Process the next available job
-#[instrument(skip(state), fields(job_id, job_kind))]
-async fn process_next_job(state: &WorkerState) -> Result<bool> {
-    // Get next job
-    let job = sqlx::query_as!(
-        Job,
-        "SELECT id, kind, payload_json, attempts, max_attempts FROM (
-            UPDATE job_queue
-            SET status = 'running',
-                locked_by = $1,
-                locked_at = NOW(),
-                attempts = attempts + 1
-            WHERE id = (
-                SELECT id
-                FROM job_queue
-                WHERE status = 'queued'
-                    AND run_after <= NOW()
-                    AND attempts < max_attempts
-                ORDER BY run_after ASC, created_at ASC
-                LIMIT 1
-                FOR UPDATE SKIP LOCKED
-            )
-            RETURNING id, kind, payload_json, attempts, max_attempts
-        ) AS updated_job",
-        state.worker_id
+/// Lock the next runnable job, skipping kinds this worker is saturated on
+async fn dequeue_job(state: &WorkerState, excluded_kinds: &[String]) -> Result<Option<Job>> {
+    // Pick a priority level by weight, then fall back to the others in
+    // descending order if another worker drained it first
+    let ready: Vec<JobPriority> = sqlx::query_scalar!(
+        include_str!("../../../db/queries/select_ready_priorities.sql"),
+        excluded_kinds
     )
-    .fetch_optional(&state.pool.0)
-    .await?;
+    .fetch_all(&state.pool.0)
+    .await?
+    .into_iter()
+    .map(JobPriority::from_i16)
+    .collect();
 
-    let job = match job {
-        Some(j) => j,
-        None => return Ok(false), // No jobs available
+    let Some(first) = JobPriority::pick_weighted(&ready, rand::random()) else {
+        return Ok(None);
     };
+    let mut levels = vec![first.clone()];
+    let mut rest: Vec<JobPriority> = ready.into_iter().filter(|p| *p != first).collect();
+    rest.sort();
+    levels.extend(rest.into_iter().rev());
+
+    let mut job = None;
+    for level in levels {
+        job = sqlx::query_as!(
+            Job,
+            include_str!("../../../db/queries/dequeue_job.sql"),
+            state.worker_id,
+            level.as_i16(),
+            excluded_kinds
+        )
+        .fetch_optional(&state.pool.0)
+        .await?;
+        if job.is_some() {
+            break;
+        }
+    }
 
+    Ok(job)
+}
+
+/// Run a locked job and record its outcome
+#[instrument(skip(state, job), fields(job_id, job_kind))]
+async fn execute_job(state: &WorkerState, job: Job, cancel: CancelToken) -> Result<()> {
     tracing::Span::current().record("job_id", &job.id);
     tracing::Span::current().record("job_kind", &job.kind);
 
@@ -219,6 +266,7 @@ async fn process_next_job(state: &WorkerState) -> Result<bool> {
 
     // Start timing for observability
     let job_start_time = std::time::Instant::now();
+    let started_at = OffsetDateTime::now_utc();
 
     // Update job queue metrics
     state.metrics_registry.jobs_queued
@@ -227,20 +275,28 @@ async fn process_next_job(state: &WorkerState) -> Result<bool> {
 
     // Process job
     let result = match job.kind.as_str() {
-        "backfill" => job_backfill(state, &job).await,
-        "compute" => job_compute(state, &job).await,
+        "backfill" => job_backfill(state, &job, &cancel).await,
+        "compute" => job_compute(state, &job, &cancel).await,
         "refresh_prices" => job_refresh_prices(state, &job).await,
         "refresh_materialized_views" => job_refresh_materialized_views(state, &job).await,
         "calculate_extremes" => job_calculate_extremes(state, &job).await,
         "cleanup_old_data" => job_cleanup_old_data(state, &job).await,
         "generate_leaderboard" => job_generate_leaderboard(state, &job).await,
         "mint_nft" => job_mint_nft(state, &job).await, // Add the new mint_nft job type
+        "renormalize" => job_renormalize(state, &job, &cancel).await,
+        SYNC_JOB_KIND => job_sync_webhook_addresses(state, &job).await,
+        "reanalyze_due_wallets" => job_reanalyze_due_wallets(state, &job).await,
+        "notify_new_moments" => job_notify_new_moments(state, &job).await,
+        "dispatch_alerts" => job_dispatch_alerts(state, &job).await,
+        EXPORT_JOB_KIND => job_export_moments(state, &job, &cancel).await,
+        EQUITY_JOB_KIND => job_compute_equity(state, &job, &cancel).await,
         _ => {
             warn!(job_kind = %job.kind, "Unknown job type");
-            Err(anyhow!("Unknown job type: {}", job.kind))
+            Err(JobError::fatal(anyhow!("Unknown job type: {}", job.kind)).into())
         }
     }
     .instrument(job_span!(job.id, job.kind));
+    let result = result.map_err(JobError::from);
 
     // Record job processing duration
     let processing_duration = job_start_time.elapsed();
@@ -248,15 +304,38 @@ async fn process_next_job(state: &WorkerState) -> Result<bool> {
         .with_label_values(&[&job.kind])
         .observe(processing_duration.as_secs_f64());
 
-    // Update job status
+    // Update job status, unless the job was reaped and handed to another
+    // worker while this one ran it
+    let mut tx = state.pool.0.begin().await?;
+    if !holds_lock(&mut *tx, &job.id, &state.worker_id).await? {
+        tx.rollback().await?;
+        warn!(job_id = %job.id, succeeded = result.is_ok(), "Lost lock before recording job outcome");
+        return Ok(());
+    }
     match result {
         Ok(()) => {
             sqlx::query!(
-                "UPDATE job_queue SET status = 'done', locked_by = NULL, locked_at = NULL, completed_at = NOW() WHERE id = $1",
-                job.id
+                "UPDATE job_queue SET status = 'done', locked_by = NULL, locked_at = NULL, completed_at = NOW() WHERE id = $1 AND locked_by = $2",
+                job.id,
+                state.worker_id
             )
-            .execute(&state.pool.0)
+            .execute(&mut *tx)
             .await?;
+            record_attempt(
+                &mut *tx,
+                &job.id,
+                job.attempts,
+                &state.worker_id,
+                started_at,
+                AttemptOutcome::Succeeded,
+                None,
+            )
+            .await?;
+            let unblocked = shared::queue::on_job_succeeded(&mut *tx, &job.id).await?;
+            tx.commit().await?;
+            if unblocked > 0 {
+                debug!(job_id = %job.id, unblocked, "Released dependent jobs");
+            }
 
             // Update observability metrics
             state.metrics_registry.jobs_processed
@@ -274,44 +353,97 @@ async fn process_next_job(state: &WorkerState) -> Result<bool> {
                 None,
             ).await;
         }
+        Err(JobError::Cancelled) => {
+            record_attempt(
+                &mut *tx,
+                &job.id,
+                job.attempts,
+                &state.worker_id,
+                started_at,
+                AttemptOutcome::Cancelled,
+                Some(&JobError::Cancelled),
+            )
+            .await?;
+            let cascaded = mark_cancelled(&mut *tx, &job.id, "cancelled while running").await?;
+            tx.commit().await?;
+
+            state.metrics_registry.jobs_failed
+                .with_label_values(&[&job.kind, "cancelled"])
+                .inc();
+            info!(job_id = %job.id, cascaded, "Job cancelled");
+        }
         Err(e) => {
-            let status = if job.attempts >= job.max_attempts {
-                "failed"
+            let exhausted = job.attempts >= job.max_attempts;
+            let outcome = if e.is_fatal() || exhausted {
+                AttemptOutcome::Failed
             } else {
-                "queued" // Retry later
+                AttemptOutcome::Retrying
             };
-
-            sqlx::query!(
-                "UPDATE job_queue SET status = $2, locked_by = NULL, locked_at = NULL, error_message = $3, run_after = NOW() + INTERVAL '5 minutes' WHERE id = $1",
-                job.id,
-                status,
-                e.to_string()
+            let delay = RetryPolicy::for_kind(&job.kind).delay(job.attempts, rand::random());
+            let retry_at = OffsetDateTime::now_utc() + delay;
+
+            record_attempt(
+                &mut *tx,
+                &job.id,
+                job.attempts,
+                &state.worker_id,
+                started_at,
+                outcome,
+                Some(&e),
             )
-            .execute(&state.pool.0)
             .await?;
-
-            // Update observability metrics
-            if status == "failed" {
-                state.metrics_registry.jobs_failed
-                    .with_label_values(&[&job.kind, "max_attempts_exceeded"])
-                    .inc();
+            if outcome == AttemptOutcome::Failed {
+                sqlx::query!(
+                    "UPDATE job_queue SET status = 'failed', locked_by = NULL, locked_at = NULL, error_message = $2, completed_at = NOW() WHERE id = $1 AND locked_by = $3",
+                    job.id,
+                    e.to_string(),
+                    state.worker_id
+                )
+                .execute(&mut *tx)
+                .await?;
+                let cancelled = shared::queue::on_job_failed(&mut *tx, &job.id).await?;
+                if cancelled > 0 {
+                    warn!(job_id = %job.id, cancelled, "Cancelled dependent jobs");
+                }
             } else {
-                state.metrics_registry.jobs_failed
-                    .with_label_values(&[&job.kind, "will_retry"])
-                    .inc();
+                sqlx::query!(
+                    "UPDATE job_queue SET status = 'queued', locked_by = NULL, locked_at = NULL, run_after = $2 WHERE id = $1 AND locked_by = $3",
+                    job.id,
+                    retry_at,
+                    state.worker_id
+                )
+                .execute(&mut *tx)
+                .await?;
             }
+            tx.commit().await?;
 
-            if status == "failed" {
-                error!(job_id = %job.id, error = %e, "Job failed permanently");
+            // Update observability metrics
+            let reason = match (outcome, e.is_fatal()) {
+                (AttemptOutcome::Failed, true) => "fatal",
+                (AttemptOutcome::Failed, false) => "max_attempts_exceeded",
+                _ => "will_retry",
+            };
+            state.metrics_registry.jobs_failed
+                .with_label_values(&[&job.kind, reason])
+                .inc();
+
+            if outcome == AttemptOutcome::Failed {
+                error!(job_id = %job.id, error = %e, error_class = e.class(), "Job failed permanently");
             } else {
-                warn!(job_id = %job.id, error = %e, attempt = job.attempts, "Job failed, will retry");
+                warn!(
+                    job_id = %job.id,
+                    error = %e,
+                    attempt = job.attempts,
+                    retry_in_secs = delay.as_secs(),
+                    "Job failed, will retry"
+                );
             }
 
-            return Err(e);
+            return Err(e.into());
         }
     }
 
-    Ok(true)
+    Ok(())
 }
 
 /// Mint NFT job handler
@@ -327,10 +459,36 @@ async fn job_mint_nft(state: &WorkerState, job: &Job) -> Result<()> {
 
 /// Backfill wallet transaction history
 #[instrument(skip(state, job))]
-async fn job_backfill(state: &WorkerState, job: &Job) -> Result<()> {
+async fn job_backfill(state: &WorkerState, job: &Job, cancel: &CancelToken) -> Result<()> {
     let payload: BackfillPayload = serde_json::from_value(job.payload_json.clone())?;
 
     info!(wallet = %payload.wallet, "Starting wallet backfill");
+    let mut progress =
+        ProgressReporter::new(state.pool.0.clone(), job.progress_root(), &job.id, &payload.wallet);
+    let result = backfill_wallet_history(state, &payload, cancel, &mut progress).await;
+    finish_on_error(&mut progress, &result).await;
+    result
+}
+
+/// Publish a failed or cancelled status so progress subscribers are not
+/// left waiting on a job that has stopped. A retry resumes `in_progress`.
+async fn finish_on_error(progress: &mut ProgressReporter, result: &Result<()>) {
+    if let Err(e) = result {
+        let status = match e.downcast_ref::<JobError>() {
+            Some(JobError::Cancelled) => BackfillStatus::Cancelled,
+            _ => BackfillStatus::Failed,
+        };
+        progress.finish(status, Some(format!("{:#}", e))).await;
+    }
+}
+
+async fn backfill_wallet_history(
+    state: &WorkerState,
+    payload: &BackfillPayload,
+    cancel: &CancelToken,
+    progress: &mut ProgressReporter,
+) -> Result<()> {
+    progress.stage(BackfillStage::Initializing).await;
 
     // Ensure wallet cursor exists
     sqlx::query!(
@@ -340,8 +498,14 @@ async fn job_backfill(state: &WorkerState, job: &Job) -> Result<()> {
     .execute(&state.pool.0)
     .await?;
 
-    // Determine backfill window
-    let from_ts = OffsetDateTime::now_utc() - Duration::days(payload.backfill_days.unwrap_or(730));
+    // Determine backfill window; incremental refreshes only go back to the
+    // previous cursor
+    let incremental_from = payload
+        .since_ts
+        .and_then(|ts| OffsetDateTime::from_unix_timestamp(ts).ok());
+    let from_ts = incremental_from.unwrap_or_else(|| {
+        OffsetDateTime::now_utc() - Duration::days(payload.backfill_days.unwrap_or(730))
+    });
 
     // Check existing coverage
     let coverage = sqlx::query!(
@@ -356,43 +520,81 @@ async fn job_backfill(state: &WorkerState, job: &Job) -> Result<()> {
     .await?;
 
     // Skip if we already have good coverage
-    if let Some(earliest) = coverage.earliest_ts {
+    if let (Some(earliest), None) = (coverage.earliest_ts, incremental_from) {
         if earliest <= from_ts && coverage.sig_count.unwrap_or(0) > 0 {
             info!(wallet = %payload.wallet, "Wallet already has sufficient coverage");
+            progress.stage(BackfillStage::ProcessingTransactions).await;
             return Ok(());
         }
     }
 
+    // A run that stopped early left a cursor behind; pick the walk up there
+    // rather than relisting from the newest signature
+    let resume = sqlx::query!(
+        "SELECT last_cursor_sig, walk_started_at FROM wallet_cursors WHERE wallet = $1",
+        payload.wallet
+    )
+    .fetch_one(&state.pool.0)
+    .await?;
+    let (mut before, walk_started_at) = match (resume.last_cursor_sig, resume.walk_started_at) {
+        (Some(sig), Some(started)) => {
+            info!(wallet = %payload.wallet, before = %sig, "Resuming backfill");
+            (Some(sig), started)
+        }
+        _ => (None, OffsetDateTime::now_utc()),
+    };
+
     // Fetch signatures from RPC
     let mut signatures_processed = 0;
-    let mut before: Option<String> = None;
-    let max_signatures = payload.max_signatures.unwrap_or(10000);
-
-    loop {
-        if signatures_processed >= max_signatures {
-            info!("Hit signature limit, will continue in next job");
+    let mut complete = false;
+    let budget = RpcBudget::new(
+        payload.max_signatures.unwrap_or(10000),
+        payload.max_enhanced_tx.unwrap_or(10000),
+    );
+    // Signatures arrive newest first, so the share of the window walked
+    // so far is a fair measure of progress
+    let window_end = walk_started_at;
+    let window_secs = (window_end - from_ts).as_seconds_f64().max(1.0);
+
+    'walk: loop {
+        cancel.check()?;
+        progress.stage(BackfillStage::FetchingSignatures).await;
+        if budget.signatures_remaining() == 0 || budget.enhanced_tx_remaining() == 0 {
+            info!("Hit plan RPC budget, will continue in next job");
             break;
         }
 
-        let signatures = fetch_wallet_signatures(
-            &state.http_client,
-            &state.config.rpc_primary,
+        let signatures = match fetch_wallet_signatures(
+            &state.rpc_pool,
             &payload.wallet,
             before.as_deref(),
             1000,
+            &budget,
         )
-        .await?;
+        .await
+        {
+            Ok(signatures) => signatures,
+            Err(e) => {
+                warn!(wallet = %payload.wallet, error = %e, "Failed to list signatures");
+                break;
+            }
+        };
 
         if signatures.is_empty() {
+            complete = true;
             break;
         }
+        progress.progress.signatures_fetched += signatures.len() as u64;
+        progress.stage(BackfillStage::FetchingTransactions).await;
 
         let mut new_signatures = 0;
 
         for sig_info in &signatures {
+            cancel.check()?;
             if sig_info.block_time < from_ts {
                 info!("Reached backfill limit timestamp");
-                break;
+                complete = true;
+                break 'walk;
             }
 
             // Skip if already exists
@@ -402,66 +604,296 @@ async fn job_backfill(state: &WorkerState, job: &Job) -> Result<()> {
                     .await?
                     .is_some();
 
-            if exists {
-                continue;
+            if !exists {
+                // Fetch and store transaction; the pool handles rate limiting.
+                // Any failure ends the walk so the next run retries from here.
+                if let Err(e) = fetch_and_store_transaction(
+                    state,
+                    &sig_info.signature,
+                    sig_info.slot,
+                    sig_info.block_time,
+                    &budget,
+                )
+                .await
+                {
+                    if !matches!(
+                        e.downcast_ref::<shared::RpcError>(),
+                        Some(shared::RpcError::BudgetExhausted(_))
+                    ) {
+                        warn!(sig = %sig_info.signature, error = %e, "Failed to fetch transaction");
+                    }
+                    break 'walk;
+                }
+                new_signatures += 1;
+                signatures_processed += 1;
+                progress.progress.transactions_parsed += 1;
+                let walked = (window_end - sig_info.block_time).as_seconds_f64();
+                progress.advance(walked / window_secs).await;
             }
 
-            // Fetch and store transaction
-            match fetch_and_store_transaction(
-                state,
-                &sig_info.signature,
-                sig_info.slot,
-                sig_info.block_time,
+            before = Some(sig_info.signature.clone());
+        }
+
+        if new_signatures == 0 {
+            // The whole page was already stored, so the rest of the window
+            // is too
+            complete = true;
+            break;
+        }
+    }
+
+    progress.stage(BackfillStage::ProcessingTransactions).await;
+
+    // Only a walk that reached the start of the window covers it up to the
+    // moment it began; otherwise keep to_ts and leave a cursor to resume from
+    if complete {
+        sqlx::query!(
+            "UPDATE wallet_cursors
+             SET from_ts = LEAST(from_ts, $2), to_ts = GREATEST(to_ts, $3),
+                 last_cursor_sig = NULL, walk_started_at = NULL, refreshed_at = NOW()
+             WHERE wallet = $1",
+            payload.wallet,
+            from_ts,
+            walk_started_at
+        )
+        .execute(&state.pool.0)
+        .await?;
+    } else {
+        sqlx::query!(
+            "UPDATE wallet_cursors SET last_cursor_sig = $2, walk_started_at = $3
+             WHERE wallet = $1",
+            payload.wallet,
+            before,
+            walk_started_at
+        )
+        .execute(&state.pool.0)
+        .await?;
+        info!(wallet = %payload.wallet, before = ?before, "Backfill stopped early; cursor saved");
+    }
+
+    info!(
+        wallet = %payload.wallet,
+        signatures_processed,
+        signatures_listed = budget.signatures_used(),
+        enhanced_tx_fetched = budget.enhanced_tx_used(),
+        "Backfill completed"
+    );
+
+    // Standalone backfills chain their own compute; in an analyze DAG the
+    // compute job already waits on this one
+    if !payload.skip_compute {
+        enqueue_compute_job(&state.pool.0, &[payload.wallet.clone()]).await?;
+    }
+
+    Ok(())
+}
+
+/// Replay archived raw transactions through the current classifier. Every
+/// mode works in batches of `batch_size` and enqueues a follow-up job for
+/// the rest.
+#[instrument(skip(state, job))]
+async fn job_renormalize(state: &WorkerState, job: &Job, cancel: &CancelToken) -> Result<()> {
+    let payload: RenormalizePayload = serde_json::from_value(job.payload_json.clone())?;
+    let batch_size = payload.batch_size.unwrap_or(500).clamp(1, 5000);
+    let batch = batch_size as usize;
+    let version = shared::constants::system::CLASSIFIER_VERSION;
+
+    let mut follow_up = None;
+    let sigs: Vec<String> = if let Some(mut sigs) = payload.sigs {
+        if sigs.len() > batch {
+            let rest = sigs.split_off(batch);
+            follow_up = Some(serde_json::json!({ "sigs": rest, "batch_size": batch_size }));
+        }
+        sigs
+    } else if let Some(prefix) = payload.prefix.as_deref() {
+        let keys = state
+            .object_store
+            .list(prefix, payload.after.as_deref(), Some(batch))
+            .await?;
+        if keys.len() == batch {
+            follow_up = Some(serde_json::json!({
+                "prefix": prefix,
+                "after": keys.last(),
+                "batch_size": batch_size,
+            }));
+        }
+        keys.iter()
+            .filter_map(|key| {
+                key.rsplit('/')
+                    .next()
+                    .and_then(|name| name.strip_suffix(".json.zst"))
+                    .map(str::to_string)
+            })
+            .collect()
+    } else {
+        // Rows that already failed under this classifier version are left
+        // alone until the next version
+        let sigs = if let Some(wallet) = payload.wallet.as_deref() {
+            sqlx::query_scalar!(
+                "SELECT t.sig FROM tx_raw t
+                 JOIN participants p ON p.sig = t.sig
+                 WHERE p.wallet = $1 AND t.classifier_version < $2
+                   AND (t.renormalize_failed_version IS NULL OR t.renormalize_failed_version < $2)
+                 ORDER BY t.ts ASC
+                 LIMIT $3",
+                wallet,
+                version,
+                batch_size
             )
-            .await
-            {
-                Ok(()) => {
-                    new_signatures += 1;
-                    signatures_processed += 1;
-                }
-                Err(e) => {
-                    warn!(sig = %sig_info.signature, error = %e, "Failed to fetch transaction");
-                }
-            }
+            .fetch_all(&state.pool.0)
+            .await?
+        } else {
+            sqlx::query_scalar!(
+                "SELECT sig FROM tx_raw
+                 WHERE classifier_version < $1
+                   AND (renormalize_failed_version IS NULL OR renormalize_failed_version < $1)
+                 ORDER BY ts ASC LIMIT $2",
+                version,
+                batch_size
+            )
+            .fetch_all(&state.pool.0)
+            .await?
+        };
+        if sigs.len() == batch {
+            follow_up = Some(serde_json::json!({
+                "wallet": payload.wallet,
+                "batch_size": batch_size,
+            }));
+        }
+        sigs
+    };
+
+    info!(count = sigs.len(), version, "Renormalizing archived transactions");
 
-            // Rate limiting
-            if new_signatures % 10 == 0 {
-                tokio::time::sleep(TokioDuration::from_millis(100)).await;
+    let mut replayed = 0usize;
+    for sig in &sigs {
+        cancel.check()?;
+        let failure = match renormalize_transaction(state, sig).await {
+            Ok(true) => {
+                replayed += 1;
+                continue;
             }
-        }
+            Ok(false) => {
+                warn!(sig = %sig, "Raw transaction missing from archive");
+                "missing from archive".to_string()
+            }
+            Err(e) => {
+                warn!(sig = %sig, error = %e, "Failed to renormalize transaction");
+                e.to_string()
+            }
+        };
+        sqlx::query!(
+            "UPDATE tx_raw SET renormalize_failed_version = $2, renormalize_error = $3
+             WHERE sig = $1",
+            sig,
+            version,
+            failure
+        )
+        .execute(&state.pool.0)
+        .await?;
+    }
 
-        before = signatures.last().map(|s| s.signature.clone());
+    info!(replayed, total = sigs.len(), "Renormalize batch completed");
 
-        if new_signatures == 0 {
-            break; // No new signatures found
+    // Keep draining in follow-up jobs
+    if let Some(payload) = follow_up {
+        sqlx::query!(
+            include_str!("../../../db/queries/enqueue_job.sql"),
+            Ulid::new().to_string(),
+            "renormalize",
+            payload,
+            OffsetDateTime::now_utc(),
+            3i32,
+            JobPriority::Low.as_i16(),
+            None::<String>
+        )
+        .execute(&state.pool.0)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Rebuild actions for one signature from its archived raw payload.
+/// Returns `false` when the archive has no object for the signature.
+async fn renormalize_transaction(state: &WorkerState, sig: &str) -> Result<bool> {
+    let row = sqlx::query!("SELECT slot, ts, object_key FROM tx_raw WHERE sig = $1", sig)
+        .fetch_optional(&state.pool.0)
+        .await?
+        .ok_or_else(|| anyhow!("No tx_raw row for {}", sig))?;
+
+    let object_key = match row.object_key {
+        Some(key) => key,
+        None => {
+            let shard = sig
+                .get(..2)
+                .ok_or_else(|| anyhow!("Malformed signature {:?}", sig))?;
+            format!("tx/{}/{}.json.zst", shard, sig)
         }
+    };
+    if !state.object_store.exists(&object_key).await? {
+        return Ok(false);
     }
 
-    // Update wallet cursor
+    let compressed = state.object_store.get(&object_key).await?;
+    let raw = zstd::decode_all(&compressed[..])?;
+    let transaction: serde_json::Value = serde_json::from_slice(&raw)?;
+
+    // Replace the previous classification atomically, keeping the path
+    // that first ingested the signature
+    let mut tx = state.pool.0.begin().await?;
+    let previous = sqlx::query_scalar!(
+        "SELECT source FROM actions WHERE sig = $1 ORDER BY log_idx LIMIT 1",
+        sig
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    sqlx::query!("DELETE FROM actions WHERE sig = $1", sig)
+        .execute(&mut *tx)
+        .await?;
+    let source = IngestSource::for_replay(previous.as_deref());
+    process_transaction_actions(&mut *tx, row.ts, &transaction, source).await?;
     sqlx::query!(
-        "UPDATE wallet_cursors SET from_ts = $2, to_ts = NOW(), last_cursor_sig = $3 WHERE wallet = $1",
-        payload.wallet,
-        from_ts,
-        before
+        "UPDATE tx_raw SET renormalize_failed_version = NULL, renormalize_error = NULL
+         WHERE sig = $1",
+        sig
     )
-    .execute(&state.pool.0)
+    .execute(&mut *tx)
     .await?;
+    tx.commit().await?;
 
-    info!(wallet = %payload.wallet, signatures_processed, "Backfill completed");
-
-    // Enqueue compute job
-    enqueue_compute_job(&state.pool.0, &[payload.wallet.clone()]).await?;
-
-    Ok(())
+    Ok(true)
 }
 
 /// Compute positions and detect moments for wallets
 #[instrument(skip(state, job))]
-async fn job_compute(state: &WorkerState, job: &Job) -> Result<()> {
+async fn job_compute(state: &WorkerState, job: &Job, cancel: &CancelToken) -> Result<()> {
     let payload: ComputePayload = serde_json::from_value(job.payload_json.clone())?;
+    let mut progress = ProgressReporter::resume(
+        state.pool.0.clone(),
+        job.progress_root(),
+        &job.id,
+        &payload.wallets.join(","),
+    )
+    .await;
+    let result = compute_wallets(state, &payload, cancel, &mut progress).await;
+    finish_on_error(&mut progress, &result).await;
+    result
+}
 
-    for wallet in &payload.wallets {
+async fn compute_wallets(
+    state: &WorkerState,
+    payload: &ComputePayload,
+    cancel: &CancelToken,
+    progress: &mut ProgressReporter,
+) -> Result<()> {
+    let started_at = OffsetDateTime::now_utc();
+    progress.stage(BackfillStage::BuildingPositions).await;
+
+    for (i, wallet) in payload.wallets.iter().enumerate() {
+        cancel.check()?;
         info!(wallet = %wallet, "Computing positions and moments");
+        let wallet_share = 1.0 / payload.wallets.len() as f64;
 
         // Get all actions for this wallet
         let from_ts = OffsetDateTime::now_utc() - Duration::days(730);
@@ -481,14 +913,31 @@ async fn job_compute(state: &WorkerState, job: &Job) -> Result<()> {
             }
         }
 
-        for (mint, mint_actions) in mints {
+        let mint_count = mints.len().max(1) as f64;
+        for (j, (mint, mint_actions)) in mints.into_iter().enumerate() {
+            cancel.check()?;
             compute_wallet_mint_positions(state, wallet, &mint, mint_actions).await?;
+            progress
+                .advance((i as f64 + (j + 1) as f64 / mint_count) * wallet_share)
+                .await;
         }
 
         // Calculate wallet extremes
         calculate_wallet_extremes(&state.pool.0, wallet).await?;
     }
 
+    progress.stage(BackfillStage::DetectingMoments).await;
+    let moments_found = sqlx::query_scalar!(
+        "SELECT COUNT(*) AS \"count!\" FROM oof_moments WHERE wallet = ANY($1) AND created_at >= $2",
+        &payload.wallets,
+        started_at
+    )
+    .fetch_one(&state.pool.0)
+    .await?;
+    progress.progress.moments_found = moments_found as u64;
+    progress.stage(BackfillStage::Finalizing).await;
+    progress.finish(BackfillStatus::Completed, None).await;
+
     Ok(())
 }
 
@@ -572,25 +1021,397 @@ async fn job_cleanup_old_data(state: &WorkerState, job: &Job) -> Result<()> {
     .await?
     .rows_affected();
 
-    info!(deleted_jobs, deleted_snapshots, "Cleanup completed");
+    // Progress events only matter while an analysis is being watched
+    let deleted_progress = sqlx::query!("DELETE FROM job_progress WHERE created_at < $1", cutoff)
+        .execute(&state.pool.0)
+        .await?
+        .rows_affected();
+    sqlx::query!("DELETE FROM job_progress_seq WHERE updated_at < $1", cutoff)
+        .execute(&state.pool.0)
+        .await?;
+
+    let deleted_export_files = delete_expired_export_files(state).await?;
+
+    info!(
+        deleted_jobs,
+        deleted_snapshots,
+        deleted_progress,
+        deleted_export_files,
+        "Cleanup completed"
+    );
 
     Ok(())
 }
 
+/// Remove the objects behind exports past their download window. The rows
+/// stay as history with an empty file list.
+async fn delete_expired_export_files(state: &WorkerState) -> Result<u64> {
+    let expired = sqlx::query!(
+        "SELECT id, files FROM exports
+         WHERE expires_at < NOW() AND files <> '[]'::jsonb
+         ORDER BY expires_at
+         LIMIT 1000"
+    )
+    .fetch_all(&state.pool.0)
+    .await?;
+
+    let mut deleted = 0;
+    for export in expired {
+        let files: Vec<ExportFile> = serde_json::from_value(export.files)?;
+        for file in &files {
+            state.object_store.delete(&file.key).await?;
+            deleted += 1;
+        }
+        sqlx::query!("UPDATE exports SET files = '[]'::jsonb WHERE id = $1", export.id)
+            .execute(&state.pool.0)
+            .await?;
+    }
+    Ok(deleted)
+}
+
 /// Generate leaderboard data
 #[instrument(skip(state, job))]
-async fn job_generate_leaderboard(state: &WorkerState, job: &Job) -> Result<()> {
-    // This would implement leaderboard calculation logic
-    info!("Generating leaderboard data");
+async fn job_generate_leaderboard(state: &WorkerState, _job: &Job) -> Result<()> {
+    // One timestamp per run so every board switches over together
+    let computed_at = OffsetDateTime::now_utc();
+    let mut tx = state.pool.0.begin().await?;
+    let mut entries = 0;
+
+    for metric in LeaderboardMetric::ALL {
+        for period in LeaderboardPeriod::ALL {
+            entries += sqlx::query!(
+                include_str!("../../../db/queries/insert_leaderboard_snapshot.sql"),
+                metric.as_str(),
+                period.as_str(),
+                period.days(),
+                computed_at,
+                LEADERBOARD_SNAPSHOT_SIZE,
+                metric.moment_kind().map(|kind| kind.as_str())
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+    }
+
+    let pruned = sqlx::query!(
+        "DELETE FROM leaderboard_snapshots WHERE computed_at < $1",
+        computed_at - Duration::days(LEADERBOARD_HISTORY_DAYS)
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    tx.commit().await?;
+
+    info!(entries, pruned, "Leaderboard snapshots generated");
+    Ok(())
+}
+
+/// Reconcile the Helius webhook's account list with `tracked_wallets`
+#[instrument(skip(state, _job))]
+async fn job_sync_webhook_addresses(state: &WorkerState, _job: &Job) -> Result<()> {
+    let registry = TrackedWalletRegistry::new(state.pool.0.clone());
+    let expired = registry.expire_idle().await?;
+
+    let Some(client) = HeliusWebhookClient::from_config(&state.config) else {
+        debug!(expired, "Helius webhook not configured, skipping address sync");
+        return Ok(());
+    };
+
+    let wallets = registry
+        .active_wallets(MAX_WEBHOOK_ADDRESSES as i64)
+        .await?;
+
+    // Helius replaces the whole list on every update, so leave it alone
+    // while the set is unchanged; a daily pass still undoes outside edits
+    if let Some((hash, synced_at)) = registry.last_sync().await? {
+        let fresh = OffsetDateTime::now_utc() - synced_at < Duration::hours(WEBHOOK_RESYNC_HOURS);
+        if fresh && hash == address_set_hash(&wallets) {
+            debug!(
+                expired,
+                watched = wallets.len(),
+                "Webhook addresses unchanged"
+            );
+            return Ok(());
+        }
+    }
+
+    let diff = client.sync_addresses(&wallets).await?;
+    registry.mark_synced(&wallets).await?;
+
+    info!(
+        expired,
+        watched = wallets.len(),
+        added = diff.added.len(),
+        removed = diff.removed.len(),
+        "Webhook addresses synced"
+    );
+
+    Ok(())
+}
+
+/// Queue incremental refreshes for tracked wallets whose plan cadence is due
+#[instrument(skip(state, _job))]
+async fn job_reanalyze_due_wallets(state: &WorkerState, _job: &Job) -> Result<()> {
+    let (cadences, days): (Vec<String>, Vec<i32>) = PlanCadence::ALL
+        .iter()
+        .filter_map(|c| Some((c.as_str().to_string(), c.refresh_interval_days()?)))
+        .unzip();
+
+    let due = sqlx::query!(
+        include_str!("../../../db/queries/select_wallets_due_for_refresh.sql"),
+        &cadences,
+        &days,
+        REANALYZE_BATCH_SIZE
+    )
+    .fetch_all(&state.pool.0)
+    .await?;
+
+    for wallet in &due {
+        let started = OffsetDateTime::now_utc();
+        let tenant = wallet.user_ids.first().map(String::as_str).unwrap_or_default();
+
+        let backfill = JobSpec::new(
+            "backfill",
+            serde_json::json!({
+                "wallet": wallet.wallet,
+                "since_ts": wallet.to_ts.unix_timestamp(),
+                "max_signatures": wallet.max_signatures,
+                "max_enhanced_tx": wallet.max_enhanced_tx,
+                "skip_compute": true
+            }),
+        )
+        .with_priority(JobPriority::Low)
+        .with_tenant(tenant);
+        let compute = JobSpec::new("compute", serde_json::json!({ "wallets": [wallet.wallet] }))
+            .with_priority(JobPriority::Low)
+            .with_tenant(tenant)
+            .after(&[backfill.id.clone()], ParentFailure::Cancel);
+        let equity = JobSpec::new(
+            EQUITY_JOB_KIND,
+            serde_json::json!({ "wallets": [wallet.wallet] }),
+        )
+        .with_priority(JobPriority::Low)
+        .with_tenant(tenant)
+        .after(&[compute.id.clone()], ParentFailure::Cancel);
+        let notify = JobSpec::new(
+            "notify_new_moments",
+            serde_json::json!({
+                "wallet": wallet.wallet,
+                "user_ids": wallet.user_ids,
+                "since_ts": started.unix_timestamp()
+            }),
+        )
+        .with_max_attempts(3)
+        .with_priority(JobPriority::Low)
+        .with_tenant(tenant)
+        .after(&[compute.id.clone()], ParentFailure::Cancel);
+
+        let root_id = Ulid::new().to_string();
+        shared::queue::enqueue_dag(&state.pool.0, &root_id, &[backfill, compute, equity, notify])
+            .await?;
+
+        // Keep the wallet out of the next run until this refresh lands
+        sqlx::query!(
+            "UPDATE wallet_cursors SET refreshed_at = $2 WHERE wallet = $1",
+            wallet.wallet,
+            started
+        )
+        .execute(&state.pool.0)
+        .await?;
+    }
+
+    info!(queued = due.len(), "Plan-cadence re-analysis queued");
+    Ok(())
+}
+
+/// Send alerts queued by `notify_new_moments`, a batch per run
+#[instrument(skip(state, _job))]
+async fn job_dispatch_alerts(state: &WorkerState, _job: &Job) -> Result<()> {
+    let dispatcher = jobs::alerts_dispatch::AlertsDispatchJob::new(state.pool.0.clone());
+    let sent = dispatcher.process_pending_alerts().await?;
+    if sent > 0 {
+        info!(sent, "Dispatched queued alerts");
+    }
+    Ok(())
+}
+
+/// Tell a wallet's owners about moments found by an automatic refresh
+#[instrument(skip(state, job))]
+async fn job_notify_new_moments(state: &WorkerState, job: &Job) -> Result<()> {
+    use jobs::alerts_dispatch::{queue_alert, AlertPayload, AlertPriority, AlertType};
+
+    let payload: NotifyNewMomentsPayload = serde_json::from_value(job.payload_json.clone())?;
+    let since = OffsetDateTime::from_unix_timestamp(payload.since_ts)?;
+
+    let moments = sqlx::query!(
+        "SELECT id, kind FROM oof_moments
+         WHERE wallet = $1 AND created_at >= $2
+         ORDER BY severity_dec DESC NULLS LAST
+         LIMIT 20",
+        payload.wallet,
+        since
+    )
+    .fetch_all(&state.pool.0)
+    .await?;
+    if moments.is_empty() {
+        debug!(wallet = %payload.wallet, "No new moments since last refresh");
+        return Ok(());
+    }
+
+    let data = serde_json::json!({
+        "wallet": payload.wallet,
+        "moment_ids": moments.iter().map(|m| &m.id).collect::<Vec<_>>(),
+        "kinds": moments.iter().map(|m| &m.kind).collect::<Vec<_>>(),
+    });
+
+    for user_id in &payload.user_ids {
+        let alert = AlertPayload::new(
+            user_id,
+            AlertType::MomentDetected,
+            data.clone(),
+            AlertPriority::Medium,
+        );
+        queue_alert(&state.pool.0, &alert).await?;
+    }
+
+    info!(
+        wallet = %payload.wallet,
+        moments = moments.len(),
+        users = payload.user_ids.len(),
+        "Queued new-moment notifications"
+    );
+    Ok(())
+}
+
+async fn job_export_moments(state: &WorkerState, job: &Job, cancel: &CancelToken) -> Result<()> {
+    let request: ExportRequest = serde_json::from_value(job.payload_json.clone())?;
+
+    sqlx::query!(
+        "UPDATE exports SET status = 'running', started_at = NOW(), error_message = NULL
+         WHERE id = $1",
+        request.export_id
+    )
+    .execute(&state.pool.0)
+    .await?;
+
+    let result = jobs::export_moments::run_export(
+        &state.pool.0,
+        state.object_store.as_ref(),
+        &request,
+        cancel,
+    )
+    .await;
+
+    match result {
+        Ok(files) => {
+            sqlx::query!(
+                "UPDATE exports
+                 SET status = 'done', files = $2, completed_at = NOW(),
+                     expires_at = NOW() + make_interval(days => $3::int)
+                 WHERE id = $1",
+                request.export_id,
+                serde_json::to_value(&files)?,
+                EXPORT_RETENTION_DAYS as i32
+            )
+            .execute(&state.pool.0)
+            .await?;
+            if let Some(quota) = &request.quota {
+                // Metered once the export is done, so a retry that
+                // rewrites the files is not counted twice
+                let rows: u64 = files.iter().map(|f| f.rows).sum();
+                ApiKeyStore::new(state.pool.0.clone())
+                    .record_rows(&request.user_id, &quota.api_key_id, rows as i64)
+                    .await?;
+            }
+            info!(
+                export_id = %request.export_id,
+                wallet = %request.wallet,
+                files = files.len(),
+                "Export completed"
+            );
+            Ok(())
+        }
+        Err(e) => {
+            // A retry flips the row back to running
+            sqlx::query!(
+                "UPDATE exports SET status = 'failed', error_message = $2 WHERE id = $1",
+                request.export_id,
+                e.to_string()
+            )
+            .execute(&state.pool.0)
+            .await?;
+            Err(e)
+        }
+    }
+}
+
+/// Rebuild daily equity curves; with no wallets given, every tracked wallet
+#[instrument(skip(state, job, cancel))]
+async fn job_compute_equity(state: &WorkerState, job: &Job, cancel: &CancelToken) -> Result<()> {
+    let payload: ComputeEquityPayload = serde_json::from_value(job.payload_json.clone())?;
+    let wallets = if payload.wallets.is_empty() {
+        TrackedWalletRegistry::new(state.pool.0.clone())
+            .active_wallets(EQUITY_REFRESH_BATCH_SIZE)
+            .await?
+    } else {
+        payload.wallets
+    };
+
+    // One wallet's bad data must not hold up the rest of the batch; the
+    // job only fails when no wallet could be computed
+    let mut days = 0;
+    let mut failed = 0;
+    for wallet in &wallets {
+        cancel.check()?;
+        match compute_wallet_equity(&state.pool.0, wallet).await {
+            Ok(written) => days += written,
+            Err(e) => {
+                failed += 1;
+                warn!(wallet = %wallet, error = %e, "Failed to compute equity curve");
+            }
+        }
+    }
+
+    if failed > 0 && failed == wallets.len() {
+        return Err(anyhow!("Equity failed for all {} wallets", failed));
+    }
+    info!(wallets = wallets.len(), failed, days, "Equity curves computed");
     Ok(())
 }
 
 // Payload structures
+#[derive(Deserialize)]
+struct RenormalizePayload {
+    /// Explicit signatures to replay; otherwise stale rows are selected
+    sigs: Option<Vec<String>>,
+    /// Restrict stale-row selection to one wallet
+    wallet: Option<String>,
+    /// Replay every archived object under this key prefix, e.g. `tx/ab/`
+    prefix: Option<String>,
+    /// Resume a prefix replay after this object key
+    after: Option<String>,
+    batch_size: Option<i64>,
+}
+
 #[derive(Deserialize)]
 struct BackfillPayload {
     wallet: String,
     backfill_days: Option<i64>,
-    max_signatures: Option<usize>,
+    max_signatures: Option<u64>,
+    max_enhanced_tx: Option<u64>,
+    #[serde(default)]
+    skip_compute: bool,
+    /// Unix timestamp to backfill back to instead of `backfill_days`
+    since_ts: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct NotifyNewMomentsPayload {
+    wallet: String,
+    user_ids: Vec<String>,
+    /// Moments created at or after this unix timestamp are new
+    since_ts: i64,
 }
 
 #[derive(Deserialize)]
@@ -598,6 +1419,12 @@ struct ComputePayload {
     wallets: Vec<String>,
 }
 
+#[derive(Deserialize)]
+struct ComputeEquityPayload {
+    #[serde(default)]
+    wallets: Vec<String>,
+}
+
 #[derive(Deserialize)]
 struct RefreshPricesPayload {
     mints: Option<Vec<String>>,
@@ -622,82 +1449,27 @@ struct SignatureInfo {
     err: Option<serde_json::Value>,
 }
 
-/// Background task to refresh prices periodically
-async fn price_refresher(state: WorkerState) -> Result<()> {
-    let mut interval = interval(TokioDuration::from_secs(300)); // Every 5 minutes
-
-    loop {
-        interval.tick().await;
-
-        match enqueue_price_refresh_job(&state.pool.0).await {
-            Ok(()) => debug!("Price refresh job enqueued"),
-            Err(e) => error!(error = %e, "Failed to enqueue price refresh job"),
-        }
-    }
-}
-
-/// Background task for cleanup and maintenance
-async fn cleanup_tasks(state: WorkerState) -> Result<()> {
-    let mut interval = interval(TokioDuration::from_secs(3600)); // Every hour
-
-    loop {
-        interval.tick().await;
-
-        // Enqueue materialized view refresh
-        if let Err(e) = enqueue_materialized_view_refresh(&state.pool.0).await {
-            error!(error = %e, "Failed to enqueue MV refresh");
-        }
-
-        // Enqueue cleanup job (daily)
-        let now = OffsetDateTime::now_utc();
-        if now.hour() == 2 {
-            // Run at 2 AM UTC
-            if let Err(e) = enqueue_cleanup_job(&state.pool.0).await {
-                error!(error = %e, "Failed to enqueue cleanup job");
-            }
-        }
-    }
-}
-
 // Helper functions continue in the next part due to length...
 
-/// Fetch wallet signatures from RPC
+/// Fetch wallet signatures through the shared RPC pool
 async fn fetch_wallet_signatures(
-    client: &Client,
-    rpc_url: &str,
+    rpc_pool: &RpcPool,
     wallet: &str,
     before: Option<&str>,
     limit: usize,
+    budget: &RpcBudget,
 ) -> Result<Vec<SignatureInfo>> {
-    let mut params = serde_json::json!([wallet, {"limit": limit}]);
-    if let Some(before_sig) = before {
-        params[1]["before"] = serde_json::json!(before_sig);
-    }
-
-    let request = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getSignaturesForAddress",
-        "params": params
-    });
-
-    let response = client
-        .post(rpc_url)
-        .json(&request)
-        .timeout(std::time::Duration::from_secs(30))
-        .send()
-        .await?
-        .error_for_status()?;
-
-    let response_json: serde_json::Value = response.json().await?;
-
-    let signatures = response_json
-        .get("result")
-        .and_then(|r| r.as_array())
-        .ok_or_else(|| anyhow!("Invalid response format"))?;
+    let signatures = match rpc_pool
+        .get_signatures_for_address(wallet, before, limit, budget)
+        .await
+    {
+        Ok(page) => page,
+        Err(shared::RpcError::BudgetExhausted(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
 
     let mut result = Vec::new();
-    for sig_obj in signatures {
+    for sig_obj in &signatures {
         if let (Some(signature), Some(slot), Some(block_time)) = (
             sig_obj.get("signature").and_then(|s| s.as_str()),
             sig_obj.get("slot").and_then(|s| s.as_i64()),
@@ -723,33 +1495,15 @@ async fn fetch_and_store_transaction(
     signature: &str,
     slot: i64,
     block_time: OffsetDateTime,
+    budget: &RpcBudget,
 ) -> Result<()> {
     // Fetch transaction
-    let request = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getTransaction",
-        "params": [signature, {"encoding": "json", "maxSupportedTransactionVersion": 0}]
-    });
-
-    let response = state
-        .http_client
-        .post(&state.config.rpc_primary)
-        .json(&request)
-        .timeout(std::time::Duration::from_secs(30))
-        .send()
+    let transaction = state
+        .rpc_pool
+        .get_transaction(signature, budget)
         .await?
-        .error_for_status()?;
-
-    let response_json: serde_json::Value = response.json().await?;
-
-    let transaction = response_json
-        .get("result")
         .ok_or_else(|| anyhow!("Transaction not found"))?;
-
-    if transaction.is_null() {
-        return Err(anyhow!("Transaction is null"));
-    }
+    let transaction = &transaction;
 
     // Compress and store transaction
     let tx_json = serde_json::to_vec(transaction)?;
@@ -771,103 +1525,22 @@ async fn fetch_and_store_transaction(
     .execute(&state.pool.0)
     .await?;
 
-    // Extract and store participants
-    let account_keys = transaction
-        .get("transaction")
-        .and_then(|t| t.get("message"))
-        .and_then(|m| m.get("accountKeys"))
-        .and_then(|ak| ak.as_array())
-        .unwrap_or(&vec![]);
-
-    for account in account_keys {
-        if let Some(pubkey) = account.as_str() {
-            sqlx::query!(
-                "INSERT INTO participants (sig, wallet) VALUES ($1, $2) ON CONFLICT DO NOTHING",
-                signature,
-                pubkey
-            )
-            .execute(&state.pool.0)
-            .await?;
-        }
-    }
-
-    // Process token transfers and other actions
-    process_transaction_actions(state, signature, slot, block_time, transaction).await?;
+    // Normalise into participants and actions
+    let mut conn = state.pool.0.acquire().await?;
+    process_transaction_actions(&mut *conn, block_time, transaction, IngestSource::Backfill).await?;
 
     Ok(())
 }
 
-/// Process transaction to extract actions
+/// Normalise a raw transaction and store its participants and actions
 async fn process_transaction_actions(
-    state: &WorkerState,
-    signature: &str,
-    slot: i64,
+    conn: &mut sqlx::PgConnection,
     block_time: OffsetDateTime,
     transaction: &serde_json::Value,
+    source: IngestSource,
 ) -> Result<()> {
-    // Extract token transfers if available (from enhanced RPC)
-    if let Some(token_transfers) = transaction
-        .get("meta")
-        .and_then(|m| m.get("tokenTransfers"))
-        .and_then(|tt| tt.as_array())
-    {
-        for (idx, transfer) in token_transfers.iter().enumerate() {
-            let action_id = Ulid::new().to_string();
-
-            let mint = transfer.get("mint").and_then(|m| m.as_str());
-            let amount = transfer
-                .get("tokenAmount")
-                .and_then(|a| a.as_str())
-                .and_then(|s| s.parse::<Decimal>().ok());
-            let from_account = transfer.get("fromUserAccount").and_then(|f| f.as_str());
-            let to_account = transfer.get("toUserAccount").and_then(|t| t.as_str());
-
-            let flags = serde_json::json!({
-                "from": from_account,
-                "to": to_account,
-                "transferType": "token"
-            });
-
-            sqlx::query!(
-                include_str!("../../../db/queries/insert_action.sql"),
-                action_id,
-                signature,
-                idx as i32,
-                slot,
-                block_time,
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", // Token program
-                "transfer",
-                mint,
-                amount,
-                None::<Decimal>, // exec_px_usd_dec
-                None::<String>,  // route
-                flags
-            )
-            .execute(&state.pool.0)
-            .await?;
-        }
-    } else {
-        // Fallback: create a minimal action to mark transaction presence
-        let action_id = Ulid::new().to_string();
-        sqlx::query!(
-            include_str!("../../../db/queries/insert_action.sql"),
-            action_id,
-            signature,
-            0i32,
-            slot,
-            block_time,
-            "",
-            "tx",
-            None::<String>,
-            None::<Decimal>,
-            None::<Decimal>,
-            None::<String>,
-            serde_json::json!({})
-        )
-        .execute(&state.pool.0)
-        .await?;
-    }
-
+    let normalized = shared::normalize::normalize_transaction(transaction)?;
+    shared::normalize::persist_normalized(conn, &normalized, block_time, source).await?;
     Ok(())
 }
 
@@ -967,66 +1640,9 @@ async fn enqueue_compute_job(pool: &PgPool, wallets: &[String]) -> Result<()> {
         "compute",
         payload,
         OffsetDateTime::now_utc(),
-        5i32 // max_attempts
-    )
-    .execute(pool)
-    .await?;
-
-    Ok(())
-}
-
-/// Enqueue a price refresh job
-async fn enqueue_price_refresh_job(pool: &PgPool) -> Result<()> {
-    let job_id = Ulid::new().to_string();
-    let payload = serde_json::json!({});
-
-    sqlx::query!(
-        include_str!("../../../db/queries/enqueue_job.sql"),
-        job_id,
-        "refresh_prices",
-        payload,
-        OffsetDateTime::now_utc(),
-        3i32
-    )
-    .execute(pool)
-    .await?;
-
-    Ok(())
-}
-
-/// Enqueue materialized view refresh job
-async fn enqueue_materialized_view_refresh(pool: &PgPool) -> Result<()> {
-    let job_id = Ulid::new().to_string();
-    let payload = serde_json::json!({});
-
-    sqlx::query!(
-        include_str!("../../../db/queries/enqueue_job.sql"),
-        job_id,
-        "refresh_materialized_views",
-        payload,
-        OffsetDateTime::now_utc(),
-        3i32
-    )
-    .execute(pool)
-    .await?;
-
-    Ok(())
-}
-
-/// Enqueue cleanup job
-async fn enqueue_cleanup_job(pool: &PgPool) -> Result<()> {
-    let job_id = Ulid::new().to_string();
-    let payload = serde_json::json!({
-        "days_to_keep": 90
-    });
-
-    sqlx::query!(
-        include_str!("../../../db/queries/enqueue_job.sql"),
-        job_id,
-        "cleanup_old_data",
-        payload,
-        OffsetDateTime::now_utc(),
-        3i32
+        5i32, // max_attempts
+        JobPriority::Normal.as_i16(),
+        None::<String>
     )
     .execute(pool)
     .await?;
@@ -1044,6 +1660,7 @@ impl Clone for WorkerState {
             metrics: self.metrics.clone(),
             object_store: Arc::clone(&self.object_store),
             http_client: self.http_client.clone(),
+            rpc_pool: Arc::clone(&self.rpc_pool),
             price_provider: Arc::clone(&self.price_provider),
             detector_engine: self.detector_engine.clone(), // This would need Clone implementation
             worker_id: self.worker_id.clone(),
@@ -1155,7 +1772,7 @@ async fn job_backfill(pg: &Pg, payload: serde_json::Value) -> anyhow::Result<()>
                 }
                 // Minimal action row
                 let _ = sqlx::query(include_str!("../../../../db/queries/insert_action.sql"))
-                    .bind(Ulid::new().to_string())
+                    .bind(shared::normalize::action_id(sig, 0, "tx"))
                     .bind(sig)
                     .bind(0i32)
                     .bind(o.get("slot").and_then(|v| v.as_i64()).unwrap_or(0))
@@ -1167,6 +1784,7 @@ async fn job_backfill(pg: &Pg, payload: serde_json::Value) -> anyhow::Result<()>
                     .bind(None::<Decimal>)
                     .bind(None::<String>)
                     .bind(serde_json::json!({}))
+                    .bind(IngestSource::Backfill.as_str())
                     .execute(&pg.0)
                     .await;
             }
@@ -1308,6 +1926,7 @@ async fn compute_wallet_mint(
                         .bind(p)
                         .bind(realized)
                         .bind(r.try_get::<Option<String>, _>("sig").ok().flatten())
+                        .bind(None::<serde_json::Value>)
                         .execute(&pg.0)
                         .await;
                         // S2E detector on exit: look ahead 7d for peak
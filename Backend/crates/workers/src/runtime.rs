@@ -0,0 +1,404 @@
+//! Concurrent job runtime.
+//!
+//! Runs up to `slots` jobs at once per worker process, with optional
+//! per-kind caps so a burst of backfills cannot occupy every slot. Idle
+//! slots wake on `NOTIFY job_queue` (sent by a trigger whenever a row
+//! becomes queued) and fall back to polling in case a notification is
+//! missed. On shutdown no new jobs are taken, in-flight jobs get a grace
+//! period, and anything still locked by this worker is handed back.
+//!
+//! Each running job heartbeats its lock, and every worker runs a reaper
+//! that requeues jobs whose worker died without releasing them. The
+//! heartbeat is also how a worker hears that a job was cancelled.
+
+use anyhow::Result;
+use futures::FutureExt;
+use shared::queue::{
+    heartbeat, mark_cancelled, reap_stale_locks, CancelToken, Heartbeat, LockTimeouts,
+};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::{watch, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::task::{JoinError, JoinHandle, JoinSet};
+use tokio::time::{interval, sleep, timeout, Duration};
+use tracing::{debug, error, info, warn};
+
+use crate::WorkerState;
+
+/// Channel the `trg_job_queue_notify` trigger publishes on
+const NOTIFY_CHANNEL: &str = "job_queue";
+
+/// How often each worker looks for stale locks
+const REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Per-kind concurrency caps, parsed from e.g. `backfill=2,renormalize=1`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KindLimits(HashMap<String, usize>);
+
+impl KindLimits {
+    /// Parse a comma-separated `kind=limit` list; malformed entries are ignored
+    pub fn parse(spec: &str) -> Self {
+        let limits = spec
+            .split(',')
+            .filter_map(|entry| {
+                let (kind, limit) = entry.split_once('=')?;
+                let limit = limit.trim().parse().ok()?;
+                Some((kind.trim().to_string(), limit))
+            })
+            .filter(|(kind, _)| !kind.is_empty())
+            .collect();
+        Self(limits)
+    }
+
+    pub fn limit_for(&self, kind: &str) -> Option<usize> {
+        self.0.get(kind).copied()
+    }
+
+    /// Kinds that have reached their cap given what is currently running
+    pub fn saturated(&self, in_flight: &HashMap<String, usize>) -> Vec<String> {
+        let mut kinds: Vec<String> = self
+            .0
+            .iter()
+            .filter(|(kind, limit)| in_flight.get(*kind).copied().unwrap_or(0) >= **limit)
+            .map(|(kind, _)| kind.clone())
+            .collect();
+        kinds.sort();
+        kinds
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    pub slots: usize,
+    pub kind_limits: KindLimits,
+    pub poll_interval: Duration,
+    pub shutdown_grace: Duration,
+    pub heartbeat: Duration,
+    pub lock_timeouts: LockTimeouts,
+}
+
+impl RuntimeConfig {
+    pub fn from_config(cfg: &shared::AppConfig) -> Self {
+        Self {
+            slots: cfg.worker_concurrency.max(1),
+            kind_limits: KindLimits::parse(&cfg.worker_kind_limits),
+            poll_interval: Duration::from_millis(cfg.worker_poll_interval_ms.max(100)),
+            shutdown_grace: Duration::from_secs(cfg.worker_shutdown_grace_secs),
+            heartbeat: Duration::from_secs(cfg.worker_heartbeat_secs.max(1)),
+            lock_timeouts: LockTimeouts::parse(
+                Duration::from_secs(cfg.worker_lock_timeout_secs),
+                &cfg.worker_lock_timeouts,
+            ),
+        }
+    }
+}
+
+/// Run jobs until `shutdown` flips to true, then drain and release locks
+pub async fn run(
+    state: Arc<WorkerState>,
+    config: RuntimeConfig,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    info!(
+        slots = config.slots,
+        kind_limits = ?config.kind_limits,
+        "Job runtime starting"
+    );
+
+    let wake = Arc::new(Notify::new());
+    let listener = tokio::spawn(listen_for_jobs(state.pool.0.clone(), wake.clone()));
+    let reaper = tokio::spawn(reap_loop(state.clone(), config.lock_timeouts.clone()));
+    let slots = Arc::new(Semaphore::new(config.slots));
+    let in_flight: Arc<Mutex<HashMap<String, usize>>> = Arc::new(Mutex::new(HashMap::new()));
+    let mut tasks = JoinSet::new();
+    let mut poll = interval(config.poll_interval);
+
+    loop {
+        // Reap finished tasks so the set does not grow unbounded
+        while let Some(Some(joined)) = tasks.join_next().now_or_never() {
+            log_join(joined);
+        }
+
+        let permit = tokio::select! {
+            permit = slots.clone().acquire_owned() => permit?,
+            _ = shutdown.changed() => break,
+        };
+        if *shutdown.borrow() {
+            break;
+        }
+
+        let excluded = config
+            .kind_limits
+            .saturated(&in_flight.lock().expect("in-flight map poisoned"));
+
+        match crate::dequeue_job(&state, &excluded).await {
+            Ok(Some(job)) => {
+                let kind = job.kind.clone();
+                *in_flight
+                    .lock()
+                    .expect("in-flight map poisoned")
+                    .entry(kind.clone())
+                    .or_default() += 1;
+
+                let state = state.clone();
+                let slot = SlotGuard {
+                    in_flight: in_flight.clone(),
+                    kind: kind.clone(),
+                    wake: wake.clone(),
+                    _permit: permit,
+                };
+                let every = config.heartbeat;
+                tasks.spawn(async move {
+                    // Held to the end so the slot frees even if the job panics
+                    let _slot = slot;
+                    let cancel = CancelToken::new();
+                    let _beat = AbortOnDrop(tokio::spawn(keep_alive(
+                        state.clone(),
+                        job.id.clone(),
+                        every,
+                        cancel.clone(),
+                    )));
+                    let result = crate::execute_job(&state, job, cancel).await;
+                    record_outcome(&state, &kind, result).await;
+                });
+            }
+            Ok(None) => {
+                drop(permit);
+                tokio::select! {
+                    _ = wake.notified() => {}
+                    _ = poll.tick() => {}
+                    _ = shutdown.changed() => break,
+                }
+            }
+            Err(e) => {
+                drop(permit);
+                error!(error = %e, "Failed to dequeue job");
+                tokio::select! {
+                    _ = sleep(config.poll_interval) => {}
+                    _ = shutdown.changed() => break,
+                }
+            }
+        }
+    }
+
+    listener.abort();
+    reaper.abort();
+    info!(in_flight = tasks.len(), "Job runtime draining");
+
+    let drained = timeout(config.shutdown_grace, async {
+        while let Some(joined) = tasks.join_next().await {
+            log_join(joined);
+        }
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            remaining = tasks.len(),
+            "Shutdown grace period elapsed, aborting in-flight jobs"
+        );
+        tasks.abort_all();
+        while tasks.join_next().await.is_some() {}
+    }
+
+    let released = release_locks(&state.pool.0, &state.worker_id).await?;
+    info!(released, "Job runtime stopped");
+    Ok(())
+}
+
+/// A job's claim on a runtime slot and its kind's in-flight count,
+/// returned on drop
+struct SlotGuard {
+    in_flight: Arc<Mutex<HashMap<String, usize>>>,
+    kind: String,
+    wake: Arc<Notify>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        // Recover the map rather than panic again while unwinding
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(n) = in_flight.get_mut(&self.kind) {
+            *n = n.saturating_sub(1);
+        }
+        drop(in_flight);
+        // A freed slot or kind may make a waiting job runnable
+        self.wake.notify_one();
+    }
+}
+
+/// Stops a job's heartbeat however the job ends, panics included
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Surface job tasks that panicked; aborted tasks are expected on shutdown
+fn log_join(joined: Result<(), JoinError>) {
+    if let Err(e) = joined {
+        if e.is_panic() {
+            error!(error = %e, "Job task panicked");
+        }
+    }
+}
+
+/// Hand back jobs this worker still holds so another worker can take them.
+/// The interrupted attempt is not counted. Jobs already asked to cancel are
+/// cancelled instead of requeued.
+pub async fn release_locks(pg: &PgPool, worker_id: &str) -> Result<u64> {
+    let mut tx = pg.begin().await?;
+    let cancelled = sqlx::query_scalar!(
+        "SELECT id FROM job_queue
+         WHERE locked_by = $1 AND status = 'running' AND cancel_requested_at IS NOT NULL",
+        worker_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for id in &cancelled {
+        mark_cancelled(&mut *tx, id, "cancelled during worker shutdown").await?;
+    }
+
+    let res = sqlx::query!(
+        "UPDATE job_queue
+         SET status = 'queued', locked_by = NULL, locked_at = NULL,
+             attempts = GREATEST(attempts - 1, 0), run_after = NOW()
+         WHERE locked_by = $1 AND status = 'running'",
+        worker_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(res.rows_affected() + cancelled.len() as u64)
+}
+
+/// Heartbeat a running job's lock until aborted, tripping `cancel` when
+/// the job is asked to stop or the lock is lost
+async fn keep_alive(state: Arc<WorkerState>, job_id: String, every: Duration, cancel: CancelToken) {
+    loop {
+        sleep(every).await;
+        match heartbeat(&state.pool.0, &job_id, &state.worker_id).await {
+            Ok(Heartbeat::Held) => {}
+            Ok(Heartbeat::CancelRequested) => {
+                if !cancel.is_cancelled() {
+                    info!(job_id = %job_id, "Cancellation requested for running job");
+                    cancel.cancel();
+                }
+            }
+            Ok(Heartbeat::Lost) => {
+                // Someone else owns the job now; stop working on it
+                warn!(job_id = %job_id, "Lost lock on running job");
+                cancel.cancel();
+                return;
+            }
+            Err(e) => warn!(job_id = %job_id, error = %e, "Job heartbeat failed"),
+        }
+    }
+}
+
+/// Periodically reclaim jobs whose worker stopped heartbeating
+async fn reap_loop(state: Arc<WorkerState>, timeouts: LockTimeouts) {
+    let mut tick = interval(REAP_INTERVAL);
+    loop {
+        tick.tick().await;
+        match reap_stale_locks(&state.pool.0, &timeouts).await {
+            Ok(report) if report.requeued + report.failed > 0 => {
+                warn!(
+                    requeued = report.requeued,
+                    failed = report.failed,
+                    cancelled = report.cancelled,
+                    "Reaped stale job locks"
+                );
+            }
+            Ok(_) => {}
+            Err(e) => error!(error = %e, "Stale-lock reaper failed"),
+        }
+    }
+}
+
+/// Forward queue notifications to the dispatcher, reconnecting on failure
+async fn listen_for_jobs(pg: PgPool, wake: Arc<Notify>) {
+    loop {
+        match PgListener::connect_with(&pg).await {
+            Ok(mut listener) => match listener.listen(NOTIFY_CHANNEL).await {
+                Ok(()) => {
+                    debug!("Listening for job notifications");
+                    loop {
+                        match listener.recv().await {
+                            Ok(_) => wake.notify_one(),
+                            Err(e) => {
+                                warn!(error = %e, "Job notification listener failed");
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!(error = %e, "Failed to LISTEN on job queue"),
+            },
+            Err(e) => warn!(error = %e, "Failed to connect job notification listener"),
+        }
+        // Polling covers the gap until we reconnect
+        sleep(Duration::from_secs(5)).await;
+    }
+}
+
+async fn record_outcome(state: &WorkerState, kind: &str, result: Result<()>) {
+    match result {
+        Ok(()) => {
+            state.metrics.increment_counter("jobs_processed_total");
+        }
+        Err(e) => {
+            error!(job_kind = %kind, error = %e, "Error processing job");
+            state.metrics.increment_counter("job_errors_total");
+            state
+                .health_checker
+                .update_check(
+                    "job_processing".to_string(),
+                    shared::observability::HealthStatus::Degraded,
+                    Some(format!("Job processing error: {}", e)),
+                    None,
+                    None,
+                )
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kind_limits() {
+        let limits = KindLimits::parse("backfill=2, renormalize = 1,bogus,=3,compute=x");
+        assert_eq!(limits.limit_for("backfill"), Some(2));
+        assert_eq!(limits.limit_for("renormalize"), Some(1));
+        assert_eq!(limits.limit_for("compute"), None);
+        assert_eq!(KindLimits::parse(""), KindLimits::default());
+    }
+
+    #[test]
+    fn test_saturated_kinds() {
+        let limits = KindLimits::parse("backfill=2,renormalize=1");
+        let mut in_flight = HashMap::new();
+        assert!(limits.saturated(&in_flight).is_empty());
+
+        in_flight.insert("backfill".to_string(), 1);
+        in_flight.insert("compute".to_string(), 10);
+        assert!(limits.saturated(&in_flight).is_empty());
+
+        in_flight.insert("backfill".to_string(), 2);
+        in_flight.insert("renormalize".to_string(), 1);
+        assert_eq!(
+            limits.saturated(&in_flight),
+            vec!["backfill", "renormalize"]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! Cron-style scheduler for recurring jobs.
+//!
+//! Every worker replica runs the scheduler against the same schedule list.
+//! When a slot comes due, each replica tries to advance the schedule's
+//! `last_fire_at` in `job_schedules`; only the one that succeeds enqueues
+//! the job, in the same transaction, so a slot is enqueued exactly once no
+//! matter how many replicas are up. Slots missed while no worker was
+//! running are not replayed: only the most recent one fires.
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use shared::{equity::EQUITY_JOB_KIND, tracking::SYNC_JOB_KIND, AppConfig, JobPriority};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime, Time};
+use tokio::sync::watch;
+use tokio::time::{interval, Duration as TokioDuration, MissedTickBehavior};
+use tracing::{debug, error, info};
+use ulid::Ulid;
+
+use crate::WorkerState;
+
+/// How often schedules are evaluated; finer than the one-minute cron grain
+const TICK: TokioDuration = TokioDuration::from_secs(20);
+
+/// How far back to look for the latest matching minute
+const MAX_LOOKBACK_DAYS: i64 = 366;
+
+/// Parsed 5-field cron expression (`minute hour day-of-month month
+/// day-of-week`), evaluated in UTC. Fields accept `*`, numbers, ranges
+/// (`1-5`), lists (`1,15`) and steps (`*/5`, `10-50/10`); day-of-week runs
+/// 0-6 from Sunday, with 7 also meaning Sunday.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(anyhow!("expected 5 cron fields, got {}", fields.len()));
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(dom, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            dom_restricted: !dom.starts_with('*'),
+            dow_restricted: !dow.starts_with('*'),
+        })
+    }
+}
+
+/// Parse one cron field into a bitset of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("zero step in cron field '{}'", field));
+        }
+
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (lo.parse()?, hi.parse()?)
+        } else {
+            let value: u32 = range.parse()?;
+            // `5/15` means "from 5 to the end, every 15"
+            (value, if part.contains('/') { max } else { value })
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(anyhow!(
+                "cron field '{}' out of range {}-{}",
+                field,
+                min,
+                max
+            ));
+        }
+
+        for value in (lo..=hi).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn has(bits: u64, value: u8) -> bool {
+    bits & (1 << value) != 0
+}
+
+impl CronSchedule {
+    fn day_matches(&self, t: OffsetDateTime) -> bool {
+        let dom = has(self.days_of_month, t.day());
+        let dow = has(self.days_of_week, t.weekday().number_days_from_sunday());
+        // Standard cron: when both day fields are restricted, either may match
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+
+    pub fn matches(&self, t: OffsetDateTime) -> bool {
+        has(self.months, u8::from(t.month()))
+            && self.day_matches(t)
+            && has(self.hours, t.hour())
+            && has(self.minutes, t.minute())
+    }
+
+    /// Latest matching minute at or before `t`, if any within a year
+    pub fn latest_at_or_before(&self, t: OffsetDateTime) -> Option<OffsetDateTime> {
+        let mut t = t.replace_time(Time::from_hms(t.hour(), t.minute(), 0).ok()?);
+        let floor = t - Duration::days(MAX_LOOKBACK_DAYS);
+
+        while t > floor {
+            let start_of_day = t.replace_time(Time::MIDNIGHT);
+            if !has(self.months, u8::from(t.month())) {
+                // Jump to the last minute of the previous month
+                t = start_of_day - Duration::days(i64::from(t.day()) - 1) - Duration::MINUTE;
+            } else if !self.day_matches(t) {
+                t = start_of_day - Duration::MINUTE;
+            } else if !has(self.hours, t.hour()) {
+                t = t - Duration::minutes(i64::from(t.minute())) - Duration::MINUTE;
+            } else if !has(self.minutes, t.minute()) {
+                t -= Duration::MINUTE;
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+/// A recurring job and the cron expression that drives it
+#[derive(Debug, Clone)]
+pub struct ScheduledJob {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub expr: String,
+    pub cron: CronSchedule,
+    pub payload: Value,
+    pub priority: JobPriority,
+    pub max_attempts: i32,
+}
+
+impl ScheduledJob {
+    fn new(name: &'static str, kind: &'static str, expr: &str) -> Result<Self> {
+        let cron = expr
+            .parse()
+            .with_context(|| format!("invalid cron expression for schedule '{}'", name))?;
+        Ok(Self {
+            name,
+            kind,
+            expr: expr.trim().to_string(),
+            cron,
+            payload: serde_json::json!({}),
+            priority: JobPriority::Low,
+            max_attempts: 3,
+        })
+    }
+
+    fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    fn with_priority(mut self, priority: JobPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Build the schedule list from config, skipping schedules left empty
+pub fn schedules_from_config(cfg: &AppConfig) -> Result<Vec<ScheduledJob>> {
+    let entries = [
+        (
+            "refresh_prices",
+            "refresh_prices",
+            &cfg.schedule_refresh_prices,
+        ),
+        (
+            "refresh_materialized_views",
+            "refresh_materialized_views",
+            &cfg.schedule_refresh_views,
+        ),
+        (
+            "cleanup_old_data",
+            "cleanup_old_data",
+            &cfg.schedule_cleanup,
+        ),
+        (
+            "generate_leaderboard",
+            "generate_leaderboard",
+            &cfg.schedule_leaderboard,
+        ),
+        (
+            "sync_webhook_addresses",
+            SYNC_JOB_KIND,
+            &cfg.schedule_webhook_sync,
+        ),
+        (
+            "plan_reanalysis",
+            "reanalyze_due_wallets",
+            &cfg.schedule_plan_reanalysis,
+        ),
+        ("wallet_equity", EQUITY_JOB_KIND, &cfg.schedule_equity),
+        (
+            "dispatch_alerts",
+            "dispatch_alerts",
+            &cfg.schedule_alert_dispatch,
+        ),
+    ];
+
+    let mut schedules = Vec::new();
+    for (name, kind, expr) in entries {
+        if expr.trim().is_empty() {
+            continue;
+        }
+        let job = ScheduledJob::new(name, kind, expr)?;
+        let job = match name {
+            "refresh_prices" => job.with_priority(JobPriority::Normal),
+            "cleanup_old_data" => job.with_payload(serde_json::json!({ "days_to_keep": 90 })),
+            _ => job,
+        };
+        schedules.push(job);
+    }
+    Ok(schedules)
+}
+
+/// Evaluate schedules until `shutdown` flips to true
+pub async fn run(
+    state: Arc<WorkerState>,
+    schedules: Vec<ScheduledJob>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    info!(
+        schedules = ?schedules.iter().map(|s| s.name).collect::<Vec<_>>(),
+        "Scheduler starting"
+    );
+
+    let mut tick = interval(TICK);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+
+        let now = OffsetDateTime::now_utc();
+        for schedule in &schedules {
+            match fire_if_due(&state.pool.0, schedule, now).await {
+                Ok(Some(job_id)) => {
+                    info!(schedule = schedule.name, job_id = %job_id, "Scheduled job enqueued")
+                }
+                Ok(None) => {}
+                Err(e) => error!(schedule = schedule.name, error = %e, "Failed to fire schedule"),
+            }
+        }
+    }
+
+    info!("Scheduler stopped");
+    Ok(())
+}
+
+/// Enqueue the schedule's latest due slot unless another replica already
+/// did. Returns the new job id when this call enqueued it.
+pub async fn fire_if_due(
+    pg: &PgPool,
+    schedule: &ScheduledJob,
+    now: OffsetDateTime,
+) -> Result<Option<String>> {
+    let Some(fire_at) = schedule.cron.latest_at_or_before(now) else {
+        return Ok(None);
+    };
+
+    let mut tx = pg.begin().await?;
+
+    // A run that has not started yet covers this slot too
+    let pending = sqlx::query_scalar!(
+        "SELECT EXISTS (SELECT 1 FROM job_queue WHERE kind = $1 AND status = 'queued') AS \"pending!\"",
+        schedule.kind
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let job_id = (!pending).then(|| Ulid::new().to_string());
+
+    let claimed = sqlx::query_scalar!(
+        include_str!("../../../db/queries/claim_schedule_slot.sql"),
+        schedule.name,
+        schedule.expr,
+        fire_at,
+        job_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if claimed.is_none() {
+        return Ok(None);
+    }
+
+    let Some(job_id) = job_id else {
+        tx.commit().await?;
+        debug!(
+            schedule = schedule.name,
+            "Previous run still queued, skipping slot"
+        );
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        include_str!("../../../db/queries/enqueue_job.sql"),
+        job_id,
+        schedule.kind,
+        schedule.payload,
+        now,
+        schedule.max_attempts,
+        schedule.priority.as_i16(),
+        None::<String>
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn cron(expr: &str) -> CronSchedule {
+        expr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_expressions() {
+        assert!("* * * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("5-1 * * * *".parse::<CronSchedule>().is_err());
+        assert!("0 0 0 * *".parse::<CronSchedule>().is_err());
+        assert!("0 2 * * 7".parse::<CronSchedule>().is_ok());
+    }
+
+    #[test]
+    fn test_matches_fields() {
+        let every_five = cron("*/5 * * * *");
+        assert!(every_five.matches(datetime!(2024-03-10 14:35 UTC)));
+        assert!(!every_five.matches(datetime!(2024-03-10 14:36 UTC)));
+
+        // 2024-03-10 is a Sunday; 7 and 0 both mean Sunday
+        let sunday = cron("0 9 * * 7");
+        assert!(sunday.matches(datetime!(2024-03-10 09:00 UTC)));
+        assert!(!sunday.matches(datetime!(2024-03-11 09:00 UTC)));
+
+        // Restricted day-of-month and day-of-week match on either
+        let either = cron("0 0 1 * 1");
+        assert!(either.matches(datetime!(2024-03-01 00:00 UTC)));
+        assert!(either.matches(datetime!(2024-03-11 00:00 UTC)));
+        assert!(!either.matches(datetime!(2024-03-12 00:00 UTC)));
+    }
+
+    #[test]
+    fn test_latest_at_or_before() {
+        let daily = cron("0 2 * * *");
+        assert_eq!(
+            daily.latest_at_or_before(datetime!(2024-03-10 14:35:27 UTC)),
+            Some(datetime!(2024-03-10 02:00 UTC))
+        );
+        assert_eq!(
+            daily.latest_at_or_before(datetime!(2024-03-10 01:59:59 UTC)),
+            Some(datetime!(2024-03-09 02:00 UTC))
+        );
+        assert_eq!(
+            daily.latest_at_or_before(datetime!(2024-03-10 02:00:30 UTC)),
+            Some(datetime!(2024-03-10 02:00 UTC))
+        );
+
+        let quarterly = cron("30 6 1 1,4,7,10 *");
+        assert_eq!(
+            quarterly.latest_at_or_before(datetime!(2024-03-10 00:00 UTC)),
+            Some(datetime!(2024-01-01 06:30 UTC))
+        );
+
+        // February 30th never happens
+        assert_eq!(
+            cron("0 0 30 2 *").latest_at_or_before(datetime!(2024-03-10 00:00 UTC)),
+            None
+        );
+    }
+}
@@ -0,0 +1,9 @@
+-- 0013_plan_rpc_budgets.sql
+-- Per-run RPC budgets enforced by the shared RPC pool during backfills
+
+ALTER TABLE plans ADD COLUMN IF NOT EXISTS max_signatures_per_run BIGINT NOT NULL DEFAULT 1000;
+ALTER TABLE plans ADD COLUMN IF NOT EXISTS max_enhanced_tx_per_run BIGINT NOT NULL DEFAULT 100;
+
+UPDATE plans SET max_signatures_per_run = 1000,  max_enhanced_tx_per_run = 100  WHERE code = 'FREE';
+UPDATE plans SET max_signatures_per_run = 5000,  max_enhanced_tx_per_run = 500  WHERE code = 'LITE';
+UPDATE plans SET max_signatures_per_run = 50000, max_enhanced_tx_per_run = 5000 WHERE code = 'PRO';
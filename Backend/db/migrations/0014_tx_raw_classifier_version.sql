@@ -0,0 +1,7 @@
+-- 0014_tx_raw_classifier_version.sql
+-- Track which classifier version produced the actions for each raw tx so
+-- archived transactions can be replayed after classifier changes.
+
+ALTER TABLE tx_raw ADD COLUMN IF NOT EXISTS classifier_version INT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_tx_raw_classifier_version ON tx_raw(classifier_version, ts);
@@ -0,0 +1,25 @@
+-- 0015_tracked_wallets.sql
+-- Wallets the Helius webhook should watch, and why. A wallet is watched
+-- while any of its rows is unexpired; rows expire after a plan-based TTL
+-- without activity.
+
+ALTER TABLE plans ADD COLUMN IF NOT EXISTS tracking_ttl_days INT NOT NULL DEFAULT 7;
+
+UPDATE plans SET tracking_ttl_days = 7  WHERE code = 'FREE';
+UPDATE plans SET tracking_ttl_days = 30 WHERE code = 'LITE';
+UPDATE plans SET tracking_ttl_days = 90 WHERE code = 'PRO';
+
+CREATE TABLE IF NOT EXISTS tracked_wallets (
+  wallet TEXT NOT NULL,
+  user_id TEXT NOT NULL,
+  reason TEXT NOT NULL, -- analyze | alerts
+  plan_code TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_active_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMPTZ NOT NULL,
+  synced_at TIMESTAMPTZ,
+  PRIMARY KEY (wallet, user_id, reason)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tracked_wallets_expires_at ON tracked_wallets(expires_at);
+CREATE INDEX IF NOT EXISTS idx_tracked_wallets_wallet ON tracked_wallets(wallet);
@@ -0,0 +1,22 @@
+-- 0016_action_ids_and_source.sql
+-- Action IDs become a hash of (sig, log_idx, kind) so the webhook, backfill
+-- and renormalize paths all produce the same row for the same action, and
+-- every row records which path first ingested it.
+
+ALTER TABLE actions ADD COLUMN IF NOT EXISTS source TEXT NOT NULL DEFAULT 'backfill';
+
+ALTER TABLE actions DROP CONSTRAINT IF EXISTS actions_source_check;
+ALTER TABLE actions ADD CONSTRAINT actions_source_check
+  CHECK (source IN ('webhook', 'rpc_ws', 'backfill', 'renormalize'));
+
+-- Rewrite existing ULIDs with the deterministic form used by
+-- shared::normalize::action_id: first 16 bytes of sha256, hex encoded
+UPDATE actions
+SET id = substr(
+  encode(sha256(convert_to(sig || ':' || log_idx || ':' || COALESCE(kind, ''), 'UTF8')), 'hex'),
+  1, 32
+);
+
+-- With ux_actions_sig_logidx and the primary key on the derived id, a
+-- replayed signature can no longer add rows
+CREATE INDEX IF NOT EXISTS idx_actions_source ON actions(source);
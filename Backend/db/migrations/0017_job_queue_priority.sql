@@ -0,0 +1,14 @@
+-- 0017_job_queue_priority.sql
+-- Priority levels (shared::JobPriority: 0 low, 1 normal, 2 high, 3 critical)
+-- and the tenant that enqueued each job, so workers can dequeue fairly
+-- across both. System jobs have no tenant.
+
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS priority SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS tenant TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_job_queue_ready_priority
+  ON job_queue(priority, run_after)
+  WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_job_queue_tenant_running
+  ON job_queue(tenant)
+  WHERE status = 'running';
@@ -0,0 +1,22 @@
+-- 0018_job_dependencies.sql
+-- Job DAGs: a job can wait on parent jobs. Jobs with unresolved parents sit
+-- in status 'blocked' until shared::queue promotes them to 'queued'.
+-- Statuses: queued|blocked|running|done|failed|cancelled
+
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS root_id TEXT;
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS on_parent_failure TEXT NOT NULL DEFAULT 'cancel';
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS error_message TEXT;
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ;
+
+ALTER TABLE job_queue DROP CONSTRAINT IF EXISTS job_queue_on_parent_failure_check;
+ALTER TABLE job_queue ADD CONSTRAINT job_queue_on_parent_failure_check
+  CHECK (on_parent_failure IN ('cancel', 'continue'));
+
+CREATE TABLE IF NOT EXISTS job_dependencies (
+  job_id TEXT NOT NULL REFERENCES job_queue(id) ON DELETE CASCADE,
+  parent_id TEXT NOT NULL REFERENCES job_queue(id) ON DELETE CASCADE,
+  PRIMARY KEY (job_id, parent_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_dependencies_parent ON job_dependencies(parent_id);
+CREATE INDEX IF NOT EXISTS idx_job_queue_root ON job_queue(root_id) WHERE root_id IS NOT NULL;
@@ -0,0 +1,18 @@
+-- 0019_job_queue_notify.sql
+-- Wake idle workers as soon as a job becomes runnable: new queued rows,
+-- retries and DAG promotions all pass through this trigger. The payload is
+-- the job kind. Workers still poll as a fallback for missed notifications.
+
+CREATE OR REPLACE FUNCTION notify_job_queue() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_notify('job_queue', NEW.kind);
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_job_queue_notify ON job_queue;
+CREATE TRIGGER trg_job_queue_notify
+  AFTER INSERT OR UPDATE OF status ON job_queue
+  FOR EACH ROW
+  WHEN (NEW.status = 'queued')
+  EXECUTE FUNCTION notify_job_queue();
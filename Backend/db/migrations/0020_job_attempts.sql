@@ -0,0 +1,19 @@
+-- 0020_job_attempts.sql
+-- One row per job attempt, so retries keep the full error history instead of
+-- overwriting job_queue.error_message.
+
+CREATE TABLE IF NOT EXISTS job_attempts (
+  job_id TEXT NOT NULL REFERENCES job_queue(id) ON DELETE CASCADE,
+  attempt INT NOT NULL,
+  worker_id TEXT,
+  started_at TIMESTAMPTZ NOT NULL,
+  finished_at TIMESTAMPTZ,
+  outcome TEXT NOT NULL CHECK (outcome IN ('succeeded', 'retrying', 'failed')),
+  error_class TEXT CHECK (error_class IN ('retryable', 'fatal')),
+  error_message TEXT,
+  PRIMARY KEY (job_id, attempt)
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_attempts_failed
+  ON job_attempts(finished_at)
+  WHERE outcome <> 'succeeded';
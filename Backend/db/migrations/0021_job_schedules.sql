@@ -0,0 +1,12 @@
+-- 0021_job_schedules.sql
+-- Last fire time of each recurring schedule. Every worker replica evaluates
+-- the same cron expressions; whichever one advances last_fire_at for a slot
+-- enqueues that run, so each slot is enqueued exactly once.
+
+CREATE TABLE IF NOT EXISTS job_schedules (
+  name TEXT PRIMARY KEY,
+  cron TEXT NOT NULL,
+  last_fire_at TIMESTAMPTZ NOT NULL,
+  last_job_id TEXT,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
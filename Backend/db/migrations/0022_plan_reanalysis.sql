@@ -0,0 +1,23 @@
+-- 0022_plan_reanalysis.sql
+-- Automatic re-analysis on the plan cadence. refreshed_at is when the
+-- wallet was last queued for a refresh (manual or automatic); to_ts alone
+-- cannot tell a quiet wallet from a stale one.
+
+ALTER TABLE wallet_cursors ADD COLUMN IF NOT EXISTS refreshed_at TIMESTAMPTZ;
+UPDATE wallet_cursors SET refreshed_at = to_ts WHERE refreshed_at IS NULL;
+
+-- Outbound user notifications, drained by the alerts dispatcher
+CREATE TABLE IF NOT EXISTS alert_queue (
+  alert_id TEXT PRIMARY KEY,
+  user_id TEXT NOT NULL,
+  alert_type TEXT NOT NULL,
+  timestamp TIMESTAMP NOT NULL,
+  data TEXT NOT NULL,
+  priority TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'failed')),
+  error_message TEXT,
+  processed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_queue_pending ON alert_queue(timestamp) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_alert_queue_user ON alert_queue(user_id, alert_type, timestamp);
@@ -0,0 +1,17 @@
+-- 0023_job_cancellation.sql
+-- Cooperative cancellation: running jobs are flagged, not killed; the worker
+-- picks the flag up on its next heartbeat.
+
+ALTER TABLE job_queue ADD COLUMN IF NOT EXISTS cancel_requested_at TIMESTAMPTZ;
+
+ALTER TABLE job_attempts DROP CONSTRAINT IF EXISTS job_attempts_outcome_check;
+ALTER TABLE job_attempts ADD CONSTRAINT job_attempts_outcome_check
+  CHECK (outcome IN ('succeeded', 'retrying', 'failed', 'cancelled'));
+
+ALTER TABLE job_attempts DROP CONSTRAINT IF EXISTS job_attempts_error_class_check;
+ALTER TABLE job_attempts ADD CONSTRAINT job_attempts_error_class_check
+  CHECK (error_class IN ('retryable', 'fatal', 'cancelled'));
+
+-- Admin listing filters by kind/status/tenant, newest first
+CREATE INDEX IF NOT EXISTS idx_job_queue_admin ON job_queue(status, kind, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_job_queue_tenant_created ON job_queue(tenant, created_at DESC);
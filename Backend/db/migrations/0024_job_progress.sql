@@ -0,0 +1,29 @@
+-- 0024_job_progress.sql
+-- Structured progress events for analysis DAGs. Workers append one row per
+-- update and the trigger notifies listeners with the DAG's root id; the API
+-- relays events over SSE and uses `id` as the SSE event id so a client can
+-- resume with Last-Event-ID.
+
+CREATE TABLE IF NOT EXISTS job_progress (
+  id BIGSERIAL PRIMARY KEY,
+  root_id TEXT NOT NULL,
+  job_id TEXT NOT NULL,
+  payload JSONB NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_progress_root ON job_progress(root_id, id);
+CREATE INDEX IF NOT EXISTS idx_job_progress_created ON job_progress(created_at);
+
+CREATE OR REPLACE FUNCTION notify_job_progress() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_notify('job_progress', NEW.root_id);
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_job_progress_notify ON job_progress;
+CREATE TRIGGER trg_job_progress_notify
+  AFTER INSERT ON job_progress
+  FOR EACH ROW
+  EXECUTE FUNCTION notify_job_progress();
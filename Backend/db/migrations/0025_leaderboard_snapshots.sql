@@ -0,0 +1,22 @@
+-- 0025_leaderboard_snapshots.sql
+-- Precomputed leaderboards. The generate_leaderboard job writes the top
+-- wallets for every metric and period as one snapshot per run; the API
+-- serves the latest snapshot. Older snapshots are kept as rank history and
+-- previous_rank records where the wallet stood in the snapshot before.
+
+CREATE TABLE IF NOT EXISTS leaderboard_snapshots (
+  metric TEXT NOT NULL,               -- LeaderboardMetric::as_str
+  period TEXT NOT NULL,               -- 7d|30d|90d
+  computed_at TIMESTAMPTZ NOT NULL,
+  rank INT NOT NULL,
+  wallet TEXT NOT NULL,
+  value NUMERIC(38,18) NOT NULL,
+  sample_size BIGINT NOT NULL,        -- trades or moments behind the value
+  previous_rank INT,                  -- NULL when new to the board
+  PRIMARY KEY (metric, period, computed_at, rank)
+);
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_snapshots_wallet
+  ON leaderboard_snapshots(wallet, metric, period, computed_at DESC);
+CREATE INDEX IF NOT EXISTS idx_leaderboard_snapshots_computed
+  ON leaderboard_snapshots(computed_at);
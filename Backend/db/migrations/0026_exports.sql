@@ -0,0 +1,24 @@
+-- 0026_exports.sql
+-- User data exports. The API inserts a row and enqueues an export_moments
+-- job whose DAG root id is the export id; the worker records the uploaded
+-- files (one per dataset) and flips the status. Downloads after expires_at
+-- are refused.
+
+CREATE TABLE IF NOT EXISTS exports (
+  id TEXT PRIMARY KEY,                -- ULID, also the job DAG root id
+  user_id TEXT NOT NULL,
+  wallet TEXT NOT NULL,
+  format TEXT NOT NULL,
+  include_transactions BOOL NOT NULL DEFAULT FALSE,
+  status TEXT NOT NULL DEFAULT 'queued',
+  files JSONB NOT NULL DEFAULT '[]'::jsonb,
+  error_message TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  started_at TIMESTAMPTZ,
+  completed_at TIMESTAMPTZ,
+  expires_at TIMESTAMPTZ,
+  CONSTRAINT exports_format_check CHECK (format IN ('csv', 'ndjson', 'parquet')),
+  CONSTRAINT exports_status_check CHECK (status IN ('queued', 'running', 'done', 'failed'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_exports_user_created ON exports(user_id, created_at DESC);
@@ -0,0 +1,14 @@
+-- 0027_tax_lots.sql
+-- Lot-level detail for tax reports. Buys without a USD price now open a lot
+-- flagged entry_px_missing (entry_px_usd_dec 0) instead of being dropped, and
+-- each realized trade records the lots its sell consumed so disposals can be
+-- reported with their own acquisition dates and bases.
+
+ALTER TABLE lots
+  ADD COLUMN IF NOT EXISTS entry_px_missing BOOL NOT NULL DEFAULT FALSE;
+
+-- [{lot_id, entry_ts, qty, entry_px_usd}] in FIFO order; entry_px_usd is null
+-- for unpriced lots, lot_id and entry_ts are null for quantity sold beyond
+-- every known lot. NULL on trades recorded before this migration.
+ALTER TABLE realized_trades
+  ADD COLUMN IF NOT EXISTS lots_json JSONB;
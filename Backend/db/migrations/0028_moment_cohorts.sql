@@ -0,0 +1,19 @@
+-- 0028_moment_cohorts.sql
+-- Moments on the same mint and kind on the same UTC day form a cohort
+-- ("you and 3,412 others sold $WIF too early"). Cohort totals are cached per
+-- (mint, kind, day) and recomputed by the API once stale.
+
+CREATE INDEX IF NOT EXISTS idx_oof_moments_mint_kind_tevent
+  ON oof_moments(mint, kind, t_event);
+
+CREATE TABLE IF NOT EXISTS moment_cohort_daily (
+  mint TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  day DATE NOT NULL,                  -- UTC day of t_event
+  moments BIGINT NOT NULL,
+  wallets BIGINT NOT NULL,            -- distinct wallets
+  missed_usd_total NUMERIC(38,18) NOT NULL,
+  missed_usd_max NUMERIC(38,18),
+  computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (mint, kind, day)
+);
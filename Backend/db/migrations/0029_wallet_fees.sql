@@ -0,0 +1,12 @@
+-- 0029_wallet_fees.sql
+-- Transaction fees, recorded when a transaction is normalised, so wallet
+-- metrics can total the fees a wallet paid. Transactions normalised before
+-- this migration have no fee until they are reclassified.
+
+ALTER TABLE tx_raw ADD COLUMN IF NOT EXISTS fee_lamports BIGINT;
+ALTER TABLE tx_raw ADD COLUMN IF NOT EXISTS fee_payer TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_tx_raw_fee_payer ON tx_raw(fee_payer)
+  WHERE fee_payer IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_episodes_wallet_end ON episodes(wallet, end_ts);
@@ -0,0 +1,21 @@
+-- 0030_wallet_equity.sql
+-- Daily equity curve per wallet, materialised by the compute_equity job from
+-- swap/transfer balance history, the buy/sell lot book and daily closes.
+-- Transfers in and out are cash flows: daily_return is the flow-adjusted
+-- (Modified Dietz) return and twr_index chains those returns from 1.0, so
+-- deposits do not show up as gains.
+
+CREATE TABLE IF NOT EXISTS wallet_equity_daily (
+  wallet TEXT NOT NULL,
+  day DATE NOT NULL,
+  equity_usd NUMERIC(38,18) NOT NULL,        -- priced balances at the day's close
+  net_flow_usd NUMERIC(38,18) NOT NULL,      -- transfers in minus out, at the close
+  cost_basis_usd NUMERIC(38,18) NOT NULL,    -- open lots
+  unrealized_pnl_usd NUMERIC(38,18) NOT NULL,
+  realized_pnl_usd NUMERIC(38,18) NOT NULL,  -- cumulative to the end of the day
+  daily_return DOUBLE PRECISION,             -- NULL when nothing was invested
+  twr_index DOUBLE PRECISION NOT NULL,
+  unpriced_mints INT NOT NULL DEFAULT 0,     -- held mints without a recent close
+  computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (wallet, day)
+);
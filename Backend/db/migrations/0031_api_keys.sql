@@ -0,0 +1,35 @@
+-- 0031_api_keys.sql
+-- API keys for programmatic access. Only a SHA-256 of each key is stored;
+-- the prefix is unique so a presented key is found without scanning, and is
+-- what listings show. api_key_usage meters rows served per key and billing
+-- period against the plan's api_rows.
+
+CREATE TABLE IF NOT EXISTS api_keys (
+  id TEXT PRIMARY KEY,                 -- ULID
+  user_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  prefix TEXT NOT NULL UNIQUE,
+  key_hash TEXT NOT NULL,              -- hex SHA-256 of the full key
+  scopes TEXT[] NOT NULL,              -- read:moments, analyze, export
+  rows_limit BIGINT,                   -- optional cap below the plan's api_rows
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMPTZ,
+  last_used_at TIMESTAMPTZ,
+  last_used_ip TEXT,
+  revoked_at TIMESTAMPTZ,
+  rotated_from TEXT REFERENCES api_keys(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_user ON api_keys(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS api_key_usage (
+  key_id TEXT NOT NULL REFERENCES api_keys(id),
+  user_id TEXT NOT NULL,
+  period_start DATE NOT NULL,
+  rows_used BIGINT NOT NULL DEFAULT 0,
+  requests BIGINT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (key_id, period_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_key_usage_user_period ON api_key_usage(user_id, period_start);
@@ -0,0 +1,12 @@
+-- 0032_wallet_timeline.sql
+-- Indexes for GET /v1/wallets/:wallet/timeline, which reads each source
+-- newest-first per wallet and merges the pages. realized_trades (wallet, ts)
+-- and oof_moments (wallet, t_event) are already covered, as is episodes
+-- (wallet, end_ts) for closes.
+
+-- A wallet's own action legs; participants also lists counterparties and
+-- fee payers, so the timeline filters on the leg's owner instead
+CREATE INDEX IF NOT EXISTS idx_actions_owner_ts ON actions ((flags_json->>'owner'), ts DESC);
+
+-- Episode opens, newest first
+CREATE INDEX IF NOT EXISTS idx_episodes_wallet_start ON episodes(wallet, start_ts DESC);
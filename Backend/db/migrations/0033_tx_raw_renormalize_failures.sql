@@ -0,0 +1,7 @@
+-- 0033_tx_raw_renormalize_failures.sql
+-- Record transactions that could not be replayed (archive object missing or
+-- classification failed) so renormalize stops selecting them on every run.
+-- They are retried once CLASSIFIER_VERSION moves past the failed version.
+
+ALTER TABLE tx_raw ADD COLUMN IF NOT EXISTS renormalize_failed_version INT;
+ALTER TABLE tx_raw ADD COLUMN IF NOT EXISTS renormalize_error TEXT;
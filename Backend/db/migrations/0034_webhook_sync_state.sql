@@ -0,0 +1,11 @@
+-- 0034_webhook_sync_state.sql
+-- The address set last pushed to the Helius webhook. Helius only accepts
+-- the full list, so the sync job skips the round trip while the tracked
+-- set still hashes the same.
+
+CREATE TABLE IF NOT EXISTS webhook_sync_state (
+  id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id), -- single row
+  addresses_hash TEXT NOT NULL,
+  address_count INT NOT NULL,
+  synced_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,14 @@
+-- 0035_staking_boosts.sql
+-- OOF staked per user and the boost tier it earns (configs/plans.yaml
+-- staking_boosts.tiers). PolicyService::get_user_context applies the
+-- largest unexpired boost on top of the user's plan.
+
+CREATE TABLE IF NOT EXISTS staking_boosts (
+  user_id TEXT PRIMARY KEY,
+  wallet_address TEXT NOT NULL,
+  staked_amount NUMERIC(38,18) NOT NULL,
+  boost_multiplier NUMERIC(10,4) NOT NULL DEFAULT 1,
+  boost_expires_at TIMESTAMPTZ NOT NULL,
+  perks_json JSONB NOT NULL DEFAULT '{}'::jsonb,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,12 @@
+-- 0036_plan_api_rate_limits.sql
+-- Requests per minute a user's API keys may make, from the plan's
+-- rate_limits in configs/plans.yaml. The limit is per user: all of a
+-- user's keys share one budget.
+
+ALTER TABLE plans ADD COLUMN IF NOT EXISTS api_requests_per_minute INT NOT NULL DEFAULT 10;
+
+UPDATE plans SET api_requests_per_minute = 10  WHERE code = 'FREE';
+UPDATE plans SET api_requests_per_minute = 30  WHERE code = 'LITE';
+UPDATE plans SET api_requests_per_minute = 60  WHERE code = 'STANDARD';
+UPDATE plans SET api_requests_per_minute = 120 WHERE code = 'PRO';
+UPDATE plans SET api_requests_per_minute = 300 WHERE code = 'ENTERPRISE';
@@ -0,0 +1,16 @@
+-- 0037_user_alert_configs.sql
+-- Where each user's alerts are delivered (shared::alerts::AlertChannel).
+-- The alerts dispatcher sends every queued alert to all of the owner's
+-- channels; alerts for users without an enabled row are marked skipped
+-- rather than failed, since there is nowhere to send them.
+
+CREATE TABLE IF NOT EXISTS user_alert_configs (
+  user_id TEXT PRIMARY KEY,
+  channels_json JSONB NOT NULL DEFAULT '[]'::jsonb,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE alert_queue DROP CONSTRAINT IF EXISTS alert_queue_status_check;
+ALTER TABLE alert_queue ADD CONSTRAINT alert_queue_status_check
+  CHECK (status IN ('pending', 'sent', 'failed', 'skipped'));
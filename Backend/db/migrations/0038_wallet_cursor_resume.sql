@@ -0,0 +1,9 @@
+-- 0038_wallet_cursor_resume.sql
+-- A backfill that stops before reaching the start of its window (RPC
+-- budget or fetch errors) keeps to_ts as it was and records where to pick
+-- the walk up: last_cursor_sig is the oldest signature handled and
+-- walk_started_at is when that walk began at the newest signature, which
+-- becomes to_ts once the walk completes. Both are NULL between walks.
+
+ALTER TABLE wallet_cursors ADD COLUMN IF NOT EXISTS walk_started_at TIMESTAMPTZ;
+UPDATE wallet_cursors SET last_cursor_sig = NULL WHERE walk_started_at IS NULL;
@@ -0,0 +1,32 @@
+-- 0039_job_progress_seq.sql
+-- Progress events get a per-DAG sequence number, used as the SSE event id.
+-- BIGSERIAL ids are handed out before commit, so two jobs of one DAG could
+-- commit out of order and a reader resuming after the larger id would
+-- never see the smaller one. job_progress_seq hands out seq under a row
+-- lock held until the event's transaction commits, so a DAG's events
+-- become visible in seq order.
+
+CREATE TABLE IF NOT EXISTS job_progress_seq (
+  root_id TEXT PRIMARY KEY,
+  last_seq BIGINT NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE job_progress ADD COLUMN IF NOT EXISTS seq BIGINT;
+
+UPDATE job_progress p
+SET seq = numbered.seq
+FROM (
+  SELECT id, ROW_NUMBER() OVER (PARTITION BY root_id ORDER BY id) AS seq
+  FROM job_progress
+) numbered
+WHERE p.id = numbered.id AND p.seq IS NULL;
+
+INSERT INTO job_progress_seq (root_id, last_seq)
+SELECT root_id, MAX(seq) FROM job_progress GROUP BY root_id
+ON CONFLICT (root_id) DO NOTHING;
+
+ALTER TABLE job_progress ALTER COLUMN seq SET NOT NULL;
+
+DROP INDEX IF EXISTS idx_job_progress_root;
+CREATE UNIQUE INDEX IF NOT EXISTS ux_job_progress_root_seq ON job_progress(root_id, seq);
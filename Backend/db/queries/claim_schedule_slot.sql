@@ -0,0 +1,13 @@
+-- name: claim_schedule_slot
+-- Claim a schedule's fire slot; returns a row only for the caller that
+-- advanced last_fire_at, so concurrent replicas cannot both enqueue it.
+-- Params: $1 name, $2 cron, $3 fire_at, $4 job_id (NULL when the run was skipped)
+INSERT INTO job_schedules (name, cron, last_fire_at, last_job_id)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (name) DO UPDATE SET
+  cron = EXCLUDED.cron,
+  last_fire_at = EXCLUDED.last_fire_at,
+  last_job_id = EXCLUDED.last_job_id,
+  updated_at = NOW()
+WHERE job_schedules.last_fire_at < EXCLUDED.last_fire_at
+RETURNING name;
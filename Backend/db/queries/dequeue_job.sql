@@ -1,19 +1,33 @@
 -- name: dequeue_job
--- Get next available job and lock it
--- Params: $1 worker_id
+-- Lock the next job at the chosen priority level. Within the level, tenants
+-- with the fewest running jobs go first so one bulk request cannot starve
+-- everyone else; ties fall back to FIFO. Running counts are taken once per
+-- dequeue rather than per candidate row.
+-- Params: $1 worker_id, $2 priority, $3 kinds this worker is not taking (text[])
+WITH running AS (
+    SELECT tenant, COUNT(*) AS n
+    FROM job_queue
+    WHERE status = 'running'
+    GROUP BY tenant
+)
 UPDATE job_queue
 SET status = 'running',
     locked_by = $1,
     locked_at = NOW(),
     attempts = attempts + 1
 WHERE id = (
-    SELECT id
-    FROM job_queue
-    WHERE status = 'queued'
-        AND run_after <= NOW()
-        AND attempts < max_attempts
-    ORDER BY run_after ASC, created_at ASC
+    SELECT q.id
+    FROM job_queue q
+    LEFT JOIN running r ON r.tenant IS NOT DISTINCT FROM q.tenant
+    WHERE q.status = 'queued'
+        AND q.priority = $2
+        AND q.run_after <= NOW()
+        AND q.attempts < q.max_attempts
+        AND NOT (q.kind = ANY($3))
+    ORDER BY COALESCE(r.n, 0) ASC,
+        q.run_after ASC,
+        q.created_at ASC
     LIMIT 1
-    FOR UPDATE SKIP LOCKED
+    FOR UPDATE OF q SKIP LOCKED
 )
-RETURNING id, kind, payload_json, attempts, max_attempts;
+RETURNING id, kind, payload_json, attempts, max_attempts, root_id;
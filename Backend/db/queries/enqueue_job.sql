@@ -1,5 +1,6 @@
 -- name: enqueue_job
 -- Insert a new job into the queue
--- Params: $1 id, $2 kind, $3 payload_json, $4 run_after, $5 max_attempts
-INSERT INTO job_queue (id, kind, payload_json, run_after, max_attempts)
-VALUES ($1, $2, $3, $4, $5);
+-- Params: $1 id, $2 kind, $3 payload_json, $4 run_after, $5 max_attempts,
+--         $6 priority (JobPriority::as_i16), $7 tenant (user id, NULL for system jobs)
+INSERT INTO job_queue (id, kind, payload_json, run_after, max_attempts, priority, tenant)
+VALUES ($1, $2, $3, $4, $5, $6, $7);
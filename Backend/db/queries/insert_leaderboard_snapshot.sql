@@ -0,0 +1,84 @@
+-- name: insert_leaderboard_snapshot
+-- Rank wallets for one metric over one period and store the top entries as
+-- the snapshot taken at $4. previous_rank comes from the latest earlier
+-- snapshot of the same metric and period.
+-- Params: $1 metric, $2 period, $3 period_days, $4 computed_at, $5 size,
+--         $6 moment kind the metric counts (NULL for every kind)
+WITH window_start AS (
+    SELECT $4::timestamptz - make_interval(days => $3::int) AS ts
+),
+moments AS (
+    SELECT om.wallet, om.kind, om.missed_usd_dec
+    FROM oof_moments om, window_start w
+    WHERE om.t_event >= w.ts AND om.t_event < $4
+        AND ($6::text IS NULL OR om.kind = $6)
+),
+exits AS (
+    SELECT rt.wallet, rt.realized_pnl_usd_dec
+    FROM realized_trades rt, window_start w
+    WHERE rt.ts >= w.ts AND rt.ts < $4
+),
+scores AS (
+    SELECT wallet, SUM(realized_pnl_usd_dec) AS value, COUNT(*) AS sample_size
+    FROM exits
+    WHERE $1::text = 'highest_gains' AND realized_pnl_usd_dec > 0
+    GROUP BY wallet
+    UNION ALL
+    SELECT wallet, COUNT(*)::numeric, COUNT(*)
+    FROM moments
+    WHERE $1 = 'most_s2e'
+    GROUP BY wallet
+    UNION ALL
+    SELECT wallet, COUNT(*)::numeric, COUNT(*)
+    FROM moments
+    WHERE $1 = 'worst_bhd'
+    GROUP BY wallet
+    UNION ALL
+    SELECT wallet, MAX(missed_usd_dec), COUNT(*)
+    FROM moments
+    WHERE $1 = 'biggest_s2e' AND missed_usd_dec IS NOT NULL
+    GROUP BY wallet
+    UNION ALL
+    SELECT wallet, MAX(missed_usd_dec), COUNT(*)
+    FROM moments
+    WHERE $1 = 'worst_bad_route' AND missed_usd_dec IS NOT NULL
+    GROUP BY wallet
+    UNION ALL
+    -- Percent of exits that were sold too early; needs 5 exits to rank
+    SELECT e.wallet, LEAST(100, 100 * COALESCE(s.n, 0)::numeric / e.n), e.n
+    FROM (
+        SELECT wallet, COUNT(*) AS n FROM exits
+        WHERE $1 = 'most_paper_handed'
+        GROUP BY wallet
+        HAVING COUNT(*) >= 5
+    ) e
+    LEFT JOIN (
+        SELECT wallet, COUNT(*) AS n FROM moments GROUP BY wallet
+    ) s ON s.wallet = e.wallet
+    UNION ALL
+    SELECT wallet, SUM(missed_usd_dec), COUNT(*)
+    FROM moments
+    WHERE $1 = 'total_missed' AND missed_usd_dec IS NOT NULL
+    GROUP BY wallet
+),
+ranked AS (
+    SELECT wallet, value, sample_size,
+           ROW_NUMBER() OVER (ORDER BY value DESC, wallet ASC) AS rank
+    FROM scores
+    WHERE value > 0
+),
+previous AS (
+    SELECT p.wallet, p.rank
+    FROM leaderboard_snapshots p
+    WHERE p.metric = $1 AND p.period = $2
+        AND p.computed_at = (
+            SELECT MAX(computed_at) FROM leaderboard_snapshots
+            WHERE metric = $1 AND period = $2 AND computed_at < $4
+        )
+)
+INSERT INTO leaderboard_snapshots
+    (metric, period, computed_at, rank, wallet, value, sample_size, previous_rank)
+SELECT $1, $2, $4, r.rank, r.wallet, r.value, r.sample_size, prev.rank
+FROM ranked r
+LEFT JOIN previous prev ON prev.wallet = r.wallet
+WHERE r.rank <= $5;
@@ -0,0 +1,10 @@
+-- name: select_daily_closes
+-- Last price of each UTC day per mint
+-- Params: $1 mints, $2 from_ts, $3 to_ts
+SELECT DISTINCT ON (mint, (ts AT TIME ZONE 'UTC')::date)
+    mint AS "mint!",
+    (ts AT TIME ZONE 'UTC')::date AS "day!",
+    price AS "close!"
+FROM token_prices
+WHERE mint = ANY($1) AND ts >= $2 AND ts < $3
+ORDER BY mint, (ts AT TIME ZONE 'UTC')::date, ts DESC;
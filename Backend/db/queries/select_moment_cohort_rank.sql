@@ -0,0 +1,15 @@
+-- name: select_moment_cohort_rank
+-- Where a wallet ranks by total missed USD within one cohort day; rank 1
+-- missed the most, ties share a rank
+-- Params: $1 mint, $2 kind, $3 day start, $4 day end, $5 wallet
+WITH per_wallet AS (
+    SELECT wallet, COALESCE(SUM(missed_usd_dec), 0) AS missed
+    FROM oof_moments
+    WHERE mint = $1 AND kind = $2 AND t_event >= $3 AND t_event < $4
+    GROUP BY wallet
+)
+SELECT
+    me.missed AS "missed!",
+    (SELECT COUNT(*) FROM per_wallet o WHERE o.missed > me.missed) + 1 AS "rank!"
+FROM per_wallet me
+WHERE me.wallet = $5;
@@ -0,0 +1,9 @@
+-- name: select_ready_priorities
+-- Priority levels that currently have runnable jobs
+-- Params: $1 kinds this worker is not taking (text[])
+SELECT DISTINCT priority
+FROM job_queue
+WHERE status = 'queued'
+    AND run_after <= NOW()
+    AND attempts < max_attempts
+    AND NOT (kind = ANY($1));
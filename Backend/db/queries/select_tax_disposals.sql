@@ -0,0 +1,22 @@
+-- name: select_tax_disposals
+-- Realized trades of a wallet group within [$2, $3), oldest first
+-- Params: $1 wallets, $2 start, $3 end
+SELECT
+    rt.exit_id,
+    rt.wallet,
+    rt.mint,
+    tf.symbol AS "symbol?",
+    rt.ts,
+    rt.qty,
+    rt.vwavg_exit_px_usd_dec,
+    rt.realized_pnl_usd_dec,
+    rt.sig,
+    rt.lots_json,
+    e.start_ts AS "episode_start?"
+FROM realized_trades rt
+LEFT JOIN token_facts tf ON tf.mint = rt.mint
+LEFT JOIN episodes e ON e.episode_id = rt.episode_id
+WHERE rt.wallet = ANY($1::text[])
+  AND rt.ts >= $2
+  AND rt.ts < $3
+ORDER BY rt.ts ASC, rt.exit_id ASC;
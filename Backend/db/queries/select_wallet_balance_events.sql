@@ -0,0 +1,15 @@
+-- name: select_wallet_balance_events
+-- Swaps and transfers that move the wallet's own balances, oldest first
+-- Params: $1 wallet
+SELECT
+    a.ts AS "ts!",
+    a.kind AS "kind!",
+    a.mint,
+    a.amount_dec,
+    a.flags_json
+FROM actions a
+JOIN participants p ON p.sig = a.sig
+WHERE p.wallet = $1
+  AND a.kind IN ('buy', 'sell', 'swap', 'transfer', 'sol_transfer')
+  AND a.flags_json->>'owner' = $1
+ORDER BY a.ts ASC, a.sig ASC, a.log_idx ASC;
@@ -0,0 +1,37 @@
+-- name: select_wallet_lot_events
+-- Lot book changes, oldest first: buys open quantity at their USD cost, and
+-- realized trades close quantity, removing the basis they realized against
+-- (proceeds minus PnL). Unpriced buys open quantity with no basis, as in
+-- the position engine.
+-- Params: $1 wallet
+SELECT
+    book.ts AS "ts!",
+    book.mint AS "mint!",
+    book.qty AS "qty!",
+    book.cost_usd AS "cost_usd!",
+    book.realized_pnl_usd AS "realized_pnl_usd!"
+FROM (
+    SELECT
+        a.ts,
+        a.mint,
+        ABS(a.amount_dec) AS qty,
+        COALESCE(ABS(a.amount_dec) * a.exec_px_usd_dec, 0) AS cost_usd,
+        0::numeric AS realized_pnl_usd
+    FROM actions a
+    JOIN participants p ON p.sig = a.sig
+    WHERE p.wallet = $1
+      AND a.kind = 'buy'
+      AND a.flags_json->>'owner' = $1
+      AND a.mint IS NOT NULL
+      AND a.amount_dec IS NOT NULL
+    UNION ALL
+    SELECT
+        rt.ts,
+        rt.mint,
+        -rt.qty,
+        -(rt.qty * rt.vwavg_exit_px_usd_dec - rt.realized_pnl_usd_dec),
+        rt.realized_pnl_usd_dec
+    FROM realized_trades rt
+    WHERE rt.wallet = $1
+) book
+ORDER BY book.ts ASC;
@@ -0,0 +1,30 @@
+-- name: select_wallets_due_for_refresh
+-- Tracked wallets whose fastest owner cadence has elapsed since the last
+-- refresh. Only wallets analyzed at least once (to_ts set) are returned.
+-- Params: $1 cadences (text[]), $2 interval days per cadence (int[]), $3 limit
+WITH cadence AS (
+  SELECT * FROM UNNEST($1::text[], $2::int[]) AS c(cadence, days)
+),
+owners AS (
+  SELECT tw.wallet, tw.user_id, c.days,
+         p.max_signatures_per_run, p.max_enhanced_tx_per_run
+  FROM tracked_wallets tw
+  JOIN user_plans up ON up.user_id = tw.user_id
+   AND (up.expires_at IS NULL OR up.expires_at > NOW())
+  JOIN plans p ON p.code = up.plan_code
+  JOIN cadence c ON c.cadence = p.cadence
+  WHERE tw.expires_at > NOW()
+)
+SELECT
+  o.wallet AS "wallet!",
+  wc.to_ts AS "to_ts!",
+  array_agg(DISTINCT o.user_id) AS "user_ids!",
+  MAX(o.max_signatures_per_run) AS "max_signatures!",
+  MAX(o.max_enhanced_tx_per_run) AS "max_enhanced_tx!"
+FROM owners o
+JOIN wallet_cursors wc ON wc.wallet = o.wallet
+WHERE wc.to_ts IS NOT NULL
+GROUP BY o.wallet, wc.to_ts, wc.refreshed_at
+HAVING COALESCE(wc.refreshed_at, wc.to_ts) < NOW() - make_interval(days => MIN(o.days))
+ORDER BY COALESCE(wc.refreshed_at, wc.to_ts) ASC
+LIMIT $3;
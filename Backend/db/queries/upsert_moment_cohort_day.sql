@@ -0,0 +1,28 @@
+-- name: upsert_moment_cohort_day
+-- Recompute one cohort day from oof_moments and cache it
+-- Params: $1 mint, $2 kind, $3 day start (UTC midnight), $4 day end
+INSERT INTO moment_cohort_daily
+  (mint, kind, day, moments, wallets, missed_usd_total, missed_usd_max, computed_at)
+SELECT
+    $1,
+    $2,
+    ($3::timestamptz AT TIME ZONE 'UTC')::date,
+    COUNT(*),
+    COUNT(DISTINCT wallet),
+    COALESCE(SUM(missed_usd_dec), 0),
+    MAX(missed_usd_dec),
+    NOW()
+FROM oof_moments
+WHERE mint = $1 AND kind = $2 AND t_event >= $3 AND t_event < $4
+ON CONFLICT (mint, kind, day) DO UPDATE SET
+    moments = EXCLUDED.moments,
+    wallets = EXCLUDED.wallets,
+    missed_usd_total = EXCLUDED.missed_usd_total,
+    missed_usd_max = EXCLUDED.missed_usd_max,
+    computed_at = EXCLUDED.computed_at
+RETURNING
+    moments,
+    wallets,
+    missed_usd_total,
+    missed_usd_max,
+    computed_at;
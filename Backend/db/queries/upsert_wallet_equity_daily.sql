@@ -0,0 +1,23 @@
+-- name: upsert_wallet_equity_daily
+-- Params: $1 wallet, $2..$10 parallel arrays (day, equity, net flow, cost
+-- basis, unrealized, realized, daily return, twr index, unpriced mints),
+-- $11 computed_at
+INSERT INTO wallet_equity_daily (
+    wallet, day, equity_usd, net_flow_usd, cost_basis_usd, unrealized_pnl_usd,
+    realized_pnl_usd, daily_return, twr_index, unpriced_mints, computed_at
+)
+SELECT $1, d.*, $11
+FROM UNNEST(
+    $2::date[], $3::numeric[], $4::numeric[], $5::numeric[], $6::numeric[],
+    $7::numeric[], $8::float8[], $9::float8[], $10::int[]
+) AS d
+ON CONFLICT (wallet, day) DO UPDATE SET
+    equity_usd = EXCLUDED.equity_usd,
+    net_flow_usd = EXCLUDED.net_flow_usd,
+    cost_basis_usd = EXCLUDED.cost_basis_usd,
+    unrealized_pnl_usd = EXCLUDED.unrealized_pnl_usd,
+    realized_pnl_usd = EXCLUDED.realized_pnl_usd,
+    daily_return = EXCLUDED.daily_return,
+    twr_index = EXCLUDED.twr_index,
+    unpriced_mints = EXCLUDED.unpriced_mints,
+    computed_at = EXCLUDED.computed_at;
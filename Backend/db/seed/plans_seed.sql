@@ -1,8 +1,8 @@
-INSERT INTO plans (code, price_usd_dec, daily_wallets, backfill_days, cadence, alerts, api_rows, perks_json)
+INSERT INTO plans (code, price_usd_dec, daily_wallets, backfill_days, cadence, alerts, api_rows, perks_json, max_signatures_per_run, max_enhanced_tx_per_run, tracking_ttl_days)
 VALUES
-  ('FREE', 0.00, 2, 180, 'manual', 0, 0, '{}'::jsonb),
-  ('LITE', 2.00, 5, 365, 'weekly', 0, 10000, '{}'::jsonb),
-  ('PRO', 10.00, 25, 730, 'daily', 10, 100000, '{"boosts":true}'::jsonb)
+  ('FREE', 0.00, 2, 180, 'manual', 0, 0, '{}'::jsonb, 1000, 100, 7),
+  ('LITE', 2.00, 5, 365, 'weekly', 0, 10000, '{}'::jsonb, 5000, 500, 30),
+  ('PRO', 10.00, 25, 730, 'daily', 10, 100000, '{"boosts":true}'::jsonb, 50000, 5000, 90)
 ON CONFLICT (code) DO UPDATE SET
   price_usd_dec = EXCLUDED.price_usd_dec,
   daily_wallets = EXCLUDED.daily_wallets,
@@ -10,4 +10,7 @@ ON CONFLICT (code) DO UPDATE SET
   cadence = EXCLUDED.cadence,
   alerts = EXCLUDED.alerts,
   api_rows = EXCLUDED.api_rows,
-  perks_json = EXCLUDED.perks_json;
+  perks_json = EXCLUDED.perks_json,
+  max_signatures_per_run = EXCLUDED.max_signatures_per_run,
+  max_enhanced_tx_per_run = EXCLUDED.max_enhanced_tx_per_run,
+  tracking_ttl_days = EXCLUDED.tracking_ttl_days;
@@ -72,7 +72,13 @@ impl TestEnvironment {
             redis_url: None,
             rpc_primary: "https://api.devnet.solana.com".to_string(),
             rpc_secondary: None,
+            rpc_max_concurrency: 4,
+            rpc_primary_rps: 10,
+            rpc_secondary_rps: 5,
             helius_webhook_secret: "test_secret".to_string(),
+            helius_api_base_url: "http://127.0.0.1:0".to_string(),
+            helius_api_key: None,
+            helius_webhook_id: None,
             jupiter_base_url: "https://price.jup.ag/v3".to_string(),
             pyth_sse: "wss://hermes.pyth.network/ws".to_string(),
             dynamic_environment_id: "test_env_id".to_string(),
@@ -84,11 +90,26 @@ impl TestEnvironment {
             r2_secret_access_key: "test_secret_key".to_string(),
             r2_bucket_name: "test-bucket".to_string(),
             r2_public_url: "https://test-assets.example.com".to_string(),
+            worker_concurrency: 2,
+            worker_kind_limits: String::new(),
+            worker_poll_interval_ms: 500,
+            worker_shutdown_grace_secs: 5,
+            worker_heartbeat_secs: 5,
+            worker_lock_timeout_secs: 60,
+            worker_lock_timeouts: String::new(),
+            schedule_refresh_prices: String::new(),
+            schedule_refresh_views: String::new(),
+            schedule_cleanup: String::new(),
+            schedule_leaderboard: String::new(),
+            schedule_webhook_sync: String::new(),
+            schedule_plan_reanalysis: String::new(),
             api_bind: "127.0.0.1:0".to_string(),
             indexer_bind: "127.0.0.1:0".to_string(),
             cors_allow_origin: Some("*".to_string()),
             app_secret: "test_app_secret_that_is_long_enough_for_validation".to_string(),
             environment: "test".to_string(),
+            admin_user_ids: vec!["test_admin".to_string()],
+            export_url_ttl_secs: 3600,
         }
     }
 